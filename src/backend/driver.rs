@@ -0,0 +1,155 @@
+// src/backend/driver.rs
+
+//! Chooses, at runtime, which kernel driver reaches MSRs and PCI configuration space.
+
+use std::{env, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+
+use super::{HardwareDriver, MsrBackend, PciAddress, PciBackend};
+use crate::utils::cpu::ProcessorNumber;
+
+/// Environment variable naming the driver to use, e.g. `WinRing0`. Every known driver
+/// is tried in turn when it is not set.
+pub const DRIVER_ENV_VAR: &str = "OC_TOOL_DRIVER";
+
+/// Uses the first of several drivers that loads, picked the first time hardware is
+/// accessed. If none loads, every access fails with the reason each one gave.
+pub struct DriverChoice {
+    candidates: Vec<Arc<dyn HardwareDriver>>,
+    chosen: OnceCell<Result<Arc<dyn HardwareDriver>, String>>,
+}
+
+impl DriverChoice {
+    /// Tries `candidates` in order.
+    pub fn new(candidates: Vec<Arc<dyn HardwareDriver>>) -> Self {
+        Self {
+            candidates,
+            chosen: OnceCell::new(),
+        }
+    }
+
+    /// Tries only the driver named by `OC_TOOL_DRIVER`, ignoring case, if it is set, and
+    /// every one of `candidates` in order otherwise.
+    pub fn from_env(candidates: Vec<Arc<dyn HardwareDriver>>) -> Self {
+        match env::var(DRIVER_ENV_VAR) {
+            Ok(name) if !name.trim().is_empty() => Self::preferring(candidates, name.trim()),
+            _ => Self::new(candidates),
+        }
+    }
+
+    /// Tries only the driver called `name`, ignoring case.
+    pub fn preferring(candidates: Vec<Arc<dyn HardwareDriver>>, name: &str) -> Self {
+        let known: Vec<String> = candidates.iter().map(|d| d.name().to_string()).collect();
+        let preferred: Vec<_> = candidates
+            .into_iter()
+            .filter(|driver| driver.name().eq_ignore_ascii_case(name))
+            .collect();
+        let choice = Self::new(preferred);
+        if choice.candidates.is_empty() {
+            let _ = choice.chosen.set(Err(format!(
+                "{} names an unknown driver '{}'; the drivers are {}",
+                DRIVER_ENV_VAR,
+                name,
+                known.join(", ")
+            )));
+        }
+        choice
+    }
+
+    /// Returns the driver in use, loading the candidates if this is the first access.
+    pub fn chosen(&self) -> Result<&Arc<dyn HardwareDriver>> {
+        self.chosen
+            .get_or_init(|| {
+                let mut reasons = Vec::new();
+                for driver in &self.candidates {
+                    match driver.load() {
+                        Ok(()) => {
+                            tracing::info!(
+                                "Using the {} driver for hardware access.",
+                                driver.name()
+                            );
+                            return Ok(driver.clone());
+                        }
+                        Err(e) => {
+                            tracing::warn!("Cannot use the {} driver: {:?}", driver.name(), e);
+                            reasons.push(format!("{}: {:#}", driver.name(), e));
+                        }
+                    }
+                }
+                if reasons.is_empty() {
+                    Err("no driver is available on this platform".to_string())
+                } else {
+                    Err(reasons.join("; "))
+                }
+            })
+            .as_ref()
+            .map_err(|reason| anyhow!("{}", reason))
+    }
+}
+
+impl MsrBackend for DriverChoice {
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> Result<u64> {
+        self.chosen()?.read_msr(cpu, index)
+    }
+
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> Result<()> {
+        self.chosen()?.write_msr(cpu, index, value)
+    }
+}
+
+impl PciBackend for DriverChoice {
+    fn read_config(&self, address: PciAddress, offset: u32) -> Result<u32> {
+        self.chosen()?.read_config(address, offset)
+    }
+
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> Result<()> {
+        self.chosen()?.write_config(address, offset, value)
+    }
+}
+
+impl HardwareDriver for DriverChoice {
+    /// Returns the name of the driver in use, or `none` if no driver loads.
+    fn name(&self) -> &str {
+        self.chosen().map_or("none", |driver| driver.name())
+    }
+
+    fn load(&self) -> Result<()> {
+        self.chosen().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::fake::FakeDriver, utils::cpu::CpuTopology};
+
+    #[test]
+    fn test_first_driver_that_loads_is_used() {
+        let topology = CpuTopology::uniform(1, 1, 1, 1);
+        let cpu = topology.cpus[0].processor;
+        let broken = Arc::new(FakeDriver::new(&topology));
+        broken.set_unavailable(Some("the service is not installed"));
+        let working = Arc::new(FakeDriver::new(&topology));
+        let candidates: Vec<Arc<dyn HardwareDriver>> = vec![broken.clone(), working.clone()];
+
+        let choice = DriverChoice::preferring(candidates.clone(), "fake");
+        choice.write_msr(cpu, 0x10, 7).unwrap();
+        assert_eq!(working.msr.get(cpu, 0x10), 7);
+        assert_eq!(broken.msr.get(cpu, 0x10), 0);
+
+        let only_broken = DriverChoice::new(vec![broken]);
+        assert_eq!(only_broken.name(), "none");
+        let error = format!("{:#}", only_broken.load().unwrap_err());
+        assert!(error.contains("the service is not installed"), "{}", error);
+
+        let unknown = DriverChoice::preferring(candidates, "InpOut32");
+        let error = format!("{:#}", unknown.load().unwrap_err());
+        assert!(
+            error.contains("unknown driver 'InpOut32'") && error.contains("Fake"),
+            "{}",
+            error
+        );
+    }
+}
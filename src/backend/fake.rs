@@ -0,0 +1,1229 @@
+// src/backend/fake.rs
+
+//! In-memory stand-ins for the system backends.
+//!
+//! Each fake keeps just enough state to behave like the real thing for the tweaks
+//! that use it, and exposes that state so tests can check what a tweak changed.
+//! `FakeSystem` bundles one of each, set up like a freshly installed machine.
+//!
+//! The one exception is [`FakeSysfs`], a sysfs and procfs tree in a temporary
+//! directory, so that the real Linux backend is what reads and writes it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use raw_cpuid::CpuIdResult;
+
+use super::{
+    linux::{selected_value, LinuxSysfs, CPU_DIR},
+    scoped, Backends, BcdBackend, CpuidBackend, DisplayBackend, DisplaySettingsType, HardwareDriver,
+    MsrBackend, PciAddress, PciBackend, PciRegister, PowerBackend, ProcessBackend, RegistryBackend,
+    RegistryHive, ScopedBackends, ScriptOptions, ScriptOutput, ServiceBackend, ShellBackend, SysfsBackend,
+    TopologyBackend, UserRightsBackend, SMN_DATA, SMN_INDEX,
+};
+use crate::{
+    power::PowerScheme,
+    utils::{
+        bcd::{BcdEntry, CURRENT_ENTRY},
+        cpu::{format_ranges, CpuTopology, LogicalCpu, ProcessorNumber},
+        registry::{parse_registry_path, RegistryKeyValue},
+    },
+};
+
+pub const BALANCED_SCHEME_GUID: u128 = 0x381b4222_f694_41f0_9685_ff5bb260df2e;
+pub const HIGH_PERFORMANCE_SCHEME_GUID: u128 = 0x8c5e7fda_e8bf_4a96_9a85_a6e23a8c635c;
+pub const POWER_SAVER_SCHEME_GUID: u128 = 0xa1841308_3541_4fab_bc81_f71556f20b4a;
+pub const ULTIMATE_PERFORMANCE_SCHEME_GUID: u128 = 0xe9a42b02_d5df_448d_aa00_03f14749eb61;
+
+/// Registry keys, each holding its values. Key paths and value names are stored
+/// lowercased, as the registry is case-insensitive.
+pub type RegistryContents = BTreeMap<(RegistryHive, String), BTreeMap<String, RegistryKeyValue>>;
+
+/// A registry that starts out empty.
+#[derive(Default)]
+pub struct FakeRegistry {
+    keys: Mutex<RegistryContents>,
+}
+
+impl FakeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a value, given the full path of its key (e.g. "HKEY_LOCAL_MACHINE\\Software\\...").
+    pub fn set(&self, path: &str, name: &str, value: RegistryKeyValue) -> Result<()> {
+        let (hive, subkey) = parse_registry_path(path)?;
+        self.write_value(hive, &subkey, name, &value)
+    }
+
+    /// Returns a value, given the full path of its key.
+    pub fn get(&self, path: &str, name: &str) -> Result<Option<RegistryKeyValue>> {
+        let (hive, subkey) = parse_registry_path(path)?;
+        self.read_value(hive, &subkey, name)
+    }
+
+    /// Returns every key and value.
+    pub fn contents(&self) -> RegistryContents {
+        self.keys.lock().unwrap().clone()
+    }
+}
+
+impl RegistryBackend for FakeRegistry {
+    fn key_exists(&self, hive: RegistryHive, subkey: &str) -> Result<bool> {
+        let subkey = subkey.to_lowercase();
+        let prefix = format!("{}\\", subkey);
+        Ok(self.keys.lock().unwrap().keys().any(|(key_hive, key)| {
+            *key_hive == hive && (*key == subkey || key.starts_with(&prefix))
+        }))
+    }
+
+    fn read_value(
+        &self,
+        hive: RegistryHive,
+        subkey: &str,
+        name: &str,
+    ) -> Result<Option<RegistryKeyValue>> {
+        Ok(self
+            .keys
+            .lock()
+            .unwrap()
+            .get(&(hive, subkey.to_lowercase()))
+            .and_then(|values| values.get(&name.to_lowercase()))
+            .cloned())
+    }
+
+    fn write_value(
+        &self,
+        hive: RegistryHive,
+        subkey: &str,
+        name: &str,
+        value: &RegistryKeyValue,
+    ) -> Result<()> {
+        if *value == RegistryKeyValue::Deleted {
+            return self.delete_value(hive, subkey, name);
+        }
+        self.keys
+            .lock()
+            .unwrap()
+            .entry((hive, subkey.to_lowercase()))
+            .or_default()
+            .insert(name.to_lowercase(), value.clone());
+        Ok(())
+    }
+
+    fn delete_value(&self, hive: RegistryHive, subkey: &str, name: &str) -> Result<()> {
+        if let Some(values) = self
+            .keys
+            .lock()
+            .unwrap()
+            .get_mut(&(hive, subkey.to_lowercase()))
+        {
+            values.remove(&name.to_lowercase());
+        }
+        Ok(())
+    }
+}
+
+/// How a logical processor misbehaves when its MSRs are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsrFault {
+    /// Writes fail with an error.
+    RejectWrites,
+    /// Writes report success but leave the old value in place.
+    IgnoreWrites,
+}
+
+/// MSRs of a processor with a fixed set of logical processors. Every MSR reads as
+/// zero until it is written.
+pub struct FakeMsr {
+    processors: BTreeSet<ProcessorNumber>,
+    values: Mutex<BTreeMap<(ProcessorNumber, u32), u64>>,
+    faults: Mutex<BTreeMap<ProcessorNumber, MsrFault>>,
+}
+
+impl FakeMsr {
+    pub fn new(topology: &CpuTopology) -> Self {
+        Self {
+            processors: topology.cpus.iter().map(|cpu| cpu.processor).collect(),
+            values: Mutex::new(BTreeMap::new()),
+            faults: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Makes writes to one logical processor misbehave, or behave again with `None`.
+    pub fn set_fault(&self, cpu: ProcessorNumber, fault: Option<MsrFault>) {
+        let mut faults = self.faults.lock().unwrap();
+        match fault {
+            Some(fault) => faults.insert(cpu, fault),
+            None => faults.remove(&cpu),
+        };
+    }
+
+    /// Sets an MSR on one logical processor.
+    pub fn set(&self, cpu: ProcessorNumber, index: u32, value: u64) {
+        self.values.lock().unwrap().insert((cpu, index), value);
+    }
+
+    /// Returns the value of an MSR on one logical processor.
+    pub fn get(&self, cpu: ProcessorNumber, index: u32) -> u64 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&(cpu, index))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns every MSR that is not zero, by logical processor and index.
+    pub fn contents(&self) -> BTreeMap<(ProcessorNumber, u32), u64> {
+        let mut values = self.values.lock().unwrap().clone();
+        values.retain(|_, value| *value != 0);
+        values
+    }
+
+    fn check_processor(&self, cpu: ProcessorNumber) -> Result<()> {
+        if !self.processors.contains(&cpu) {
+            bail!(
+                "Logical processor {} does not exist; the system has {}",
+                cpu,
+                self.processors.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl MsrBackend for FakeMsr {
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> Result<u64> {
+        self.check_processor(cpu)?;
+        Ok(self.get(cpu, index))
+    }
+
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> Result<()> {
+        self.check_processor(cpu)?;
+        match self.faults.lock().unwrap().get(&cpu) {
+            Some(MsrFault::RejectWrites) => {
+                bail!("Writing MSR 0x{:X} on {} failed", index, cpu)
+            }
+            Some(MsrFault::IgnoreWrites) => {}
+            None => self.set(cpu, index, value),
+        }
+        Ok(())
+    }
+}
+
+/// PCI configuration space of a Zen processor's host bridge and data fabric, with the
+/// host bridge's SMN index/data pair. Every register reads as zero until it is written,
+/// and devices that do not exist read as all ones and ignore writes, as on hardware.
+pub struct FakePci {
+    devices: BTreeSet<PciAddress>,
+    values: Mutex<BTreeMap<PciRegister, u32>>,
+    /// The SMN address last written to the index register.
+    smn_index: Mutex<u32>,
+    /// Registers that ignore writes.
+    locked: Mutex<BTreeSet<PciRegister>>,
+}
+
+impl FakePci {
+    /// Creates the host bridge at 00:00.0 and the data fabric functions at 00:18.0-7.
+    pub fn new() -> Self {
+        let mut devices = BTreeSet::from([PciAddress::HOST_BRIDGE]);
+        devices.extend((0..8).map(|function| PciAddress::new(0, 0x18, function)));
+        Self {
+            devices,
+            values: Mutex::new(BTreeMap::new()),
+            smn_index: Mutex::new(0),
+            locked: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Sets a configuration space or SMN register.
+    pub fn set(&self, register: PciRegister, value: u32) {
+        self.values.lock().unwrap().insert(register, value);
+    }
+
+    /// Returns the value of a configuration space or SMN register.
+    pub fn get(&self, register: PciRegister) -> u32 {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&register)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Makes a register ignore writes, as registers locked by firmware do, or take them
+    /// again.
+    pub fn set_locked(&self, register: PciRegister, locked: bool) {
+        let mut registers = self.locked.lock().unwrap();
+        if locked {
+            registers.insert(register);
+        } else {
+            registers.remove(&register);
+        }
+    }
+
+    /// Returns every register that is not zero.
+    pub fn contents(&self) -> BTreeMap<PciRegister, u32> {
+        let mut values = self.values.lock().unwrap().clone();
+        values.retain(|_, value| *value != 0);
+        values
+    }
+
+    /// Returns the register an access at `offset` of `address` reaches, or `None` for
+    /// the SMN index register.
+    fn register(&self, address: PciAddress, offset: u32) -> Result<Option<PciRegister>> {
+        if !offset.is_multiple_of(4) || offset >= 0x1000 {
+            bail!(
+                "Offset 0x{:X} of {} is not a configuration DWORD",
+                offset,
+                address
+            );
+        }
+        Ok(match (address, offset) {
+            (PciAddress::HOST_BRIDGE, SMN_INDEX) => None,
+            (PciAddress::HOST_BRIDGE, SMN_DATA) => Some(PciRegister::Smn {
+                address: *self.smn_index.lock().unwrap(),
+            }),
+            _ => Some(PciRegister::Config { address, offset }),
+        })
+    }
+}
+
+impl Default for FakePci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PciBackend for FakePci {
+    fn read_config(&self, address: PciAddress, offset: u32) -> Result<u32> {
+        let register = self.register(address, offset)?;
+        if !self.devices.contains(&address) {
+            return Ok(u32::MAX);
+        }
+        Ok(match register {
+            Some(register) => self.get(register),
+            None => *self.smn_index.lock().unwrap(),
+        })
+    }
+
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> Result<()> {
+        let register = self.register(address, offset)?;
+        if !self.devices.contains(&address) {
+            return Ok(());
+        }
+        match register {
+            Some(register) if self.locked.lock().unwrap().contains(&register) => {}
+            Some(register) => self.set(register, value),
+            None => *self.smn_index.lock().unwrap() = value,
+        }
+        Ok(())
+    }
+}
+
+/// A hardware driver made of a `FakeMsr` and a `FakePci`, which can be made to fail to
+/// load as a missing or blocked driver does.
+pub struct FakeDriver {
+    pub msr: Arc<FakeMsr>,
+    pub pci: Arc<FakePci>,
+    unavailable: Mutex<Option<String>>,
+}
+
+impl FakeDriver {
+    /// Creates a driver for a processor laid out as `topology`, which loads.
+    pub fn new(topology: &CpuTopology) -> Self {
+        Self::with(Arc::new(FakeMsr::new(topology)), Arc::new(FakePci::new()))
+    }
+
+    /// Creates a driver that reaches the given MSRs and PCI registers.
+    pub fn with(msr: Arc<FakeMsr>, pci: Arc<FakePci>) -> Self {
+        Self {
+            msr,
+            pci,
+            unavailable: Mutex::new(None),
+        }
+    }
+
+    /// Makes the driver fail to load, and every access fail, for the given reason, or
+    /// load again with `None`.
+    pub fn set_unavailable(&self, reason: Option<&str>) {
+        *self.unavailable.lock().unwrap() = reason.map(str::to_string);
+    }
+}
+
+impl MsrBackend for FakeDriver {
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> Result<u64> {
+        self.load()?;
+        self.msr.read_msr(cpu, index)
+    }
+
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> Result<()> {
+        self.load()?;
+        self.msr.write_msr(cpu, index, value)
+    }
+}
+
+impl PciBackend for FakeDriver {
+    fn read_config(&self, address: PciAddress, offset: u32) -> Result<u32> {
+        self.load()?;
+        self.pci.read_config(address, offset)
+    }
+
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> Result<()> {
+        self.load()?;
+        self.pci.write_config(address, offset, value)
+    }
+}
+
+impl HardwareDriver for FakeDriver {
+    fn name(&self) -> &str {
+        "Fake"
+    }
+
+    fn load(&self) -> Result<()> {
+        match &*self.unavailable.lock().unwrap() {
+            Some(reason) => bail!("{}", reason),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A fixed topology.
+pub struct FakeTopology(pub CpuTopology);
+
+impl TopologyBackend for FakeTopology {
+    fn topology(&self) -> Result<CpuTopology> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A processor that answers CPUID from recorded leaves. Leaves that were not
+/// recorded read as zero.
+pub struct FakeCpuid {
+    leaves: HashMap<(u32, u32), CpuIdResult>,
+}
+
+impl FakeCpuid {
+    /// Creates a processor from `(leaf, subleaf, [eax, ebx, ecx, edx])` entries.
+    pub fn new(leaves: &[(u32, u32, [u32; 4])]) -> Self {
+        Self {
+            leaves: leaves
+                .iter()
+                .map(|&(leaf, subleaf, [eax, ebx, ecx, edx])| {
+                    ((leaf, subleaf), CpuIdResult { eax, ebx, ecx, edx })
+                })
+                .collect(),
+        }
+    }
+
+    /// A Ryzen 9 7950X: AMD family 19h model 61h, a Zen 4 processor.
+    pub fn ryzen_7950x() -> Self {
+        Self::new(&[
+            (0x0, 0, [0x0000_0010, 0x6874_7541, 0x444D_4163, 0x6974_6E65]),
+            (0x1, 0, [0x00A6_0F12, 0x0020_0800, 0x7EF8_320B, 0x178B_FBFF]),
+        ])
+    }
+
+    /// A Core i9-9900K: Intel family 6h model 9Eh, a Coffee Lake processor.
+    pub fn core_i9_9900k() -> Self {
+        Self::new(&[
+            (0x0, 0, [0x0000_0016, 0x756E_6547, 0x6C65_746E, 0x4965_6E69]),
+            (0x1, 0, [0x0009_06EC, 0x0010_0800, 0x7FFA_FBFF, 0xBFEB_FBFF]),
+        ])
+    }
+}
+
+impl CpuidBackend for FakeCpuid {
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuIdResult {
+        self.leaves
+            .get(&(leaf, subleaf))
+            .copied()
+            .unwrap_or(CpuIdResult {
+                eax: 0,
+                ebx: 0,
+                ecx: 0,
+                edx: 0,
+            })
+    }
+}
+
+/// A shell that runs nothing. Scripts print whatever output was set for them, or
+/// nothing, and every script run is recorded.
+#[derive(Default)]
+pub struct FakeShell {
+    outputs: Mutex<HashMap<String, ScriptOutput>>,
+    history: Mutex<Vec<String>>,
+}
+
+impl FakeShell {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the standard output of a script, which otherwise succeeds at once. Scripts
+    /// are matched ignoring surrounding whitespace.
+    pub fn set_output(&self, script: &str, output: &str) {
+        self.set_result(
+            script,
+            ScriptOutput {
+                stdout: output.trim().to_string(),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Sets everything a script produces. A script whose `duration` is longer than the
+    /// timeout it is run with times out.
+    pub fn set_result(&self, script: &str, output: ScriptOutput) {
+        self.outputs
+            .lock()
+            .unwrap()
+            .insert(script.trim().to_string(), output);
+    }
+
+    /// Returns every script that was run, oldest first.
+    pub fn history(&self) -> Vec<String> {
+        self.history.lock().unwrap().clone()
+    }
+}
+
+impl ShellBackend for FakeShell {
+    fn run_powershell(&self, script: &str, options: &ScriptOptions) -> Result<ScriptOutput> {
+        let script = script.trim().to_string();
+        let output = self
+            .outputs
+            .lock()
+            .unwrap()
+            .get(&script)
+            .cloned()
+            .unwrap_or_default();
+        self.history.lock().unwrap().push(script);
+        if options
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            bail!("PowerShell script was cancelled");
+        }
+        if let Some(timeout) = options.timeout.filter(|timeout| output.duration > *timeout) {
+            bail!("PowerShell script timed out after {:?}", timeout);
+        }
+        Ok(output)
+    }
+}
+
+/// Services that can be started and stopped. Unknown services are stopped.
+#[derive(Default)]
+pub struct FakeServices {
+    running: Mutex<BTreeSet<String>>,
+}
+
+impl FakeServices {
+    pub fn new<'a>(running: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            running: Mutex::new(running.into_iter().map(str::to_string).collect()),
+        }
+    }
+
+    /// Returns the names of the running services.
+    pub fn running(&self) -> BTreeSet<String> {
+        self.running.lock().unwrap().clone()
+    }
+}
+
+impl ServiceBackend for FakeServices {
+    fn is_running(&self, name: &str) -> Result<bool> {
+        Ok(self.running.lock().unwrap().contains(name))
+    }
+
+    fn start(&self, name: &str) -> Result<()> {
+        self.running.lock().unwrap().insert(name.to_string());
+        Ok(())
+    }
+
+    fn stop(&self, name: &str) -> Result<()> {
+        self.running.lock().unwrap().remove(name);
+        Ok(())
+    }
+}
+
+/// Power schemes and their settings.
+pub struct FakePower {
+    schemes: Mutex<Vec<PowerScheme>>,
+    /// Schemes that can be duplicated but are not listed, like Ultimate Performance.
+    hidden: Vec<PowerScheme>,
+    active: Mutex<u128>,
+    values: Mutex<BTreeMap<(u128, u128, u128), u32>>,
+}
+
+impl FakePower {
+    /// Returns the schemes of a fresh Windows install, with Balanced active.
+    pub fn windows_defaults() -> Self {
+        Self {
+            schemes: Mutex::new(Self::default_schemes()),
+            hidden: vec![PowerScheme {
+                name: "Ultimate Performance".to_string(),
+                guid: ULTIMATE_PERFORMANCE_SCHEME_GUID,
+            }],
+            active: Mutex::new(BALANCED_SCHEME_GUID),
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn default_schemes() -> Vec<PowerScheme> {
+        [
+            ("Balanced", BALANCED_SCHEME_GUID),
+            ("High performance", HIGH_PERFORMANCE_SCHEME_GUID),
+            ("Power saver", POWER_SAVER_SCHEME_GUID),
+        ]
+        .into_iter()
+        .map(|(name, guid)| PowerScheme {
+            name: name.to_string(),
+            guid,
+        })
+        .collect()
+    }
+
+    fn find(&self, guid: u128) -> Result<PowerScheme> {
+        self.schemes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|scheme| scheme.guid == guid)
+            .cloned()
+            .ok_or_else(|| anyhow!("Power scheme {:032x} does not exist", guid))
+    }
+}
+
+impl PowerBackend for FakePower {
+    fn schemes(&self) -> Result<Vec<PowerScheme>> {
+        Ok(self.schemes.lock().unwrap().clone())
+    }
+
+    fn active_scheme(&self) -> Result<PowerScheme> {
+        self.find(*self.active.lock().unwrap())
+    }
+
+    fn set_active_scheme(&self, guid: u128) -> Result<()> {
+        self.find(guid)?;
+        *self.active.lock().unwrap() = guid;
+        Ok(())
+    }
+
+    fn duplicate_scheme(&self, guid: u128) -> Result<u128> {
+        let source = match self.find(guid) {
+            Ok(scheme) => scheme,
+            Err(e) => self
+                .hidden
+                .iter()
+                .find(|scheme| scheme.guid == guid)
+                .cloned()
+                .ok_or(e)?,
+        };
+        let mut schemes = self.schemes.lock().unwrap();
+        let mut copy = source.guid;
+        while copy == source.guid || schemes.iter().any(|scheme| scheme.guid == copy) {
+            copy = copy.wrapping_add(1);
+        }
+        schemes.push(PowerScheme {
+            name: source.name,
+            guid: copy,
+        });
+        Ok(copy)
+    }
+
+    fn read_ac_value_index(&self, scheme: u128, subgroup: u128, setting: u128) -> Result<u32> {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&(scheme, subgroup, setting))
+            .copied()
+            .ok_or_else(|| anyhow!("Power setting {:032x} has no value", setting))
+    }
+
+    fn write_ac_value_index(
+        &self,
+        scheme: u128,
+        subgroup: u128,
+        setting: u128,
+        value: u32,
+    ) -> Result<()> {
+        self.find(scheme)?;
+        self.values
+            .lock()
+            .unwrap()
+            .insert((scheme, subgroup, setting), value);
+        Ok(())
+    }
+
+    fn restore_default_schemes(&self) -> Result<()> {
+        *self.schemes.lock().unwrap() = Self::default_schemes();
+        *self.active.lock().unwrap() = BALANCED_SCHEME_GUID;
+        self.values.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// Account rights, starting with none granted.
+#[derive(Default)]
+pub struct FakeUserRights {
+    rights: Mutex<BTreeSet<(String, String)>>,
+}
+
+impl FakeUserRights {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every granted right, as (account, right) pairs.
+    pub fn granted(&self) -> BTreeSet<(String, String)> {
+        self.rights.lock().unwrap().clone()
+    }
+}
+
+impl UserRightsBackend for FakeUserRights {
+    fn has_right(&self, account: &str, right: &str) -> Result<bool> {
+        Ok(self
+            .rights
+            .lock()
+            .unwrap()
+            .contains(&(account.to_string(), right.to_string())))
+    }
+
+    fn set_right(&self, account: &str, right: &str, grant: bool) -> Result<()> {
+        let mut rights = self.rights.lock().unwrap();
+        let entry = (account.to_string(), right.to_string());
+        if grant {
+            rights.insert(entry);
+        } else {
+            rights.remove(&entry);
+        }
+        Ok(())
+    }
+}
+
+/// A display with a fixed list of modes.
+pub struct FakeDisplay {
+    modes: Vec<DisplaySettingsType>,
+    current: Mutex<DisplaySettingsType>,
+}
+
+impl FakeDisplay {
+    /// Creates a display in `current` mode, which must be one of `modes`.
+    pub fn new(modes: Vec<DisplaySettingsType>, current: DisplaySettingsType) -> Self {
+        Self {
+            modes,
+            current: Mutex::new(current),
+        }
+    }
+
+    /// Returns a 1080p display running at 144 Hz.
+    pub fn full_hd() -> Self {
+        let mode = |width, height, refresh_rate| DisplaySettingsType {
+            width,
+            height,
+            refresh_rate,
+        };
+        Self::new(
+            vec![
+                mode(640, 480, 60),
+                mode(800, 600, 60),
+                mode(1280, 720, 60),
+                mode(1920, 1080, 60),
+                mode(1920, 1080, 144),
+            ],
+            mode(1920, 1080, 144),
+        )
+    }
+}
+
+impl DisplayBackend for FakeDisplay {
+    fn modes(&self) -> Result<Vec<DisplaySettingsType>> {
+        Ok(self.modes.clone())
+    }
+
+    fn current_mode(&self) -> Result<DisplaySettingsType> {
+        Ok(self.current.lock().unwrap().clone())
+    }
+
+    fn set_mode(&self, mode: &DisplaySettingsType) -> Result<()> {
+        if !self.modes.contains(mode) {
+            bail!("The display does not support {}", mode);
+        }
+        *self.current.lock().unwrap() = mode.clone();
+        Ok(())
+    }
+}
+
+/// Running processes, by lowercased executable name.
+#[derive(Default)]
+pub struct FakeProcesses {
+    running: Mutex<BTreeSet<String>>,
+}
+
+impl FakeProcesses {
+    pub fn new<'a>(running: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            running: Mutex::new(running.into_iter().map(str::to_lowercase).collect()),
+        }
+    }
+
+    /// Returns the executable names of the running processes.
+    pub fn running(&self) -> BTreeSet<String> {
+        self.running.lock().unwrap().clone()
+    }
+}
+
+impl ProcessBackend for FakeProcesses {
+    fn is_running(&self, exe_name: &str) -> Result<bool> {
+        Ok(self
+            .running
+            .lock()
+            .unwrap()
+            .contains(&exe_name.to_lowercase()))
+    }
+
+    fn terminate(&self, exe_name: &str) -> Result<()> {
+        self.running
+            .lock()
+            .unwrap()
+            .remove(&exe_name.to_lowercase());
+        Ok(())
+    }
+
+    fn start(&self, path: &str) -> Result<()> {
+        let exe_name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+        self.running.lock().unwrap().insert(exe_name.to_lowercase());
+        Ok(())
+    }
+}
+
+/// The boot configuration data store, holding a single `{current}` Windows Boot Loader
+/// with the elements a fresh install sets.
+pub struct FakeBcd {
+    current: Mutex<BcdEntry>,
+}
+
+impl FakeBcd {
+    pub fn new() -> Self {
+        let elements = [
+            ("description", "Windows 11"),
+            ("nx", "OptIn"),
+            ("bootmenupolicy", "Standard"),
+            ("recoveryenabled", "Yes"),
+        ];
+        Self {
+            current: Mutex::new(BcdEntry {
+                kind: "Windows Boot Loader".to_string(),
+                identifier: "{b2721d73-1db4-4c62-bf78-c548a880142d}".to_string(),
+                elements: elements
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Returns the elements of `{current}`.
+    pub fn elements(&self) -> BTreeMap<String, String> {
+        self.current.lock().unwrap().elements.clone()
+    }
+
+    fn entry(&self, entry: &str) -> Result<MutexGuard<'_, BcdEntry>> {
+        if !entry.eq_ignore_ascii_case(CURRENT_ENTRY) {
+            bail!("The BCD store has no entry {}", entry);
+        }
+        Ok(self.current.lock().unwrap())
+    }
+}
+
+impl Default for FakeBcd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BcdBackend for FakeBcd {
+    fn read_entry(&self, entry: &str) -> Result<BcdEntry> {
+        Ok(self.entry(entry)?.clone())
+    }
+
+    fn set(&self, entry: &str, element: &str, value: &str) -> Result<()> {
+        self.entry(entry)?
+            .elements
+            .insert(element.to_ascii_lowercase(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, entry: &str, element: &str) -> Result<()> {
+        self.entry(entry)?
+            .elements
+            .remove(&element.to_ascii_lowercase())
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("{} does not set {}", entry, element))
+    }
+}
+
+/// Returns the Linux number of a logical processor, counting across groups of 64.
+fn linux_number(cpu: &LogicalCpu) -> usize {
+    cpu.processor.group as usize * 64 + cpu.processor.index as usize
+}
+
+/// Lists, as sysfs does, e.g. `0-3,8`, the logical processors of `topology` that `filter` picks.
+fn cpu_list(topology: &CpuTopology, filter: impl Fn(&LogicalCpu) -> bool) -> String {
+    let numbers: Vec<usize> = topology
+        .cpus
+        .iter()
+        .filter(|cpu| filter(cpu))
+        .map(linux_number)
+        .collect();
+    format_ranges(&numbers).replace(", ", ",")
+}
+
+/// Counts the trees created by this process, to give each its own directory.
+static SYSFS_TREES: AtomicUsize = AtomicUsize::new(0);
+
+/// Trees older than this are left over from earlier test runs and are removed.
+const STALE_SYSFS_TREE: Duration = Duration::from_secs(60 * 60);
+
+/// A sysfs and procfs tree in a temporary directory, laid out like a Linux machine's
+/// with the `schedutil` governor, three idle states per processor, and the kernel's
+/// default memory and scheduler settings. The directory is removed when this is dropped,
+/// or by a later run if the tree was still installed when the process exited.
+pub struct FakeSysfs {
+    root: PathBuf,
+    files: LinuxSysfs,
+}
+
+impl FakeSysfs {
+    /// Creates a tree with a directory for each logical processor of `topology`.
+    pub fn new(topology: &CpuTopology) -> Self {
+        let temp_dir = env::temp_dir();
+        for entry in fs::read_dir(&temp_dir).into_iter().flatten().flatten() {
+            let stale = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > STALE_SYSFS_TREE);
+            if stale
+                && entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("oc_tool-sysfs-")
+            {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+
+        let root = temp_dir.join(format!(
+            "oc_tool-sysfs-{}-{}",
+            process::id(),
+            SYSFS_TREES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        let tree = Self {
+            files: LinuxSysfs::new(&root),
+            root,
+        };
+
+        tree.set(
+            &format!("{}/online", CPU_DIR),
+            &cpu_list(topology, |_| true),
+        );
+        for cpu in &topology.cpus {
+            let dir = format!("{}/cpu{}", CPU_DIR, linux_number(cpu));
+            tree.set(
+                &format!("{}/topology/thread_siblings_list", dir),
+                &cpu_list(topology, |other| other.core == cpu.core),
+            );
+            tree.set(&format!("{}/cache/index3/level", dir), "3");
+            tree.set(
+                &format!("{}/cache/index3/shared_cpu_list", dir),
+                &cpu_list(topology, |other| other.ccx == cpu.ccx),
+            );
+            tree.set(&format!("{}/cpufreq/scaling_governor", dir), "schedutil");
+            tree.set(
+                &format!("{}/cpufreq/scaling_available_governors", dir),
+                "performance schedutil powersave",
+            );
+            for (state, name) in ["POLL", "C1", "C2"].iter().enumerate() {
+                let state = format!("{}/cpuidle/state{}", dir, state);
+                tree.set(&format!("{}/name", state), name);
+                tree.set(&format!("{}/disable", state), "0");
+            }
+        }
+        tree.set(
+            "/sys/kernel/mm/transparent_hugepage/enabled",
+            "always [madvise] never",
+        );
+        tree.set("/proc/sys/vm/swappiness", "60");
+        tree.set("/proc/sys/kernel/sched_autogroup_enabled", "1");
+        tree.set("/proc/sys/kernel/sched_rt_runtime_us", "950000");
+        tree
+    }
+
+    /// Returns the directory the tree is in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Creates or overwrites a file, given its absolute path on a real system.
+    pub fn set(&self, path: &str, contents: &str) {
+        let file = self.files.resolve(path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(file, contents).unwrap();
+    }
+
+    /// Removes a file or a whole directory, as a kernel without the feature would lack it.
+    pub fn remove(&self, path: &str) {
+        let path = self.files.resolve(path);
+        let _ = fs::remove_file(&path).or_else(|_| fs::remove_dir_all(&path));
+    }
+
+    /// Returns the value of every file in the tree, by absolute path. For multiple choice
+    /// files only the selected value is kept, as that is all writing one can change.
+    pub fn contents(&self) -> Result<BTreeMap<String, String>> {
+        let mut contents = BTreeMap::new();
+        let mut dirs = vec![String::new()];
+        while let Some(dir) = dirs.pop() {
+            for name in self.files.list(if dir.is_empty() { "/" } else { &dir })? {
+                let path = format!("{}/{}", dir, name);
+                if self.files.resolve(&path).is_dir() {
+                    dirs.push(path);
+                } else if let Some(value) = self.files.read(&path)? {
+                    contents.insert(path, selected_value(&value).to_string());
+                }
+            }
+        }
+        Ok(contents)
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+impl SysfsBackend for FakeSysfs {
+    fn read(&self, path: &str) -> Result<Option<String>> {
+        self.files.read(path)
+    }
+
+    fn write(&self, path: &str, value: &str) -> Result<()> {
+        self.files.write(path, value)
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        self.files.list(path)
+    }
+}
+
+/// Everything the fakes of a `FakeSystem` hold, for comparing before and after a change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeState {
+    pub registry: RegistryContents,
+    pub msrs: BTreeMap<(ProcessorNumber, u32), u64>,
+    pub pci: BTreeMap<PciRegister, u32>,
+    pub services: BTreeSet<String>,
+    pub power_schemes: Vec<PowerScheme>,
+    pub active_power_scheme: PowerScheme,
+    pub user_rights: BTreeSet<(String, String)>,
+    pub display_mode: DisplaySettingsType,
+    pub processes: BTreeSet<String>,
+    pub sysfs: BTreeMap<String, String>,
+    pub bcd: BTreeMap<String, String>,
+}
+
+/// One fake of each backend, set up like a freshly installed machine: an empty
+/// registry, a Ryzen 9 7950X with every MSR and PCI register zero, a few services and Explorer running,
+/// the default power schemes with Balanced active, a 1080p display, the boot entry of
+/// a fresh install, and the sysfs tree of a Linux install on the same machine.
+#[derive(Clone)]
+pub struct FakeSystem {
+    pub registry: Arc<FakeRegistry>,
+    pub msr: Arc<FakeMsr>,
+    pub pci: Arc<FakePci>,
+    /// The driver both `msr` and `pci` are reached through.
+    pub driver: Arc<FakeDriver>,
+    pub cpuid: Arc<FakeCpuid>,
+    pub topology: Arc<FakeTopology>,
+    pub shell: Arc<FakeShell>,
+    pub services: Arc<FakeServices>,
+    pub power: Arc<FakePower>,
+    pub user_rights: Arc<FakeUserRights>,
+    pub display: Arc<FakeDisplay>,
+    pub processes: Arc<FakeProcesses>,
+    pub sysfs: Arc<FakeSysfs>,
+    pub bcd: Arc<FakeBcd>,
+}
+
+impl FakeSystem {
+    /// Creates a fake machine with the given number of logical processors, each its
+    /// own core, filling processor groups of 64 in order.
+    pub fn new(cpus: usize) -> Self {
+        Self::with_topology(CpuTopology::uniform(1, 1, cpus, 1))
+    }
+
+    /// Creates a fake machine whose processor is laid out as `topology`.
+    pub fn with_topology(topology: CpuTopology) -> Self {
+        let driver = FakeDriver::new(&topology);
+        Self {
+            registry: Arc::new(FakeRegistry::new()),
+            msr: driver.msr.clone(),
+            pci: driver.pci.clone(),
+            driver: Arc::new(driver),
+            cpuid: Arc::new(FakeCpuid::ryzen_7950x()),
+            sysfs: Arc::new(FakeSysfs::new(&topology)),
+            topology: Arc::new(FakeTopology(topology)),
+            shell: Arc::new(FakeShell::new()),
+            services: Arc::new(FakeServices::new(["DiagTrack", "SysMain", "Winmgmt"])),
+            power: Arc::new(FakePower::windows_defaults()),
+            user_rights: Arc::new(FakeUserRights::new()),
+            display: Arc::new(FakeDisplay::full_hd()),
+            processes: Arc::new(FakeProcesses::new(["explorer.exe", "winlogon.exe"])),
+            bcd: Arc::new(FakeBcd::new()),
+        }
+    }
+
+    /// Returns the fakes as a set of backends.
+    pub fn backends(&self) -> Backends {
+        Backends {
+            registry: self.registry.clone(),
+            driver: self.driver.clone(),
+            cpuid: self.cpuid.clone(),
+            topology: self.topology.clone(),
+            shell: self.shell.clone(),
+            services: self.services.clone(),
+            power: self.power.clone(),
+            user_rights: self.user_rights.clone(),
+            display: self.display.clone(),
+            processes: self.processes.clone(),
+            sysfs: self.sysfs.clone(),
+            bcd: self.bcd.clone(),
+        }
+    }
+
+    /// Makes every tweak on the current thread, and every task an orchestrator created on
+    /// it runs, use these fakes until the returned guard is dropped.
+    pub fn install(&self) -> ScopedBackends {
+        scoped(self.backends())
+    }
+
+    /// Returns everything the fakes hold. Registry keys without values are left out,
+    /// since reverting a change deletes the value but keeps the key it created.
+    pub fn state(&self) -> Result<FakeState> {
+        let mut registry = self.registry.contents();
+        registry.retain(|_, values| !values.is_empty());
+        Ok(FakeState {
+            registry,
+            msrs: self.msr.contents(),
+            pci: self.pci.contents(),
+            services: self.services.running(),
+            power_schemes: self.power.schemes()?,
+            active_power_scheme: self.power.active_scheme()?,
+            user_rights: self.user_rights.granted(),
+            display_mode: self.display.current_mode()?,
+            processes: self.processes.running(),
+            sysfs: self.sysfs.contents()?,
+            bcd: self.bcd.elements(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_is_case_insensitive() {
+        let registry = FakeRegistry::new();
+        registry
+            .set(
+                r"HKEY_LOCAL_MACHINE\SYSTEM\CurrentControlSet\Control",
+                "Value",
+                RegistryKeyValue::Dword(1),
+            )
+            .unwrap();
+
+        assert_eq!(
+            registry
+                .get(
+                    r"HKEY_LOCAL_MACHINE\System\currentcontrolset\CONTROL",
+                    "value"
+                )
+                .unwrap(),
+            Some(RegistryKeyValue::Dword(1))
+        );
+        assert!(registry
+            .key_exists(RegistryHive::LocalMachine, r"system\CurrentControlSet")
+            .unwrap());
+        assert!(!registry
+            .key_exists(RegistryHive::CurrentUser, r"SYSTEM\CurrentControlSet")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_ultimate_performance_can_be_duplicated() {
+        let power = FakePower::windows_defaults();
+        assert!(power
+            .schemes()
+            .unwrap()
+            .iter()
+            .all(|scheme| scheme.guid != ULTIMATE_PERFORMANCE_SCHEME_GUID));
+
+        let copy = power
+            .duplicate_scheme(ULTIMATE_PERFORMANCE_SCHEME_GUID)
+            .unwrap();
+        power.set_active_scheme(copy).unwrap();
+        assert_eq!(power.active_scheme().unwrap().name, "Ultimate Performance");
+    }
+
+    #[test]
+    fn test_msr_rejects_missing_processors() {
+        let msr = FakeMsr::new(&CpuTopology::uniform(1, 1, 66, 1));
+        let cpu = |group, index| ProcessorNumber { group, index };
+        msr.write_msr(cpu(1, 1), 0x10, 0xFF).unwrap();
+        assert_eq!(msr.read_msr(cpu(1, 1), 0x10).unwrap(), 0xFF);
+        assert_eq!(msr.read_msr(cpu(0, 1), 0x10).unwrap(), 0);
+        assert!(msr.read_msr(cpu(1, 2), 0x10).is_err());
+    }
+
+    #[test]
+    fn test_pci_smn_index_selects_the_data_register() {
+        let pci = FakePci::new();
+        let smn = |address| PciRegister::Smn { address };
+        pci.set(smn(0x5A000), 0x1234);
+
+        pci.write_config(PciAddress::HOST_BRIDGE, SMN_INDEX, 0x5A000)
+            .unwrap();
+        assert_eq!(
+            pci.read_config(PciAddress::HOST_BRIDGE, SMN_DATA).unwrap(),
+            0x1234
+        );
+        pci.write_config(PciAddress::HOST_BRIDGE, SMN_INDEX, 0x5A004)
+            .unwrap();
+        pci.write_config(PciAddress::HOST_BRIDGE, SMN_DATA, 0xFF)
+            .unwrap();
+        assert_eq!(pci.get(smn(0x5A004)), 0xFF);
+        assert_eq!(pci.get(smn(0x5A000)), 0x1234);
+
+        let missing = PciAddress::new(0, 0x1F, 0);
+        assert_eq!(pci.read_config(missing, 0).unwrap(), u32::MAX);
+        assert!(pci.read_config(PciAddress::HOST_BRIDGE, 0xB9).is_err());
+    }
+}
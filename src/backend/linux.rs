@@ -0,0 +1,303 @@
+// src/backend/linux.rs
+
+//! The Linux implementations of the system backends.
+//!
+//! Linux exposes everything through files: kernel settings and the processor topology
+//! through sysfs and procfs, MSRs through the `msr` module's `/dev/cpu/N/msr`, and PCI
+//! configuration space through sysfs. Every path is resolved under a root directory,
+//! which is `/` on a running system and a copy of the tree in tests.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use super::{HardwareDriver, MsrBackend, PciAddress, PciBackend, SysfsBackend, TopologyBackend};
+use crate::utils::cpu::{parse_ranges, CpuIdentity, CpuTopology, GroupMask, ProcessorNumber};
+
+/// Directory holding a directory per logical processor, `cpu0`, `cpu1` and so on.
+pub const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+/// Returns the backends that act on the running Linux system.
+#[cfg(target_os = "linux")]
+pub fn system_backends() -> super::Backends {
+    use std::sync::Arc;
+
+    use super::driver::DriverChoice;
+
+    let files = Arc::new(LinuxSysfs::new("/"));
+    super::Backends {
+        driver: Arc::new(DriverChoice::from_env(vec![Arc::new(LinuxMsrDriver::new(
+            "/",
+        ))])),
+        topology: files.clone(),
+        sysfs: files,
+        ..super::unsupported::system_backends()
+    }
+}
+
+/// Returns the value a multiple choice sysfs file has selected, which the kernel marks
+/// with brackets, e.g. `madvise` for `always [madvise] never`. Other files are returned
+/// as they are.
+pub fn selected_value(contents: &str) -> &str {
+    let contents = contents.trim();
+    contents
+        .split_once('[')
+        .and_then(|(_, rest)| rest.split_once(']'))
+        .map_or(contents, |(selected, _)| selected.trim())
+}
+
+/// Returns the number Linux gives a logical processor, as in `/dev/cpu/N/msr`.
+fn linux_cpu_number(cpu: ProcessorNumber) -> usize {
+    cpu.group as usize * 64 + cpu.index as usize
+}
+
+/// Returns the processor groups holding a set of Linux processor numbers.
+fn group_masks(cpus: &BTreeSet<usize>) -> Vec<GroupMask> {
+    let mut masks: Vec<GroupMask> = Vec::new();
+    for cpu in cpus {
+        let group = (cpu / 64) as u16;
+        let bit = 1u64 << (cpu % 64);
+        match masks.iter_mut().find(|mask| mask.group == group) {
+            Some(mask) => mask.mask |= bit,
+            None => masks.push(GroupMask { group, mask: bit }),
+        }
+    }
+    masks
+}
+
+/// Reads and writes sysfs and procfs, and reads the processor topology from sysfs.
+pub struct LinuxSysfs {
+    root: PathBuf,
+}
+
+impl LinuxSysfs {
+    /// Resolves every path under `root`, which is `/` on a running system.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns where an absolute path such as `/proc/sys/vm/swappiness` is under the root.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    /// Lays out the online logical processors from their sysfs topology and cache
+    /// directories, with every `ccx_per_ccd` L3 caches making up one die.
+    pub fn read_topology(&self, ccx_per_ccd: usize) -> Result<CpuTopology> {
+        let online = self
+            .read(&format!("{}/online", CPU_DIR))?
+            .with_context(|| format!("{}/online does not exist", CPU_DIR))?;
+        let online = parse_ranges(&online)
+            .with_context(|| format!("Failed to parse {}/online '{}'", CPU_DIR, online))?;
+
+        let mut cores: Vec<BTreeSet<usize>> = Vec::new();
+        let mut l3_caches: Vec<BTreeSet<usize>> = Vec::new();
+        for cpu in &online {
+            let dir = format!("{}/cpu{}", CPU_DIR, cpu);
+            let siblings = match self.read(&format!("{}/topology/thread_siblings_list", dir))? {
+                Some(list) => parse_ranges(&list)
+                    .with_context(|| format!("Failed to parse the siblings of CPU {}", cpu))?,
+                None => BTreeSet::from([*cpu]),
+            };
+            if !cores.contains(&siblings) {
+                cores.push(siblings);
+            }
+
+            for index in self.list(&format!("{}/cache", dir))? {
+                let cache = format!("{}/cache/{}", dir, index);
+                if self.read(&format!("{}/level", cache))?.as_deref() != Some("3") {
+                    continue;
+                }
+                if let Some(list) = self.read(&format!("{}/shared_cpu_list", cache))? {
+                    let shared = parse_ranges(&list)
+                        .with_context(|| format!("Failed to parse the L3 cache of CPU {}", cpu))?;
+                    if !l3_caches.contains(&shared) {
+                        l3_caches.push(shared);
+                    }
+                }
+            }
+        }
+
+        let cores: Vec<GroupMask> = cores.iter().flat_map(group_masks).collect();
+        let l3_caches: Vec<Vec<GroupMask>> = l3_caches.iter().map(group_masks).collect();
+        Ok(CpuTopology::from_masks(&cores, &l3_caches, ccx_per_ccd))
+    }
+}
+
+impl SysfsBackend for LinuxSysfs {
+    fn read(&self, path: &str) -> Result<Option<String>> {
+        match fs::read_to_string(self.resolve(path)) {
+            Ok(contents) => Ok(Some(contents.trim().to_string())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read '{}'", path)),
+        }
+    }
+
+    fn write(&self, path: &str, value: &str) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(self.resolve(path))
+            .with_context(|| format!("Failed to open '{}' for writing", path))?;
+        file.write_all(format!("{}\n", value).as_bytes())
+            .with_context(|| format!("Failed to write '{}' to '{}'", value, path))
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(self.resolve(path)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to list '{}'", path)),
+        };
+        let mut names = entries
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, std::io::Error>>()
+            .with_context(|| format!("Failed to list '{}'", path))?;
+        names.sort();
+        Ok(names)
+    }
+}
+
+impl TopologyBackend for LinuxSysfs {
+    fn topology(&self) -> Result<CpuTopology> {
+        self.read_topology(CpuIdentity::detect().ccx_per_ccd())
+    }
+}
+
+/// Reaches MSRs through the `msr` kernel module's `/dev/cpu/N/msr` files, and PCI
+/// configuration space through `/sys/bus/pci/devices`. Both need root.
+pub struct LinuxMsrDriver {
+    root: PathBuf,
+}
+
+impl LinuxMsrDriver {
+    /// Resolves the device files under `root`, which is `/` on a running system.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn msr_path(&self, cpu: ProcessorNumber) -> PathBuf {
+        self.root
+            .join(format!("dev/cpu/{}/msr", linux_cpu_number(cpu)))
+    }
+
+    fn config_path(&self, address: PciAddress) -> PathBuf {
+        self.root.join(format!(
+            "sys/bus/pci/devices/0000:{:02x}:{:02x}.{:x}/config",
+            address.bus, address.device, address.function
+        ))
+    }
+}
+
+/// Reads `buffer.len()` bytes at `offset` of a device file.
+fn read_at(path: &Path, offset: u64, buffer: &mut [u8]) -> Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open '{}'", path.display()))?;
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(buffer))
+        .with_context(|| format!("Failed to read '{}' at 0x{:X}", path.display(), offset))
+}
+
+/// Writes `bytes` at `offset` of a device file.
+fn write_at(path: &Path, offset: u64, bytes: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open '{}' for writing", path.display()))?;
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.write_all(bytes))
+        .with_context(|| format!("Failed to write '{}' at 0x{:X}", path.display(), offset))
+}
+
+impl MsrBackend for LinuxMsrDriver {
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        read_at(&self.msr_path(cpu), index as u64, &mut bytes)
+            .with_context(|| format!("Failed to read MSR 0x{:X} on {}", index, cpu))?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> Result<()> {
+        write_at(&self.msr_path(cpu), index as u64, &value.to_le_bytes())
+            .with_context(|| format!("Failed to write MSR 0x{:X} on {}", index, cpu))
+    }
+}
+
+impl PciBackend for LinuxMsrDriver {
+    fn read_config(&self, address: PciAddress, offset: u32) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        read_at(&self.config_path(address), offset as u64, &mut bytes)
+            .with_context(|| format!("Failed to read PCI {} offset 0x{:X}", address, offset))?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> Result<()> {
+        write_at(
+            &self.config_path(address),
+            offset as u64,
+            &value.to_le_bytes(),
+        )
+        .with_context(|| format!("Failed to write PCI {} offset 0x{:X}", address, offset))
+    }
+}
+
+impl HardwareDriver for LinuxMsrDriver {
+    fn name(&self) -> &str {
+        "msr"
+    }
+
+    /// Checks that the first processor's MSR file can be opened. The module cannot be
+    /// loaded from here, as that needs `modprobe`.
+    fn load(&self) -> Result<()> {
+        let path = self.root.join("dev/cpu/0/msr");
+        match File::open(&path) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => anyhow::bail!(
+                "'{}' does not exist; load the msr kernel module with `modprobe msr`",
+                path.display()
+            ),
+            Err(e) => Err(e).with_context(|| {
+                format!("Failed to open '{}'; MSR access needs root", path.display())
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::FakeSysfs;
+
+    #[test]
+    fn test_topology_is_read_from_sysfs() {
+        let topology = CpuTopology::uniform(2, 1, 4, 2);
+        let tree = FakeSysfs::new(&topology);
+        let files = LinuxSysfs::new(tree.root());
+
+        assert_eq!(files.read_topology(1).unwrap(), topology);
+        assert_eq!(selected_value("always [madvise] never"), "madvise");
+        assert_eq!(selected_value("schedutil\n"), "schedutil");
+    }
+
+    #[test]
+    fn test_msr_file_is_read_at_the_register_index() {
+        let tree = FakeSysfs::new(&CpuTopology::uniform(1, 1, 2, 1));
+        let driver = LinuxMsrDriver::new(tree.root());
+        assert!(driver.load().is_err());
+
+        let cpu = ProcessorNumber { group: 0, index: 1 };
+        tree.set("/dev/cpu/0/msr", "");
+        tree.set("/dev/cpu/1/msr", "");
+        driver.load().unwrap();
+        driver.write_msr(cpu, 0x1A0, 0x8500_0089).unwrap();
+        assert_eq!(driver.read_msr(cpu, 0x1A0).unwrap(), 0x8500_0089);
+        assert!(driver
+            .read_msr(ProcessorNumber { group: 0, index: 0 }, 0x1A0)
+            .is_err());
+    }
+}
@@ -0,0 +1,665 @@
+// src/backend/mod.rs
+
+//! Access to the parts of the system that tweaks read and change.
+//!
+//! Tweaks never call the operating system directly. They go through the backends
+//! returned here, which are the Win32 implementations on Windows and the file-based
+//! ones from [`linux`] on Linux. A thread can be `scoped` to the fakes from [`fake`]
+//! instead, so that every tweak can be exercised on any platform, and tests that each
+//! use their own fakes can run side by side.
+
+pub mod driver;
+pub mod fake;
+pub mod linux;
+mod unsupported;
+#[cfg(windows)]
+pub mod windows;
+
+use std::{
+    cell::RefCell,
+    fmt,
+    marker::PhantomData,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use raw_cpuid::CpuIdResult;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    power::PowerScheme,
+    utils::{
+        bcd::BcdEntry,
+        cpu::{CpuTopology, ProcessorNumber},
+        registry::RegistryKeyValue,
+    },
+};
+
+/// The root keys of the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RegistryHive {
+    LocalMachine,
+    CurrentUser,
+    ClassesRoot,
+    Users,
+    CurrentConfig,
+}
+
+impl fmt::Display for RegistryHive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RegistryHive::LocalMachine => "HKEY_LOCAL_MACHINE",
+            RegistryHive::CurrentUser => "HKEY_CURRENT_USER",
+            RegistryHive::ClassesRoot => "HKEY_CLASSES_ROOT",
+            RegistryHive::Users => "HKEY_USERS",
+            RegistryHive::CurrentConfig => "HKEY_CURRENT_CONFIG",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A display mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplaySettingsType {
+    pub width: i32,
+    pub height: i32,
+    pub refresh_rate: i32,
+}
+
+impl fmt::Display for DisplaySettingsType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} @ {} Hz",
+            self.width, self.height, self.refresh_rate
+        )
+    }
+}
+
+/// Reads and writes registry values.
+pub trait RegistryBackend: Send + Sync {
+    /// Returns whether `subkey` exists under `hive`.
+    fn key_exists(&self, hive: RegistryHive, subkey: &str) -> Result<bool>;
+
+    /// Reads a value. Returns `Ok(None)` if the value or its key does not exist.
+    fn read_value(
+        &self,
+        hive: RegistryHive,
+        subkey: &str,
+        name: &str,
+    ) -> Result<Option<RegistryKeyValue>>;
+
+    /// Sets a value, creating the key and any missing parent keys.
+    /// `RegistryKeyValue::Deleted` deletes the value instead.
+    fn write_value(
+        &self,
+        hive: RegistryHive,
+        subkey: &str,
+        name: &str,
+        value: &RegistryKeyValue,
+    ) -> Result<()>;
+
+    /// Deletes a value. Succeeds if the value or its key does not exist.
+    fn delete_value(&self, hive: RegistryHive, subkey: &str, name: &str) -> Result<()>;
+}
+
+/// Reads and writes model-specific registers.
+pub trait MsrBackend: Send + Sync {
+    /// Reads an MSR on one logical processor.
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> Result<u64>;
+
+    /// Writes a full MSR on one logical processor.
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> Result<()>;
+}
+
+/// The location of a PCI device: its bus, device and function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PciAddress {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress {
+    /// The host bridge at 00:00.0, through which AMD processors expose SMN.
+    pub const HOST_BRIDGE: PciAddress = PciAddress::new(0, 0, 0);
+
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            bus,
+            device,
+            function,
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02X}:{:02X}.{:X}",
+            self.bus, self.device, self.function
+        )
+    }
+}
+
+/// A 32-bit register reached through PCI configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PciRegister {
+    /// A DWORD of a device's configuration space, at an offset that is a multiple of 4.
+    Config { address: PciAddress, offset: u32 },
+    /// A register of the AMD System Management Network, read and written through the
+    /// index/data pair at offsets B8h and BCh of the host bridge.
+    Smn { address: u32 },
+}
+
+impl fmt::Display for PciRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PciRegister::Config { address, offset } => {
+                write!(f, "PCI {} offset 0x{:X}", address, offset)
+            }
+            PciRegister::Smn { address } => write!(f, "SMN 0x{:08X}", address),
+        }
+    }
+}
+
+/// Reads and writes PCI configuration space.
+pub trait PciBackend: Send + Sync {
+    /// Reads the DWORD at `offset` of a device's configuration space.
+    fn read_config(&self, address: PciAddress, offset: u32) -> Result<u32>;
+
+    /// Writes the DWORD at `offset` of a device's configuration space.
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> Result<()>;
+}
+
+/// A kernel driver that gives access to MSRs and PCI configuration space. WinRing0 is
+/// one; others implement the same trait and are chosen between at runtime by
+/// [`driver::DriverChoice`].
+pub trait HardwareDriver: MsrBackend + PciBackend {
+    /// Name of the driver, e.g. `WinRing0`.
+    fn name(&self) -> &str;
+
+    /// Loads the driver if it is not loaded yet.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the driver can be used.
+    /// - `Err(anyhow::Error)` explaining why it cannot, e.g. because it is missing or
+    ///   Windows blocks it.
+    fn load(&self) -> Result<()>;
+}
+
+/// Reads and writes the kernel's settings files on Linux: sysfs under `/sys` and
+/// procfs under `/proc`. Paths are absolute, e.g. `/proc/sys/vm/swappiness`.
+pub trait SysfsBackend: Send + Sync {
+    /// Reads a file.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(String))` with the contents, surrounding whitespace trimmed.
+    /// - `Ok(None)` if the file does not exist.
+    /// - `Err(anyhow::Error)` if it cannot be read, e.g. without the right permissions.
+    fn read(&self, path: &str) -> Result<Option<String>>;
+
+    /// Writes a file. The kernel creates these files, so the file must exist.
+    fn write(&self, path: &str, value: &str) -> Result<()>;
+
+    /// Returns the names of the entries of a directory, sorted, or an empty list if the
+    /// directory does not exist.
+    fn list(&self, path: &str) -> Result<Vec<String>>;
+}
+
+/// Reads and changes the boot configuration data (BCD) store on Windows. Entries are
+/// named as `bcdedit` accepts them, e.g. `{current}`, and elements by their `bcdedit`
+/// name, e.g. `useplatformclock`. Changes take effect at the next boot.
+pub trait BcdBackend: Send + Sync {
+    /// Reads a boot entry.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(BcdEntry)` with every element the entry sets.
+    /// - `Err(anyhow::Error)` if the store cannot be read or has no such entry.
+    fn read_entry(&self, entry: &str) -> Result<BcdEntry>;
+
+    /// Sets an element of a boot entry.
+    fn set(&self, entry: &str, element: &str, value: &str) -> Result<()>;
+
+    /// Deletes an element of a boot entry, so that Windows uses its default. The
+    /// element must be set.
+    fn delete(&self, entry: &str, element: &str) -> Result<()>;
+}
+
+/// Describes how the logical processors are laid out.
+pub trait TopologyBackend: Send + Sync {
+    /// Returns every logical processor, with its processor group, core, CCX and CCD.
+    fn topology(&self) -> Result<CpuTopology>;
+}
+
+/// Queries the processor.
+pub trait CpuidBackend: Send + Sync {
+    /// Runs CPUID for a leaf (EAX) and subleaf (ECX).
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuIdResult;
+}
+
+/// Runs the CPUID instruction on the current core. CPUID does not need the operating
+/// system, so this is the system backend on every platform.
+pub struct NativeCpuid;
+
+impl CpuidBackend for NativeCpuid {
+    fn cpuid(&self, leaf: u32, subleaf: u32) -> CpuIdResult {
+        raw_cpuid::native_cpuid::cpuid_count(leaf, subleaf)
+    }
+}
+
+/// What a script that ran to the end produced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptOutput {
+    /// Standard output, surrounding whitespace trimmed.
+    pub stdout: String,
+    /// Standard error, surrounding whitespace trimmed.
+    pub stderr: String,
+    pub exit_code: i32,
+    /// How long the script ran.
+    pub duration: Duration,
+}
+
+/// Stops a running script from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every script run with this token to stop. Their processes are killed.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Limits on how a script runs.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOptions {
+    /// Kills the script once it has run this long. `None` waits for it to finish.
+    pub timeout: Option<Duration>,
+    /// Kills the script once cancelled.
+    pub cancel: Option<CancelToken>,
+}
+
+/// Runs scripts.
+pub trait ShellBackend: Send + Sync {
+    /// Runs a PowerShell script, killing it and any processes it started if it runs
+    /// past `options.timeout` or `options.cancel` is cancelled.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(ScriptOutput)` once the script has finished, whatever its exit code and
+    ///   standard error.
+    /// - `Err(anyhow::Error)` if the script could not be started, timed out or was
+    ///   cancelled.
+    fn run_powershell(&self, script: &str, options: &ScriptOptions) -> Result<ScriptOutput>;
+}
+
+/// Queries and controls services.
+pub trait ServiceBackend: Send + Sync {
+    /// Returns whether a service is running.
+    fn is_running(&self, name: &str) -> Result<bool>;
+
+    /// Starts a service.
+    fn start(&self, name: &str) -> Result<()>;
+
+    /// Sends a service the stop control.
+    fn stop(&self, name: &str) -> Result<()>;
+}
+
+/// Lists, switches and edits power schemes.
+pub trait PowerBackend: Send + Sync {
+    /// Returns every power scheme that can be made active.
+    fn schemes(&self) -> Result<Vec<PowerScheme>>;
+
+    /// Returns the active power scheme.
+    fn active_scheme(&self) -> Result<PowerScheme>;
+
+    /// Makes a power scheme active.
+    fn set_active_scheme(&self, guid: u128) -> Result<()>;
+
+    /// Copies a power scheme, including hidden ones such as Ultimate Performance.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(u128)` with the GUID of the copy.
+    /// - `Err(anyhow::Error)` if the scheme does not exist or cannot be copied.
+    fn duplicate_scheme(&self, guid: u128) -> Result<u128>;
+
+    /// Reads the value a power setting has while on AC power.
+    fn read_ac_value_index(&self, scheme: u128, subgroup: u128, setting: u128) -> Result<u32>;
+
+    /// Sets the value a power setting has while on AC power.
+    fn write_ac_value_index(
+        &self,
+        scheme: u128,
+        subgroup: u128,
+        setting: u128,
+        value: u32,
+    ) -> Result<()>;
+
+    /// Puts back the power schemes Windows ships with, removing any others.
+    fn restore_default_schemes(&self) -> Result<()>;
+}
+
+/// Grants and revokes account rights, such as `SeLockMemoryPrivilege`.
+pub trait UserRightsBackend: Send + Sync {
+    /// Returns whether an account holds a right.
+    fn has_right(&self, account: &str, right: &str) -> Result<bool>;
+
+    /// Grants a right to an account, or revokes it. Revoking a right the account
+    /// does not hold succeeds.
+    fn set_right(&self, account: &str, right: &str, grant: bool) -> Result<()>;
+}
+
+/// Lists and changes display modes of the primary display.
+pub trait DisplayBackend: Send + Sync {
+    /// Returns every mode the display supports.
+    fn modes(&self) -> Result<Vec<DisplaySettingsType>>;
+
+    /// Returns the mode the display is in.
+    fn current_mode(&self) -> Result<DisplaySettingsType>;
+
+    /// Switches the display to a mode and saves it as the default.
+    fn set_mode(&self, mode: &DisplaySettingsType) -> Result<()>;
+}
+
+/// Finds, terminates and starts processes.
+pub trait ProcessBackend: Send + Sync {
+    /// Returns whether any process with the given executable name is running.
+    fn is_running(&self, exe_name: &str) -> Result<bool>;
+
+    /// Terminates every process with the given executable name.
+    fn terminate(&self, exe_name: &str) -> Result<()>;
+
+    /// Starts an executable.
+    fn start(&self, path: &str) -> Result<()>;
+}
+
+/// One backend for each part of the system.
+#[derive(Clone)]
+pub struct Backends {
+    pub registry: Arc<dyn RegistryBackend>,
+    /// Reaches both MSRs and PCI configuration space.
+    pub driver: Arc<dyn HardwareDriver>,
+    pub cpuid: Arc<dyn CpuidBackend>,
+    pub topology: Arc<dyn TopologyBackend>,
+    pub shell: Arc<dyn ShellBackend>,
+    pub services: Arc<dyn ServiceBackend>,
+    pub power: Arc<dyn PowerBackend>,
+    pub user_rights: Arc<dyn UserRightsBackend>,
+    pub display: Arc<dyn DisplayBackend>,
+    pub processes: Arc<dyn ProcessBackend>,
+    pub sysfs: Arc<dyn SysfsBackend>,
+    pub bcd: Arc<dyn BcdBackend>,
+}
+
+impl Backends {
+    /// Returns the backends that act on the running system.
+    ///
+    /// Nothing is loaded or opened until a backend is first used.
+    #[cfg(windows)]
+    pub fn system() -> Self {
+        windows::system_backends()
+    }
+
+    /// Returns the backends that act on the running system. Linux has no registry,
+    /// services or power schemes, so only the sysfs, topology and hardware backends work.
+    #[cfg(target_os = "linux")]
+    pub fn system() -> Self {
+        linux::system_backends()
+    }
+
+    /// Returns the backends that act on the running system. Only Windows and Linux
+    /// are supported, so every operation fails.
+    #[cfg(not(any(windows, target_os = "linux")))]
+    pub fn system() -> Self {
+        unsupported::system_backends()
+    }
+}
+
+static SYSTEM_BACKENDS: Lazy<Backends> = Lazy::new(Backends::system);
+
+thread_local! {
+    /// The backends that replace the system ones on this thread, set by `scoped`.
+    static SCOPED_BACKENDS: RefCell<Option<Backends>> = const { RefCell::new(None) };
+}
+
+/// Held for the duration of every MSR access, so that read-modify-write sequences
+/// on different threads do not interleave.
+static MSR_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held for the duration of every PCI access, so that SMN index and data accesses on
+/// different threads do not interleave.
+static PCI_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replaces the backends on the current thread until the returned guard is dropped.
+/// Other threads keep using their own backends.
+pub fn scoped(backends: Backends) -> ScopedBackends {
+    let previous = SCOPED_BACKENDS.with(|scoped| scoped.replace(Some(backends)));
+    ScopedBackends {
+        previous,
+        _thread: PhantomData,
+    }
+}
+
+/// Puts back the backends the thread used before `scoped` when dropped.
+#[must_use = "the backends are only replaced until the guard is dropped"]
+pub struct ScopedBackends {
+    previous: Option<Backends>,
+    /// Keeps the guard on the thread whose backends it replaced.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for ScopedBackends {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        SCOPED_BACKENDS.with(|scoped| *scoped.borrow_mut() = previous);
+    }
+}
+
+/// Runs `f` on the backends of the current thread.
+fn with_current<T>(f: impl FnOnce(&Backends) -> T) -> T {
+    SCOPED_BACKENDS.with(|scoped| match scoped.borrow().as_ref() {
+        Some(backends) => f(backends),
+        None => f(&SYSTEM_BACKENDS),
+    })
+}
+
+/// Returns the backends the current thread uses.
+pub fn current() -> Backends {
+    with_current(Backends::clone)
+}
+
+/// Returns the registry backend.
+pub fn registry() -> Arc<dyn RegistryBackend> {
+    with_current(|backends| backends.registry.clone())
+}
+
+/// Returns the driver that reaches MSRs and PCI configuration space.
+pub fn driver() -> Arc<dyn HardwareDriver> {
+    with_current(|backends| backends.driver.clone())
+}
+
+/// Explains why MSRs and PCI configuration space cannot be reached, loading the
+/// driver if it is not loaded yet.
+///
+/// Returns `None` if the driver loads.
+pub fn hardware_unavailable() -> Option<String> {
+    driver()
+        .load()
+        .err()
+        .map(|e| format!("No hardware access driver could be loaded ({:#})", e))
+}
+
+/// Exclusive access to the MSR backend, returned by `msr`.
+pub struct MsrAccess {
+    backend: Arc<dyn HardwareDriver>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Deref for MsrAccess {
+    type Target = dyn MsrBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
+    }
+}
+
+/// Returns the MSR backend, waiting until no other thread is using it.
+///
+/// # Returns
+///
+/// - `Ok(MsrAccess)` that keeps other threads out until it is dropped.
+/// - `Err(anyhow::Error)` if a thread panicked while holding it.
+pub fn msr() -> Result<MsrAccess> {
+    let lock = MSR_LOCK
+        .lock()
+        .map_err(|e| anyhow::anyhow!("Failed to lock the MSR backend: {:?}", e))?;
+    Ok(MsrAccess {
+        backend: driver(),
+        _lock: lock,
+    })
+}
+
+/// Offset of the SMN index register in the host bridge's configuration space.
+pub(crate) const SMN_INDEX: u32 = 0xB8;
+/// Offset of the SMN data register in the host bridge's configuration space.
+pub(crate) const SMN_DATA: u32 = 0xBC;
+
+/// Exclusive access to the PCI backend, returned by `pci`.
+pub struct PciAccess {
+    backend: Arc<dyn HardwareDriver>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Deref for PciAccess {
+    type Target = dyn PciBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
+    }
+}
+
+impl PciAccess {
+    /// Reads an SMN register by selecting it in the host bridge's index register and
+    /// reading the data register.
+    pub fn read_smn(&self, address: u32) -> Result<u32> {
+        self.write_config(PciAddress::HOST_BRIDGE, SMN_INDEX, address)
+            .with_context(|| format!("Failed to select SMN 0x{:08X}", address))?;
+        self.read_config(PciAddress::HOST_BRIDGE, SMN_DATA)
+            .with_context(|| format!("Failed to read SMN 0x{:08X}", address))
+    }
+
+    /// Writes an SMN register by selecting it in the host bridge's index register and
+    /// writing the data register.
+    pub fn write_smn(&self, address: u32, value: u32) -> Result<()> {
+        self.write_config(PciAddress::HOST_BRIDGE, SMN_INDEX, address)
+            .with_context(|| format!("Failed to select SMN 0x{:08X}", address))?;
+        self.write_config(PciAddress::HOST_BRIDGE, SMN_DATA, value)
+            .with_context(|| format!("Failed to write SMN 0x{:08X}", address))
+    }
+
+    /// Reads a configuration space or SMN register.
+    pub fn read(&self, register: PciRegister) -> Result<u32> {
+        match register {
+            PciRegister::Config { address, offset } => self
+                .read_config(address, offset)
+                .with_context(|| format!("Failed to read {}", register)),
+            PciRegister::Smn { address } => self.read_smn(address),
+        }
+    }
+
+    /// Writes a configuration space or SMN register.
+    pub fn write(&self, register: PciRegister, value: u32) -> Result<()> {
+        match register {
+            PciRegister::Config { address, offset } => self
+                .write_config(address, offset, value)
+                .with_context(|| format!("Failed to write {}", register)),
+            PciRegister::Smn { address } => self.write_smn(address, value),
+        }
+    }
+}
+
+/// Returns the PCI backend, waiting until no other thread is using it.
+///
+/// # Returns
+///
+/// - `Ok(PciAccess)` that keeps other threads out until it is dropped.
+/// - `Err(anyhow::Error)` if a thread panicked while holding it.
+pub fn pci() -> Result<PciAccess> {
+    let lock = PCI_LOCK
+        .lock()
+        .map_err(|e| anyhow::anyhow!("Failed to lock the PCI backend: {:?}", e))?;
+    Ok(PciAccess {
+        backend: driver(),
+        _lock: lock,
+    })
+}
+
+/// Returns the CPUID backend.
+pub fn cpuid() -> Arc<dyn CpuidBackend> {
+    with_current(|backends| backends.cpuid.clone())
+}
+
+/// Returns the topology backend.
+pub fn topology() -> Arc<dyn TopologyBackend> {
+    with_current(|backends| backends.topology.clone())
+}
+
+/// Returns the shell backend.
+pub fn shell() -> Arc<dyn ShellBackend> {
+    with_current(|backends| backends.shell.clone())
+}
+
+/// Returns the service backend.
+pub fn services() -> Arc<dyn ServiceBackend> {
+    with_current(|backends| backends.services.clone())
+}
+
+/// Returns the power scheme backend.
+pub fn power() -> Arc<dyn PowerBackend> {
+    with_current(|backends| backends.power.clone())
+}
+
+/// Returns the user rights backend.
+pub fn user_rights() -> Arc<dyn UserRightsBackend> {
+    with_current(|backends| backends.user_rights.clone())
+}
+
+/// Returns the display backend.
+pub fn display() -> Arc<dyn DisplayBackend> {
+    with_current(|backends| backends.display.clone())
+}
+
+/// Returns the process backend.
+pub fn processes() -> Arc<dyn ProcessBackend> {
+    with_current(|backends| backends.processes.clone())
+}
+
+/// Returns the sysfs and procfs backend.
+pub fn sysfs() -> Arc<dyn SysfsBackend> {
+    with_current(|backends| backends.sysfs.clone())
+}
+
+/// Returns the boot configuration data backend.
+pub fn bcd() -> Arc<dyn BcdBackend> {
+    with_current(|backends| backends.bcd.clone())
+}
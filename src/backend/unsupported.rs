@@ -0,0 +1,236 @@
+// src/backend/unsupported.rs
+
+use anyhow::{bail, Result};
+
+use super::{
+    BcdBackend, DisplayBackend, DisplaySettingsType, HardwareDriver, MsrBackend, PciAddress,
+    PciBackend, PowerBackend, ProcessBackend, RegistryBackend, RegistryHive, ScriptOptions,
+    ScriptOutput, ServiceBackend, ShellBackend, SysfsBackend, TopologyBackend, UserRightsBackend,
+};
+use crate::{
+    power::PowerScheme,
+    utils::{
+        bcd::BcdEntry,
+        cpu::{CpuTopology, ProcessorNumber},
+        registry::RegistryKeyValue,
+    },
+};
+
+/// Stands in for every system backend a platform does not have, such as the registry
+/// on Linux or sysfs on Windows.
+pub(super) struct Unsupported;
+
+fn unsupported<T>(what: &str) -> Result<T> {
+    bail!("{} is not supported on this platform", what)
+}
+
+/// Returns backends that fail at everything, apart from CPUID.
+#[cfg(not(windows))]
+pub fn system_backends() -> super::Backends {
+    use std::sync::Arc;
+
+    use super::{Backends, NativeCpuid};
+
+    let backend = Arc::new(Unsupported);
+    Backends {
+        registry: backend.clone(),
+        driver: backend.clone(),
+        cpuid: Arc::new(NativeCpuid),
+        topology: backend.clone(),
+        shell: backend.clone(),
+        services: backend.clone(),
+        power: backend.clone(),
+        user_rights: backend.clone(),
+        display: backend.clone(),
+        processes: backend.clone(),
+        sysfs: backend.clone(),
+        bcd: backend,
+    }
+}
+
+impl RegistryBackend for Unsupported {
+    fn key_exists(&self, _hive: RegistryHive, _subkey: &str) -> Result<bool> {
+        unsupported("The registry")
+    }
+
+    fn read_value(
+        &self,
+        _hive: RegistryHive,
+        _subkey: &str,
+        _name: &str,
+    ) -> Result<Option<RegistryKeyValue>> {
+        unsupported("The registry")
+    }
+
+    fn write_value(
+        &self,
+        _hive: RegistryHive,
+        _subkey: &str,
+        _name: &str,
+        _value: &RegistryKeyValue,
+    ) -> Result<()> {
+        unsupported("The registry")
+    }
+
+    fn delete_value(&self, _hive: RegistryHive, _subkey: &str, _name: &str) -> Result<()> {
+        unsupported("The registry")
+    }
+}
+
+impl MsrBackend for Unsupported {
+    fn read_msr(&self, _cpu: ProcessorNumber, _index: u32) -> Result<u64> {
+        unsupported("MSR access")
+    }
+
+    fn write_msr(&self, _cpu: ProcessorNumber, _index: u32, _value: u64) -> Result<()> {
+        unsupported("MSR access")
+    }
+}
+
+impl PciBackend for Unsupported {
+    fn read_config(&self, _address: PciAddress, _offset: u32) -> Result<u32> {
+        unsupported("PCI configuration space access")
+    }
+
+    fn write_config(&self, _address: PciAddress, _offset: u32, _value: u32) -> Result<()> {
+        unsupported("PCI configuration space access")
+    }
+}
+
+impl HardwareDriver for Unsupported {
+    fn name(&self) -> &str {
+        "none"
+    }
+
+    fn load(&self) -> Result<()> {
+        unsupported("Hardware access")
+    }
+}
+
+impl TopologyBackend for Unsupported {
+    fn topology(&self) -> Result<CpuTopology> {
+        unsupported("Reading the processor topology")
+    }
+}
+
+impl ShellBackend for Unsupported {
+    fn run_powershell(&self, _script: &str, _options: &ScriptOptions) -> Result<ScriptOutput> {
+        unsupported("PowerShell")
+    }
+}
+
+impl ServiceBackend for Unsupported {
+    fn is_running(&self, _name: &str) -> Result<bool> {
+        unsupported("Service control")
+    }
+
+    fn start(&self, _name: &str) -> Result<()> {
+        unsupported("Service control")
+    }
+
+    fn stop(&self, _name: &str) -> Result<()> {
+        unsupported("Service control")
+    }
+}
+
+impl PowerBackend for Unsupported {
+    fn schemes(&self) -> Result<Vec<PowerScheme>> {
+        unsupported("Power schemes")
+    }
+
+    fn active_scheme(&self) -> Result<PowerScheme> {
+        unsupported("Power schemes")
+    }
+
+    fn set_active_scheme(&self, _guid: u128) -> Result<()> {
+        unsupported("Power schemes")
+    }
+
+    fn duplicate_scheme(&self, _guid: u128) -> Result<u128> {
+        unsupported("Power schemes")
+    }
+
+    fn read_ac_value_index(&self, _scheme: u128, _subgroup: u128, _setting: u128) -> Result<u32> {
+        unsupported("Power schemes")
+    }
+
+    fn write_ac_value_index(
+        &self,
+        _scheme: u128,
+        _subgroup: u128,
+        _setting: u128,
+        _value: u32,
+    ) -> Result<()> {
+        unsupported("Power schemes")
+    }
+
+    fn restore_default_schemes(&self) -> Result<()> {
+        unsupported("Power schemes")
+    }
+}
+
+impl UserRightsBackend for Unsupported {
+    fn has_right(&self, _account: &str, _right: &str) -> Result<bool> {
+        unsupported("Account rights")
+    }
+
+    fn set_right(&self, _account: &str, _right: &str, _grant: bool) -> Result<()> {
+        unsupported("Account rights")
+    }
+}
+
+impl DisplayBackend for Unsupported {
+    fn modes(&self) -> Result<Vec<DisplaySettingsType>> {
+        unsupported("Changing display modes")
+    }
+
+    fn current_mode(&self) -> Result<DisplaySettingsType> {
+        unsupported("Changing display modes")
+    }
+
+    fn set_mode(&self, _mode: &DisplaySettingsType) -> Result<()> {
+        unsupported("Changing display modes")
+    }
+}
+
+impl ProcessBackend for Unsupported {
+    fn is_running(&self, _exe_name: &str) -> Result<bool> {
+        unsupported("Process control")
+    }
+
+    fn terminate(&self, _exe_name: &str) -> Result<()> {
+        unsupported("Process control")
+    }
+
+    fn start(&self, _path: &str) -> Result<()> {
+        unsupported("Process control")
+    }
+}
+
+impl SysfsBackend for Unsupported {
+    fn read(&self, _path: &str) -> Result<Option<String>> {
+        unsupported("Linux kernel settings")
+    }
+
+    fn write(&self, _path: &str, _value: &str) -> Result<()> {
+        unsupported("Linux kernel settings")
+    }
+
+    fn list(&self, _path: &str) -> Result<Vec<String>> {
+        unsupported("Linux kernel settings")
+    }
+}
+
+impl BcdBackend for Unsupported {
+    fn read_entry(&self, _entry: &str) -> Result<BcdEntry> {
+        unsupported("Boot configuration")
+    }
+
+    fn set(&self, _entry: &str, _element: &str, _value: &str) -> Result<()> {
+        unsupported("Boot configuration")
+    }
+
+    fn delete(&self, _entry: &str, _element: &str) -> Result<()> {
+        unsupported("Boot configuration")
+    }
+}
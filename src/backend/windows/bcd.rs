@@ -0,0 +1,57 @@
+// src/backend/windows/bcd.rs
+
+use std::{os::windows::process::CommandExt, process::Command};
+
+use anyhow::{Context, Result};
+use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+use crate::{
+    backend::BcdBackend,
+    utils::bcd::{parse_bcdedit_enum, BcdEntry},
+};
+
+/// The boot configuration data store, through `bcdedit.exe`. Needs administrator rights.
+pub struct BcdEdit;
+
+/// Runs `bcdedit.exe` without a console window.
+///
+/// # Returns
+///
+/// - `Ok(String)` with the standard output.
+/// - `Err(anyhow::Error)` with the message `bcdedit` printed if it fails.
+fn bcdedit(args: &[&str]) -> Result<String> {
+    let output = Command::new("bcdedit.exe")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW.0)
+        .output()
+        .context("Failed to start bcdedit.exe")?;
+    if !output.status.success() {
+        // bcdedit prints its errors to standard output
+        let message = [output.stderr, output.stdout]
+            .iter()
+            .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string())
+            .find(|message| !message.is_empty())
+            .unwrap_or_default();
+        anyhow::bail!("bcdedit {} failed: {}", args.join(" "), message);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl BcdBackend for BcdEdit {
+    fn read_entry(&self, entry: &str) -> Result<BcdEntry> {
+        let output = bcdedit(&["/enum", entry, "/v"])?;
+        parse_bcdedit_enum(&output)
+            .with_context(|| format!("Failed to parse the BCD entry {}", entry))?
+            .into_iter()
+            .next()
+            .with_context(|| format!("The BCD store has no entry {}", entry))
+    }
+
+    fn set(&self, entry: &str, element: &str, value: &str) -> Result<()> {
+        bcdedit(&["/set", entry, element, value]).map(|_| ())
+    }
+
+    fn delete(&self, entry: &str, element: &str) -> Result<()> {
+        bcdedit(&["/deletevalue", entry, element]).map(|_| ())
+    }
+}
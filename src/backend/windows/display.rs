@@ -0,0 +1,156 @@
+// src/backend/windows/display.rs
+
+use std::mem::zeroed;
+
+use anyhow::{anyhow, Result};
+use windows::{
+    core::PCWSTR,
+    Win32::Graphics::Gdi::{
+        ChangeDisplaySettingsW, EnumDisplaySettingsW, CDS_UPDATEREGISTRY, DEVMODEW, DISP_CHANGE,
+        DISP_CHANGE_SUCCESSFUL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT, DM_PELSWIDTH,
+        ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE,
+    },
+};
+
+use crate::backend::{DisplayBackend, DisplaySettingsType};
+
+/// Display modes of the primary display, through GDI.
+pub struct GdiDisplay;
+
+/// Retrieves all available display settings using EnumDisplaySettingsW.
+fn get_display_settings() -> Vec<DisplaySettingsType> {
+    let mut settings = Vec::new();
+    let mut dev_mode: DEVMODEW = unsafe { zeroed() };
+    dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+    let mut mode_num = 0;
+
+    loop {
+        let result = unsafe {
+            EnumDisplaySettingsW(
+                PCWSTR::null(),
+                ENUM_DISPLAY_SETTINGS_MODE(mode_num),
+                &mut dev_mode,
+            )
+        };
+        if !result.as_bool() {
+            break;
+        }
+
+        settings.push(DisplaySettingsType {
+            width: dev_mode.dmPelsWidth as i32,
+            height: dev_mode.dmPelsHeight as i32,
+            refresh_rate: dev_mode.dmDisplayFrequency as i32,
+        });
+
+        mode_num += 1;
+    }
+
+    settings
+}
+
+/// Applies the specified display settings using ChangeDisplaySettingsW.
+/// Returns the DISP_CHANGE result code.
+fn set_display_settings(new_settings: &DisplaySettingsType) -> DISP_CHANGE {
+    let mut dev_mode: DEVMODEW = unsafe { zeroed() };
+    dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+    dev_mode.dmPelsWidth = new_settings.width as u32;
+    dev_mode.dmPelsHeight = new_settings.height as u32;
+    dev_mode.dmDisplayFrequency = new_settings.refresh_rate as u32;
+    dev_mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY;
+
+    unsafe { ChangeDisplaySettingsW(Some(&dev_mode), CDS_UPDATEREGISTRY) }
+}
+
+/// Retrieves the current display settings using EnumDisplaySettingsW with ENUM_CURRENT_SETTINGS.
+fn get_current_display_settings() -> Result<DisplaySettingsType> {
+    let mut dev_mode: DEVMODEW = unsafe { zeroed() };
+    dev_mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+    let result =
+        unsafe { EnumDisplaySettingsW(PCWSTR::null(), ENUM_CURRENT_SETTINGS, &mut dev_mode) };
+    if !result.as_bool() {
+        return Err(anyhow!("Failed to retrieve current display settings."));
+    }
+
+    Ok(DisplaySettingsType {
+        width: dev_mode.dmPelsWidth as i32,
+        height: dev_mode.dmPelsHeight as i32,
+        refresh_rate: dev_mode.dmDisplayFrequency as i32,
+    })
+}
+
+impl DisplayBackend for GdiDisplay {
+    fn modes(&self) -> Result<Vec<DisplaySettingsType>> {
+        Ok(get_display_settings())
+    }
+
+    fn current_mode(&self) -> Result<DisplaySettingsType> {
+        get_current_display_settings()
+    }
+
+    fn set_mode(&self, mode: &DisplaySettingsType) -> Result<()> {
+        let result = set_display_settings(mode);
+        match result {
+            DISP_CHANGE_SUCCESSFUL => Ok(()),
+            _ => Err(anyhow!(
+                "Failed to change the display mode to {}. Error code: {:?}",
+                mode,
+                result
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_state() {
+        let default = get_current_display_settings().unwrap();
+        println!("Current display settings: {:?}", default);
+        let result = set_display_settings(&default);
+        assert_eq!(result, DISP_CHANGE_SUCCESSFUL);
+    }
+
+    #[test]
+    fn test_change_refresh_rate_to_30() {
+        let result = set_display_settings(&DisplaySettingsType {
+            width: 3840,
+            height: 2160,
+            refresh_rate: 30,
+        });
+        assert_eq!(result, DISP_CHANGE_SUCCESSFUL);
+    }
+
+    #[test]
+    fn test_change_refresh_rate_to_60() {
+        let result = set_display_settings(&DisplaySettingsType {
+            width: 3840,
+            height: 2160,
+            refresh_rate: 60,
+        });
+        assert_eq!(result, DISP_CHANGE_SUCCESSFUL);
+    }
+
+    #[test]
+    fn test_res_1024_768_60() {
+        let result = set_display_settings(&DisplaySettingsType {
+            width: 1024,
+            height: 768,
+            refresh_rate: 60,
+        });
+        assert_eq!(result, DISP_CHANGE_SUCCESSFUL);
+    }
+
+    #[test]
+    fn test_4k_60() {
+        let result = set_display_settings(&DisplaySettingsType {
+            width: 3840,
+            height: 2160,
+            refresh_rate: 60,
+        });
+        assert_eq!(result, DISP_CHANGE_SUCCESSFUL);
+    }
+}
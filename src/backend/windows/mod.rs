@@ -0,0 +1,55 @@
+// src/backend/windows/mod.rs
+
+//! The Win32 implementations of the system backends.
+
+mod bcd;
+mod display;
+pub mod power;
+mod powershell;
+mod processes;
+mod registry;
+mod services;
+mod topology;
+mod user_rights;
+pub mod winring0;
+
+use std::sync::Arc;
+
+pub use bcd::BcdEdit;
+pub use display::GdiDisplay;
+pub use power::WindowsPower;
+pub use powershell::WindowsShell;
+pub use processes::WindowsProcesses;
+pub use registry::WindowsRegistry;
+pub use services::WindowsServices;
+pub use topology::WindowsTopology;
+pub use user_rights::LsaUserRights;
+pub use winring0::WinRing0Backend;
+
+use super::{
+    driver::DriverChoice, unsupported::Unsupported, Backends, HardwareDriver, NativeCpuid,
+};
+
+/// Returns every driver that can reach MSRs and PCI configuration space, in the order
+/// they are tried.
+pub fn hardware_drivers() -> Vec<Arc<dyn HardwareDriver>> {
+    vec![Arc::new(WinRing0Backend::default())]
+}
+
+/// Returns the backends that act on the running Windows system.
+pub fn system_backends() -> Backends {
+    Backends {
+        registry: Arc::new(WindowsRegistry),
+        driver: Arc::new(DriverChoice::from_env(hardware_drivers())),
+        cpuid: Arc::new(NativeCpuid),
+        topology: Arc::new(WindowsTopology::default()),
+        shell: Arc::new(WindowsShell),
+        services: Arc::new(WindowsServices),
+        power: Arc::new(WindowsPower),
+        user_rights: Arc::new(LsaUserRights),
+        display: Arc::new(GdiDisplay),
+        processes: Arc::new(WindowsProcesses),
+        sysfs: Arc::new(Unsupported),
+        bcd: Arc::new(BcdEdit),
+    }
+}
@@ -0,0 +1,324 @@
+// src/backend/windows/powershell.rs
+
+use std::{
+    ffi::CString,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use windows::{
+    core::{PCWSTR, PSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, SetHandleInformation, HANDLE, HANDLE_FLAGS, HANDLE_FLAG_INHERIT, TRUE,
+            WAIT_OBJECT_0, WAIT_TIMEOUT,
+        },
+        Security::SECURITY_ATTRIBUTES,
+        Storage::FileSystem::ReadFile,
+        System::{
+            JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            },
+            Pipes::CreatePipe,
+            Threading::{
+                CreateProcessA, GetExitCodeProcess, ResumeThread, TerminateProcess,
+                WaitForSingleObject, CREATE_NO_WINDOW, CREATE_SUSPENDED, PROCESS_INFORMATION,
+                STARTF_USESHOWWINDOW, STARTF_USESTDHANDLES, STARTUPINFOA,
+            },
+        },
+        UI::WindowsAndMessaging::SW_HIDE,
+    },
+};
+
+use crate::{
+    backend::{ScriptOptions, ScriptOutput, ShellBackend},
+    utils::powershell::powershell_command_line,
+};
+
+/// How often a running script is checked for a timeout or cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs scripts in hidden PowerShell processes.
+pub struct WindowsShell;
+
+impl ShellBackend for WindowsShell {
+    fn run_powershell(
+        &self,
+        script: &str,
+        options: &ScriptOptions,
+    ) -> anyhow::Result<ScriptOutput> {
+        execute_powershell_script(script, options)
+    }
+}
+
+/// A handle that can be moved to the thread reading from it.
+struct SendHandle(HANDLE);
+
+// SAFETY: kernel handles may be used from any thread.
+unsafe impl Send for SendHandle {}
+
+/// Closes every handle it holds when dropped, so that early returns do not leak them.
+#[derive(Default)]
+struct Handles(Vec<HANDLE>);
+
+impl Drop for Handles {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            unsafe {
+                let _ = CloseHandle(*handle);
+            }
+        }
+    }
+}
+
+/// Executes a PowerShell script using Windows APIs and captures stdout and stderr separately.
+///
+/// The script runs in a job object that kills it, and every process it started, when the
+/// job is closed. The job is terminated when the script runs past `options.timeout` or
+/// `options.cancel` is cancelled.
+///
+/// # Arguments
+///
+/// * `script` - The PowerShell script to execute.
+/// * `options` - The timeout and cancellation token.
+///
+/// # Returns
+///
+/// * `Ok(ScriptOutput)` with the standard output, standard error and exit code.
+/// * `Err(anyhow::Error)` if the script could not be run, timed out or was cancelled.
+fn execute_powershell_script(
+    script: &str,
+    options: &ScriptOptions,
+) -> anyhow::Result<ScriptOutput> {
+    let started = Instant::now();
+    let mut handles = Handles::default();
+
+    // Step 1: Create security attributes to allow handle inheritance
+    let sa = SECURITY_ATTRIBUTES {
+        nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+        bInheritHandle: TRUE,
+        lpSecurityDescriptor: std::ptr::null_mut(),
+    };
+
+    // Step 2: Create pipes for stdout and stderr
+    let mut stdout_read: HANDLE = HANDLE(std::ptr::null_mut());
+    let mut stdout_write: HANDLE = HANDLE(std::ptr::null_mut());
+    let mut stderr_read: HANDLE = HANDLE(std::ptr::null_mut());
+    let mut stderr_write: HANDLE = HANDLE(std::ptr::null_mut());
+
+    unsafe {
+        // Create stdout pipe
+        CreatePipe(&mut stdout_read, &mut stdout_write, Some(&sa), 0)
+            .context("Failed to create stdout pipe")?;
+        handles.0.extend([stdout_read, stdout_write]);
+
+        // Ensure the read handle is not inherited
+        SetHandleInformation(stdout_read, HANDLE_FLAG_INHERIT.0, HANDLE_FLAGS(0))
+            .context("Failed to set handle information for stdout_read")?;
+
+        // Create stderr pipe
+        CreatePipe(&mut stderr_read, &mut stderr_write, Some(&sa), 0)
+            .context("Failed to create stderr pipe")?;
+        handles.0.extend([stderr_read, stderr_write]);
+
+        // Ensure the read handle is not inherited
+        SetHandleInformation(stderr_read, HANDLE_FLAG_INHERIT.0, HANDLE_FLAGS(0))
+            .context("Failed to set handle information for stderr_read")?;
+    }
+
+    // Step 3: Create a job that kills the script and its children once closed
+    let job = unsafe { CreateJobObjectW(None, PCWSTR::null()) }
+        .context("Failed to create a job object for the PowerShell process")?;
+    handles.0.push(job);
+    let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+    limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    unsafe {
+        SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &limits as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        )
+    }
+    .context("Failed to limit the PowerShell job object")?;
+
+    // Step 4: Set up the STARTUPINFOA structure
+    let startup_info = STARTUPINFOA {
+        cb: std::mem::size_of::<STARTUPINFOA>() as u32,
+        dwFlags: STARTF_USESHOWWINDOW | STARTF_USESTDHANDLES,
+        wShowWindow: SW_HIDE.0 as u16,
+        hStdOutput: stdout_write,
+        hStdError: stderr_write,
+        ..Default::default()
+    };
+
+    // Step 5: Prepare the command line
+    // Holds the script file of a long script until the process has exited
+    let command = powershell_command_line(script)?;
+
+    // Convert to CString to ensure it's null-terminated
+    let command_line_cstr = CString::new(command.command_line.as_str())
+        .with_context(|| "Failed to convert command line to CString")?;
+
+    // Obtain a mutable pointer and cast it to *mut u8
+    let command_line_ptr = command_line_cstr.as_ptr() as *mut u8;
+
+    // Wrap it in PSTR
+    let command_line = PSTR(command_line_ptr);
+
+    // Step 6: Create the PowerShell process, suspended until it is in the job
+    let mut process_info = PROCESS_INFORMATION::default();
+    let success = unsafe {
+        CreateProcessA(
+            None,                                // lpApplicationName
+            command_line,                        // lpCommandLine
+            None,                                // lpProcessAttributes
+            None,                                // lpThreadAttributes
+            TRUE,                                // bInheritHandles
+            CREATE_NO_WINDOW | CREATE_SUSPENDED, // dwCreationFlags
+            None,                                // lpEnvironment
+            None,                                // lpCurrentDirectory
+            &startup_info,                       // lpStartupInfo
+            &mut process_info,                   // lpProcessInformation
+        )
+    };
+
+    if success.is_err() {
+        tracing::error!("Failed to create PowerShell process.");
+        return Err(anyhow::anyhow!("Failed to execute PowerShell script"));
+    }
+    handles.0.push(process_info.hProcess);
+    handles.0.push(process_info.hThread);
+
+    if let Err(e) = unsafe { AssignProcessToJobObject(job, process_info.hProcess) } {
+        unsafe {
+            let _ = TerminateJobObject(job, 1);
+            let _ = TerminateProcess(process_info.hProcess, 1);
+        }
+        return Err(e).context("Failed to add the PowerShell process to its job object");
+    }
+    unsafe { ResumeThread(process_info.hThread) };
+
+    // Close the write ends of the pipes in the parent process, so that the reads below
+    // end once the script and its children exit
+    unsafe {
+        let _ = CloseHandle(stdout_write);
+        let _ = CloseHandle(stderr_write);
+    }
+    // The reader threads close the read ends
+    let pipes = [stdout_read, stdout_write, stderr_read, stderr_write];
+    handles.0.retain(|handle| !pipes.contains(handle));
+
+    // Step 7: Read from stdout and stderr on their own threads, so that neither pipe
+    // fills up while the script is waited for
+    let stdout_reader = spawn_reader(stdout_read);
+    let stderr_reader = spawn_reader(stderr_read);
+
+    // Step 8: Wait for the process to complete, killing it on timeout or cancellation
+    let stopped = loop {
+        let wait_result =
+            unsafe { WaitForSingleObject(process_info.hProcess, POLL_INTERVAL.as_millis() as u32) };
+        if wait_result == WAIT_OBJECT_0 {
+            break None;
+        }
+        if wait_result != WAIT_TIMEOUT {
+            tracing::error!(
+                "WaitForSingleObject failed with return value: {:?}",
+                wait_result
+            );
+            break Some(anyhow::anyhow!("Failed to wait for PowerShell process"));
+        }
+        if let Some(timeout) = options
+            .timeout
+            .filter(|timeout| started.elapsed() > *timeout)
+        {
+            break Some(anyhow::anyhow!(
+                "PowerShell script timed out after {:?}",
+                timeout
+            ));
+        }
+        if options
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            break Some(anyhow::anyhow!("PowerShell script was cancelled"));
+        }
+    };
+
+    if let Some(error) = stopped {
+        tracing::error!("{}; killing the PowerShell process.", error);
+        unsafe {
+            let _ = TerminateJobObject(job, 1);
+        }
+        // The pipes close once every process in the job has exited
+        let _ = stdout_reader.join();
+        let _ = stderr_reader.join();
+        return Err(error);
+    }
+
+    let mut exit_code = 0u32;
+    unsafe { GetExitCodeProcess(process_info.hProcess, &mut exit_code) }
+        .context("Failed to read the exit code of the PowerShell process")?;
+
+    // Processes the script left running are killed with the job, which closes the pipes
+    drop(handles);
+    let stdout = stdout_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Reading stdout panicked"))?
+        .context("Failed to read stdout from PowerShell script")?;
+    let stderr = stderr_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Reading stderr panicked"))?
+        .context("Failed to read stderr from PowerShell script")?;
+
+    Ok(ScriptOutput {
+        stdout: stdout.trim().to_string(),
+        stderr: stderr.trim().to_string(),
+        exit_code: exit_code as i32,
+        duration: started.elapsed(),
+    })
+}
+
+/// Reads a pipe to its end on a new thread, closing it afterwards.
+fn spawn_reader(handle: HANDLE) -> thread::JoinHandle<anyhow::Result<String>> {
+    let handle = SendHandle(handle);
+    thread::spawn(move || {
+        let handle = handle;
+        let output = read_from_pipe(handle.0);
+        unsafe {
+            let _ = CloseHandle(handle.0);
+        }
+        output
+    })
+}
+
+// Helper function to read from a pipe handle until EOF
+fn read_from_pipe(handle: HANDLE) -> anyhow::Result<String> {
+    let mut buffer = [0u8; 4096];
+    let mut output = Vec::new();
+    let mut bytes_read: u32 = 0;
+
+    loop {
+        let success =
+            unsafe { ReadFile(handle, Some(&mut buffer), Some(&mut bytes_read), None).is_ok() };
+
+        if !success {
+            // You might want to handle specific errors here
+            break;
+        }
+
+        if bytes_read == 0 {
+            // EOF reached
+            break;
+        }
+
+        output.extend_from_slice(&buffer[..bytes_read as usize]);
+    }
+
+    let output_str = String::from_utf8_lossy(&output).to_string();
+    Ok(output_str)
+}
@@ -0,0 +1,142 @@
+// src/backend/windows/processes.rs
+
+use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
+
+use anyhow::{Error, Result};
+use tracing::{error, info};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::CloseHandle,
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+                TH32CS_SNAPPROCESS,
+            },
+            Threading::{
+                CreateProcessW, OpenProcess, TerminateProcess, PROCESS_CREATION_FLAGS,
+                PROCESS_INFORMATION, PROCESS_TERMINATE, STARTUPINFOW,
+            },
+        },
+    },
+};
+
+use crate::backend::ProcessBackend;
+
+/// Processes, through the ToolHelp snapshot API.
+pub struct WindowsProcesses;
+
+/// Calls `f` with the PID of every running process whose executable name matches
+/// `exe_name`, until it returns `false`.
+fn for_each_process(exe_name: &str, mut f: impl FnMut(u32) -> bool) -> Result<()> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)? };
+    let mut entry = PROCESSENTRY32W {
+        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+        ..Default::default()
+    };
+
+    if unsafe { Process32FirstW(snapshot, &mut entry).is_ok() } {
+        loop {
+            // Convert the wide string to a Rust String, trimming null terminators.
+            let exe_name_utf16: Vec<u16> = entry
+                .szExeFile
+                .iter()
+                .take_while(|&&c| c != 0)
+                .cloned()
+                .collect();
+            let name = String::from_utf16_lossy(&exe_name_utf16);
+
+            if name.eq_ignore_ascii_case(exe_name) && !f(entry.th32ProcessID) {
+                break;
+            }
+
+            if !unsafe { Process32NextW(snapshot, &mut entry).is_ok() } {
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = unsafe { CloseHandle(snapshot) } {
+        error!("Failed to close snapshot handle: {:?}", e);
+    }
+
+    Ok(())
+}
+
+impl ProcessBackend for WindowsProcesses {
+    fn is_running(&self, exe_name: &str) -> Result<bool> {
+        let mut found = false;
+        for_each_process(exe_name, |_| {
+            found = true;
+            false
+        })?;
+        Ok(found)
+    }
+
+    fn terminate(&self, exe_name: &str) -> Result<()> {
+        for_each_process(exe_name, |pid| {
+            // Open the process with termination rights.
+            match unsafe { OpenProcess(PROCESS_TERMINATE, false, pid) } {
+                Ok(process_handle) if !process_handle.is_invalid() => {
+                    // Terminate the process.
+                    let success = unsafe { TerminateProcess(process_handle, 0).is_ok() };
+                    if success {
+                        info!("Terminated {} with PID {}", exe_name, pid);
+                    } else {
+                        error!("Failed to terminate {} with PID {}", exe_name, pid);
+                    }
+
+                    // Close the process handle.
+                    if let Err(e) = unsafe { CloseHandle(process_handle) } {
+                        error!("Failed to close process handle: {:?}", e);
+                    }
+                }
+                _ => error!("Failed to open {} with PID {}", exe_name, pid),
+            }
+            true
+        })
+    }
+
+    fn start(&self, path: &str) -> Result<()> {
+        let application_name_wide: Vec<u16> = OsStr::new(path)
+            .encode_wide()
+            .chain(std::iter::once(0)) // Null terminator.
+            .collect();
+
+        let mut startup_info: STARTUPINFOW = unsafe { std::mem::zeroed() };
+        startup_info.cb = std::mem::size_of::<STARTUPINFOW>() as u32;
+        let mut process_info: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
+
+        // Create the process.
+        let result = unsafe {
+            CreateProcessW(
+                PCWSTR(application_name_wide.as_ptr()),
+                PWSTR(std::ptr::null_mut()), // lpCommandLine is optional when lpApplicationName is provided.
+                None,
+                None,
+                false,
+                PROCESS_CREATION_FLAGS(0),
+                None,
+                None,
+                &startup_info,
+                &mut process_info,
+            )
+        };
+
+        if result.is_err() {
+            return Err(Error::msg(format!("Failed to start {}", path)));
+        }
+
+        // Close process and thread handles to prevent handle leaks.
+        unsafe {
+            if let Err(e) = CloseHandle(process_info.hProcess) {
+                error!("Failed to close process handle: {:?}", e);
+            }
+            if let Err(e) = CloseHandle(process_info.hThread) {
+                error!("Failed to close thread handle: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+}
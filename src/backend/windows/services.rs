@@ -0,0 +1,269 @@
+// src/backend/windows/services.rs
+
+use std::{
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use tracing::error;
+use widestring::U16CString;
+use windows::{
+    core::PCWSTR,
+    Win32::System::Services::{
+        CloseServiceHandle, ControlService, OpenSCManagerW, OpenServiceW, QueryServiceStatusEx,
+        StartServiceW, SC_HANDLE, SC_MANAGER_ALL_ACCESS, SC_MANAGER_CONNECT,
+        SC_STATUS_PROCESS_INFO, SERVICE_CONTROL_STOP, SERVICE_QUERY_STATUS, SERVICE_START,
+        SERVICE_STATUS, SERVICE_STATUS_PROCESS, SERVICE_STOP, SERVICE_STOPPED,
+    },
+};
+
+use crate::backend::ServiceBackend;
+
+/// The Windows Service Control Manager. Each call opens its own handles and gives
+/// up on any step that takes longer than a second.
+pub struct WindowsServices;
+
+impl ServiceBackend for WindowsServices {
+    /// Returns whether a service is in any state other than stopped.
+    fn is_running(&self, name: &str) -> anyhow::Result<bool> {
+        let scm_handle = open_scm_handle().map_err(|e| anyhow!(e))?;
+        let service_handle = open_service_handle(scm_handle, name)
+            .map_err(|e| anyhow!("Failed to open service '{}': {}", name, e))?;
+        let status = query_service_status(service_handle)
+            .map_err(|e| anyhow!("Failed to query status for service '{}': {}", name, e))?;
+        Ok(status.dwCurrentState != SERVICE_STOPPED)
+    }
+
+    fn start(&self, service_name: &str) -> anyhow::Result<()> {
+        unsafe {
+            // Open the Service Control Manager
+            let scm_handle =
+                match OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT) {
+                    Ok(handle) => handle,
+                    Err(_) => {
+                        return Err(anyhow::anyhow!(
+                            "Failed to open Service Control Manager: {:?}",
+                            windows::core::Error::from_win32()
+                        ));
+                    }
+                };
+
+            // Open the specified service with SERVICE_START and SERVICE_QUERY_STATUS permissions
+            let service_handle = match OpenServiceW(
+                scm_handle,
+                PCWSTR::from_raw(widestring::U16CString::from_str(service_name)?.as_ptr()),
+                SERVICE_START | SERVICE_QUERY_STATUS, // Updated permissions
+            ) {
+                Ok(handle) => handle,
+                Err(_) => {
+                    match CloseServiceHandle(scm_handle) {
+                        Ok(_) => (),
+                        Err(_) => {
+                            panic!(
+                                "Failed to close Service Control Manager handle: {:?}",
+                                windows::core::Error::from_win32()
+                            );
+                        }
+                    }
+                    return Err(anyhow::anyhow!(
+                        "Failed to open service '{}': {:?}",
+                        service_name,
+                        windows::core::Error::from_win32()
+                    ));
+                }
+            };
+
+            // Start the service
+            let start_result = StartServiceW(
+                service_handle, // hservice
+                None,           // lpserviceargvectors
+            );
+
+            // Close handles
+            match CloseServiceHandle(service_handle) {
+                Ok(_) => (),
+                Err(_) => {
+                    panic!(
+                        "Failed to close service handle: {:?}",
+                        windows::core::Error::from_win32()
+                    );
+                }
+            }
+            match CloseServiceHandle(scm_handle) {
+                Ok(_) => (),
+                Err(_) => {
+                    panic!(
+                        "Failed to close Service Control Manager handle: {:?}",
+                        windows::core::Error::from_win32()
+                    );
+                }
+            }
+
+            if start_result.is_ok() {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "Failed to start service '{}': {:?}",
+                    service_name,
+                    windows::core::Error::from_win32()
+                ))
+            }
+        }
+    }
+
+    fn stop(&self, name: &str) -> anyhow::Result<()> {
+        let scm_handle = open_scm_handle().map_err(|e| anyhow!(e))?;
+        let service_handle = open_service_handle(scm_handle, name)
+            .map_err(|e| anyhow!("Failed to open service '{}': {}", name, e))?;
+        stop_service(service_handle)
+            .map_err(|e| anyhow!("Failed to stop service '{}': {}", name, e))
+    }
+}
+
+// Define a shared handle wrapper
+#[derive(Debug, Clone)]
+struct SharedHandle(SC_HANDLE);
+
+// Implement Send and Sync for SharedHandle
+unsafe impl Send for SharedHandle {}
+unsafe impl Sync for SharedHandle {}
+
+// Implement Drop to ensure the handle is closed when the last SharedHandle is dropped
+impl Drop for SharedHandle {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = CloseServiceHandle(self.0) {
+                error!("Failed to close service handle: {:?}", e);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SendSCHandle(Arc<SharedHandle>);
+
+// Implement Send and Sync for SendSCHandle
+unsafe impl Send for SendSCHandle {}
+unsafe impl Sync for SendSCHandle {}
+
+/// Helper function to execute a closure with a timeout.
+fn execute_with_timeout<F, T>(f: F, timeout: Duration) -> Result<T, &'static str>
+where
+    F: FnOnce() -> Result<T, &'static str> + Send + 'static,
+    T: Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let result = f();
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => Err("Operation timed out"),
+    }
+}
+
+/// Attempts to open the Service Control Manager with a timeout.
+fn open_scm_handle() -> Result<SendSCHandle, &'static str> {
+    execute_with_timeout(
+        || {
+            unsafe {
+                let handle = OpenSCManagerW(
+                    None,                  // Local machine
+                    None,                  // ServicesActive database
+                    SC_MANAGER_ALL_ACCESS, // Full access to the service control manager
+                )
+                .map_err(|_| "Failed to open Service Control Manager")?;
+
+                if handle.is_invalid() {
+                    return Err("Failed to open Service Control Manager");
+                }
+
+                Ok(SendSCHandle(Arc::new(SharedHandle(handle))))
+            }
+        },
+        Duration::from_secs(1),
+    )
+}
+
+/// Attempts to open a specific service with a timeout.
+fn open_service_handle(
+    scm_handle: SendSCHandle,
+    service_name: &str,
+) -> Result<SendSCHandle, &'static str> {
+    let service_name_w = U16CString::from_str(service_name)
+        .map_err(|_| "Failed to convert service name to wide string")?;
+    execute_with_timeout(
+        move || {
+            unsafe {
+                let sc_handle = scm_handle.0.clone(); // Clone the Arc
+                let handle = OpenServiceW(
+                    sc_handle.0,
+                    PCWSTR(service_name_w.as_ptr()),
+                    SERVICE_STOP | SERVICE_QUERY_STATUS,
+                )
+                .map_err(|_| "Failed to open service handle")?;
+
+                if handle.is_invalid() {
+                    return Err("Failed to open service handle");
+                }
+
+                Ok(SendSCHandle(Arc::new(SharedHandle(handle))))
+            }
+        },
+        Duration::from_secs(1),
+    )
+}
+
+/// Attempts to query the status of a service with a timeout.
+fn query_service_status(
+    service_handle: SendSCHandle,
+) -> Result<SERVICE_STATUS_PROCESS, &'static str> {
+    execute_with_timeout(
+        move || {
+            unsafe {
+                let sc_handle = service_handle.0.clone();
+                let mut buffer = vec![0u8; std::mem::size_of::<SERVICE_STATUS_PROCESS>()];
+                let mut bytes_needed = 0;
+
+                let result = QueryServiceStatusEx(
+                    sc_handle.0,
+                    SC_STATUS_PROCESS_INFO,
+                    Some(&mut buffer),
+                    &mut bytes_needed,
+                );
+
+                if result.is_err() {
+                    return Err("Failed to query service status");
+                }
+
+                // Safely interpret the buffer as SERVICE_STATUS_PROCESS
+                let status =
+                    std::ptr::read_unaligned(buffer.as_ptr() as *const SERVICE_STATUS_PROCESS);
+
+                Ok(status)
+            }
+        },
+        Duration::from_secs(1),
+    )
+}
+
+/// Attempts to stop a service with a timeout.
+fn stop_service(service_handle: SendSCHandle) -> Result<(), &'static str> {
+    execute_with_timeout(
+        move || unsafe {
+            let sc_handle = service_handle.0.clone();
+            let mut svc_status: SERVICE_STATUS = std::mem::zeroed();
+            let result = ControlService(sc_handle.0, SERVICE_CONTROL_STOP, &mut svc_status);
+
+            if result.is_err() {
+                return Err("Failed to stop service");
+            }
+
+            Ok(())
+        },
+        Duration::from_secs(1),
+    )
+}
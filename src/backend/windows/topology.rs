@@ -0,0 +1,89 @@
+// src/backend/windows/topology.rs
+
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use windows::Win32::System::SystemInformation::{
+    GetLogicalProcessorInformationEx, RelationAll, RelationCache, RelationProcessorCore,
+    GROUP_AFFINITY, SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX,
+};
+
+use crate::{
+    backend::TopologyBackend,
+    utils::cpu::{CpuIdentity, CpuTopology, GroupMask},
+};
+
+/// The processor topology, read once through `GetLogicalProcessorInformationEx`.
+#[derive(Default)]
+pub struct WindowsTopology {
+    topology: OnceCell<CpuTopology>,
+}
+
+impl From<&GROUP_AFFINITY> for GroupMask {
+    fn from(affinity: &GROUP_AFFINITY) -> Self {
+        GroupMask {
+            group: affinity.Group,
+            mask: affinity.Mask as u64,
+        }
+    }
+}
+
+/// Reads the physical cores and L3 caches of every processor group.
+fn read_topology() -> Result<CpuTopology> {
+    let mut length = 0u32;
+    // The first call fails with ERROR_INSUFFICIENT_BUFFER and reports the size needed
+    let _ = unsafe { GetLogicalProcessorInformationEx(RelationAll, None, &mut length) };
+
+    // Allocate in u64s so that the records are suitably aligned
+    let mut buffer = vec![0u64; (length as usize).div_ceil(8)];
+    unsafe {
+        GetLogicalProcessorInformationEx(
+            RelationAll,
+            Some(buffer.as_mut_ptr() as *mut SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX),
+            &mut length,
+        )
+    }
+    .context("Failed to read the logical processor information")?;
+
+    let mut cores = Vec::new();
+    let mut l3_caches = Vec::new();
+    let base = buffer.as_ptr() as *const u8;
+    let mut offset = 0usize;
+    while offset < length as usize {
+        // SAFETY: the records are laid out back to back within the `length` bytes written,
+        // each `Size` bytes long, and their fields match the relationship they report.
+        let info =
+            unsafe { &*(base.add(offset) as *const SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX) };
+        if info.Relationship == RelationProcessorCore {
+            let processor = unsafe { &info.Anonymous.Processor };
+            cores.push(GroupMask::from(&processor.GroupMask[0]));
+        } else if info.Relationship == RelationCache {
+            let cache = unsafe { &info.Anonymous.Cache };
+            if cache.Level == 3 {
+                let masks = unsafe {
+                    std::slice::from_raw_parts(
+                        cache.Anonymous.GroupMasks.as_ptr(),
+                        cache.GroupCount.max(1) as usize,
+                    )
+                };
+                l3_caches.push(masks.iter().map(GroupMask::from).collect());
+            }
+        }
+
+        if info.Size == 0 {
+            break;
+        }
+        offset += info.Size as usize;
+    }
+
+    Ok(CpuTopology::from_masks(
+        &cores,
+        &l3_caches,
+        CpuIdentity::detect().ccx_per_ccd(),
+    ))
+}
+
+impl TopologyBackend for WindowsTopology {
+    fn topology(&self) -> Result<CpuTopology> {
+        self.topology.get_or_try_init(read_topology).cloned()
+    }
+}
@@ -0,0 +1,258 @@
+// src/backend/windows/user_rights.rs
+
+use std::ptr;
+
+use anyhow::Result;
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{GetLastError, NTSTATUS, STATUS_OBJECT_NAME_NOT_FOUND},
+        Security::{
+            Authentication::Identity::{
+                LsaAddAccountRights, LsaClose, LsaEnumerateAccountRights, LsaFreeMemory,
+                LsaNtStatusToWinError, LsaOpenPolicy, LsaRemoveAccountRights, LSA_HANDLE,
+                LSA_OBJECT_ATTRIBUTES, LSA_UNICODE_STRING,
+            },
+            LookupAccountNameW, PSID, SID_NAME_USE,
+        },
+    },
+};
+
+use crate::backend::UserRightsBackend;
+
+/// Group Policy related constants.
+pub static POLICY_CREATE_ACCOUNT: u32 = 0x00000010;
+pub static POLICY_LOOKUP_NAMES: u32 = 0x00000800;
+
+/// Account rights managed through the Local Security Authority.
+pub struct LsaUserRights;
+
+/// Opens the local LSA policy object with the given access rights.
+///
+/// # Returns
+///
+/// - `Ok(LsaHandleGuard)` that closes the handle when dropped.
+/// - `Err(anyhow::Error)` if `LsaOpenPolicy` fails.
+fn open_policy(desired_access: u32) -> Result<LsaHandleGuard> {
+    let object_attributes = LSA_OBJECT_ATTRIBUTES::default();
+    // Initialize the policy handle to zero to avoid using uninitialized memory
+    let mut policy_handle = LSA_HANDLE(0);
+    let status =
+        unsafe { LsaOpenPolicy(None, &object_attributes, desired_access, &mut policy_handle) };
+    if status != NTSTATUS(0) {
+        let win_err = unsafe { LsaNtStatusToWinError(status) };
+        tracing::error!("LsaOpenPolicy failed with error code: {}", win_err);
+        return Err(anyhow::Error::msg(format!(
+            "LsaOpenPolicy failed with error code: {}",
+            win_err
+        )));
+    }
+    Ok(LsaHandleGuard {
+        handle: policy_handle,
+    })
+}
+
+/// Looks up the SID of an account.
+///
+/// # Returns
+///
+/// - `Ok(Vec<u8>)` with the SID, which `PSID` can point into.
+/// - `Err(anyhow::Error)` if `LookupAccountNameW` fails.
+fn lookup_account_sid(account: &str) -> Result<Vec<u8>> {
+    let mut sid_size = 0u32;
+    let mut domain_name_size = 0u32;
+    let mut sid_name_use = SID_NAME_USE(0);
+    let account_wide: Vec<u16> = account.encode_utf16().chain(Some(0)).collect();
+
+    unsafe {
+        // First call to LookupAccountNameW to get buffer sizes
+        let lookup_result = LookupAccountNameW(
+            PCWSTR(ptr::null()),
+            PCWSTR(account_wide.as_ptr()),
+            PSID(ptr::null_mut()),
+            &mut sid_size,
+            PWSTR(ptr::null_mut()),
+            &mut domain_name_size,
+            &mut sid_name_use as *mut _,
+        );
+
+        // Check if the function call failed due to insufficient buffer
+        if lookup_result.is_ok() || GetLastError().0 != 122 {
+            // 122 is ERROR_INSUFFICIENT_BUFFER
+            tracing::error!(
+                "LookupAccountNameW failed to get buffer sizes. Error code: {}",
+                GetLastError().0
+            );
+            return Err(anyhow::Error::msg(format!(
+                "LookupAccountNameW failed to get buffer sizes. Error code: {}",
+                GetLastError().0
+            )));
+        }
+
+        let mut sid_buffer = vec![0u8; sid_size as usize];
+        let mut domain_name_buffer = vec![0u16; domain_name_size as usize];
+
+        // Second call to LookupAccountNameW to get the actual data
+        let lookup_result = LookupAccountNameW(
+            PCWSTR(ptr::null()),
+            PCWSTR(account_wide.as_ptr()),
+            PSID(sid_buffer.as_mut_ptr() as *mut _),
+            &mut sid_size,
+            PWSTR(domain_name_buffer.as_mut_ptr()),
+            &mut domain_name_size,
+            &mut sid_name_use as *mut _,
+        );
+
+        if lookup_result.is_err() {
+            let error_code = GetLastError();
+            tracing::error!("LookupAccountNameW failed. Error code: {}", error_code.0);
+            return Err(anyhow::Error::msg(format!(
+                "LookupAccountNameW failed. Error code: {}",
+                error_code.0
+            )));
+        }
+
+        Ok(sid_buffer)
+    }
+}
+
+impl UserRightsBackend for LsaUserRights {
+    fn has_right(&self, account: &str, right: &str) -> Result<bool> {
+        let policy = open_policy(POLICY_LOOKUP_NAMES)?;
+        let mut sid_buffer = lookup_account_sid(account)?;
+        let sid = PSID(sid_buffer.as_mut_ptr() as *mut _);
+
+        unsafe {
+            // Prepare to enumerate account rights
+            let mut rights_ptr: *mut LSA_UNICODE_STRING = ptr::null_mut();
+            let mut rights_count: u32 = 0;
+
+            // Call LsaEnumerateAccountRights to get the rights assigned to the SID
+            let status =
+                LsaEnumerateAccountRights(policy.handle, sid, &mut rights_ptr, &mut rights_count);
+
+            if status == NTSTATUS(0) {
+                // Create a slice from the returned rights
+                let rights_slice = std::slice::from_raw_parts(rights_ptr, rights_count as usize);
+
+                let right_wide: Vec<u16> = right.encode_utf16().collect();
+
+                // Check if the right is present in the account's rights
+                let has_right = rights_slice.iter().any(|assigned| {
+                    let assigned_str = std::slice::from_raw_parts(
+                        assigned.Buffer.0,
+                        (assigned.Length / 2) as usize,
+                    );
+                    assigned_str == right_wide.as_slice()
+                });
+
+                // Free the memory allocated by LsaEnumerateAccountRights
+                let free_status = LsaFreeMemory(Some(rights_ptr as *mut _));
+                if free_status != NTSTATUS(0) {
+                    tracing::error!(
+                        "LsaFreeMemory failed with error code: {}",
+                        LsaNtStatusToWinError(free_status)
+                    );
+                }
+
+                Ok(has_right)
+            } else if status == STATUS_OBJECT_NAME_NOT_FOUND {
+                // The account has no rights assigned
+                Ok(false)
+            } else {
+                let win_err = LsaNtStatusToWinError(status);
+                tracing::error!(
+                    "LsaEnumerateAccountRights failed with error code: {}",
+                    win_err
+                );
+                Err(anyhow::Error::msg(format!(
+                    "LsaEnumerateAccountRights failed with error code: {}",
+                    win_err
+                )))
+            }
+        }
+    }
+
+    fn set_right(&self, account: &str, right: &str, grant: bool) -> Result<()> {
+        let policy = open_policy(POLICY_CREATE_ACCOUNT | POLICY_LOOKUP_NAMES)?;
+        let mut sid_buffer = lookup_account_sid(account)?;
+        let sid = PSID(sid_buffer.as_mut_ptr() as *mut _);
+
+        let right_wide: Vec<u16> = right.encode_utf16().collect();
+        let right_lsa_string = LSA_UNICODE_STRING {
+            Length: (right_wide.len() * 2) as u16,
+            MaximumLength: (right_wide.len() * 2) as u16,
+            Buffer: PWSTR(right_wide.as_ptr() as *mut _),
+        };
+        let user_rights = [right_lsa_string];
+
+        unsafe {
+            if grant {
+                let status = LsaAddAccountRights(policy.handle, sid, &user_rights);
+                if status != NTSTATUS(0) {
+                    let win_err = LsaNtStatusToWinError(status);
+                    tracing::error!("LsaAddAccountRights failed with error code: {}", win_err);
+                    return Err(anyhow::Error::msg(format!(
+                        "LsaAddAccountRights failed with error code: {}",
+                        win_err
+                    )));
+                }
+                tracing::info!(
+                    "Successfully added privilege '{}' to user '{}'.",
+                    right,
+                    account
+                );
+            } else {
+                let status = LsaRemoveAccountRights(policy.handle, sid, false, Some(&user_rights));
+                if status != NTSTATUS(0) {
+                    let win_err = LsaNtStatusToWinError(status);
+                    // Treat error code 2 (ERROR_FILE_NOT_FOUND) as success
+                    if win_err != 2 {
+                        tracing::error!(
+                            "LsaRemoveAccountRights failed with error code: {}",
+                            win_err
+                        );
+                        return Err(anyhow::Error::msg(format!(
+                            "LsaRemoveAccountRights failed with error code: {}",
+                            win_err
+                        )));
+                    }
+                    tracing::debug!(
+                        "Privilege '{}' was not assigned to user '{}'; nothing to remove.",
+                        right,
+                        account
+                    );
+                } else {
+                    tracing::info!(
+                        "Successfully removed privilege '{}' from user '{}'.",
+                        right,
+                        account
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Guard to ensure the LSA_HANDLE is properly closed.
+pub struct LsaHandleGuard {
+    pub handle: LSA_HANDLE,
+}
+
+impl Drop for LsaHandleGuard {
+    fn drop(&mut self) {
+        unsafe {
+            let status = LsaClose(self.handle);
+            if status != NTSTATUS(0) {
+                tracing::error!(
+                    "LsaClose failed with error code: {}",
+                    LsaNtStatusToWinError(status)
+                );
+            } else {
+                tracing::debug!("Successfully closed LSA_HANDLE.");
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+// src/backend/windows/winring0.rs
+
+use std::ffi::CStr;
+
+use anyhow::Context;
+use cstr::cstr;
+use once_cell::sync::OnceCell;
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::{GetLastError, BOOL, E_FAIL, E_INVALIDARG, HMODULE},
+        System::{
+            LibraryLoader::{GetProcAddress, LoadLibraryW},
+            SystemInformation::GROUP_AFFINITY,
+            Threading::{GetCurrentThread, SetThreadGroupAffinity},
+        },
+    },
+};
+
+use crate::{
+    backend::{HardwareDriver, MsrBackend, PciAddress, PciBackend},
+    utils::{cpu::ProcessorNumber, winring0::WINRING0_DLL},
+};
+
+static INITIALIZE_OLS: &CStr = cstr!("InitializeOls");
+static DEINITIALIZE_OLS: &CStr = cstr!("DeinitializeOls");
+static WRMSR: &CStr = cstr!("Wrmsr");
+static RDMSR: &CStr = cstr!("Rdmsr");
+static READ_PCI_CONFIG_LOCAL: &CStr = cstr!("ReadPciConfigDwordEx");
+static WRITE_PCI_CONFIG_LOCAL: &CStr = cstr!("WritePciConfigDwordEx");
+
+type InitializeOls = unsafe extern "system" fn() -> BOOL;
+type DeinitializeOls = unsafe extern "system" fn();
+type ReadMsr = unsafe extern "system" fn(u32, *mut u32, *mut u32) -> BOOL;
+type WriteMsr = unsafe extern "system" fn(u32, u32, u32) -> BOOL;
+type ReadPciConfigDwordEx = unsafe extern "system" fn(u32, u32, *mut u32) -> BOOL;
+type WritePciConfigDwordEx = unsafe extern "system" fn(u32, u32, u32) -> BOOL;
+
+pub struct WinRing0 {
+    _lib: HMODULE,
+    _initialize: InitializeOls,
+    deinitialize: DeinitializeOls,
+    read_msr: ReadMsr,
+    write_msr: WriteMsr,
+    read_pci_config: ReadPciConfigDwordEx,
+    write_pci_config: WritePciConfigDwordEx,
+}
+
+// Ensure WinRing0 is Send and Sync
+unsafe impl Send for WinRing0 {}
+unsafe impl Sync for WinRing0 {}
+
+impl WinRing0 {
+    pub fn new() -> windows::core::Result<Self> {
+        let winring0_dll_path = std::env::current_dir()?.join(WINRING0_DLL);
+
+        unsafe {
+            let dll_path_w: Vec<u16> = winring0_dll_path
+                .to_str()
+                .ok_or_else(|| Error::new(E_FAIL, "Failed to convert PathBuf to str"))?
+                .encode_utf16()
+                .chain(std::iter::once(0))
+                .collect();
+            let lib = LoadLibraryW(PCWSTR(dll_path_w.as_ptr()))?;
+
+            let _initialize: InitializeOls = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(INITIALIZE_OLS.as_ptr() as *const u8))
+                    .ok_or_else(|| Error::new(E_FAIL, "Failed to get InitializeOls"))?,
+            );
+
+            let deinitialize: DeinitializeOls = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(DEINITIALIZE_OLS.as_ptr() as *const u8))
+                    .ok_or(Error::new(E_FAIL, "Failed to get DeinitializeOls"))?,
+            );
+            let read_msr: ReadMsr = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(RDMSR.as_ptr() as *const u8))
+                    .ok_or(Error::new(E_FAIL, "Failed to get Rdmsr"))?,
+            );
+            let write_msr: WriteMsr = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(WRMSR.as_ptr() as *const u8))
+                    .ok_or(Error::new(E_FAIL, "Failed to get Wrmsr"))?,
+            );
+
+            let read_pci_config: ReadPciConfigDwordEx = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(READ_PCI_CONFIG_LOCAL.as_ptr() as *const u8))
+                    .ok_or(Error::new(E_FAIL, "Failed to get ReadPciConfigDwordEx"))?,
+            );
+
+            let write_pci_config: WritePciConfigDwordEx = std::mem::transmute(
+                GetProcAddress(lib, PCSTR(WRITE_PCI_CONFIG_LOCAL.as_ptr() as *const u8))
+                    .ok_or(Error::new(E_FAIL, "Failed to get WritePciConfigDwordEx"))?,
+            );
+
+            // **Initialize WinRing0**
+            let init_result = _initialize();
+            if !init_result.as_bool() {
+                let error_code = GetLastError();
+                return Err(Error::new(
+                    E_FAIL,
+                    format!(
+                        "Failed to initialize WinRing0. GetLastError: {}",
+                        error_code.0
+                    ),
+                ));
+            }
+
+            Ok(WinRing0 {
+                _lib: lib,
+                _initialize,
+                deinitialize,
+                read_msr,
+                write_msr,
+                read_pci_config,
+                write_pci_config,
+            })
+        }
+    }
+
+    /// Reads the MSR for a specific logical processor
+    pub fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> windows::core::Result<u64> {
+        // Perform the MSR read operation
+        let mut eax: u32 = 0;
+        let mut edx: u32 = 0;
+        let result = on_processor(cpu, || unsafe {
+            (self.read_msr)(index, &mut eax, &mut edx)
+        })?;
+
+        if result.as_bool() {
+            Ok(((edx as u64) << 32) | eax as u64)
+        } else {
+            let error_code = unsafe { GetLastError() };
+            Err(Error::new(
+                E_FAIL,
+                format!(
+                    "Failed to read MSR 0x{:X} on {}. GetLastError: {}",
+                    index, cpu, error_code.0
+                ),
+            ))
+        }
+    }
+
+    /// Writes to the MSR for a specific logical processor
+    pub fn write_msr(
+        &self,
+        cpu: ProcessorNumber,
+        index: u32,
+        value: u64,
+    ) -> windows::core::Result<()> {
+        // Perform the MSR write operation
+        let eax = (value & 0xFFFFFFFF) as u32;
+        let edx = (value >> 32) as u32;
+        let result = on_processor(cpu, || unsafe { (self.write_msr)(index, eax, edx) })?;
+
+        if result.as_bool() {
+            Ok(())
+        } else {
+            let error_code = unsafe { GetLastError() };
+            Err(Error::new(
+                E_FAIL,
+                format!(
+                    "Failed to write MSR 0x{:X} on {}. GetLastError: {}",
+                    index, cpu, error_code.0
+                ),
+            ))
+        }
+    }
+
+    /// Reads a PCI configuration DWORD of the device at `address`
+    pub fn read_pci_config(&self, address: PciAddress, offset: u32) -> windows::core::Result<u32> {
+        unsafe {
+            let mut value: u32 = 0;
+            let result =
+                (self.read_pci_config)(pci_bus_address(address)?, offset, &mut value as *mut u32);
+            if result.as_bool() {
+                Ok(value)
+            } else {
+                Err(Error::new(
+                    E_FAIL,
+                    format!(
+                        "Failed to read PCI config of {} at offset 0x{:X}",
+                        address, offset
+                    ),
+                ))
+            }
+        }
+    }
+
+    /// Writes a PCI configuration DWORD of the device at `address`
+    pub fn write_pci_config(
+        &self,
+        address: PciAddress,
+        offset: u32,
+        value: u32,
+    ) -> windows::core::Result<()> {
+        unsafe {
+            let result = (self.write_pci_config)(pci_bus_address(address)?, offset, value);
+            if result.as_bool() {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    E_FAIL,
+                    format!(
+                        "Failed to write PCI config of {} at offset 0x{:X}",
+                        address, offset
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Encodes a PCI address the way WinRing0 expects it: the bus in bits 15:8, the device
+/// in bits 7:3 and the function in bits 2:0.
+fn pci_bus_address(address: PciAddress) -> windows::core::Result<u32> {
+    if address.device > 0x1F || address.function > 0x7 {
+        return Err(Error::new(
+            E_INVALIDARG,
+            format!("Invalid PCI address {}", address),
+        ));
+    }
+    Ok(((address.bus as u32) << 8) | ((address.device as u32) << 3) | address.function as u32)
+}
+
+/// Runs `f` with the current thread pinned to one logical processor, then restores the
+/// thread's previous affinity.
+///
+/// WinRing0's affinity-mask variants of the MSR calls only reach the first 64 logical
+/// processors, so the thread is moved to the processor's group instead.
+fn on_processor<T>(cpu: ProcessorNumber, f: impl FnOnce() -> T) -> windows::core::Result<T> {
+    let mask = 1usize
+        .checked_shl(cpu.index as u32)
+        .ok_or_else(|| Error::new(E_INVALIDARG, format!("Invalid processor {}", cpu)))?;
+    let affinity = GROUP_AFFINITY {
+        Mask: mask,
+        Group: cpu.group,
+        Reserved: [0; 3],
+    };
+    let mut previous = GROUP_AFFINITY::default();
+
+    unsafe {
+        let thread = GetCurrentThread();
+        SetThreadGroupAffinity(thread, &affinity, Some(&mut previous)).ok()?;
+        let result = f();
+        SetThreadGroupAffinity(thread, &previous, None).ok()?;
+        Ok(result)
+    }
+}
+
+// Drop implementation to deinitialize WinRing0
+impl Drop for WinRing0 {
+    fn drop(&mut self) {
+        unsafe {
+            (self.deinitialize)();
+        }
+    }
+}
+
+/// MSR and PCI configuration space access through the WinRing0 driver, which is loaded
+/// the first time either is read or written.
+#[derive(Default)]
+pub struct WinRing0Backend {
+    driver: OnceCell<WinRing0>,
+}
+
+impl WinRing0Backend {
+    /// Returns the driver, loading it if this is the first access.
+    fn driver(&self) -> anyhow::Result<&WinRing0> {
+        self.driver
+            .get_or_try_init(WinRing0::new)
+            .context("Failed to initialize WinRing0")
+    }
+}
+
+impl MsrBackend for WinRing0Backend {
+    fn read_msr(&self, cpu: ProcessorNumber, index: u32) -> anyhow::Result<u64> {
+        Ok(self.driver()?.read_msr(cpu, index)?)
+    }
+
+    fn write_msr(&self, cpu: ProcessorNumber, index: u32, value: u64) -> anyhow::Result<()> {
+        Ok(self.driver()?.write_msr(cpu, index, value)?)
+    }
+}
+
+impl PciBackend for WinRing0Backend {
+    fn read_config(&self, address: PciAddress, offset: u32) -> anyhow::Result<u32> {
+        Ok(self.driver()?.read_pci_config(address, offset)?)
+    }
+
+    fn write_config(&self, address: PciAddress, offset: u32, value: u32) -> anyhow::Result<()> {
+        Ok(self.driver()?.write_pci_config(address, offset, value)?)
+    }
+}
+
+impl HardwareDriver for WinRing0Backend {
+    fn name(&self) -> &str {
+        "WinRing0"
+    }
+
+    fn load(&self) -> anyhow::Result<()> {
+        self.driver().map(|_| ())
+    }
+}
@@ -0,0 +1,347 @@
+// src/backup.rs
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    backend::PciRegister, tweaks::TweakId, utils::paths::app_data_dir,
+    utils::registry::RegistryKeyValue,
+};
+
+/// Version of the backup file format written by this build.
+///
+/// - 1: PowerShell snapshots hold the text their read script printed.
+/// - 2: PowerShell snapshots hold the read script's output as JSON.
+pub const BACKUP_FORMAT_VERSION: u32 = 2;
+
+/// The values a tweak touches, as they were before the tweak was first changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TweakSnapshot {
+    /// Every registry value the tweak can modify.
+    Registry(Vec<RegistryValueSnapshot>),
+    /// The full MSR words the tweak can modify, per core.
+    Msr(Vec<MsrValueSnapshot>),
+    /// The full PCI configuration space and SMN registers the tweak can modify.
+    Pci(Vec<PciValueSnapshot>),
+    /// The Linux sysfs and procfs files the tweak can modify.
+    Sysfs(Vec<SysfsValueSnapshot>),
+    /// The boot configuration elements the tweak can modify.
+    Bcd(Vec<BcdValueSnapshot>),
+    /// Output of the tweak's PowerShell read script, as JSON.
+    Powershell(String),
+    /// Whether the current user held the policy's privilege.
+    GroupPolicy(bool),
+}
+
+/// A single registry value captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegistryValueSnapshot {
+    pub path: String,
+    pub key: String,
+    /// `None` if the value did not exist.
+    pub value: Option<RegistryKeyValue>,
+}
+
+/// A single MSR word captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MsrValueSnapshot {
+    pub core: usize,
+    pub index: u32,
+    pub value: u64,
+}
+
+/// A single PCI configuration space or SMN register captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PciValueSnapshot {
+    pub register: PciRegister,
+    pub value: u32,
+}
+
+/// A single sysfs or procfs file captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SysfsValueSnapshot {
+    pub path: String,
+    /// The value the file selected, for multiple choice files.
+    pub value: String,
+}
+
+/// A single element of a boot entry captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BcdValueSnapshot {
+    pub entry: String,
+    pub element: String,
+    /// `None` if the entry did not set the element.
+    pub value: Option<String>,
+}
+
+/// A snapshot together with the time it was taken.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// Seconds since the Unix epoch.
+    pub captured_at: u64,
+    pub snapshot: TweakSnapshot,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupFile {
+    version: u32,
+    entries: BTreeMap<String, BackupEntry>,
+}
+
+/// Persisted store of the original values of every tweak that has been changed.
+///
+/// A tweak is only recorded the first time it is changed, so the store always holds
+/// what the system looked like before this tool touched it. The entry is dropped
+/// once the original values have been restored.
+#[derive(Debug, Default)]
+pub struct BackupStore {
+    /// File the store is persisted to, or `None` to keep it in memory only.
+    path: Option<PathBuf>,
+    /// Entries keyed by the name of their `TweakId`, so that backups of tweaks
+    /// unknown to this build are kept.
+    entries: BTreeMap<String, BackupEntry>,
+}
+
+impl BackupStore {
+    /// Creates an empty store that is never written to disk.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Loads the store from `path`. A missing file is treated as an empty store.
+    pub fn load(path: &Path) -> Result<Self> {
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read backups '{}'", path.display()))?;
+            let file: BackupFile = serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse backups '{}'", path.display()))?;
+            if file.version > BACKUP_FORMAT_VERSION {
+                anyhow::bail!(
+                    "Backups '{}' use format version {}, but this build only supports up to version {}",
+                    path.display(),
+                    file.version,
+                    BACKUP_FORMAT_VERSION
+                );
+            }
+            let mut entries = file.entries;
+            if file.version < 2 {
+                for entry in entries.values_mut() {
+                    if let TweakSnapshot::Powershell(text) = &mut entry.snapshot {
+                        *text = powershell_text_as_json(text);
+                    }
+                }
+            }
+            entries
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries,
+        })
+    }
+
+    /// Loads the store from the app data directory.
+    pub fn load_default() -> Result<Self> {
+        Self::load(&app_data_dir()?.join("backups.json"))
+    }
+
+    /// Returns the backup recorded for a tweak, if any.
+    pub fn get(&self, id: TweakId) -> Option<&BackupEntry> {
+        self.entries.get(&id.to_string())
+    }
+
+    /// Returns whether a backup has been recorded for a tweak.
+    pub fn contains(&self, id: TweakId) -> bool {
+        self.entries.contains_key(&id.to_string())
+    }
+
+    /// Returns the IDs of every backed-up tweak known to this build.
+    pub fn ids(&self) -> Vec<TweakId> {
+        self.entries
+            .keys()
+            .filter_map(|key| TweakId::from_str(key).ok())
+            .collect()
+    }
+
+    /// Records a snapshot for a tweak, unless one already exists.
+    ///
+    /// # Returns
+    /// - `Ok(true)` if the snapshot was recorded.
+    /// - `Ok(false)` if the tweak already had a backup.
+    /// - `Err(anyhow::Error)` if the store could not be saved.
+    pub fn record(&mut self, id: TweakId, snapshot: TweakSnapshot) -> Result<bool> {
+        if self.contains(id) {
+            return Ok(false);
+        }
+
+        let captured_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.entries.insert(
+            id.to_string(),
+            BackupEntry {
+                captured_at,
+                snapshot,
+            },
+        );
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Removes the backup of a tweak, once its original values have been restored.
+    pub fn remove(&mut self, id: TweakId) -> Result<()> {
+        if self.entries.remove(&id.to_string()).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the store to disk. The file is replaced atomically so that a crash
+    /// cannot leave a half-written backup behind.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let file = BackupFile {
+            version: BACKUP_FORMAT_VERSION,
+            entries: self.entries.clone(),
+        };
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write backups '{}'", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace backups '{}'", path.display()))
+    }
+}
+
+/// Converts what a read script printed, as recorded by version 1 backups, into the
+/// JSON `ConvertTo-Json` would have given for it. Each line is one value, with `True`,
+/// `False` and whole numbers read the way PowerShell prints them.
+fn powershell_text_as_json(text: &str) -> String {
+    let mut values: Vec<Value> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            if line.eq_ignore_ascii_case("true") {
+                Value::Bool(true)
+            } else if line.eq_ignore_ascii_case("false") {
+                Value::Bool(false)
+            } else if let Ok(number) = line.parse::<i64>() {
+                Value::from(number)
+            } else {
+                Value::from(line)
+            }
+        })
+        .collect();
+    match values.len() {
+        0 => String::new(),
+        1 => values.remove(0).to_string(),
+        _ => Value::Array(values).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry_snapshot(value: Option<RegistryKeyValue>) -> TweakSnapshot {
+        TweakSnapshot::Registry(vec![RegistryValueSnapshot {
+            path: "HKEY_LOCAL_MACHINE\\SYSTEM\\Test".to_string(),
+            key: "Value".to_string(),
+            value,
+        }])
+    }
+
+    #[test]
+    fn test_first_snapshot_is_kept() {
+        let mut store = BackupStore::in_memory();
+        let original = registry_snapshot(Some(RegistryKeyValue::Dword(7)));
+
+        assert!(store
+            .record(TweakId::DisableHPET, original.clone())
+            .unwrap());
+        assert!(!store
+            .record(TweakId::DisableHPET, registry_snapshot(None))
+            .unwrap());
+        assert_eq!(store.get(TweakId::DisableHPET).unwrap().snapshot, original);
+
+        store.remove(TweakId::DisableHPET).unwrap();
+        assert!(!store.contains(TweakId::DisableHPET));
+    }
+
+    #[test]
+    fn test_store_round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("oc_tool_backup_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backups.json");
+
+        let mut store = BackupStore::load(&path).unwrap();
+        store
+            .record(
+                TweakId::DisableHPET,
+                TweakSnapshot::Msr(vec![MsrValueSnapshot {
+                    core: 3,
+                    index: 0xC0011020,
+                    value: 0x0000_0400_0000_0000,
+                }]),
+            )
+            .unwrap();
+        store
+            .record(TweakId::DisableDps, registry_snapshot(None))
+            .unwrap();
+
+        let reloaded = BackupStore::load(&path).unwrap();
+        assert_eq!(reloaded.entries, store.entries);
+        assert_eq!(
+            reloaded.ids(),
+            vec![TweakId::DisableDps, TweakId::DisableHPET]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_version_1_powershell_snapshots_are_read_as_json() {
+        let dir =
+            std::env::temp_dir().join(format!("oc_tool_backup_v1_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backups.json");
+        let entry = |text: &str| serde_json::json!({ "captured_at": 0, "snapshot": { "Powershell": text } });
+        let file = serde_json::json!({
+            "version": 1,
+            "entries": {
+                "DisableDps": entry("Running\r\n"),
+                "DisableRamCompression": entry("True"),
+                "DisableSuperfetch": entry("2\n4\n"),
+                "ProcessIdleTasks": entry(""),
+            },
+        });
+        fs::write(&path, file.to_string()).unwrap();
+
+        let store = BackupStore::load(&path).unwrap();
+        let json = |id| match &store.get(id).unwrap().snapshot {
+            TweakSnapshot::Powershell(json) => json.clone(),
+            snapshot => panic!("{:?}", snapshot),
+        };
+        assert_eq!(json(TweakId::DisableDps), "\"Running\"");
+        assert_eq!(json(TweakId::DisableRamCompression), "true");
+        assert_eq!(json(TweakId::DisableSuperfetch), "[2,4]");
+        assert_eq!(json(TweakId::ProcessIdleTasks), "");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,935 @@
+// src/bin/oc_cli.rs
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use oc_tool::{
+    backup::BackupStore,
+    orchestrator::{TaskOrchestrator, TweakAction, TweakResult, TweakTask},
+    profile::{find_saved_profile, Profile},
+    tweaks::{self, relations, Tweak, TweakId, TweakOption},
+    utils::{
+        cpu::{parse_ranges, CoreSelection},
+        windows::is_elevated,
+    },
+};
+use once_cell::unsync::Lazy;
+use serde::Serialize;
+
+/// Every requested operation succeeded.
+const EXIT_SUCCESS: u8 = 0;
+/// Every requested operation failed, or the command could not run at all.
+const EXIT_FAILURE: u8 = 1;
+/// Some, but not all, of the requested operations failed.
+const EXIT_PARTIAL_FAILURE: u8 = 3;
+/// A change was refused because of the tweaks related to it, and nothing was changed.
+const EXIT_BLOCKED: u8 = 3;
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  all operations succeeded
+  1  all operations failed, or the command could not run
+  2  invalid command line
+  3  some operations failed (partial failure), or a change was blocked by a related tweak";
+
+#[derive(Parser)]
+#[command(
+    name = "oc_cli",
+    version,
+    about = "Headless front end for the OC Tool tweak catalog",
+    after_help = EXIT_CODES_HELP
+)]
+struct Cli {
+    /// Print machine-readable JSON instead of human-readable text.
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every tweak in the catalog together with its options.
+    List,
+    /// Read the current state of the given tweaks, or of every tweak this system supports
+    /// if none are given.
+    Status {
+        /// Tweak IDs to read (e.g. DisableHPET).
+        ids: Vec<String>,
+    },
+    /// Apply a tweak. Toggles default to "Enabled" and actions to "Run".
+    Apply {
+        /// Tweak ID to apply (e.g. DisableHPET).
+        id: String,
+        /// Option to apply (e.g. "Enabled", "Disabled", "Level 2").
+        option: Option<String>,
+        /// Apply even if the change conflicts with an enabled tweak, or puts back a tweak
+        /// an enabled tweak requires.
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        cores: CoreArgs,
+    },
+    /// Show what applying a tweak would change, without changing anything.
+    Plan {
+        /// Tweak ID to plan (e.g. DisableHPET).
+        id: String,
+        /// Option to plan for, with the same default as `apply`.
+        option: Option<String>,
+        #[command(flatten)]
+        cores: CoreArgs,
+    },
+    /// Revert a tweak to its default state.
+    Revert {
+        /// Tweak ID to revert.
+        id: String,
+        /// Revert even if an enabled tweak requires this one.
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        cores: CoreArgs,
+    },
+    /// Put tweaks back to the values they had before oc_tool first changed them.
+    Restore {
+        /// Tweak IDs to restore. Restores every backed-up tweak if none are given.
+        ids: Vec<String>,
+    },
+    /// List changes a previous run was interrupted in the middle of, or resolve them.
+    Recover {
+        /// Undo the modifications every interrupted change made.
+        #[arg(long, conflicts_with = "roll_forward")]
+        roll_back: bool,
+        /// Finish every interrupted change by running it again.
+        #[arg(long)]
+        roll_forward: bool,
+    },
+    /// Apply a saved profile. Only tweaks whose state or cores differ from the profile are
+    /// changed.
+    ApplyProfile {
+        /// Name of a saved profile, or path to a profile file.
+        profile: String,
+        /// Apply even if the profile would leave conflicting tweaks enabled, or put back a
+        /// tweak an enabled tweak requires.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Save the current state of every tweak as a named profile.
+    SaveProfile {
+        /// Name of the profile (e.g. "SuperPi 32M").
+        name: String,
+        /// Write the profile to this file instead of the profiles directory.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// The cores a per-core tweak is applied on. Every core unless either flag is given.
+#[derive(Args)]
+struct CoreArgs {
+    /// Only change these logical processors, e.g. "0-7,16". Per-core (MSR) tweaks only.
+    #[arg(long, conflicts_with = "ccds")]
+    cores: Option<String>,
+    /// Only change the logical processors of these CCDs, e.g. "1". Per-core (MSR) tweaks only.
+    #[arg(long)]
+    ccds: Option<String>,
+}
+
+impl CoreArgs {
+    /// Narrows a per-core tweak down to the cores given, if any were.
+    fn apply_to(&self, id: TweakId, tweak: &Tweak) -> Result<()> {
+        let selection = match (&self.cores, &self.ccds) {
+            (Some(cores), _) => CoreSelection::Cpus(
+                parse_ranges(cores).with_context(|| format!("Invalid --cores '{}'", cores))?,
+            ),
+            (None, Some(ccds)) => CoreSelection::Ccds(
+                parse_ranges(ccds).with_context(|| format!("Invalid --ccds '{}'", ccds))?,
+            ),
+            (None, None) => return Ok(()),
+        };
+        if tweak.method.targets().is_none() {
+            anyhow::bail!("{} is not applied per core", id);
+        }
+        tweak.method.set_targets(selection)
+    }
+}
+
+/// Static description of a tweak, as printed by `list`.
+#[derive(Serialize)]
+struct TweakEntry<'a> {
+    id: String,
+    name: &'a str,
+    category: String,
+    options: Vec<String>,
+    requires_reboot: bool,
+    /// Why the tweak cannot be used on this system, if it cannot.
+    unsupported: Option<String>,
+}
+
+/// Outcome of a single operation on a tweak.
+#[derive(Serialize)]
+struct TaskReport {
+    id: String,
+    name: &'static str,
+    success: bool,
+    state: Option<String>,
+    /// Why the state is "Mixed", one line per setting.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<String>,
+    error: Option<String>,
+    requires_reboot: bool,
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            if cli.json {
+                println!("{}", serde_json::json!({ "error": format!("{:#}", e) }));
+            } else {
+                eprintln!("error: {:#}", e);
+            }
+            ExitCode::from(if e.is::<BlockedChange>() {
+                EXIT_BLOCKED
+            } else {
+                EXIT_FAILURE
+            })
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<u8> {
+    let (mut tweaks, errors) = tweaks::load_all_tweaks();
+    for e in &errors {
+        eprintln!("warning: {:#}", e);
+    }
+
+    if !matches!(cli.command, Command::List) && !is_elevated() {
+        eprintln!("warning: not running as administrator; most tweaks will fail to read or apply");
+    }
+
+    // A single orchestrator serves the whole run, as it keeps the journal locked while
+    // it is open. `list` never needs one.
+    let orchestrator = Lazy::new(TaskOrchestrator::new);
+
+    if !matches!(cli.command, Command::List | Command::Recover { .. }) {
+        let pending = orchestrator.pending_recovery();
+        if !pending.is_empty() {
+            eprintln!(
+                "warning: {} change{} from a previous run did not finish; run `oc_cli recover`",
+                pending.len(),
+                if pending.len() != 1 { "s" } else { "" }
+            );
+        }
+    }
+
+    match &cli.command {
+        Command::List => {
+            list(&tweaks, cli.json)?;
+            Ok(EXIT_SUCCESS)
+        }
+        Command::Status { ids } => {
+            // Without IDs, tweaks this system cannot run are left out rather than failing
+            let ids = if ids.is_empty() {
+                tweaks
+                    .iter()
+                    .filter(|(_, tweak)| tweak.method.unsupported().is_none())
+                    .map(|(id, _)| *id)
+                    .collect()
+            } else {
+                ids.iter()
+                    .map(|id| parse_tweak_id(id))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            let requests = ids
+                .into_iter()
+                .map(|id| (id, TweakAction::ReadInitialState))
+                .collect();
+            let reports = to_reports(&tweaks, run_tasks(&orchestrator, &tweaks, requests)?);
+            print_reports(&reports, cli.json)?;
+            Ok(exit_code(&reports))
+        }
+        Command::Apply {
+            id,
+            option,
+            force,
+            cores,
+        } => {
+            let id = parse_tweak_id(id)?;
+            let tweak = get_tweak(&tweaks, id)?;
+            cores.apply_to(id, tweak)?;
+            let option = match option {
+                Some(value) => parse_option(tweak, value)?,
+                None => default_apply_option(tweak)?,
+            };
+
+            let changes = [(id, option.clone())];
+            read_related_states(&orchestrator, &mut tweaks, &changes)?;
+            check_relations(&tweaks, &changes, *force)?;
+
+            let results = run_tasks(
+                &orchestrator,
+                &tweaks,
+                BTreeMap::from([(id, TweakAction::from(option))]),
+            )?;
+            let reports = to_reports(&tweaks, results);
+            print_reports(&reports, cli.json)?;
+            Ok(exit_code(&reports))
+        }
+        Command::Plan { id, option, cores } => {
+            let id = parse_tweak_id(id)?;
+            let tweak = get_tweak(&tweaks, id)?;
+            cores.apply_to(id, tweak)?;
+            let option = match option {
+                Some(value) => parse_option(tweak, value)?,
+                None => default_apply_option(tweak)?,
+            };
+            let plan = tweak
+                .method
+                .plan(&option)
+                .with_context(|| format!("Failed to plan {} for {}", option, id))?;
+
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "id": id.to_string(),
+                        "option": option.to_string(),
+                        "steps": plan.steps,
+                    }))?
+                );
+            } else {
+                println!("{} -> {}", id, option);
+                for line in plan.to_string().lines() {
+                    println!("  {}", line);
+                }
+            }
+            Ok(EXIT_SUCCESS)
+        }
+        Command::Revert { id, force, cores } => {
+            let id = parse_tweak_id(id)?;
+            let tweak = get_tweak(&tweaks, id)?;
+            cores.apply_to(id, tweak)?;
+
+            // Reverting puts the tweak back to its default, so that is what gets checked
+            let changes = [(
+                id,
+                tweak
+                    .method
+                    .default_option()
+                    .unwrap_or(TweakOption::Enabled(false)),
+            )];
+            read_related_states(&orchestrator, &mut tweaks, &changes)?;
+            check_relations(&tweaks, &changes, *force)?;
+
+            let results = run_tasks(
+                &orchestrator,
+                &tweaks,
+                BTreeMap::from([(id, TweakAction::Disable)]),
+            )?;
+            let reports = to_reports(&tweaks, results);
+            print_reports(&reports, cli.json)?;
+            Ok(exit_code(&reports))
+        }
+        Command::Restore { ids } => {
+            let ids = if ids.is_empty() {
+                BackupStore::load_default()?
+                    .ids()
+                    .into_iter()
+                    .filter(|id| tweaks.contains_key(id))
+                    .collect()
+            } else {
+                ids.iter()
+                    .map(|id| parse_tweak_id(id))
+                    .collect::<Result<Vec<_>>>()?
+            };
+            if ids.is_empty() {
+                if cli.json {
+                    println!("[]");
+                } else {
+                    println!("No original values recorded; nothing to restore");
+                }
+                return Ok(EXIT_SUCCESS);
+            }
+
+            let requests = ids
+                .into_iter()
+                .map(|id| (id, TweakAction::RestoreOriginal))
+                .collect();
+            let reports = to_reports(&tweaks, run_tasks(&orchestrator, &tweaks, requests)?);
+            print_reports(&reports, cli.json)?;
+            Ok(exit_code(&reports))
+        }
+        Command::Recover {
+            roll_back,
+            roll_forward,
+        } => recover(&orchestrator, &tweaks, *roll_back, *roll_forward, cli.json),
+        Command::ApplyProfile { profile, force } => {
+            let profile = resolve_profile(profile)?;
+
+            // Compare against the live system state rather than the tweaks' defaults.
+            let ids: Vec<TweakId> = profile
+                .tweaks
+                .keys()
+                .copied()
+                .filter(|id| tweaks.contains_key(id))
+                .collect();
+            read_states(&orchestrator, &mut tweaks, &ids)?;
+
+            let changes = profile.changes(&tweaks);
+            if changes.is_empty() {
+                if cli.json {
+                    println!("[]");
+                } else {
+                    println!("Profile '{}' is already applied", profile.name);
+                }
+                return Ok(EXIT_SUCCESS);
+            }
+
+            read_related_states(&orchestrator, &mut tweaks, &changes)?;
+            check_relations(&tweaks, &changes, *force)?;
+            if let Some((id, e)) = profile.set_targets(&tweaks, &changes).into_iter().next() {
+                return Err(e.context(format!("Cannot choose the cores {} is applied on", id)));
+            }
+
+            let sequences = relations::order_changes(&tweaks, changes)
+                .into_iter()
+                .map(|sequence| {
+                    sequence
+                        .into_iter()
+                        .map(|(id, option)| (id, TweakAction::from(option)))
+                        .collect()
+                })
+                .collect();
+            let reports = to_reports(&tweaks, run_sequences(&orchestrator, &tweaks, sequences)?);
+            print_reports(&reports, cli.json)?;
+            Ok(exit_code(&reports))
+        }
+        Command::SaveProfile { name, output } => {
+            let ids: Vec<TweakId> = tweaks.keys().copied().collect();
+            let failed = read_states(&orchestrator, &mut tweaks, &ids)?;
+            if !failed.is_empty() {
+                let reports = to_reports(&tweaks, failed);
+                print_reports(&reports, cli.json)?;
+                anyhow::bail!("Could not read the state of every tweak; profile not saved");
+            }
+
+            let profile = Profile::capture(name, &tweaks);
+            let path = match output {
+                Some(path) => {
+                    profile.save(path)?;
+                    path.clone()
+                }
+                None => profile.save_to_profiles_dir()?,
+            };
+
+            if cli.json {
+                println!(
+                    "{}",
+                    serde_json::json!({ "name": profile.name, "path": path, "tweaks": profile.tweaks.len() })
+                );
+            } else {
+                println!(
+                    "Saved profile '{}' ({} tweaks) to {}",
+                    profile.name,
+                    profile.tweaks.len(),
+                    path.display()
+                );
+            }
+            Ok(EXIT_SUCCESS)
+        }
+    }
+}
+
+/// Lists interrupted changes, or rolls every one of them back or forward.
+fn recover(
+    orchestrator: &TaskOrchestrator,
+    tweaks: &BTreeMap<TweakId, Tweak<'static>>,
+    roll_back: bool,
+    roll_forward: bool,
+    json: bool,
+) -> Result<u8> {
+    let pending = orchestrator.pending_recovery();
+
+    if !roll_back && !roll_forward {
+        if json {
+            let entries: Vec<_> = pending
+                .iter()
+                .map(|transaction| {
+                    serde_json::json!({
+                        "id": transaction.tweak,
+                        "action": format!("{:?}", transaction.action),
+                        "modifications": transaction.steps.len(),
+                        "completed": transaction.steps.iter().filter(|(_, done)| *done).count(),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+        } else if pending.is_empty() {
+            println!("No interrupted changes");
+        } else {
+            for transaction in &pending {
+                println!(
+                    "{:<36} {:<20} {} of {} modifications made",
+                    transaction.tweak,
+                    format!("{:?}", transaction.action),
+                    transaction.steps.iter().filter(|(_, done)| *done).count(),
+                    transaction.steps.len()
+                );
+            }
+            println!("Run `oc_cli recover --roll-back` or `oc_cli recover --roll-forward` to resolve them");
+        }
+        return Ok(EXIT_SUCCESS);
+    }
+
+    let mut reports = Vec::new();
+    let mut submitted = 0;
+    for transaction in &pending {
+        let tweak = transaction.id().and_then(|id| tweaks.get(&id));
+        let outcome = if roll_back {
+            orchestrator.roll_back_pending(transaction, tweak.map(|tweak| tweak.method.as_ref()))
+        } else {
+            match tweak {
+                Some(tweak) => orchestrator
+                    .roll_forward_pending(transaction, tweak.method.clone())
+                    .map(|_| submitted += 1),
+                None => Err(anyhow::anyhow!(
+                    "Tweak {} is not available on this system",
+                    transaction.tweak
+                )),
+            }
+        };
+        // Rolled forward changes are reported once their task finishes
+        if roll_back || outcome.is_err() {
+            reports.push(TaskReport {
+                id: transaction.tweak.clone(),
+                name: tweak.map(|tweak| tweak.name).unwrap_or_default(),
+                success: outcome.is_ok(),
+                state: None,
+                details: Vec::new(),
+                error: outcome.err().map(|e| format!("{:#}", e)),
+                requires_reboot: tweak.is_some_and(|tweak| tweak.requires_reboot),
+            });
+        }
+    }
+
+    let mut results = Vec::new();
+    for _ in 0..submitted {
+        results.push(
+            orchestrator
+                .recv_result()
+                .context("Task orchestrator stopped before all tasks completed")?,
+        );
+    }
+    reports.extend(to_reports(tweaks, results));
+
+    print_reports(&reports, json)?;
+    Ok(exit_code(&reports))
+}
+
+/// Prints the static tweak catalog.
+fn list(tweaks: &BTreeMap<TweakId, Tweak<'static>>, json: bool) -> Result<()> {
+    let entries: Vec<TweakEntry> = tweaks
+        .iter()
+        .map(|(id, tweak)| TweakEntry {
+            id: id.to_string(),
+            name: tweak.name,
+            category: format!("{:?}", tweak.category),
+            options: tweak.options.iter().map(|o| o.to_string()).collect(),
+            requires_reboot: tweak.requires_reboot,
+            unsupported: tweak.method.unsupported(),
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        for entry in entries {
+            println!(
+                "{:<36} {:<10} {:<48} [{}]{}{}",
+                entry.id,
+                entry.category,
+                entry.name,
+                entry.options.join(", "),
+                if entry.requires_reboot {
+                    " (reboot)"
+                } else {
+                    ""
+                },
+                entry
+                    .unsupported
+                    .map(|reason| format!(" (unsupported: {})", reason))
+                    .unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Submits one task per request to the orchestrator and waits for all of them to finish.
+/// Results are returned in tweak ID order.
+fn run_tasks(
+    orchestrator: &TaskOrchestrator,
+    tweaks: &BTreeMap<TweakId, Tweak<'static>>,
+    requests: BTreeMap<TweakId, TweakAction>,
+) -> Result<Vec<TweakResult>> {
+    run_sequences(
+        orchestrator,
+        tweaks,
+        requests.into_iter().map(|request| vec![request]).collect(),
+    )
+}
+
+/// Runs sequences of tasks concurrently, with the tasks of each sequence running in
+/// order, and waits for every result.
+fn run_sequences(
+    orchestrator: &TaskOrchestrator,
+    tweaks: &BTreeMap<TweakId, Tweak<'static>>,
+    sequences: Vec<Vec<(TweakId, TweakAction)>>,
+) -> Result<Vec<TweakResult>> {
+    let mut expected = 0;
+    for sequence in dedupe_sequences(sequences) {
+        let tasks = sequence
+            .into_iter()
+            .map(|(id, action)| {
+                Ok(TweakTask {
+                    id,
+                    method: get_tweak(tweaks, id)?.method.clone(),
+                    action,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        expected += tasks.len();
+        orchestrator.submit_sequence(tasks)?;
+    }
+
+    let mut results = BTreeMap::new();
+    for _ in 0..expected {
+        let result = orchestrator
+            .recv_result()
+            .context("Task orchestrator stopped before all tasks completed")?;
+        results.insert(result.id, result);
+    }
+
+    Ok(results.into_values().collect())
+}
+
+/// Drops every task on a tweak that an earlier task already covers, so that each tweak
+/// produces exactly one result.
+fn dedupe_sequences(
+    sequences: Vec<Vec<(TweakId, TweakAction)>>,
+) -> Vec<Vec<(TweakId, TweakAction)>> {
+    let mut seen = BTreeSet::new();
+    sequences
+        .into_iter()
+        .map(|sequence| {
+            sequence
+                .into_iter()
+                .filter(|(id, _)| seen.insert(*id))
+                .collect::<Vec<_>>()
+        })
+        .filter(|sequence| !sequence.is_empty())
+        .collect()
+}
+
+/// Reads the current state of every tweak related to the changed tweaks, so that
+/// their relations can be checked against the live system.
+fn read_related_states(
+    orchestrator: &TaskOrchestrator,
+    tweaks: &mut BTreeMap<TweakId, Tweak<'static>>,
+    changes: &[(TweakId, TweakOption)],
+) -> Result<()> {
+    let mut ids = BTreeSet::new();
+    for (id, _) in changes {
+        ids.extend(relations::related(tweaks, *id, |r| &r.requires));
+        ids.extend(relations::related(tweaks, *id, |r| &r.conflicts_with));
+        ids.extend(relations::related(tweaks, *id, |r| &r.overlaps));
+    }
+    ids.retain(|id| !changes.iter().any(|(changed, _)| changed == id));
+
+    let ids: Vec<TweakId> = ids.into_iter().collect();
+    for failed in read_states(orchestrator, tweaks, &ids)? {
+        eprintln!(
+            "warning: could not read the state of {}, so its relations are not checked",
+            failed.id
+        );
+    }
+    Ok(())
+}
+
+/// Refuses changes that would leave conflicting tweaks enabled or put back a tweak an
+/// enabled tweak requires, unless forced, and warns about missing requirements and
+/// overlapping tweaks.
+fn check_relations(
+    tweaks: &BTreeMap<TweakId, Tweak<'static>>,
+    changes: &[(TweakId, TweakOption)],
+    force: bool,
+) -> Result<()> {
+    let mut states = relations::current_states(tweaks);
+    states.extend(changes.iter().cloned());
+
+    let mut blocked = Vec::new();
+    for (id, option) in changes {
+        let check = relations::check_change(tweaks, &states, *id, option);
+        for message in check.messages(tweaks) {
+            eprintln!("warning: {}: {}", id, message);
+        }
+        if check.is_blocked() {
+            blocked.push(id.to_string());
+        }
+    }
+
+    if !blocked.is_empty() && !force {
+        return Err(BlockedChange(blocked.join(", ")).into());
+    }
+    Ok(())
+}
+
+/// Changes to the listed tweaks were refused because of the tweaks related to them.
+#[derive(Debug, thiserror::Error)]
+#[error("{0} blocked by related tweaks; pass --force to change anyway")]
+struct BlockedChange(String);
+
+/// Reads the current state of the given tweaks and stores it on each tweak.
+///
+/// # Returns
+/// The results of the reads that failed.
+fn read_states(
+    orchestrator: &TaskOrchestrator,
+    tweaks: &mut BTreeMap<TweakId, Tweak<'static>>,
+    ids: &[TweakId],
+) -> Result<Vec<TweakResult>> {
+    let requests = ids
+        .iter()
+        .map(|id| (*id, TweakAction::ReadInitialState))
+        .collect();
+
+    let mut failed = Vec::new();
+    for result in run_tasks(orchestrator, tweaks, requests)? {
+        match (&result.state, tweaks.get_mut(&result.id)) {
+            (Some(state), Some(tweak)) if result.success => tweak.state = state.clone(),
+            _ => failed.push(result),
+        }
+    }
+    Ok(failed)
+}
+
+fn to_reports(
+    tweaks: &BTreeMap<TweakId, Tweak<'static>>,
+    results: Vec<TweakResult>,
+) -> Vec<TaskReport> {
+    results
+        .into_iter()
+        .map(|result| {
+            let tweak = &tweaks[&result.id];
+            let details = match &result.state {
+                Some(TweakOption::Mixed(settings)) => tweaks::describe_mixed(settings),
+                _ => Vec::new(),
+            };
+            TaskReport {
+                id: result.id.to_string(),
+                name: tweak.name,
+                success: result.success,
+                state: result.state.map(|state| state.to_string()),
+                details,
+                error: result.error.map(|e| format!("{:#}", e)),
+                requires_reboot: tweak.requires_reboot,
+            }
+        })
+        .collect()
+}
+
+/// Resolves a profile argument, trying a saved profile name first and then a file path.
+fn resolve_profile(value: &str) -> Result<Profile> {
+    if let Some(profile) = find_saved_profile(value)? {
+        return Ok(profile);
+    }
+
+    let path = PathBuf::from(value);
+    if path.is_file() {
+        return Profile::load(&path);
+    }
+
+    anyhow::bail!(
+        "No saved profile named '{}' and no profile file at that path",
+        value
+    )
+}
+
+fn print_reports(reports: &[TaskReport], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(reports)?);
+        return Ok(());
+    }
+
+    for report in reports {
+        match (&report.state, &report.error) {
+            (_, Some(error)) => {
+                println!("{:<36} {:<20} {}", report.id, "FAILED", error);
+            }
+            (Some(state), None) => {
+                println!("{:<36} {:<20} {}", report.id, state, report.name);
+                for line in &report.details {
+                    println!("    {}", line);
+                }
+            }
+            (None, None) => {
+                println!("{:<36} {:<20} {}", report.id, "OK", report.name);
+            }
+        }
+    }
+
+    let pending_reboot = reports
+        .iter()
+        .filter(|r| r.success && r.requires_reboot)
+        .count();
+    if pending_reboot > 0 {
+        eprintln!(
+            "{} tweak{} may require a restart to take effect",
+            pending_reboot,
+            if pending_reboot != 1 { "s" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+/// Derives the process exit code from a set of task reports.
+fn exit_code(reports: &[TaskReport]) -> u8 {
+    let failed = reports.iter().filter(|r| !r.success).count();
+    if failed == 0 {
+        EXIT_SUCCESS
+    } else if failed == reports.len() {
+        EXIT_FAILURE
+    } else {
+        EXIT_PARTIAL_FAILURE
+    }
+}
+
+fn parse_tweak_id(value: &str) -> Result<TweakId> {
+    TweakId::from_str(value).map_err(|_| {
+        anyhow::anyhow!(
+            "Unknown tweak ID '{}'. Run `oc_cli list` to see all IDs.",
+            value
+        )
+    })
+}
+
+fn get_tweak<'a, 'b>(
+    tweaks: &'a BTreeMap<TweakId, Tweak<'b>>,
+    id: TweakId,
+) -> Result<&'a Tweak<'b>> {
+    tweaks
+        .get(&id)
+        .with_context(|| format!("Tweak {} is not available on this system", id))
+}
+
+/// Resolves a user-supplied option string against the options a tweak offers.
+fn parse_option(tweak: &Tweak, value: &str) -> Result<TweakOption> {
+    let toggle = match value.to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" | "enable" | "enabled" => Some(TweakOption::Enabled(true)),
+        "off" | "false" | "0" | "disable" | "disabled" => Some(TweakOption::Enabled(false)),
+        _ => None,
+    };
+    if let Some(option) = toggle.filter(|option| tweak.options.contains(option)) {
+        return Ok(option);
+    }
+
+    tweak
+        .options
+        .iter()
+        .find(|option| option.to_string().eq_ignore_ascii_case(value))
+        .cloned()
+        .with_context(|| {
+            format!(
+                "'{}' is not an option of '{}'. Valid options: {}",
+                value,
+                tweak.name,
+                tweak
+                    .options
+                    .iter()
+                    .map(|o| format!("\"{}\"", o))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Picks the option `apply` uses when none was given on the command line.
+fn default_apply_option(tweak: &Tweak) -> Result<TweakOption> {
+    [TweakOption::Enabled(true), TweakOption::Run]
+        .into_iter()
+        .find(|option| tweak.options.contains(option))
+        .with_context(|| {
+            format!(
+                "'{}' has several options; choose one of: {}",
+                tweak.name,
+                tweak
+                    .options
+                    .iter()
+                    .map(|o| format!("\"{}\"", o))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeated_tweaks_run_once() {
+        let sequences = vec![
+            vec![
+                (TweakId::DisableHPET, TweakAction::Enable),
+                (TweakId::DisableDps, TweakAction::Disable),
+            ],
+            vec![(TweakId::DisableHPET, TweakAction::Disable)],
+            vec![
+                (TweakId::DisableDps, TweakAction::Enable),
+                (TweakId::TscSyncPolicy, TweakAction::ReadInitialState),
+            ],
+        ];
+        assert_eq!(
+            dedupe_sequences(sequences),
+            vec![
+                vec![
+                    (TweakId::DisableHPET, TweakAction::Enable),
+                    (TweakId::DisableDps, TweakAction::Disable),
+                ],
+                vec![(TweakId::TscSyncPolicy, TweakAction::ReadInitialState)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reverting_a_required_tweak_is_blocked() {
+        let (mut tweaks, _) = tweaks::load_all_tweaks();
+        tweaks
+            .get_mut(&TweakId::EnableMtrrFixedDramAttributes)
+            .unwrap()
+            .state = TweakOption::Enabled(true);
+        let changes = [(
+            TweakId::EnableMtrrFixedDramModification,
+            TweakOption::Enabled(false),
+        )];
+
+        let e = check_relations(&tweaks, &changes, false).unwrap_err();
+        assert!(e.is::<BlockedChange>(), "{:#}", e);
+        check_relations(&tweaks, &changes, true).unwrap();
+    }
+}
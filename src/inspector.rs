@@ -0,0 +1,307 @@
+// src/inspector.rs
+
+//! Reads and edits arbitrary MSRs for the MSR inspector, outside of any tweak.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use tracing::{error, info};
+
+use crate::{
+    backend,
+    tweaks::{
+        msr::{method::MsrField, registers},
+        relations::TweakSetting,
+        Tweak, TweakId,
+    },
+    utils::cpu::LogicalCpu,
+};
+
+/// Parses a hexadecimal number, with or without a `0x` prefix or `_` separators.
+pub fn parse_hex(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let digits = trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .unwrap_or(trimmed)
+        .replace('_', "");
+    u64::from_str_radix(&digits, 16)
+        .with_context(|| format!("'{}' is not a hexadecimal number", trimmed))
+}
+
+/// A named range of bits within an MSR, used to decode its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedField {
+    pub name: String,
+    pub low_bit: u32,
+    pub high_bit: u32,
+    pub description: String,
+}
+
+impl NamedField {
+    /// Returns a modification that writes `value` to the field of MSR `index`.
+    pub fn field(&self, index: u32, value: u64) -> MsrField {
+        MsrField::new(index, self.low_bit..=self.high_bit, value)
+    }
+}
+
+/// Returns the named fields of an MSR: those of the register database, and any other
+/// bits tweaks write, named after the tweak. Each run of adjacent bits a tweak writes
+/// is one field.
+pub fn known_fields(tweaks: &BTreeMap<TweakId, Tweak>, index: u32) -> Vec<NamedField> {
+    let register = registers::register_at(index);
+    let mut fields: Vec<NamedField> = register
+        .map(|register| {
+            register
+                .fields
+                .iter()
+                .map(|field| NamedField {
+                    name: field.name.to_string(),
+                    low_bit: field.low_bit,
+                    high_bit: field.high_bit,
+                    description: field.description.to_string(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for tweak in tweaks.values() {
+        let mut bits: Vec<u32> = tweak
+            .method
+            .settings()
+            .into_iter()
+            .filter_map(|setting| match setting {
+                TweakSetting::MsrBit { index: i, bit }
+                    if i == index && register.and_then(|r| r.field_at(bit)).is_none() =>
+                {
+                    Some(bit)
+                }
+                _ => None,
+            })
+            .collect();
+        bits.sort_unstable();
+        bits.dedup();
+
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for bit in bits {
+            match runs.last_mut() {
+                Some((_, high)) if *high + 1 == bit => *high = bit,
+                _ => runs.push((bit, bit)),
+            }
+        }
+        fields.extend(runs.into_iter().map(|(low_bit, high_bit)| NamedField {
+            name: tweak.name.to_string(),
+            low_bit,
+            high_bit,
+            description: format!("Written by the '{}' tweak", tweak.name),
+        }));
+    }
+    fields.sort_by_key(|field| (field.low_bit, field.high_bit));
+    fields
+}
+
+/// The value of an MSR on one logical processor, or why it could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsrReading {
+    pub cpu: LogicalCpu,
+    pub value: Result<u64, String>,
+}
+
+/// The values of one MSR across a set of logical processors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsrInspection {
+    pub index: u32,
+    pub readings: Vec<MsrReading>,
+}
+
+impl MsrInspection {
+    /// Reads an MSR on each of the given logical processors. A processor that fails to
+    /// read it is recorded with the error rather than stopping the others.
+    pub fn read(index: u32, cpus: &[LogicalCpu]) -> Result<Self> {
+        // Lock the MSR backend to prevent concurrent access
+        let msr = backend::msr()?;
+        let readings = cpus
+            .iter()
+            .map(|cpu| MsrReading {
+                cpu: *cpu,
+                value: msr
+                    .read_msr(cpu.processor, index)
+                    .map_err(|e| format!("{:#}", e)),
+            })
+            .collect();
+        Ok(Self { index, readings })
+    }
+
+    /// Returns the value most processors read, the lowest one on a tie.
+    pub fn common_value(&self) -> Option<u64> {
+        let mut counts: BTreeMap<u64, usize> = BTreeMap::new();
+        for value in self.readings.iter().filter_map(|r| r.value.as_ref().ok()) {
+            *counts.entry(*value).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then(b.cmp(a)))
+            .map(|(value, _)| value)
+    }
+
+    /// Returns the bits that differ from the common value on at least one processor.
+    pub fn differing_bits(&self) -> u64 {
+        let Some(common) = self.common_value() else {
+            return 0;
+        };
+        self.readings
+            .iter()
+            .filter_map(|reading| reading.value.as_ref().ok())
+            .fold(0, |bits, value| bits | (value ^ common))
+    }
+}
+
+/// One processor's MSR value before and after a planned write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWrite {
+    pub cpu: LogicalCpu,
+    pub current: u64,
+    pub new: u64,
+}
+
+/// Works out what writing a field would change on each processor, without writing it.
+pub fn plan_write(field: &MsrField, cpus: &[LogicalCpu]) -> Result<Vec<PlannedWrite>> {
+    let msr = backend::msr()?;
+    cpus.iter()
+        .map(|cpu| {
+            let current = msr
+                .read_msr(cpu.processor, field.index)
+                .with_context(|| format!("Failed to read MSR 0x{:X} on {}", field.index, cpu))?;
+            Ok(PlannedWrite {
+                cpu: *cpu,
+                current,
+                new: field.write(current),
+            })
+        })
+        .collect()
+}
+
+/// The values an MSR had before the inspector wrote it, so that they can be put back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsrWrite {
+    pub index: u32,
+    pub previous: Vec<(LogicalCpu, u64)>,
+}
+
+impl MsrWrite {
+    /// Writes back the value every processor had before the write.
+    pub fn restore(&self) -> Result<()> {
+        let msr = backend::msr()?;
+        for (cpu, value) in &self.previous {
+            msr.write_msr(cpu.processor, self.index, *value)
+                .with_context(|| format!("Failed to restore MSR 0x{:X} on {}", self.index, cpu))?;
+        }
+        info!(
+            "Restored MSR 0x{:X} on {} logical processors.",
+            self.index,
+            self.previous.len()
+        );
+        Ok(())
+    }
+}
+
+/// Writes a field on each of the given processors, leaving every other bit unchanged,
+/// and checks that it reads back.
+///
+/// # Returns
+/// - `Ok(MsrWrite)` holding every processor's previous value.
+/// - `Err(anyhow::Error)` if any access fails, after putting back the processors
+///   already written.
+pub fn write_field(field: &MsrField, cpus: &[LogicalCpu]) -> Result<MsrWrite> {
+    let msr = backend::msr()?;
+    let mut written = MsrWrite {
+        index: field.index,
+        previous: Vec::with_capacity(cpus.len()),
+    };
+
+    let result = cpus.iter().try_for_each(|cpu| -> Result<()> {
+        let current = msr
+            .read_msr(cpu.processor, field.index)
+            .with_context(|| format!("Failed to read MSR 0x{:X} on {}", field.index, cpu))?;
+        let new = field.write(current);
+        written.previous.push((*cpu, current));
+        msr.write_msr(cpu.processor, field.index, new)
+            .with_context(|| format!("Failed to write MSR 0x{:X} on {}", field.index, cpu))?;
+
+        let updated = msr
+            .read_msr(cpu.processor, field.index)
+            .with_context(|| format!("Failed to read MSR 0x{:X} on {}", field.index, cpu))?;
+        if updated != new {
+            anyhow::bail!(
+                "MSR 0x{:X} on {} reads 0x{:016X} after writing 0x{:016X}",
+                field.index,
+                cpu,
+                updated,
+                new
+            );
+        }
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        drop(msr);
+        if let Err(restore_error) = written.restore() {
+            error!(
+                "Failed to put back MSR 0x{:X} after a failed write: {:?}",
+                field.index, restore_error
+            );
+        }
+        return Err(e);
+    }
+
+    info!(
+        "Wrote {} of MSR 0x{:X} on {} logical processors.",
+        field.bits_label(),
+        field.index,
+        cpus.len()
+    );
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tweaks::all_tweaks, utils::cpu::CpuTopology};
+
+    #[test]
+    fn test_differing_bits_against_the_common_value() {
+        let topology = CpuTopology::uniform(1, 1, 4, 1);
+        let values = [Ok(0x10), Ok(0x10), Ok(0x12), Err("no access".to_string())];
+        let inspection = MsrInspection {
+            index: 0xC001_1020,
+            readings: topology
+                .cpus
+                .iter()
+                .zip(values)
+                .map(|(cpu, value)| MsrReading { cpu: *cpu, value })
+                .collect(),
+        };
+        assert_eq!(inspection.common_value(), Some(0x10));
+        assert_eq!(inspection.differing_bits(), 0x2);
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0xC001_1020").unwrap(), 0xC001_1020);
+        assert_eq!(parse_hex(" 1a4 ").unwrap(), 0x1A4);
+        assert!(parse_hex("0x").is_err());
+        assert!(parse_hex("MSR").is_err());
+    }
+
+    #[test]
+    fn test_known_fields_come_from_the_register_database() {
+        let fields = known_fields(&all_tweaks(), 0xC000_0108);
+        let stride = fields
+            .iter()
+            .find(|field| field.low_bit == 1)
+            .expect("bit 1 of the prefetch control MSR is a known field");
+        assert_eq!(stride.high_bit, 1);
+        assert_eq!(stride.name, "L1Stride");
+        assert_eq!(fields.iter().filter(|field| field.low_bit == 1).count(), 1);
+    }
+}
@@ -0,0 +1,672 @@
+// src/journal.rs
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions, TryLockError},
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{
+    backend::{self, PciRegister},
+    orchestrator::TweakAction,
+    tweaks::TweakId,
+    utils::{
+        cpu::CpuTopology,
+        paths::app_data_dir,
+        registry::{
+            create_or_modify_registry_value, delete_registry_value, try_read_registry_value,
+            RegistryKeyValue,
+        },
+    },
+};
+
+/// A single low-level modification made while applying a tweak.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum JournalStep {
+    /// A registry value write or delete. `None` means the value does not exist.
+    Registry {
+        path: String,
+        key: String,
+        before: Option<RegistryKeyValue>,
+        after: Option<RegistryKeyValue>,
+    },
+    /// A full MSR write on one core.
+    Msr {
+        core: usize,
+        index: u32,
+        before: u64,
+        after: u64,
+    },
+    /// A full PCI configuration space or SMN register write.
+    Pci {
+        register: PciRegister,
+        before: u32,
+        after: u32,
+    },
+    /// A write to a Linux sysfs or procfs file.
+    Sysfs {
+        path: String,
+        before: String,
+        after: String,
+    },
+    /// A change to an element of a boot entry. `None` means the element is not set.
+    Bcd {
+        entry: String,
+        element: String,
+        before: Option<String>,
+        after: Option<String>,
+    },
+}
+
+/// One line of the journal file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum JournalRecord {
+    /// A task started changing a tweak.
+    Begin {
+        txn: u64,
+        tweak: String,
+        action: TweakAction,
+    },
+    /// A modification is about to be made.
+    Intent {
+        txn: u64,
+        seq: u32,
+        step: JournalStep,
+    },
+    /// A modification was made.
+    Done { txn: u64, seq: u32 },
+    /// The task finished, whether it succeeded or not.
+    End { txn: u64 },
+    /// The ID the next transaction gets. Written first whenever the file is compacted,
+    /// so that IDs are never reused, even by a later run.
+    Checkpoint { next_txn: u64 },
+}
+
+/// A change that was interrupted before it finished, found when the journal was opened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingTransaction {
+    pub txn: u64,
+    /// Name of the tweak's `TweakId`.
+    pub tweak: String,
+    /// The action that was being performed.
+    pub action: TweakAction,
+    /// Every modification that was started, in order, and whether it completed.
+    pub steps: Vec<(JournalStep, bool)>,
+}
+
+impl PendingTransaction {
+    /// Returns the ID of the tweak that was being changed, if this build knows it.
+    pub fn id(&self) -> Option<TweakId> {
+        TweakId::from_str(&self.tweak).ok()
+    }
+
+    /// Writes back the value every started modification replaced, newest first.
+    ///
+    /// Modifications that never completed are rolled back too, as it is unknown
+    /// whether they reached the system.
+    pub fn roll_back(&self) -> Result<()> {
+        for (step, _) in self.steps.iter().rev() {
+            match step {
+                JournalStep::Registry {
+                    path, key, before, ..
+                } => match before {
+                    Some(value) => {
+                        create_or_modify_registry_value(path, key, value).with_context(|| {
+                            format!("Failed to restore value '{}' in '{}'", key, path)
+                        })?
+                    }
+                    None => {
+                        if try_read_registry_value(path, key)?.is_some() {
+                            delete_registry_value(path, key).with_context(|| {
+                                format!("Failed to delete value '{}' in '{}'", key, path)
+                            })?;
+                        }
+                    }
+                },
+                JournalStep::Msr {
+                    core,
+                    index,
+                    before,
+                    ..
+                } => {
+                    let cpu = *CpuTopology::detect()?.cpu(*core)?;
+                    let msr = backend::msr()?;
+                    msr.write_msr(cpu.processor, *index, *before)
+                        .with_context(|| format!("Failed to write MSR 0x{:X} on {}", index, cpu))?;
+                }
+                JournalStep::Pci {
+                    register, before, ..
+                } => {
+                    backend::pci()?.write(*register, *before)?;
+                }
+                JournalStep::Sysfs { path, before, .. } => {
+                    backend::sysfs().write(path, before)?;
+                }
+                JournalStep::Bcd {
+                    entry,
+                    element,
+                    before,
+                    ..
+                } => {
+                    let bcd = backend::bcd();
+                    match before {
+                        Some(value) => bcd.set(entry, element, value)?,
+                        None if bcd.read_entry(entry)?.get(element).is_some() => {
+                            bcd.delete(entry, element)?
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct JournalInner {
+    /// `None` if the journal is disabled.
+    file: Option<File>,
+    next_txn: u64,
+    /// Transactions of this process that have not ended yet.
+    open: BTreeSet<u64>,
+    /// Interrupted transactions from a previous run that have not been resolved.
+    pending: Vec<PendingTransaction>,
+}
+
+/// Write-ahead journal of tweak changes.
+///
+/// The orchestrator begins a transaction for every task that changes a tweak, and
+/// tweak methods record each individual modification through `record_step` before
+/// and after making it. If the process dies half way through a task, the next
+/// launch finds the unfinished transaction and can roll it forward or back.
+pub struct Journal {
+    inner: Mutex<JournalInner>,
+}
+
+/// The transaction the current thread is recording modifications into.
+struct ActiveTransaction {
+    journal: Arc<Journal>,
+    txn: u64,
+    next_seq: u32,
+}
+
+thread_local! {
+    static ACTIVE_TRANSACTION: RefCell<Option<ActiveTransaction>> = const { RefCell::new(None) };
+}
+
+impl Journal {
+    /// Creates a journal that records nothing.
+    pub fn disabled() -> Self {
+        Self {
+            inner: Mutex::new(JournalInner {
+                file: None,
+                next_txn: 1,
+                open: BTreeSet::new(),
+                pending: Vec::new(),
+            }),
+        }
+    }
+
+    /// Opens the journal at `path`, collecting any transactions a previous run left unfinished.
+    ///
+    /// The file stays locked until the journal is dropped, so that another process
+    /// cannot compact away the records of a change this one is making.
+    ///
+    /// # Returns
+    /// - `Ok(Journal)` holding the lock.
+    /// - `Err(anyhow::Error)` if the file cannot be read, or another process has it open.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open journal '{}'", path.display()))?;
+        file.try_lock().map_err(|e| match e {
+            TryLockError::WouldBlock => anyhow::anyhow!(
+                "Journal '{}' is in use by another instance of oc_tool",
+                path.display()
+            ),
+            TryLockError::Error(e) => anyhow::Error::new(e)
+                .context(format!("Failed to lock journal '{}'", path.display())),
+        })?;
+
+        let records = read_records(&file)?;
+        let next_txn = records
+            .iter()
+            .map(|record| match record {
+                JournalRecord::Begin { txn, .. }
+                | JournalRecord::Intent { txn, .. }
+                | JournalRecord::Done { txn, .. }
+                | JournalRecord::End { txn } => *txn + 1,
+                JournalRecord::Checkpoint { next_txn } => *next_txn,
+            })
+            .max()
+            .unwrap_or(1);
+        let pending = pending_transactions(records);
+
+        // Compact the file down to the records of the unfinished transactions
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write_record(&mut file, &JournalRecord::Checkpoint { next_txn })?;
+        for transaction in &pending {
+            for record in transaction_records(transaction) {
+                write_record(&mut file, &record)?;
+            }
+        }
+
+        Ok(Self {
+            inner: Mutex::new(JournalInner {
+                file: Some(file),
+                next_txn,
+                open: BTreeSet::new(),
+                pending,
+            }),
+        })
+    }
+
+    /// Opens the journal in the app data directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(&app_data_dir()?.join("journal.jsonl"))
+    }
+
+    /// Returns the interrupted transactions that have not been resolved yet.
+    pub fn pending(&self) -> Vec<PendingTransaction> {
+        self.lock()
+            .map(|inner| inner.pending.clone())
+            .unwrap_or_default()
+    }
+
+    /// Marks an interrupted transaction as dealt with, after it was rolled forward or back.
+    pub fn resolve(&self, txn: u64) -> Result<()> {
+        let mut inner = self.lock()?;
+        inner.pending.retain(|transaction| transaction.txn != txn);
+        inner.write(&JournalRecord::End { txn })
+    }
+
+    /// Begins a transaction for a task and makes it the current thread's active
+    /// transaction until the returned guard is dropped.
+    pub fn begin(self: &Arc<Self>, id: TweakId, action: &TweakAction) -> Result<TransactionGuard> {
+        let txn = {
+            let mut inner = self.lock()?;
+            let txn = inner.next_txn;
+            inner.next_txn += 1;
+            inner.write(&JournalRecord::Begin {
+                txn,
+                tweak: id.to_string(),
+                action: action.clone(),
+            })?;
+            inner.open.insert(txn);
+            txn
+        };
+
+        ACTIVE_TRANSACTION.with(|active| {
+            *active.borrow_mut() = Some(ActiveTransaction {
+                journal: self.clone(),
+                txn,
+                next_seq: 0,
+            })
+        });
+        Ok(TransactionGuard {
+            journal: self.clone(),
+            txn,
+        })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, JournalInner>> {
+        self.inner
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Failed to lock journal: {:?}", e))
+    }
+}
+
+impl JournalInner {
+    /// Appends a record and flushes it to disk. Once nothing is left open or
+    /// pending, the file is emptied down to a checkpoint so it does not grow without
+    /// bound.
+    fn write(&mut self, record: &JournalRecord) -> Result<()> {
+        if let JournalRecord::End { txn } = record {
+            self.open.remove(txn);
+        }
+
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+
+        if matches!(record, JournalRecord::End { .. })
+            && self.open.is_empty()
+            && self.pending.is_empty()
+        {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            let checkpoint = JournalRecord::Checkpoint {
+                next_txn: self.next_txn,
+            };
+            return write_record(file, &checkpoint);
+        }
+
+        write_record(file, record)
+    }
+}
+
+/// Ends a transaction when dropped.
+pub struct TransactionGuard {
+    journal: Arc<Journal>,
+    txn: u64,
+}
+
+impl Drop for TransactionGuard {
+    fn drop(&mut self) {
+        ACTIVE_TRANSACTION.with(|active| active.borrow_mut().take());
+        let result = self
+            .journal
+            .lock()
+            .and_then(|mut inner| inner.write(&JournalRecord::End { txn: self.txn }));
+        if let Err(e) = result {
+            error!("Failed to end journal transaction {}: {:?}", self.txn, e);
+        }
+    }
+}
+
+/// Records a modification in the current thread's active transaction and makes it.
+///
+/// The step is written to disk before `f` runs, and marked done after it succeeds.
+/// If no transaction is active, `f` is simply run.
+///
+/// # Returns
+/// - The result of `f`.
+/// - `Err(anyhow::Error)` if the step could not be journaled, in which case `f` is not run.
+pub fn record_step<T>(step: JournalStep, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let active = ACTIVE_TRANSACTION.with(|active| {
+        active.borrow_mut().as_mut().map(|active| {
+            let seq = active.next_seq;
+            active.next_seq += 1;
+            (active.journal.clone(), active.txn, seq)
+        })
+    });
+
+    let Some((journal, txn, seq)) = active else {
+        return f();
+    };
+
+    journal
+        .lock()?
+        .write(&JournalRecord::Intent { txn, seq, step })
+        .context("Failed to journal modification")?;
+
+    let value = f()?;
+
+    if let Err(e) = journal
+        .lock()
+        .and_then(|mut inner| inner.write(&JournalRecord::Done { txn, seq }))
+    {
+        error!(
+            "Failed to mark journal step {}/{} as done: {:?}",
+            txn, seq, e
+        );
+    }
+    Ok(value)
+}
+
+fn write_record(file: &mut File, record: &JournalRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data().context("Failed to flush journal to disk")
+}
+
+/// Reads every record from the journal. A torn final line from a crash mid-write
+/// is skipped.
+fn read_records(file: &File) -> Result<Vec<JournalRecord>> {
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping unreadable journal record '{}': {:?}", line, e),
+        }
+    }
+    Ok(records)
+}
+
+/// Collects the transactions that were begun but never ended.
+fn pending_transactions(records: Vec<JournalRecord>) -> Vec<PendingTransaction> {
+    let mut transactions: BTreeMap<u64, PendingTransaction> = BTreeMap::new();
+    let mut seqs: BTreeMap<(u64, u32), usize> = BTreeMap::new();
+
+    for record in records {
+        match record {
+            JournalRecord::Begin { txn, tweak, action } => {
+                transactions.insert(
+                    txn,
+                    PendingTransaction {
+                        txn,
+                        tweak,
+                        action,
+                        steps: Vec::new(),
+                    },
+                );
+            }
+            JournalRecord::Intent { txn, seq, step } => {
+                if let Some(transaction) = transactions.get_mut(&txn) {
+                    seqs.insert((txn, seq), transaction.steps.len());
+                    transaction.steps.push((step, false));
+                }
+            }
+            JournalRecord::Done { txn, seq } => {
+                if let (Some(transaction), Some(index)) =
+                    (transactions.get_mut(&txn), seqs.get(&(txn, seq)))
+                {
+                    transaction.steps[*index].1 = true;
+                }
+            }
+            JournalRecord::End { txn } => {
+                transactions.remove(&txn);
+            }
+            JournalRecord::Checkpoint { .. } => {}
+        }
+    }
+
+    transactions.into_values().collect()
+}
+
+/// Rebuilds the records describing a pending transaction.
+fn transaction_records(transaction: &PendingTransaction) -> Vec<JournalRecord> {
+    let mut records = vec![JournalRecord::Begin {
+        txn: transaction.txn,
+        tweak: transaction.tweak.clone(),
+        action: transaction.action.clone(),
+    }];
+    for (seq, (step, done)) in transaction.steps.iter().enumerate() {
+        records.push(JournalRecord::Intent {
+            txn: transaction.txn,
+            seq: seq as u32,
+            step: step.clone(),
+        });
+        if *done {
+            records.push(JournalRecord::Done {
+                txn: transaction.txn,
+                seq: seq as u32,
+            });
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::tweaks::TweakOption;
+
+    fn journal_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "oc_tool_journal_test_{}_{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("journal.jsonl")
+    }
+
+    /// Simulates the process dying before a transaction ends: the transaction is never
+    /// ended, and the file is closed, releasing its lock.
+    fn crash(journal: &Journal, guard: TransactionGuard) {
+        std::mem::forget(guard);
+        ACTIVE_TRANSACTION.with(|active| active.borrow_mut().take());
+        journal.lock().unwrap().file = None;
+    }
+
+    /// Checks that the journal was compacted down to a checkpoint.
+    fn assert_only_checkpoint(path: &Path) {
+        let records = read_records(&File::open(path).unwrap()).unwrap();
+        assert!(
+            matches!(records[..], [JournalRecord::Checkpoint { .. }]),
+            "{:?}",
+            records
+        );
+    }
+
+    fn msr_step(core: usize) -> JournalStep {
+        JournalStep::Msr {
+            core,
+            index: 0xC0011020,
+            before: 0,
+            after: 1 << 10,
+        }
+    }
+
+    #[test]
+    fn test_interrupted_transaction_is_pending_after_reopen() {
+        let path = journal_path("interrupted");
+        {
+            let journal = Arc::new(Journal::open(&path).unwrap());
+            let guard = journal
+                .begin(TweakId::DisableHPET, &TweakAction::Enable)
+                .unwrap();
+            record_step(msr_step(0), || Ok(())).unwrap();
+            let _ = record_step(msr_step(1), || -> Result<()> { anyhow::bail!("crashed") });
+            crash(&journal, guard);
+        }
+
+        let journal = Journal::open(&path).unwrap();
+        let pending = journal.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id(), Some(TweakId::DisableHPET));
+        assert!(matches!(pending[0].action, TweakAction::Enable));
+        assert_eq!(
+            pending[0].steps,
+            vec![(msr_step(0), true), (msr_step(1), false)]
+        );
+
+        // Still pending if we crash again before resolving it
+        drop(journal);
+        let journal = Journal::open(&path).unwrap();
+        assert_eq!(journal.pending().len(), 1);
+
+        journal.resolve(pending[0].txn).unwrap();
+        drop(journal);
+        assert!(Journal::open(&path).unwrap().pending().is_empty());
+        assert_only_checkpoint(&path);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_finished_transactions_are_not_pending() {
+        let path = journal_path("finished");
+        {
+            let journal = Arc::new(Journal::open(&path).unwrap());
+            let _guard = journal
+                .begin(
+                    TweakId::AggressivePrefetchProfile,
+                    &TweakAction::Set(TweakOption::Option("Level 2".to_string())),
+                )
+                .unwrap();
+            record_step(msr_step(0), || Ok(())).unwrap();
+        }
+
+        assert!(Journal::open(&path).unwrap().pending().is_empty());
+        assert_only_checkpoint(&path);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_torn_record_is_skipped() {
+        let path = journal_path("torn");
+        let begin = serde_json::to_string(&JournalRecord::Begin {
+            txn: 4,
+            tweak: "DisableHPET".to_string(),
+            action: TweakAction::Disable,
+        })
+        .unwrap();
+        fs::write(&path, format!("{}\n{{\"Intent\":{{\"txn\":4,", begin)).unwrap();
+
+        let journal = Journal::open(&path).unwrap();
+        let pending = journal.pending();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].steps.is_empty());
+
+        // New transactions never reuse an ID from the file
+        let journal = Arc::new(journal);
+        let guard = journal
+            .begin(TweakId::DisableDps, &TweakAction::Enable)
+            .unwrap();
+        assert_eq!(guard.txn, 5);
+        drop(guard);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_journal_is_locked_while_open() {
+        let path = journal_path("locked");
+        let journal = Arc::new(Journal::open(&path).unwrap());
+        let guard = journal
+            .begin(TweakId::DisableHPET, &TweakAction::Enable)
+            .unwrap();
+        record_step(msr_step(0), || Ok(())).unwrap();
+
+        // A second instance must not compact away the change in flight
+        let error = Journal::open(&path).err().unwrap();
+        assert!(format!("{:#}", error).contains("in use"), "{:#}", error);
+        crash(&journal, guard);
+        drop(journal);
+
+        let journal = Arc::new(Journal::open(&path).unwrap());
+        let pending = journal.pending();
+        assert_eq!(pending[0].steps, vec![(msr_step(0), true)]);
+        journal.resolve(pending[0].txn).unwrap();
+
+        // Transaction IDs carry on from the previous run after the file is emptied
+        drop(journal);
+        let journal = Arc::new(Journal::open(&path).unwrap());
+        let guard = journal
+            .begin(TweakId::DisableDps, &TweakAction::Enable)
+            .unwrap();
+        assert_eq!(guard.txn, pending[0].txn + 1);
+        drop(guard);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_record_step_without_transaction_runs_directly() {
+        assert_eq!(record_step(msr_step(0), || Ok(7)).unwrap(), 7);
+    }
+}
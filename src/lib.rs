@@ -1,8 +1,13 @@
 // src/mod.rs
 
+pub mod backend;
+pub mod backup;
 pub mod constants;
+pub mod inspector;
+pub mod journal;
 pub mod orchestrator;
 pub mod power;
+pub mod profile;
 pub mod tweaks;
 pub mod ui;
 pub mod utils;
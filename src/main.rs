@@ -1,18 +1,36 @@
 // src/main.rs
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
 
 use anyhow::Context;
 use eframe::{egui, App, Frame, NativeOptions};
 use egui::{Button, FontFamily, FontId, RichText};
 use egui_dialogs::{DialogDetails, Dialogs, StandardDialog, StandardReply};
 use oc_tool::{
+    backend,
     constants::{
         LABEL_FONT_SIZE, TWEAK_CONTAINER_HEIGHT, TWEAK_CONTAINER_WIDTH, UI_PADDING, UI_SPACING,
         WINDOW_HEIGHT, WINDOW_WIDTH,
     },
+    inspector::{
+        known_fields, parse_hex, plan_write, write_field, MsrInspection, MsrWrite, NamedField,
+        PlannedWrite,
+    },
+    journal::PendingTransaction,
     orchestrator::{TaskOrchestrator, TweakAction, TweakTask},
-    tweaks::{self, Tweak, TweakCategory, TweakId, TweakOption, TweakStatus},
+    profile::{saved_profiles, Profile},
+    tweaks::{
+        self,
+        msr::{method::MsrField, registers},
+        plan::TweakPlan,
+        relations,
+        relations::TweakSetting,
+        CoreState, Tweak, TweakCategory, TweakId, TweakOption, TweakStatus,
+    },
     ui::{
         button::{ActionButton, ButtonState, BUTTON_DIMENSIONS},
         combobox::SettingsComboBox,
@@ -20,11 +38,9 @@ use oc_tool::{
         TweakWidget,
     },
     utils::{
+        cpu::{parse_ranges, CoreSelection, CpuTopology, LogicalCpu},
         windows::{is_elevated, reboot_into_bios, reboot_system},
-        winring0::{
-            setup_winring0_driver, verify_winring0_driver, WINRING0_DRIVER,
-            WINRING0_SETUP_USER_MESSAGE,
-        },
+        winring0::{setup_winring0_driver, verify_winring0_driver, WINRING0_SETUP_USER_MESSAGE},
     },
 };
 use tracing::Level;
@@ -45,6 +61,69 @@ pub struct MyApp {
     pub pending_initial_state_reads: usize,
 
     pub dialogs: Dialogs<'static>,
+
+    // State for the profiles window
+    pub show_profiles: bool,
+    pub profile_name_input: String,
+    pub saved_profiles: Vec<(PathBuf, Profile)>,
+    pub profile_error: Option<String>,
+
+    // Changes a previous run was interrupted in the middle of
+    pub pending_recovery: Vec<PendingTransaction>,
+    pub recovery_error: Option<String>,
+
+    // Errors from user-defined tweak files that could not be loaded
+    pub custom_tweak_errors: Vec<String>,
+
+    // Why MSRs and PCI registers cannot be reached, if no driver loaded
+    pub hardware_unavailable: Option<String>,
+
+    // The change being previewed in the plan window, if it is open
+    pub plan_preview: Option<PlanPreview>,
+
+    // The cores of a per-core tweak being chosen in the cores window, if it is open
+    pub core_editor: Option<CoreEditor>,
+
+    // State for the MSR inspector window
+    pub show_msr_inspector: bool,
+    pub msr_inspector: MsrInspector,
+}
+
+/// A dry run of a change to a tweak, shown before the change is made
+pub struct PlanPreview {
+    pub id: TweakId,
+    pub option: TweakOption,
+    pub plan: Result<TweakPlan, String>,
+}
+
+/// The cores a per-core tweak is applied on, being chosen in the cores window
+pub struct CoreEditor {
+    pub id: TweakId,
+    pub topology: CpuTopology,
+    /// Numbers of the logical processors ticked in the window
+    pub selected: BTreeSet<usize>,
+    /// The option each logical processor is in, read when the window was opened
+    pub states: Result<Vec<CoreState>, String>,
+}
+
+/// An MSR read on a set of cores in the MSR inspector, and the field being edited
+#[derive(Default)]
+pub struct MsrInspector {
+    pub index_input: String,
+    /// Logical processor numbers to read, e.g. `0-3, 8`; blank for every one
+    pub cores_input: String,
+    pub cpus: Vec<LogicalCpu>,
+    pub inspection: Option<MsrInspection>,
+    pub fields: Vec<NamedField>,
+    /// The field being edited and the value to write to it, in hexadecimal
+    pub low_bit: u32,
+    pub high_bit: u32,
+    pub value_input: String,
+    /// A write waiting to be confirmed, with each core's value before and after
+    pub pending: Option<(MsrField, Vec<PlannedWrite>)>,
+    /// The values the last write replaced
+    pub last_write: Option<MsrWrite>,
+    pub error: Option<String>,
 }
 
 impl MyApp {
@@ -52,10 +131,36 @@ impl MyApp {
         let app_span = tracing::span!(Level::INFO, "App Initialization");
         let _app_guard = app_span.enter();
 
-        let mut tweaks = tweaks::all_tweaks();
+        let (mut tweaks, custom_tweak_errors) = tweaks::load_all_tweaks();
+        let custom_tweak_errors: Vec<String> = custom_tweak_errors
+            .iter()
+            .map(|e| format!("{:#}", e))
+            .collect();
         let orchestrator = TaskOrchestrator::new();
 
-        let pending_initial_state_reads = tweaks.len();
+        // Tweaks this system cannot run are shown disabled, and their state is never read
+        for (id, tweak) in tweaks.iter_mut() {
+            if let Some(reason) = tweak.method.unsupported() {
+                tracing::info!("{:?} -> Unsupported: {}", id, reason);
+                tweak.status = TweakStatus::Unsupported(reason);
+            }
+        }
+        let hardware_unavailable = backend::hardware_unavailable();
+        if let Some(reason) = &hardware_unavailable {
+            tracing::warn!("{}. MSR tweaks are unavailable.", reason);
+        }
+        let pending_initial_state_reads = tweaks
+            .values()
+            .filter(|tweak| !matches!(tweak.status, TweakStatus::Unsupported(_)))
+            .count();
+
+        let pending_recovery = orchestrator.pending_recovery();
+        if !pending_recovery.is_empty() {
+            tracing::warn!(
+                "Found {} interrupted change(s) from a previous run.",
+                pending_recovery.len()
+            );
+        }
 
         let mut dialogs = Dialogs::new();
 
@@ -65,8 +170,8 @@ impl MyApp {
                 StandardDialog::info("Warning", "This program must be run in administrator mode.")
                     .buttons(vec![("OK".into(), StandardReply::Cancel)]),
             ));
-        } else {
-            // Attempt to verify WinRing0 setup
+        } else if hardware_unavailable.is_some() {
+            // Offer to set up WinRing0, without which the app runs without MSR tweaks
             match verify_winring0_driver() {
                 Ok(_) => {
                     tracing::debug!("WinRing0 driver already set up.");
@@ -80,15 +185,18 @@ impl MyApp {
                         )
                         .buttons(vec![
                             ("Accept".into(), StandardReply::Yes),
-                            ("Cancel".into(), StandardReply::Cancel),
+                            ("Continue without".into(), StandardReply::No),
                         ]),
                     ));
                 }
             }
         }
 
-        // Submit initial state read tasks for all tweaks
+        // Submit initial state read tasks for all supported tweaks
         for (id, tweak) in tweaks.iter_mut() {
+            if matches!(tweak.status, TweakStatus::Unsupported(_)) {
+                continue;
+            }
             let task = TweakTask {
                 id: *id,
                 method: tweak.method.clone(),
@@ -109,6 +217,18 @@ impl MyApp {
             initial_states_loaded: false,
             pending_initial_state_reads,
             dialogs,
+            show_profiles: false,
+            profile_name_input: String::new(),
+            saved_profiles: Vec::new(),
+            profile_error: None,
+            pending_recovery,
+            recovery_error: None,
+            custom_tweak_errors,
+            hardware_unavailable,
+            plan_preview: None,
+            core_editor: None,
+            show_msr_inspector: false,
+            msr_inspector: MsrInspector::default(),
         }
     }
 
@@ -146,6 +266,16 @@ impl MyApp {
                             tweak.status = TweakStatus::Idle;
                             tracing::debug!("Successfully disabled tweak {:?}", result.id);
                         }
+                        TweakAction::RestoreOriginal => {
+                            if let Some(state) = result.state.clone() {
+                                tweak.state = state;
+                            }
+                            tweak.status = TweakStatus::Idle;
+                            tracing::debug!(
+                                "Successfully restored original values of tweak {:?}",
+                                result.id
+                            );
+                        }
                         TweakAction::ReadInitialState => {
                             // Update the tweak state with the initial state read
                             tweak.state = result.state.clone().unwrap();
@@ -194,7 +324,8 @@ impl MyApp {
     }
 
     fn cleanup(&mut self) {
-        drop(WINRING0_DRIVER.lock().unwrap());
+        // Wait for any MSR access still in progress to finish
+        drop(backend::msr());
     }
 
     fn draw_ui(&mut self, ui: &mut egui::Ui) {
@@ -256,6 +387,11 @@ impl MyApp {
             .map(|tweak| (tweak.name, tweak.description, tweak.widget));
 
         if let Some((tweak_name, tweak_description, tweak_widget)) = tweak_data {
+            let unsupported = match self.tweaks.get(&tweak_id).map(|tweak| &tweak.status) {
+                Some(TweakStatus::Unsupported(reason)) => Some(reason.clone()),
+                _ => None,
+            };
+
             // Define a unique grid ID for each tweak to avoid conflicts
             egui::Grid::new(format!("tweak_grid_{:?}", tweak_id))
                 .num_columns(2)
@@ -271,14 +407,53 @@ impl MyApp {
                                     RichText::new(tweak_description)
                                         .font(FontId::new(12.0, FontFamily::Proportional)),
                                 );
+                                for note in self.relation_notes(tweak_id) {
+                                    ui.label(
+                                        RichText::new(note)
+                                            .font(FontId::new(12.0, FontFamily::Proportional))
+                                            .color(egui::Color32::YELLOW),
+                                    );
+                                }
                                 if let Some(tweak_entry) = self.tweaks.get(&tweak_id) {
-                                    if let TweakStatus::Failed(ref err) = tweak_entry.status {
-                                        ui.colored_label(
-                                            egui::Color32::RED,
-                                            format!("Error: {}", err),
-                                        );
+                                    match tweak_entry.status {
+                                        TweakStatus::Failed(ref err) => {
+                                            ui.colored_label(
+                                                egui::Color32::RED,
+                                                format!("Error: {}", err),
+                                            );
+                                        }
+                                        TweakStatus::Unsupported(ref reason) => {
+                                            ui.colored_label(
+                                                egui::Color32::GRAY,
+                                                format!("Unsupported: {}", reason),
+                                            );
+                                        }
+                                        _ => {}
                                     }
                                 }
+                                if unsupported.is_none()
+                                    && ui
+                                        .small_button("Preview changes")
+                                        .on_hover_text(
+                                            "Show what changing this tweak will modify, without modifying it",
+                                        )
+                                        .clicked()
+                                {
+                                    self.preview_change(tweak_id, None);
+                                }
+                                if unsupported.is_none() {
+                                    self.draw_core_targets(ui, tweak_id);
+                                }
+                                if self.orchestrator.has_backup(tweak_id)
+                                    && ui
+                                        .small_button("Restore original")
+                                        .on_hover_text(
+                                            "Put back the values this tweak had before it was first changed",
+                                        )
+                                        .clicked()
+                                {
+                                    self.restore_original(tweak_id);
+                                }
                                 // add vertical spacing
                                 ui.add_space(UI_SPACING);
                             });
@@ -291,16 +466,22 @@ impl MyApp {
                         let widget_width = BUTTON_DIMENSIONS[0];
                         ui.set_width(widget_width);
 
-                        match tweak_widget {
-                            TweakWidget::Toggle => self.draw_toggle_widget(ui, tweak_id),
-                            TweakWidget::Button => self.draw_button_widget(ui, tweak_id),
-                            TweakWidget::SettingsComboBox => {
-                                // Ensure combo box aligns with other widgets
-                                ui.with_layout(
-                                    egui::Layout::right_to_left(egui::Align::TOP),
-                                    |ui| self.draw_combo_box_widget(ui, tweak_id),
-                                );
+                        // Tweaks this system cannot run are greyed out, explaining why on hover
+                        let widget = ui.add_enabled_ui(unsupported.is_none(), |ui| {
+                            match tweak_widget {
+                                TweakWidget::Toggle => self.draw_toggle_widget(ui, tweak_id),
+                                TweakWidget::Button => self.draw_button_widget(ui, tweak_id),
+                                TweakWidget::SettingsComboBox => {
+                                    // Ensure combo box aligns with other widgets
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::TOP),
+                                        |ui| self.draw_combo_box_widget(ui, tweak_id),
+                                    );
+                                }
                             }
+                        });
+                        if let Some(reason) = unsupported {
+                            widget.response.on_disabled_hover_text(reason);
                         }
                     });
 
@@ -310,48 +491,80 @@ impl MyApp {
         }
     }
 
+    /// Describes how a tweak relates to other tweaks, and anything to look out for in
+    /// its current state
+    fn relation_notes(&self, tweak_id: TweakId) -> Vec<String> {
+        let Some(tweak) = self.tweaks.get(&tweak_id) else {
+            return Vec::new();
+        };
+        let names = |ids: Vec<TweakId>| {
+            ids.iter()
+                .filter_map(|id| self.tweaks.get(id).map(|tweak| tweak.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut notes = Vec::new();
+        if !tweak.relations.requires.is_empty() {
+            notes.push(format!(
+                "Requires: {}",
+                names(tweak.relations.requires.clone())
+            ));
+        }
+        let conflicts = relations::related(&self.tweaks, tweak_id, |r| &r.conflicts_with);
+        if !conflicts.is_empty() {
+            notes.push(format!(
+                "Conflicts with: {}",
+                names(conflicts.into_iter().collect())
+            ));
+        }
+        let overlaps = relations::related(&self.tweaks, tweak_id, |r| &r.overlaps);
+        if !overlaps.is_empty() {
+            notes.push(format!(
+                "Overlaps: {}",
+                names(overlaps.into_iter().collect())
+            ));
+        }
+
+        let states = relations::current_states(&self.tweaks);
+        let check = relations::check_change(&self.tweaks, &states, tweak_id, &tweak.state);
+        if !check.missing_requirements.is_empty() {
+            notes.push(format!(
+                "Has no effect unless {} is enabled",
+                names(check.missing_requirements)
+            ));
+        }
+        notes
+    }
+
     fn draw_toggle_widget(&mut self, ui: &mut egui::Ui, tweak_id: TweakId) {
+        let mut change = None;
         if let Some(tweak) = self.tweaks.get_mut(&tweak_id) {
             let mut is_enabled = tweak.state == TweakOption::Enabled(true);
             let has_error = matches!(tweak.status, TweakStatus::Failed(_));
+            let is_mixed = matches!(tweak.state, TweakOption::Mixed(_));
 
-            let widget = ToggleSwitch::new(&mut is_enabled).with_error(has_error);
+            let widget = ToggleSwitch::new(&mut is_enabled)
+                .with_error(has_error)
+                .with_mixed(is_mixed);
 
-            let response_toggle = ui.add(widget);
+            let response_toggle = with_mixed_tooltip(ui.add(widget), &tweak.state);
 
             if response_toggle.changed() {
-                tweak.state = if is_enabled {
-                    TweakOption::Enabled(true)
-                } else {
-                    TweakOption::Enabled(false)
-                };
-                if tweak.requires_reboot {
-                    tweak.pending_reboot = true;
-                }
-                tweak.status = TweakStatus::Busy;
-                let result = self.orchestrator.submit_task(TweakTask {
-                    id: tweak_id,
-                    method: tweak.method.clone(),
-                    action: if is_enabled {
-                        TweakAction::Enable
-                    } else {
-                        TweakAction::Disable
-                    },
-                });
-                match result {
-                    Ok(_) => {}
-                    Err(e) => {
-                        tweak.status = TweakStatus::Failed(e.to_string());
-                    }
-                }
+                change = Some(TweakOption::Enabled(is_enabled));
             }
         }
+        if let Some(option) = change {
+            self.request_change(tweak_id, option);
+        }
     }
 
     fn draw_button_widget(&mut self, ui: &mut egui::Ui, tweak_id: TweakId) {
         if let Some(tweak_entry) = self.tweaks.get_mut(&tweak_id) {
             let button_state = match tweak_entry.status {
-                TweakStatus::Idle | TweakStatus::Failed(_) => ButtonState::Default,
+                TweakStatus::Idle | TweakStatus::Failed(_) | TweakStatus::Unsupported(_) => {
+                    ButtonState::Default
+                }
                 TweakStatus::Busy => ButtonState::InProgress,
             };
 
@@ -384,19 +597,23 @@ impl MyApp {
         ui: &mut egui::Ui,
         tweak_id: TweakId,
     ) -> anyhow::Result<()> {
+        let mut change = None;
         if let Some(tweak) = self.tweaks.get_mut(&tweak_id) {
             // Extract the list of options (keys) from the BTreeMap
             let options: Vec<TweakOption> = tweak.options.to_vec();
 
-            // Determine the currently selected option's index
-            let mut selected_option = options
-                .iter()
-                .position(|option| option == &tweak.state)
-                .context("Failed to find selected option index.")?;
+            // Determine the currently selected option's index, past the end if mixed
+            let mut selected_option = match tweak.state {
+                TweakOption::Mixed(_) => options.len(),
+                _ => options
+                    .iter()
+                    .position(|option| option == &tweak.state)
+                    .context("Failed to find selected option index.")?,
+            };
 
             // Create the combo box widget with the list of options
             let widget = SettingsComboBox::new(tweak_id, &mut selected_option, options.clone());
-            let response_combo = ui.add(widget);
+            let response_combo = with_mixed_tooltip(ui.add(widget), &tweak.state);
 
             if response_combo.changed() {
                 tracing::debug!(
@@ -405,26 +622,993 @@ impl MyApp {
                     selected_option
                 );
                 // Retrieve the newly selected option based on the index
-                let new_option = options[selected_option].clone();
+                change = Some(options[selected_option].clone());
+            }
+        }
+        if let Some(option) = change {
+            self.request_change(tweak_id, option);
+        }
+        Ok(())
+    }
+
+    /// Queues a change to a tweak, unless it conflicts with a tweak that is enabled or
+    /// puts back a tweak that an enabled tweak requires
+    fn request_change(&mut self, tweak_id: TweakId, option: TweakOption) {
+        let states = relations::current_states(&self.tweaks);
+        let check = relations::check_change(&self.tweaks, &states, tweak_id, &option);
+        let messages = check.messages(&self.tweaks);
 
-                tweak.state = new_option.clone();
+        let Some(tweak) = self.tweaks.get_mut(&tweak_id) else {
+            return;
+        };
+        if check.is_blocked() {
+            tracing::warn!("{:?} -> Change blocked: {}", tweak_id, messages.join("; "));
+            tweak.status = TweakStatus::Failed(messages.join(". "));
+            return;
+        }
+        if !messages.is_empty() {
+            tracing::warn!("{:?} -> {}", tweak_id, messages.join("; "));
+        }
+
+        let action = TweakAction::from(option.clone());
+        tweak.state = option;
+        if tweak.requires_reboot {
+            tweak.pending_reboot = true;
+        }
+        tweak.status = TweakStatus::Busy;
+        if let Err(e) = self.orchestrator.submit_task(TweakTask {
+            id: tweak_id,
+            method: tweak.method.clone(),
+            action,
+        }) {
+            tweak.status = TweakStatus::Failed(e.to_string());
+        }
+    }
+
+    /// Works out what changing a tweak to `option` would do and opens the plan window.
+    /// Without an option, previews the change its widget would make next.
+    fn preview_change(&mut self, tweak_id: TweakId, option: Option<TweakOption>) {
+        let Some(tweak) = self.tweaks.get(&tweak_id) else {
+            return;
+        };
+        let option = option.unwrap_or_else(|| match (tweak.widget, &tweak.state) {
+            (TweakWidget::Button, _) => TweakOption::Enabled(true),
+            (_, TweakOption::Enabled(state)) => TweakOption::Enabled(!state),
+            (_, state) => tweak
+                .options
+                .iter()
+                .find(|option| *option != state)
+                .unwrap_or(state)
+                .clone(),
+        });
+        let plan = tweak.method.plan(&option).map_err(|e| {
+            tracing::error!("{:?} -> Failed to plan change: {:?}", tweak_id, e);
+            format!("{:#}", e)
+        });
+        self.plan_preview = Some(PlanPreview {
+            id: tweak_id,
+            option,
+            plan,
+        });
+    }
+
+    fn draw_plan_window(&mut self, ctx: &egui::Context) {
+        let Some(preview) = self.plan_preview.as_ref() else {
+            return;
+        };
+        let Some(tweak) = self.tweaks.get(&preview.id) else {
+            self.plan_preview = None;
+            return;
+        };
+
+        let mut selected = None;
+        let mut apply = false;
+        let mut close = false;
+        egui::Window::new(format!("Preview: {}", tweak.name))
+            .collapsible(false)
+            .resizable(true)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if *tweak.widget != TweakWidget::Button {
+                    egui::ComboBox::from_label("Option")
+                        .selected_text(preview.option.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in &tweak.options {
+                                if ui
+                                    .selectable_label(*option == preview.option, option.to_string())
+                                    .clicked()
+                                {
+                                    selected = Some(option.clone());
+                                }
+                            }
+                        });
+                }
+                ui.separator();
+
+                match &preview.plan {
+                    Ok(plan) => {
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                if plan.steps.is_empty() {
+                                    ui.label("Nothing to do");
+                                }
+                                for step in &plan.steps {
+                                    let text = RichText::new(step.to_string())
+                                        .font(FontId::new(12.0, FontFamily::Monospace));
+                                    if step.is_noop() {
+                                        ui.label(text.weak());
+                                    } else {
+                                        ui.label(text);
+                                    }
+                                }
+                            });
+                    }
+                    Err(err) => {
+                        ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                    }
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            preview.plan.is_ok() && !matches!(tweak.status, TweakStatus::Busy),
+                            Button::new("Apply"),
+                        )
+                        .clicked()
+                    {
+                        apply = true;
+                    }
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        let id = preview.id;
+        if let Some(option) = selected {
+            self.preview_change(id, Some(option));
+        } else if apply {
+            if let Some(preview) = self.plan_preview.take() {
+                self.request_change(id, preview.option);
+            }
+        } else if close {
+            self.plan_preview = None;
+        }
+    }
+
+    /// Shows which cores a per-core tweak is applied on, with a button to choose them
+    fn draw_core_targets(&mut self, ui: &mut egui::Ui, tweak_id: TweakId) {
+        let Some(targets) = self
+            .tweaks
+            .get(&tweak_id)
+            .and_then(|tweak| tweak.method.targets())
+        else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!("Applied on {}", targets))
+                    .font(FontId::new(12.0, FontFamily::Proportional)),
+            );
+            if ui
+                .small_button("Choose cores")
+                .on_hover_text(
+                    "Show the state of every core, and pick the cores or CCDs to apply on",
+                )
+                .clicked()
+            {
+                self.open_core_editor(tweak_id);
+            }
+        });
+    }
+
+    /// Reads the topology and the state of a per-core tweak on every core, and opens the
+    /// cores window
+    fn open_core_editor(&mut self, tweak_id: TweakId) {
+        let Some(tweak) = self.tweaks.get_mut(&tweak_id) else {
+            return;
+        };
+        let targets = tweak.method.targets().unwrap_or_default();
+        let topology = match CpuTopology::detect() {
+            Ok(topology) => topology,
+            Err(e) => {
+                tracing::error!("{:?} -> Failed to read the CPU topology: {:?}", tweak_id, e);
+                tweak.status = TweakStatus::Failed(format!("{:#}", e));
+                return;
+            }
+        };
+        let selected = targets
+            .select(&topology)
+            .map(|cpus| cpus.iter().map(|cpu| cpu.number).collect())
+            .unwrap_or_default();
+        let states = tweak.method.core_states().map_err(|e| {
+            tracing::error!("{:?} -> Failed to read per-core state: {:?}", tweak_id, e);
+            format!("{:#}", e)
+        });
+        self.core_editor = Some(CoreEditor {
+            id: tweak_id,
+            topology,
+            selected,
+            states,
+        });
+    }
+
+    fn draw_core_editor_window(&mut self, ctx: &egui::Context) {
+        let Some(editor) = self.core_editor.as_mut() else {
+            return;
+        };
+        let Some(tweak) = self.tweaks.get(&editor.id) else {
+            self.core_editor = None;
+            return;
+        };
+
+        let mut apply = false;
+        let mut refresh = false;
+        let mut close = false;
+        egui::Window::new(format!("Cores: {}", tweak.name))
+            .collapsible(false)
+            .resizable(true)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if let Err(err) = &editor.states {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("All").clicked() {
+                        editor.selected =
+                            editor.topology.cpus.iter().map(|cpu| cpu.number).collect();
+                    }
+                    if ui.button("None").clicked() {
+                        editor.selected.clear();
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for ccd in 0..editor.topology.ccd_count() {
+                            let numbers: Vec<usize> = editor
+                                .topology
+                                .cpus
+                                .iter()
+                                .filter(|cpu| cpu.ccd == ccd)
+                                .map(|cpu| cpu.number)
+                                .collect();
+                            let mut whole = numbers.iter().all(|n| editor.selected.contains(n));
+                            if ui.checkbox(&mut whole, format!("CCD {}", ccd)).changed() {
+                                for number in &numbers {
+                                    if whole {
+                                        editor.selected.insert(*number);
+                                    } else {
+                                        editor.selected.remove(number);
+                                    }
+                                }
+                            }
+
+                            egui::Grid::new(format!("core_grid_{}", ccd))
+                                .num_columns(8)
+                                .show(ui, |ui| {
+                                    for (column, number) in numbers.iter().enumerate() {
+                                        let cpu = &editor.topology.cpus[*number];
+                                        let state =
+                                            editor.states.as_ref().ok().and_then(|states| {
+                                                states
+                                                    .iter()
+                                                    .find(|state| state.cpu.number == *number)
+                                            });
+                                        let (color, description) = match state
+                                            .map(|state| &state.option)
+                                        {
+                                            Some(Some(TweakOption::Enabled(false))) => {
+                                                (egui::Color32::GRAY, "Disabled".to_string())
+                                            }
+                                            Some(Some(option)) => {
+                                                (egui::Color32::GREEN, option.to_string())
+                                            }
+                                            Some(None) => (
+                                                egui::Color32::YELLOW,
+                                                "matches no option".to_string(),
+                                            ),
+                                            None => (egui::Color32::GRAY, "unknown".to_string()),
+                                        };
+
+                                        let mut ticked = editor.selected.contains(number);
+                                        let label = RichText::new(number.to_string()).color(color);
+                                        if ui
+                                            .checkbox(&mut ticked, label)
+                                            .on_hover_text(format!(
+                                                "{}, core {}, CCX {}: {}",
+                                                cpu, cpu.core, cpu.ccx, description
+                                            ))
+                                            .changed()
+                                        {
+                                            if ticked {
+                                                editor.selected.insert(*number);
+                                            } else {
+                                                editor.selected.remove(number);
+                                            }
+                                        }
+                                        if column % 8 == 7 {
+                                            ui.end_row();
+                                        }
+                                    }
+                                });
+                            ui.add_space(UI_SPACING);
+                        }
+                    });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !editor.selected.is_empty()
+                                && !matches!(tweak.status, TweakStatus::Busy),
+                            Button::new("Use selected cores"),
+                        )
+                        .on_hover_text(
+                            "Apply, revert and read this tweak on the selected cores only",
+                        )
+                        .clicked()
+                    {
+                        apply = true;
+                    }
+                    if ui.button("Refresh").clicked() {
+                        refresh = true;
+                    }
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        let id = editor.id;
+        if apply {
+            let targets = CoreSelection::from_cpus(&editor.topology, &editor.selected);
+            self.core_editor = None;
+            self.set_core_targets(id, targets);
+        } else if refresh {
+            self.open_core_editor(id);
+        } else if close {
+            self.core_editor = None;
+        }
+    }
+
+    /// Narrows a per-core tweak down to the given cores and reads its state on them
+    fn set_core_targets(&mut self, tweak_id: TweakId, targets: CoreSelection) {
+        let Some(tweak) = self.tweaks.get_mut(&tweak_id) else {
+            return;
+        };
+        if let Err(e) = tweak.method.set_targets(targets) {
+            tracing::error!("{:?} -> Failed to choose cores: {:?}", tweak_id, e);
+            tweak.status = TweakStatus::Failed(format!("{:#}", e));
+            return;
+        }
+        tweak.status = TweakStatus::Busy;
+        if let Err(e) = self.orchestrator.submit_task(TweakTask {
+            id: tweak_id,
+            method: tweak.method.clone(),
+            action: TweakAction::ReadInitialState,
+        }) {
+            tweak.status = TweakStatus::Failed(e.to_string());
+        }
+    }
+
+    /// Queues a task that puts back the values a tweak had before it was first changed
+    fn restore_original(&mut self, tweak_id: TweakId) {
+        if let Some(tweak) = self.tweaks.get_mut(&tweak_id) {
+            if tweak.requires_reboot {
+                tweak.pending_reboot = true;
+            }
+            tweak.status = TweakStatus::Busy;
+            if let Err(e) = self.orchestrator.submit_task(TweakTask {
+                id: tweak_id,
+                method: tweak.method.clone(),
+                action: TweakAction::RestoreOriginal,
+            }) {
+                tweak.status = TweakStatus::Failed(e.to_string());
+            }
+        }
+    }
+
+    /// Finishes an interrupted change by running its action again
+    fn roll_forward(&mut self, transaction: &PendingTransaction) -> anyhow::Result<()> {
+        let tweak = transaction
+            .id()
+            .and_then(|id| self.tweaks.get_mut(&id))
+            .with_context(|| format!("Tweak '{}' is not available", transaction.tweak))?;
+        self.orchestrator
+            .roll_forward_pending(transaction, tweak.method.clone())?;
+        tweak.status = TweakStatus::Busy;
+        if tweak.requires_reboot {
+            tweak.pending_reboot = true;
+        }
+        Ok(())
+    }
+
+    /// Undoes an interrupted change and re-reads the state of its tweak
+    fn roll_back(&mut self, transaction: &PendingTransaction) -> anyhow::Result<()> {
+        let method = transaction
+            .id()
+            .and_then(|id| self.tweaks.get(&id))
+            .map(|tweak| tweak.method.as_ref());
+        self.orchestrator.roll_back_pending(transaction, method)?;
+        let Some(id) = transaction.id() else {
+            return Ok(());
+        };
+        if let Some(tweak) = self.tweaks.get_mut(&id) {
+            tweak.status = TweakStatus::Busy;
+            if tweak.requires_reboot {
+                tweak.pending_reboot = true;
+            }
+            self.orchestrator.submit_task(TweakTask {
+                id,
+                method: tweak.method.clone(),
+                action: TweakAction::ReadInitialState,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn draw_recovery_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Interrupted changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(
+                    "OC Tool closed while the following changes were being made. \
+                     Roll them forward to finish them, or back to undo them.",
+                );
+                ui.separator();
+
+                let mut to_roll_forward = None;
+                let mut to_roll_back = None;
+                for (index, transaction) in self.pending_recovery.iter().enumerate() {
+                    let name = transaction
+                        .id()
+                        .and_then(|id| self.tweaks.get(&id))
+                        .map(|tweak| tweak.name)
+                        .unwrap_or(&transaction.tweak);
+                    let done = transaction.steps.iter().filter(|(_, done)| *done).count();
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} ({:?}, {} of {} modifications made)",
+                            name,
+                            transaction.action,
+                            done,
+                            transaction.steps.len()
+                        ));
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("Roll back").clicked() {
+                                to_roll_back = Some(index);
+                            }
+                            if ui
+                                .add_enabled(
+                                    transaction.id().is_some(),
+                                    Button::new("Roll forward"),
+                                )
+                                .clicked()
+                            {
+                                to_roll_forward = Some(index);
+                            }
+                        });
+                    });
+                }
+
+                let resolved = match (to_roll_forward, to_roll_back) {
+                    (Some(index), _) => {
+                        let transaction = self.pending_recovery[index].clone();
+                        Some((index, self.roll_forward(&transaction)))
+                    }
+                    (_, Some(index)) => {
+                        let transaction = self.pending_recovery[index].clone();
+                        Some((index, self.roll_back(&transaction)))
+                    }
+                    _ => None,
+                };
+                match resolved {
+                    Some((index, Ok(_))) => {
+                        self.pending_recovery.remove(index);
+                        self.recovery_error = None;
+                    }
+                    Some((_, Err(e))) => {
+                        tracing::error!("Failed to recover interrupted change: {:?}", e);
+                        self.recovery_error = Some(format!("{:#}", e));
+                    }
+                    None => {}
+                }
+
+                if let Some(ref err) = self.recovery_error {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                }
+            });
+    }
+
+    /// Reloads the list of saved profiles from disk
+    fn refresh_saved_profiles(&mut self) {
+        match saved_profiles() {
+            Ok(profiles) => self.saved_profiles = profiles,
+            Err(e) => {
+                tracing::error!("Failed to load saved profiles: {:?}", e);
+                self.profile_error = Some(format!("Failed to load profiles: {:#}", e));
+            }
+        }
+    }
+
+    /// Queues a task for every tweak whose current state or cores differ from the profile.
+    ///
+    /// Nothing is applied if the profile would leave conflicting tweaks enabled, or put
+    /// back a tweak that an enabled tweak requires.
+    /// Related tweaks are applied one after another, in dependency order.
+    fn apply_profile(&mut self, profile: &Profile) {
+        let changes = profile.changes(&self.tweaks);
+
+        let blocked = relations::blocked_changes(&self.tweaks, &changes);
+        if !blocked.is_empty() {
+            let reasons: Vec<String> = blocked
+                .iter()
+                .map(|(id, check)| {
+                    let name = self.tweaks.get(id).map_or("", |tweak| tweak.name);
+                    format!("{}: {}", name, check.messages(&self.tweaks).join(". "))
+                })
+                .collect();
+            self.profile_error = Some(format!(
+                "Profile '{}' was not applied. {}",
+                profile.name,
+                reasons.join("; ")
+            ));
+            return;
+        }
+
+        // Per-core tweaks whose cores cannot be chosen, e.g. a CCD this processor does
+        // not have, are left out
+        let failed = profile.set_targets(&self.tweaks, &changes);
+        for (id, e) in &failed {
+            tracing::error!("{:?} -> Failed to choose cores from profile: {:?}", id, e);
+            if let Some(tweak) = self.tweaks.get_mut(id) {
+                tweak.status = TweakStatus::Failed(format!("{:#}", e));
+            }
+        }
+        let changes: Vec<(TweakId, TweakOption)> = changes
+            .into_iter()
+            .filter(|(id, _)| !failed.iter().any(|(failed, _)| failed == id))
+            .collect();
+
+        for sequence in relations::order_changes(&self.tweaks, changes) {
+            let mut tasks = Vec::new();
+            for (id, option) in sequence {
+                let Some(tweak) = self.tweaks.get_mut(&id) else {
+                    continue;
+                };
                 if tweak.requires_reboot {
                     tweak.pending_reboot = true;
                 }
+                tweak.status = TweakStatus::Busy;
+                tasks.push(TweakTask {
+                    id,
+                    method: tweak.method.clone(),
+                    action: TweakAction::from(option),
+                });
+            }
 
-                // Submit the task with the new option
-                self.orchestrator
-                    .submit_task(TweakTask {
-                        id: tweak_id,
-                        method: tweak.method.clone(),
-                        action: TweakAction::Set(new_option),
-                    })
-                    .context("Failed to submit registry task for tweak.")?;
+            let ids: Vec<TweakId> = tasks.iter().map(|task| task.id).collect();
+            if let Err(e) = self.orchestrator.submit_sequence(tasks) {
+                for id in ids {
+                    if let Some(tweak) = self.tweaks.get_mut(&id) {
+                        tweak.status = TweakStatus::Failed(e.to_string());
+                    }
+                }
+            }
+        }
+    }
 
-                tweak.status = TweakStatus::Busy;
+    fn draw_profiles_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_profiles;
+        egui::Window::new("Profiles")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.profile_name_input);
+                    let name = self.profile_name_input.trim().to_string();
+                    if ui
+                        .add_enabled(!name.is_empty(), Button::new("Save current"))
+                        .clicked()
+                    {
+                        match Profile::capture(&name, &self.tweaks).save_to_profiles_dir() {
+                            Ok(path) => {
+                                tracing::debug!("Saved profile '{}' to {:?}", name, path);
+                                self.profile_name_input.clear();
+                                self.profile_error = None;
+                                self.refresh_saved_profiles();
+                            }
+                            Err(e) => {
+                                tracing::error!("Failed to save profile: {:?}", e);
+                                self.profile_error = Some(format!("{:#}", e));
+                            }
+                        }
+                    }
+                });
+
+                if let Some(ref err) = self.profile_error {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                }
+
+                ui.separator();
+
+                if self.saved_profiles.is_empty() {
+                    ui.label("No saved profiles.");
+                }
+
+                let mut to_apply = None;
+                let mut to_delete = None;
+                for (index, (_, profile)) in self.saved_profiles.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(&profile.name);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("Delete").clicked() {
+                                to_delete = Some(index);
+                            }
+                            if ui.button("Apply").clicked() {
+                                to_apply = Some(index);
+                            }
+                        });
+                    });
+                }
+
+                if let Some(index) = to_apply {
+                    let profile = self.saved_profiles[index].1.clone();
+                    self.apply_profile(&profile);
+                }
+                if let Some(index) = to_delete {
+                    let (path, _) = &self.saved_profiles[index];
+                    match fs::remove_file(path) {
+                        Ok(_) => self.profile_error = None,
+                        Err(e) => {
+                            tracing::error!("Failed to delete profile {:?}: {:?}", path, e);
+                            self.profile_error = Some(format!("Failed to delete profile: {}", e));
+                        }
+                    }
+                    self.refresh_saved_profiles();
+                }
+            });
+        self.show_profiles = open;
+    }
+
+    /// Reads the MSR and cores entered in the MSR inspector
+    fn read_inspected_msr(&mut self) {
+        let inspector = &mut self.msr_inspector;
+        inspector.pending = None;
+        let result = (|| -> anyhow::Result<()> {
+            let index = u32::try_from(parse_hex(&inspector.index_input)?)
+                .context("MSR indices are 32 bits")?;
+            let topology = CpuTopology::detect()?;
+            let selection = if inspector.cores_input.trim().is_empty() {
+                CoreSelection::All
+            } else {
+                CoreSelection::Cpus(parse_ranges(&inspector.cores_input)?)
+            };
+            let cpus = selection.select(&topology)?;
+            inspector.inspection = Some(MsrInspection::read(index, &cpus)?);
+            inspector.fields = known_fields(&self.tweaks, index);
+            inspector.cpus = cpus;
+            Ok(())
+        })();
+        inspector.error = result.err().map(|e| {
+            tracing::error!("Failed to read MSR: {:?}", e);
+            format!("{:#}", e)
+        });
+    }
+
+    /// Re-reads the state of the tweaks that use an MSR after the inspector changed it
+    fn refresh_tweaks_using_msr(&mut self, index: u32) {
+        for (id, tweak) in self.tweaks.iter_mut() {
+            let uses_msr = tweak.method.settings().iter().any(
+                |setting| matches!(setting, TweakSetting::MsrBit { index: i, .. } if *i == index),
+            );
+            if !uses_msr || matches!(tweak.status, TweakStatus::Busy) {
+                continue;
+            }
+            tweak.status = TweakStatus::Busy;
+            if let Err(e) = self.orchestrator.submit_task(TweakTask {
+                id: *id,
+                method: tweak.method.clone(),
+                action: TweakAction::ReadInitialState,
+            }) {
+                tweak.status = TweakStatus::Failed(e.to_string());
             }
         }
-        Ok(())
+    }
+
+    fn draw_msr_inspector_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_msr_inspector;
+        let mut read = false;
+        let mut preview = false;
+        let mut write = false;
+        let mut restore = false;
+        let inspector = &mut self.msr_inspector;
+        egui::Window::new("MSR inspector")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("MSR");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut inspector.index_input)
+                            .hint_text("0xC0011020")
+                            .desired_width(100.0),
+                    );
+                    ui.label("Cores");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut inspector.cores_input)
+                            .hint_text("all")
+                            .desired_width(100.0),
+                    );
+                    if ui.button("Read").clicked() {
+                        read = true;
+                    }
+                });
+
+                if let Some(ref err) = inspector.error {
+                    ui.colored_label(egui::Color32::RED, format!("Error: {}", err));
+                }
+
+                let Some(inspection) = &inspector.inspection else {
+                    return;
+                };
+                let common = inspection.common_value().unwrap_or_default();
+                let differing = inspection.differing_bits();
+                ui.separator();
+                match registers::register_at(inspection.index) {
+                    Some(register) => ui.label(format!(
+                        "MSR 0x{:X}: {} ({})",
+                        inspection.index, register.name, register.description
+                    )),
+                    None => ui.label(format!("MSR 0x{:X}", inspection.index)),
+                };
+
+                // Each core's value, with the ones that differ from the rest highlighted
+                egui::ScrollArea::vertical()
+                    .id_salt("msr_values")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("msr_values_grid")
+                            .num_columns(2)
+                            .show(ui, |ui| {
+                                for reading in &inspection.readings {
+                                    ui.label(reading.cpu.to_string());
+                                    let text = match &reading.value {
+                                        Ok(value) if *value != common => RichText::new(
+                                            format!("0x{:016X}", value),
+                                        )
+                                        .color(egui::Color32::YELLOW),
+                                        Ok(value) => RichText::new(format!("0x{:016X}", value)),
+                                        Err(err) => RichText::new(err).color(egui::Color32::RED),
+                                    };
+                                    ui.label(text.font(FontId::new(12.0, FontFamily::Monospace)));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+
+                // The most common value bit by bit, from bit 63 down
+                egui::Grid::new("msr_bits_grid")
+                    .num_columns(17)
+                    .spacing([4.0, 2.0])
+                    .show(ui, |ui| {
+                        for row in 0..4 {
+                            let top = 63 - row * 16;
+                            ui.label(
+                                RichText::new(format!("{}:{}", top, top - 15))
+                                    .font(FontId::new(12.0, FontFamily::Monospace)),
+                            );
+                            for bit in (top - 15..=top).rev() {
+                                let set = (common >> bit) & 1;
+                                let mut text = RichText::new(set.to_string())
+                                    .font(FontId::new(12.0, FontFamily::Monospace));
+                                if (differing >> bit) & 1 == 1 {
+                                    text = text.color(egui::Color32::YELLOW);
+                                }
+                                let name = inspector
+                                    .fields
+                                    .iter()
+                                    .find(|field| field.low_bit <= bit && bit <= field.high_bit)
+                                    .map(|field| field.name.as_str())
+                                    .unwrap_or("unnamed");
+                                ui.label(text).on_hover_text(format!("bit {}: {}", bit, name));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if differing != 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "Highlighted bits differ between cores",
+                    );
+                }
+                ui.separator();
+
+                // Fields named after the tweaks that write them
+                if inspector.fields.is_empty() {
+                    ui.label("No tweak uses this MSR.");
+                }
+                egui::Grid::new("msr_fields_grid")
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for field in &inspector.fields {
+                            let bits = field.field(inspection.index, 0);
+                            ui.label(bits.bits_label());
+                            ui.label(&field.name).on_hover_text(&field.description);
+                            ui.label(format!("0x{:X}", bits.read(common)));
+                            if ui.small_button("Edit").clicked() {
+                                inspector.low_bit = field.low_bit;
+                                inspector.high_bit = field.high_bit;
+                                inspector.value_input = format!("0x{:X}", bits.read(common));
+                                inspector.pending = None;
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Bits");
+                    ui.add(egui::DragValue::new(&mut inspector.high_bit).range(0..=63));
+                    ui.label(":");
+                    ui.add(egui::DragValue::new(&mut inspector.low_bit).range(0..=63));
+                    ui.label("Value");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut inspector.value_input)
+                            .hint_text("0x1")
+                            .desired_width(100.0),
+                    );
+                    if ui
+                        .add_enabled(
+                            inspector.low_bit <= inspector.high_bit,
+                            Button::new("Preview write"),
+                        )
+                        .clicked()
+                    {
+                        preview = true;
+                    }
+                });
+
+                if let Some((field, plan)) = &inspector.pending {
+                    ui.label(format!(
+                        "Write 0x{:X} to {} of MSR 0x{:X}:",
+                        field.value,
+                        field.bits_label(),
+                        field.index
+                    ));
+                    egui::ScrollArea::vertical()
+                        .id_salt("msr_write_plan")
+                        .max_height(150.0)
+                        .show(ui, |ui| {
+                            for step in plan {
+                                let text = RichText::new(format!(
+                                    "{}: 0x{:016X} -> 0x{:016X}",
+                                    step.cpu, step.current, step.new
+                                ))
+                                .font(FontId::new(12.0, FontFamily::Monospace));
+                                if step.current == step.new {
+                                    ui.label(text.weak());
+                                } else {
+                                    ui.label(text);
+                                }
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Write")
+                            .on_hover_text("Write the field on these cores")
+                            .clicked()
+                        {
+                            write = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            inspector.pending = None;
+                        }
+                    });
+                }
+
+                if let Some(last_write) = &inspector.last_write {
+                    if ui
+                        .button("Restore previous value")
+                        .on_hover_text(format!(
+                            "Write back the value MSR 0x{:X} had on each core before the last write",
+                            last_write.index
+                        ))
+                        .clicked()
+                    {
+                        restore = true;
+                    }
+                }
+            });
+        self.show_msr_inspector = open;
+
+        if read {
+            self.read_inspected_msr();
+        } else if preview {
+            self.preview_msr_write();
+        } else if write {
+            self.write_msr_field();
+        } else if restore {
+            self.restore_msr_write();
+        }
+    }
+
+    /// Works out what the field entered in the MSR inspector would change on each core,
+    /// for the user to confirm
+    fn preview_msr_write(&mut self) {
+        let inspector = &mut self.msr_inspector;
+        let Some(inspection) = &inspector.inspection else {
+            return;
+        };
+        let result = parse_hex(&inspector.value_input).and_then(|value| {
+            let field = MsrField::new(
+                inspection.index,
+                inspector.low_bit..=inspector.high_bit,
+                value,
+            );
+            if value & !(field.mask() >> field.low_bit) != 0 {
+                anyhow::bail!("0x{:X} does not fit in {} bits", value, field.width());
+            }
+            let plan = plan_write(&field, &inspector.cpus)?;
+            Ok((field, plan))
+        });
+        match result {
+            Ok(pending) => {
+                inspector.pending = Some(pending);
+                inspector.error = None;
+            }
+            Err(e) => {
+                tracing::error!("Failed to plan MSR write: {:?}", e);
+                inspector.pending = None;
+                inspector.error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Writes the confirmed field on the inspected cores
+    fn write_msr_field(&mut self) {
+        let Some((field, _)) = self.msr_inspector.pending.take() else {
+            return;
+        };
+        match write_field(&field, &self.msr_inspector.cpus) {
+            Ok(written) => {
+                self.msr_inspector.last_write = Some(written);
+                self.read_inspected_msr();
+            }
+            Err(e) => {
+                tracing::error!("Failed to write MSR 0x{:X}: {:?}", field.index, e);
+                self.read_inspected_msr();
+                self.msr_inspector.error = Some(format!("{:#}", e));
+            }
+        }
+        self.refresh_tweaks_using_msr(field.index);
+    }
+
+    /// Puts back the values the last write in the MSR inspector replaced
+    fn restore_msr_write(&mut self) {
+        let Some(last_write) = self.msr_inspector.last_write.take() else {
+            return;
+        };
+        let index = last_write.index;
+        let result = last_write.restore();
+        self.read_inspected_msr();
+        if let Err(e) = result {
+            tracing::error!("Failed to restore MSR 0x{:X}: {:?}", index, e);
+            self.msr_inspector.error = Some(format!("{:#}", e));
+            self.msr_inspector.last_write = Some(last_write);
+        }
+        self.refresh_tweaks_using_msr(index);
     }
 
     fn draw_status_bar(&mut self, ctx: &egui::Context) {
@@ -454,6 +1638,34 @@ impl MyApp {
                                 .font(FontId::proportional(LABEL_FONT_SIZE)),
                             );
 
+                            if !self.custom_tweak_errors.is_empty() {
+                                ui.separator();
+                                let count = self.custom_tweak_errors.len();
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{} tweak file{} failed to load",
+                                        count,
+                                        if count != 1 { "s" } else { "" }
+                                    ))
+                                    .font(FontId::proportional(LABEL_FONT_SIZE))
+                                    .color(egui::Color32::RED),
+                                )
+                                .on_hover_text(self.custom_tweak_errors.join("\n\n"));
+                            }
+
+                            if let Some(reason) = &self.hardware_unavailable {
+                                ui.separator();
+                                ui.label(
+                                    RichText::new("No hardware driver")
+                                        .font(FontId::proportional(LABEL_FONT_SIZE))
+                                        .color(egui::Color32::RED),
+                                )
+                                .on_hover_text(format!(
+                                    "{}. MSR tweaks and the MSR inspector are unavailable.",
+                                    reason
+                                ));
+                            }
+
                             ui.separator();
                             ui.with_layout(
                                 egui::Layout::right_to_left(egui::Align::Center),
@@ -515,6 +1727,29 @@ impl MyApp {
                                             ));
                                         }
                                     }
+
+                                    if ui
+                                        .add(
+                                            Button::new("MSR inspector")
+                                                .min_size(BUTTON_DIMENSIONS),
+                                        )
+                                        .on_hover_text(
+                                            "Read and edit any MSR on every core or a few of them",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.show_msr_inspector = !self.show_msr_inspector;
+                                    }
+
+                                    if ui
+                                        .add(Button::new("Profiles").min_size(BUTTON_DIMENSIONS))
+                                        .clicked()
+                                    {
+                                        self.show_profiles = !self.show_profiles;
+                                        if self.show_profiles {
+                                            self.refresh_saved_profiles();
+                                        }
+                                    }
                                 },
                             );
                         });
@@ -547,6 +1782,9 @@ impl App for MyApp {
                             ));
                         }
                     },
+                    Ok(StandardReply::No) => {
+                        tracing::info!("Continuing without the WinRing0 driver.");
+                    }
                     _ => {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                     }
@@ -568,6 +1806,26 @@ impl App for MyApp {
             } else {
                 self.draw_status_bar(ctx);
 
+                if self.show_profiles {
+                    self.draw_profiles_window(ctx);
+                }
+
+                if !self.pending_recovery.is_empty() {
+                    self.draw_recovery_window(ctx);
+                }
+
+                if self.plan_preview.is_some() {
+                    self.draw_plan_window(ctx);
+                }
+
+                if self.core_editor.is_some() {
+                    self.draw_core_editor_window(ctx);
+                }
+
+                if self.show_msr_inspector {
+                    self.draw_msr_inspector_window(ctx);
+                }
+
                 egui::CentralPanel::default().show(ctx, |ui| {
                     egui::ScrollArea::vertical()
                         .auto_shrink([false; 2])
@@ -584,6 +1842,19 @@ impl App for MyApp {
     }
 }
 
+/// Explains a `Mixed` state in the widget's tooltip, setting by setting.
+fn with_mixed_tooltip(response: egui::Response, state: &TweakOption) -> egui::Response {
+    let TweakOption::Mixed(settings) = state else {
+        return response;
+    };
+    let mut text = String::from("Matches none of the options:");
+    for line in tweaks::describe_mixed(settings) {
+        text.push('\n');
+        text.push_str(&line);
+    }
+    response.on_hover_text(text)
+}
+
 fn main() -> eframe::Result<()> {
     // Initialize logging based on build mode
     #[cfg(debug_assertions)]
@@ -1,11 +1,21 @@
-// src/worker.rs
+// src/orchestrator.rs
 
-use std::{sync::Arc, thread};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread,
+};
 
-use anyhow::Error;
+use anyhow::{Context, Error};
 use crossbeam::channel;
+use serde::{Deserialize, Serialize};
 
-use crate::tweaks::{TweakId, TweakMethod, TweakOption};
+use crate::{
+    backend::{self, Backends},
+    backup::BackupStore,
+    journal::{Journal, PendingTransaction},
+    tweaks::{TweakId, TweakMethod, TweakOption},
+};
 
 /// Represents the result of a processed task.
 #[derive(Debug)]
@@ -25,18 +35,44 @@ pub struct TweakTask {
 }
 
 /// Actions that can be performed on a tweak.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TweakAction {
     Enable,
     Disable,
     Set(TweakOption),
     ReadInitialState,
+    /// Puts back the values recorded before the tweak was first changed.
+    RestoreOriginal,
+}
+
+impl TweakAction {
+    /// Returns whether the action modifies the system, rather than only reading it.
+    fn changes_state(&self) -> bool {
+        !matches!(self, TweakAction::ReadInitialState)
+    }
+}
+
+impl From<TweakOption> for TweakAction {
+    /// Maps a desired option onto the action that puts a tweak into it.
+    fn from(option: TweakOption) -> Self {
+        match option {
+            TweakOption::Enabled(true) => TweakAction::Enable,
+            TweakOption::Enabled(false) => TweakAction::Disable,
+            option => TweakAction::Set(option),
+        }
+    }
 }
 
 /// The orchestrator managing task execution.
 pub struct TaskOrchestrator {
     result_receiver: channel::Receiver<TweakResult>,
     result_sender: channel::Sender<TweakResult>,
+    /// Original values of every tweak that has been changed.
+    backups: Arc<Mutex<BackupStore>>,
+    /// Write-ahead journal of every change in progress.
+    journal: Arc<Journal>,
+    /// The backends every task runs against, those of the thread that created the orchestrator.
+    backends: Backends,
 }
 
 impl Default for TaskOrchestrator {
@@ -46,94 +82,443 @@ impl Default for TaskOrchestrator {
 }
 
 impl TaskOrchestrator {
-    /// Creates a new TaskOrchestrator.
+    /// Creates a new TaskOrchestrator that keeps its backups and journal in the app data directory.
     pub fn new() -> Self {
+        let backups = BackupStore::load_default().unwrap_or_else(|e| {
+            tracing::error!(
+                "Failed to load backups, original values will not be persisted: {:?}",
+                e
+            );
+            BackupStore::in_memory()
+        });
+        let journal = Journal::open_default().unwrap_or_else(|e| {
+            tracing::error!(
+                "Failed to open journal, changes will not be recoverable: {:?}",
+                e
+            );
+            Journal::disabled()
+        });
+        Self::with_stores(backups, journal)
+    }
+
+    /// Creates a new TaskOrchestrator that uses the given backup store and journal. Its
+    /// tasks run against the backends of the calling thread, e.g. the fakes a test installed.
+    pub fn with_stores(backups: BackupStore, journal: Journal) -> Self {
         let (result_sender, result_receiver) = channel::unbounded::<TweakResult>();
         Self {
             result_sender,
             result_receiver,
+            backups: Arc::new(Mutex::new(backups)),
+            journal: Arc::new(journal),
+            backends: backend::current(),
         }
     }
 
+    /// Returns the changes a previous run was interrupted in the middle of.
+    pub fn pending_recovery(&self) -> Vec<PendingTransaction> {
+        self.journal.pending()
+    }
+
+    /// Undoes every modification an interrupted change made. `method` is the method of
+    /// the tweak that was being changed, or `None` if this build does not know it.
+    ///
+    /// # Returns
+    /// - `Ok(())` once every modification was undone.
+    /// - `Err(anyhow::Error)` if one could not be, or if the tweak does not journal its
+    ///   modifications, so that there is no record of what to undo.
+    pub fn roll_back_pending(
+        &self,
+        transaction: &PendingTransaction,
+        method: Option<&dyn TweakMethod>,
+    ) -> anyhow::Result<()> {
+        if transaction.steps.is_empty() && !method.is_some_and(|method| method.journaled()) {
+            anyhow::bail!(
+                "{} cannot be rolled back, as it does not record its modifications; roll it forward or set it again",
+                transaction.tweak
+            );
+        }
+        let _backends = backend::scoped(self.backends.clone());
+        transaction.roll_back()?;
+        self.journal.resolve(transaction.txn)
+    }
+
+    /// Finishes an interrupted change by running its action again. The change stays
+    /// pending until the task succeeds, so that it can be recovered again if the task
+    /// fails or is interrupted too.
+    pub fn roll_forward_pending(
+        &self,
+        transaction: &PendingTransaction,
+        method: Arc<dyn TweakMethod>,
+    ) -> anyhow::Result<()> {
+        let id = transaction
+            .id()
+            .with_context(|| format!("Unknown tweak '{}'", transaction.tweak))?;
+        self.spawn_sequence(
+            vec![TweakTask {
+                id,
+                method,
+                action: transaction.action.clone(),
+            }],
+            Some(transaction.txn),
+        );
+        Ok(())
+    }
+
+    /// Returns whether the original values of a tweak have been recorded.
+    pub fn has_backup(&self, id: TweakId) -> bool {
+        self.backups
+            .lock()
+            .map(|backups| backups.contains(id))
+            .unwrap_or(false)
+    }
+
     /// Submits a new task to be processed.
     pub fn submit_task(&self, task: TweakTask) -> anyhow::Result<()> {
+        self.submit_sequence(vec![task])
+    }
+
+    /// Submits tasks that must run one after another, in the given order.
+    ///
+    /// Used for tweaks that depend on each other or change the same settings, which
+    /// must not be applied at the same time. Each task still produces its own result.
+    pub fn submit_sequence(&self, tasks: Vec<TweakTask>) -> anyhow::Result<()> {
+        self.spawn_sequence(tasks, None);
+        Ok(())
+    }
+
+    /// Runs tasks one after another on a new thread. If every task succeeds and
+    /// `resolves` names an interrupted transaction, it is marked as dealt with.
+    fn spawn_sequence(&self, tasks: Vec<TweakTask>, resolves: Option<u64>) {
         let result_sender = self.result_sender.clone();
+        let backups = self.backups.clone();
+        let journal = self.journal.clone();
+        let backends = self.backends.clone();
         thread::spawn(move || {
-            let result = match task.action {
-                TweakAction::Enable => match task.method.apply(TweakOption::Enabled(true)) {
-                    Ok(_) => TweakResult {
-                        id: task.id,
-                        success: true,
-                        error: None,
-                        action: TweakAction::Enable,
-                        state: Some(TweakOption::Enabled(true)),
-                    },
-                    Err(e) => TweakResult {
-                        id: task.id,
-                        success: false,
-                        error: Some(e),
-                        action: TweakAction::Enable,
-                        state: None,
-                    },
-                },
-                TweakAction::Disable => match task.method.revert() {
-                    Ok(_) => TweakResult {
-                        id: task.id,
-                        success: true,
-                        error: None,
-                        action: TweakAction::Disable,
-                        state: Some(TweakOption::Enabled(false)),
-                    },
-                    Err(e) => TweakResult {
-                        id: task.id,
-                        success: false,
-                        error: Some(e),
-                        action: TweakAction::Disable,
-                        state: None,
-                    },
-                },
-                TweakAction::Set(option) => match task.method.apply(option.clone()) {
-                    Ok(_) => TweakResult {
-                        id: task.id,
-                        success: true,
-                        error: None,
-                        action: TweakAction::Set(option.clone()),
-                        state: Some(option),
-                    },
-                    Err(e) => TweakResult {
-                        id: task.id,
-                        success: false,
-                        error: Some(e),
-                        action: TweakAction::Set(option),
-                        state: None,
-                    },
-                },
-                TweakAction::ReadInitialState => match task.method.initial_state() {
-                    Ok(state) => TweakResult {
-                        id: task.id,
-                        success: true,
-                        error: None,
-                        action: TweakAction::ReadInitialState,
-                        state: Some(state),
-                    },
-                    Err(e) => TweakResult {
-                        id: task.id,
-                        success: false,
-                        error: Some(e),
-                        action: TweakAction::ReadInitialState,
-                        state: None,
-                    },
-                },
-            };
-            if let Err(e) = result_sender.send(result) {
-                tracing::error!("Failed to send result: {:?}", e);
+            let _backends = backend::scoped(backends);
+            let count = tasks.len();
+            let mut succeeded = true;
+            for (index, task) in tasks.into_iter().enumerate() {
+                let result = process_task(&task, &backups, &journal);
+                succeeded &= result.success;
+
+                // Resolve before the last result is reported, so that a caller which
+                // stops once it has every result never leaves the transaction pending
+                if let Some(txn) = resolves.filter(|_| succeeded && index + 1 == count) {
+                    if let Err(e) = journal.resolve(txn) {
+                        tracing::error!("Failed to resolve journal transaction {}: {:?}", txn, e);
+                    }
+                }
+                if let Err(e) = result_sender.send(result) {
+                    tracing::error!("Failed to send result: {:?}", e);
+                }
             }
         });
-        Ok(())
     }
 
     /// Attempts to receive a task result without blocking.
     pub fn try_recv_result(&self) -> Option<TweakResult> {
         self.result_receiver.try_recv().ok()
     }
+
+    /// Blocks until the next task result is available.
+    pub fn recv_result(&self) -> Option<TweakResult> {
+        self.result_receiver.recv().ok()
+    }
+}
+
+/// Runs a single task to completion, backing up and journaling it as needed.
+fn process_task(
+    task: &TweakTask,
+    backups: &Mutex<BackupStore>,
+    journal: &Arc<Journal>,
+) -> TweakResult {
+    let failed = |error: Error| TweakResult {
+        id: task.id,
+        success: false,
+        error: Some(error),
+        action: task.action.clone(),
+        state: None,
+    };
+
+    // Record the original values before the tweak is changed for the first time.
+    // If they cannot be captured, the change is not made.
+    if matches!(
+        task.action,
+        TweakAction::Enable | TweakAction::Disable | TweakAction::Set(_)
+    ) {
+        if let Err(e) = run_guarded(|| record_backup(backups, task)) {
+            tracing::error!(
+                "{:?} -> Failed to back up original values: {:?}",
+                task.id,
+                e
+            );
+            return failed(e.context("Failed to back up the original values"));
+        }
+    }
+
+    // Journal the change so that it can be recovered if the process dies part way through
+    let transaction = if task.action.changes_state() {
+        match journal.begin(task.id, &task.action) {
+            Ok(transaction) => Some(transaction),
+            Err(e) => {
+                tracing::error!("{:?} -> Failed to journal change: {:?}", task.id, e);
+                return failed(e.context("Failed to journal the change"));
+            }
+        }
+    } else {
+        None
+    };
+
+    let result = match task.action.clone() {
+        TweakAction::Enable => {
+            match run_guarded(|| task.method.apply(TweakOption::Enabled(true))) {
+                Ok(_) => TweakResult {
+                    id: task.id,
+                    success: true,
+                    error: None,
+                    action: TweakAction::Enable,
+                    state: Some(TweakOption::Enabled(true)),
+                },
+                Err(e) => TweakResult {
+                    id: task.id,
+                    success: false,
+                    error: Some(e),
+                    action: TweakAction::Enable,
+                    state: None,
+                },
+            }
+        }
+        TweakAction::Disable => match run_guarded(|| task.method.revert()) {
+            Ok(_) => TweakResult {
+                id: task.id,
+                success: true,
+                error: None,
+                action: TweakAction::Disable,
+                state: Some(TweakOption::Enabled(false)),
+            },
+            Err(e) => TweakResult {
+                id: task.id,
+                success: false,
+                error: Some(e),
+                action: TweakAction::Disable,
+                state: None,
+            },
+        },
+        TweakAction::Set(option) => match run_guarded(|| task.method.apply(option.clone())) {
+            Ok(_) => TweakResult {
+                id: task.id,
+                success: true,
+                error: None,
+                action: TweakAction::Set(option.clone()),
+                state: Some(option),
+            },
+            Err(e) => TweakResult {
+                id: task.id,
+                success: false,
+                error: Some(e),
+                action: TweakAction::Set(option),
+                state: None,
+            },
+        },
+        TweakAction::ReadInitialState => match run_guarded(|| task.method.initial_state()) {
+            Ok(state) => TweakResult {
+                id: task.id,
+                success: true,
+                error: None,
+                action: TweakAction::ReadInitialState,
+                state: Some(state),
+            },
+            Err(e) => TweakResult {
+                id: task.id,
+                success: false,
+                error: Some(e),
+                action: TweakAction::ReadInitialState,
+                state: None,
+            },
+        },
+        TweakAction::RestoreOriginal => match run_guarded(|| restore_backup(backups, task)) {
+            Ok(state) => TweakResult {
+                id: task.id,
+                success: true,
+                error: None,
+                action: TweakAction::RestoreOriginal,
+                state: Some(state),
+            },
+            Err(e) => TweakResult {
+                id: task.id,
+                success: false,
+                error: Some(e),
+                action: TweakAction::RestoreOriginal,
+                state: None,
+            },
+        },
+    };
+
+    // End the transaction before reporting, so a finished task is never left pending
+    drop(transaction);
+    result
+}
+
+/// Snapshots a tweak into the backup store, unless it already has a backup.
+fn record_backup(backups: &Mutex<BackupStore>, task: &TweakTask) -> anyhow::Result<()> {
+    let lock_backups = || {
+        backups
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Failed to lock backup store: {:?}", e))
+    };
+
+    if lock_backups()?.contains(task.id) {
+        return Ok(());
+    }
+
+    // Take the snapshot without holding the lock, as it may be slow
+    if let Some(snapshot) = task.method.snapshot()? {
+        if lock_backups()?.record(task.id, snapshot)? {
+            tracing::info!("{:?} -> Recorded original values.", task.id);
+        }
+    }
+    Ok(())
+}
+
+/// Restores a tweak from the backup store and drops its backup.
+///
+/// # Returns
+/// The state of the tweak after the restore.
+fn restore_backup(backups: &Mutex<BackupStore>, task: &TweakTask) -> anyhow::Result<TweakOption> {
+    let lock_backups = || {
+        backups
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Failed to lock backup store: {:?}", e))
+    };
+
+    let entry = lock_backups()?
+        .get(task.id)
+        .cloned()
+        .with_context(|| format!("No original values recorded for {:?}", task.id))?;
+
+    task.method.restore(&entry.snapshot)?;
+    lock_backups()?.remove(task.id)?;
+    tracing::info!("{:?} -> Restored original values.", task.id);
+
+    task.method.initial_state()
+}
+
+/// Runs a tweak method call, converting a panic into an error so that every
+/// submitted task still produces a result.
+fn run_guarded<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(anyhow::anyhow!("Tweak method panicked: {}", message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::{backend::fake::FakeSystem, tweaks::all_tweaks};
+
+    /// A tweak that journals nothing, and fails to apply until told otherwise.
+    #[derive(Default)]
+    struct FlakyMethod {
+        works: AtomicBool,
+    }
+
+    impl TweakMethod for FlakyMethod {
+        fn initial_state(&self) -> anyhow::Result<TweakOption> {
+            Ok(TweakOption::Enabled(false))
+        }
+
+        fn apply(&self, _option: TweakOption) -> anyhow::Result<()> {
+            if !self.works.load(Ordering::SeqCst) {
+                anyhow::bail!("not yet");
+            }
+            Ok(())
+        }
+
+        fn revert(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_interrupted_change_stays_pending_until_recovered() {
+        let dir =
+            std::env::temp_dir().join(format!("oc_tool_orchestrator_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("journal.jsonl");
+        fs::write(
+            &path,
+            "{\"Begin\":{\"txn\":3,\"tweak\":\"DisableDps\",\"action\":\"Enable\"}}\n",
+        )
+        .unwrap();
+        let orchestrator =
+            TaskOrchestrator::with_stores(BackupStore::in_memory(), Journal::open(&path).unwrap());
+        let transaction = orchestrator.pending_recovery().remove(0);
+        let method = Arc::new(FlakyMethod::default());
+
+        // Nothing was journaled, so there is no telling what to undo
+        let error = orchestrator
+            .roll_back_pending(&transaction, Some(method.as_ref()))
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("cannot be rolled back"));
+        assert_eq!(orchestrator.pending_recovery().len(), 1);
+
+        // A roll forward that fails leaves the change pending
+        orchestrator
+            .roll_forward_pending(&transaction, method.clone())
+            .unwrap();
+        assert!(!orchestrator.recv_result().unwrap().success);
+        assert_eq!(orchestrator.pending_recovery().len(), 1);
+
+        method.works.store(true, Ordering::SeqCst);
+        orchestrator
+            .roll_forward_pending(&transaction, method.clone())
+            .unwrap();
+        assert!(orchestrator.recv_result().unwrap().success);
+        assert!(orchestrator.pending_recovery().is_empty());
+
+        drop(orchestrator);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tasks_use_the_backends_of_the_creating_thread() {
+        let system = FakeSystem::new(4);
+        let orchestrator = {
+            let _backends = system.install();
+            TaskOrchestrator::with_stores(BackupStore::in_memory(), Journal::disabled())
+        };
+        let before = system.state().unwrap();
+
+        let method = all_tweaks()
+            .remove(&TweakId::LargeSystemCache)
+            .unwrap()
+            .method;
+        orchestrator
+            .submit_task(TweakTask {
+                id: TweakId::LargeSystemCache,
+                method: method.clone(),
+                action: TweakAction::Enable,
+            })
+            .unwrap();
+        let result = orchestrator.recv_result().unwrap();
+        assert!(result.success, "{:?}", result.error);
+
+        // The task changed the fakes, even though this thread never used them
+        assert_ne!(system.state().unwrap(), before);
+        let _backends = system.install();
+        assert_eq!(method.initial_state().unwrap(), TweakOption::Enabled(true));
+    }
 }
@@ -0,0 +1,457 @@
+// src/profile.rs
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    tweaks::{Tweak, TweakId, TweakOption},
+    ui::TweakWidget,
+    utils::{cpu::CoreSelection, paths::profiles_dir},
+};
+
+/// Version of the profile file format written by this build. Version 2 added the
+/// cores each per-core tweak targets.
+pub const PROFILE_FORMAT_VERSION: u32 = 2;
+
+/// A named set of tweak options (e.g. "SuperPi 32M") that can be saved to disk
+/// and re-applied later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Display name of the profile.
+    pub name: String,
+    /// The option each tweak should be set to.
+    pub tweaks: BTreeMap<TweakId, TweakOption>,
+    /// The cores each per-core tweak is applied on. Tweaks left out are applied on
+    /// every core.
+    pub targets: BTreeMap<TweakId, CoreSelection>,
+    /// Entries this build does not understand, such as tweaks that were removed or
+    /// added in another version. They are kept so that re-saving does not drop them.
+    pub unknown: BTreeMap<String, Value>,
+}
+
+/// On-disk representation of a profile. Tweaks are keyed by the name of their
+/// `TweakId`, so a file stays loadable when variants are added or removed.
+#[derive(Serialize, Deserialize)]
+struct ProfileFile {
+    version: u32,
+    name: String,
+    tweaks: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    targets: BTreeMap<String, CoreSelection>,
+}
+
+impl Profile {
+    /// Captures the current state of every stateful tweak, and the cores of every
+    /// per-core tweak that is not applied on all of them. Action tweaks (buttons)
+    /// have no lasting state and are left out, as are tweaks in a `Mixed` state, which
+    /// cannot be applied.
+    pub fn capture(name: &str, tweaks: &BTreeMap<TweakId, Tweak>) -> Self {
+        let captured: Vec<(&TweakId, &Tweak)> = tweaks
+            .iter()
+            .filter(|(_, tweak)| {
+                *tweak.widget != TweakWidget::Button
+                    && !matches!(tweak.state, TweakOption::Mixed(_))
+                    && tweak.method.unsupported().is_none()
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            tweaks: captured
+                .iter()
+                .map(|(id, tweak)| (**id, tweak.state.clone()))
+                .collect(),
+            targets: captured
+                .iter()
+                .filter_map(|(id, tweak)| {
+                    tweak
+                        .method
+                        .targets()
+                        .filter(|targets| *targets != CoreSelection::All)
+                        .map(|targets| (**id, targets))
+                })
+                .collect(),
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the cores the profile applies a tweak on.
+    pub fn targets_for(&self, id: TweakId) -> CoreSelection {
+        self.targets.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Points every per-core tweak among `changes` at the cores the profile applies it
+    /// on, ahead of applying it.
+    ///
+    /// # Returns
+    /// The tweaks whose cores could not be set, e.g. because the profile names a CCD
+    /// this processor does not have, with the reason.
+    pub fn set_targets(
+        &self,
+        tweaks: &BTreeMap<TweakId, Tweak>,
+        changes: &[(TweakId, TweakOption)],
+    ) -> Vec<(TweakId, Error)> {
+        changes
+            .iter()
+            .filter_map(|(id, _)| {
+                let tweak = tweaks.get(id)?;
+                tweak.method.targets()?;
+                let error = tweak.method.set_targets(self.targets_for(*id)).err()?;
+                Some((*id, error))
+            })
+            .collect()
+    }
+
+    /// Returns the tweaks whose current state differs from the profile, or that are
+    /// applied on other cores than the profile's, together with the option the profile
+    /// wants. Tweaks that are not available, cannot be used on this system, or whose
+    /// saved option no longer exists, are skipped.
+    pub fn changes(&self, tweaks: &BTreeMap<TweakId, Tweak>) -> Vec<(TweakId, TweakOption)> {
+        self.tweaks
+            .iter()
+            .filter_map(|(id, option)| {
+                let Some(tweak) = tweaks.get(id) else {
+                    warn!("Profile '{}': tweak {:?} is not available.", self.name, id);
+                    return None;
+                };
+                if let Some(reason) = tweak.method.unsupported() {
+                    warn!("Profile '{}': {:?} is skipped. {}", self.name, id, reason);
+                    return None;
+                }
+                if !tweak.options.contains(option) {
+                    warn!(
+                        "Profile '{}': {:?} has no option {:?}.",
+                        self.name, id, option
+                    );
+                    return None;
+                }
+                let retarget = tweak
+                    .method
+                    .targets()
+                    .is_some_and(|targets| targets != self.targets_for(*id));
+                (tweak.state != *option || retarget).then(|| (*id, option.clone()))
+            })
+            .collect()
+    }
+
+    /// Parses a profile from its JSON representation.
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let file: ProfileFile =
+            serde_json::from_str(contents).context("Failed to parse profile JSON")?;
+
+        if file.version > PROFILE_FORMAT_VERSION {
+            anyhow::bail!(
+                "Profile '{}' uses format version {}, but this build only supports up to version {}",
+                file.name,
+                file.version,
+                PROFILE_FORMAT_VERSION
+            );
+        }
+
+        let mut tweaks = BTreeMap::new();
+        let mut unknown = BTreeMap::new();
+        for (key, value) in file.tweaks {
+            let id = TweakId::from_str(&key);
+            let option = serde_json::from_value::<TweakOption>(value.clone());
+            match (id, option) {
+                (Ok(id), Ok(option)) => {
+                    tweaks.insert(id, option);
+                }
+                _ => {
+                    warn!(
+                        "Profile '{}': ignoring unrecognised entry '{}'.",
+                        file.name, key
+                    );
+                    unknown.insert(key, value);
+                }
+            }
+        }
+
+        let mut targets = BTreeMap::new();
+        for (key, selection) in file.targets {
+            match TweakId::from_str(&key) {
+                Ok(id) => {
+                    targets.insert(id, selection);
+                }
+                Err(_) => warn!(
+                    "Profile '{}': ignoring the cores of unrecognised entry '{}'.",
+                    file.name, key
+                ),
+            }
+        }
+
+        Ok(Self {
+            name: file.name,
+            tweaks,
+            targets,
+            unknown,
+        })
+    }
+
+    /// Serializes the profile to pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        let mut tweaks = self.unknown.clone();
+        for (id, option) in &self.tweaks {
+            tweaks.insert(id.to_string(), serde_json::to_value(option)?);
+        }
+
+        let file = ProfileFile {
+            version: PROFILE_FORMAT_VERSION,
+            name: self.name.clone(),
+            tweaks,
+            targets: self
+                .targets
+                .iter()
+                .map(|(id, selection)| (id.to_string(), selection.clone()))
+                .collect(),
+        };
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    /// Loads a profile from a file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profile '{}'", path.display()))?;
+        Self::from_json(&contents)
+            .with_context(|| format!("Failed to load profile '{}'", path.display()))
+    }
+
+    /// Saves the profile to a file, replacing any existing one.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json()?)
+            .with_context(|| format!("Failed to write profile '{}'", path.display()))
+    }
+
+    /// Saves the profile into the profiles directory and returns the file it was written to.
+    pub fn save_to_profiles_dir(&self) -> Result<PathBuf> {
+        self.save_in(&profiles_dir()?)
+    }
+
+    /// Saves the profile into `dir`, under a file name derived from its name.
+    ///
+    /// Different names can map to the same file name, e.g. `a/b` and `a_b`, so a
+    /// file holding a profile with another name is never overwritten.
+    ///
+    /// # Returns
+    /// - `Ok(PathBuf)` with the file the profile was written to.
+    /// - `Err(anyhow::Error)` if the file holds another profile, or cannot be written.
+    fn save_in(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(profile_file_name(&self.name));
+        if path.exists() {
+            let existing = Profile::load(&path)
+                .context("Refusing to overwrite a file that is not a readable profile")?;
+            if existing.name != self.name {
+                anyhow::bail!(
+                    "Cannot save profile '{}': '{}' already holds profile '{}'",
+                    self.name,
+                    path.display(),
+                    existing.name
+                );
+            }
+        }
+        self.save(&path)?;
+        Ok(path)
+    }
+}
+
+/// Loads every profile in the profiles directory, sorted by name. Files that
+/// fail to load are logged and skipped.
+pub fn saved_profiles() -> Result<Vec<(PathBuf, Profile)>> {
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(profiles_dir()?)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match Profile::load(&path) {
+            Ok(profile) => profiles.push((path, profile)),
+            Err(e) => warn!("Skipping profile '{}': {:?}", path.display(), e),
+        }
+    }
+    profiles.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// Looks up a saved profile by its name.
+pub fn find_saved_profile(name: &str) -> Result<Option<Profile>> {
+    Ok(saved_profiles()?
+        .into_iter()
+        .map(|(_, profile)| profile)
+        .find(|profile| profile.name == name))
+}
+
+/// Builds a file name for a profile, replacing characters Windows does not allow.
+fn profile_file_name(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    format!("{}.json", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tweaks::{TweakCategory, TweakMethod};
+
+    struct NoopMethod;
+
+    impl TweakMethod for NoopMethod {
+        fn initial_state(&self) -> Result<TweakOption> {
+            Ok(TweakOption::Enabled(false))
+        }
+
+        fn apply(&self, _option: TweakOption) -> Result<()> {
+            Ok(())
+        }
+
+        fn revert(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn toggle(state: bool) -> Tweak<'static> {
+        let mut tweak = Tweak::winapi(
+            "Test",
+            "Test tweak",
+            TweakCategory::System,
+            NoopMethod,
+            &TweakWidget::Toggle,
+            false,
+        );
+        tweak.state = TweakOption::Enabled(state);
+        tweak
+    }
+
+    #[test]
+    fn test_changes_only_include_differing_tweaks() {
+        let tweaks = BTreeMap::from([
+            (TweakId::DisableHPET, toggle(true)),
+            (TweakId::DisableDps, toggle(false)),
+        ]);
+        let profile = Profile {
+            name: "Bench".to_string(),
+            tweaks: BTreeMap::from([
+                (TweakId::DisableHPET, TweakOption::Enabled(true)),
+                (TweakId::DisableDps, TweakOption::Enabled(true)),
+                (TweakId::KillExplorer, TweakOption::Enabled(true)),
+            ]),
+            targets: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+        };
+
+        assert_eq!(
+            profile.changes(&tweaks),
+            vec![(TweakId::DisableDps, TweakOption::Enabled(true))]
+        );
+    }
+
+    #[test]
+    fn test_mixed_tweaks_are_not_captured() {
+        let mut mixed = toggle(false);
+        mixed.state = TweakOption::Mixed(Vec::new());
+        let tweaks = BTreeMap::from([
+            (TweakId::DisableHPET, toggle(true)),
+            (TweakId::DisableDps, mixed),
+        ]);
+
+        let profile = Profile::capture("Bench", &tweaks);
+        assert_eq!(
+            profile.tweaks,
+            BTreeMap::from([(TweakId::DisableHPET, TweakOption::Enabled(true))])
+        );
+    }
+
+    #[test]
+    fn test_profile_round_trip() {
+        let profile = Profile {
+            name: "SuperPi 32M".to_string(),
+            tweaks: BTreeMap::from([
+                (TweakId::DisableHPET, TweakOption::Enabled(true)),
+                (
+                    TweakId::AggressivePrefetchProfile,
+                    TweakOption::Option("Level 2".to_string()),
+                ),
+            ]),
+            targets: BTreeMap::from([(
+                TweakId::AggressivePrefetchProfile,
+                CoreSelection::Ccds([1].into()),
+            )]),
+            unknown: BTreeMap::new(),
+        };
+
+        let json = profile.to_json().unwrap();
+        assert_eq!(Profile::from_json(&json).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_unknown_tweak_ids_are_kept() {
+        let json = r#"{
+            "version": 1,
+            "name": "3DMark CPU",
+            "tweaks": {
+                "DisableHPET": { "Enabled": true },
+                "SomeTweakFromTheFuture": { "Enabled": false }
+            }
+        }"#;
+
+        let profile = Profile::from_json(json).unwrap();
+        assert_eq!(
+            profile.tweaks,
+            BTreeMap::from([(TweakId::DisableHPET, TweakOption::Enabled(true))])
+        );
+        assert!(profile.unknown.contains_key("SomeTweakFromTheFuture"));
+
+        let reloaded = Profile::from_json(&profile.to_json().unwrap()).unwrap();
+        assert!(reloaded.unknown.contains_key("SomeTweakFromTheFuture"));
+    }
+
+    #[test]
+    fn test_newer_format_version_is_rejected() {
+        let json = r#"{ "version": 999, "name": "Future", "tweaks": {} }"#;
+        assert!(Profile::from_json(json).is_err());
+    }
+
+    #[test]
+    fn test_profile_file_name() {
+        assert_eq!(profile_file_name("SuperPi 32M"), "SuperPi 32M.json");
+        assert_eq!(profile_file_name("a/b:c"), "a_b_c.json");
+    }
+
+    #[test]
+    fn test_saving_never_overwrites_another_profile() {
+        let dir = std::env::temp_dir().join(format!("oc_tool_profile_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let profile = |name: &str| Profile {
+            name: name.to_string(),
+            tweaks: BTreeMap::from([(TweakId::DisableHPET, TweakOption::Enabled(true))]),
+            targets: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+        };
+
+        let path = profile("a_b").save_in(&dir).unwrap();
+        assert!(profile("a/b").save_in(&dir).is_err());
+        assert_eq!(Profile::load(&path).unwrap().name, "a_b");
+
+        // Saving a profile again under the same name replaces it
+        let mut updated = profile("a_b");
+        updated.tweaks.clear();
+        assert_eq!(updated.save_in(&dir).unwrap(), path);
+        assert!(Profile::load(&path).unwrap().tweaks.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
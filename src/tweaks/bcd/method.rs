@@ -0,0 +1,440 @@
+// src/tweaks/bcd/method.rs
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use tracing::{debug, error};
+
+use crate::{
+    backend::{self, BcdBackend},
+    backup::{BcdValueSnapshot, TweakSnapshot},
+    journal::{record_step, JournalStep},
+    tweaks::{
+        default_option_of,
+        plan::{PlanStep, TweakPlan},
+        relations::TweakSetting,
+        SettingState, TweakId, TweakMethod, TweakOption,
+    },
+    utils::bcd::BcdEntry,
+};
+
+/// A value of an element of a boot entry.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct BcdValue {
+    /// The element's `bcdedit` name, e.g. `useplatformclock`.
+    pub element: String,
+    /// The value as `bcdedit /enum` prints it, e.g. `Yes` rather than `true`, or `None`
+    /// to delete the element so that Windows uses its default.
+    pub value: Option<String>,
+}
+
+impl BcdValue {
+    /// Sets `element` to `value`.
+    pub fn set(element: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            element: element.into(),
+            value: Some(value.into()),
+        }
+    }
+
+    /// Deletes `element`.
+    pub fn delete(element: impl Into<String>) -> Self {
+        Self {
+            element: element.into(),
+            value: None,
+        }
+    }
+
+    /// Returns whether `entry` holds this value. `bcdedit` ignores case.
+    fn is_set_in(&self, entry: &BcdEntry) -> bool {
+        same_value(entry.get(&self.element), self.value.as_deref())
+    }
+}
+
+/// Compares element values the way `bcdedit` does, ignoring case.
+fn same_value(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Defines a set of elements of a boot entry, which in combination make up a single
+/// tweak. Changes take effect at the next boot.
+#[derive(Debug)]
+pub struct BcdTweak {
+    /// Unique ID for the tweak.
+    pub id: TweakId,
+    /// The boot entry to change, usually `{current}`.
+    pub entry: String,
+    /// Mapping from `TweakOption` to the element values set for it.
+    pub options: IndexMap<TweakOption, Vec<BcdValue>>,
+}
+
+impl BcdTweak {
+    /// Returns every distinct element any option changes, in the order the options list
+    /// them.
+    fn elements(&self) -> Vec<&str> {
+        let mut elements: Vec<&str> = Vec::new();
+        for value in self.options.values().flatten() {
+            if !elements.contains(&value.element.as_str()) {
+                elements.push(&value.element);
+            }
+        }
+        elements
+    }
+
+    /// Reads the boot entry the tweak changes.
+    fn read_entry(&self, bcd: &dyn BcdBackend) -> Result<BcdEntry> {
+        bcd.read_entry(&self.entry)
+            .with_context(|| format!("{:?} -> Failed to read boot entry {}", self.id, self.entry))
+    }
+
+    /// Sets or deletes an element, recording the change in the journal before it is
+    /// made, and checks that it reads back.
+    fn write_element(
+        &self,
+        bcd: &dyn BcdBackend,
+        element: &str,
+        current: Option<&str>,
+        new: Option<&str>,
+    ) -> Result<()> {
+        let step = JournalStep::Bcd {
+            entry: self.entry.clone(),
+            element: element.to_string(),
+            before: current.map(str::to_string),
+            after: new.map(str::to_string),
+        };
+        record_step(step, || {
+            match new {
+                Some(value) => bcd.set(&self.entry, element, value),
+                None => bcd.delete(&self.entry, element),
+            }
+            .with_context(|| format!("{:?} -> Failed to change {}", self.id, element))
+        })?;
+
+        let updated = self.read_entry(bcd)?;
+        if !same_value(updated.get(element), new) {
+            anyhow::bail!(
+                "{} reads {} after setting {}",
+                element,
+                updated.get(element).unwrap_or("<unset>"),
+                new.unwrap_or("<unset>")
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets `values`, all or nothing. If any change fails or does not read back, every
+    /// element changed so far is put back to its original value. `action` names the
+    /// operation in messages.
+    fn write_values(&self, values: &[BcdValue], action: &str) -> Result<()> {
+        let bcd = backend::bcd();
+        let entry = self.read_entry(bcd.as_ref())?;
+
+        let mut written: Vec<(&str, Option<&str>)> = Vec::new();
+        for value in values {
+            if value.is_set_in(&entry) {
+                continue;
+            }
+            let current = entry.get(&value.element);
+            written.push((&value.element, current));
+            let Err(cause) = self.write_element(
+                bcd.as_ref(),
+                &value.element,
+                current,
+                value.value.as_deref(),
+            ) else {
+                continue;
+            };
+            error!(
+                "{:?} -> Failed to {}: {:#}. Restoring the original values.",
+                self.id, action, cause
+            );
+
+            for (element, original) in written.iter().rev() {
+                let restored = self.read_entry(bcd.as_ref()).and_then(|entry| {
+                    let current = entry.get(element);
+                    if same_value(current, *original) {
+                        Ok(())
+                    } else {
+                        self.write_element(bcd.as_ref(), element, current, *original)
+                    }
+                });
+                if let Err(e) = restored {
+                    error!("{:?} -> Failed to restore {}: {:?}", self.id, element, e);
+                    return Err(cause.context(format!(
+                        "{:?} -> Failed to {}, and {} could not be restored",
+                        self.id, action, element
+                    )));
+                }
+            }
+            return Err(cause.context(format!(
+                "{:?} -> Failed to {}; every element was restored",
+                self.id, action
+            )));
+        }
+
+        tracing::info!(
+            "{:?} -> Successfully {} {} boot elements.",
+            self.id,
+            if action == "revert" {
+                "reverted"
+            } else {
+                "applied"
+            },
+            values.len()
+        );
+        Ok(())
+    }
+}
+
+impl TweakMethod for BcdTweak {
+    /// Reads the boot entry and returns the option its elements match.
+    ///
+    /// # Returns
+    /// - `Ok(TweakOption)` indicating the current state, or `TweakOption::Mixed` with
+    ///   every element if no option matches.
+    /// - `Err(anyhow::Error)` if the boot entry cannot be read.
+    fn initial_state(&self) -> Result<TweakOption> {
+        debug!(
+            "{:?} -> Determining the initial state of the BCD tweak.",
+            self.id
+        );
+
+        let entry = self.read_entry(backend::bcd().as_ref())?;
+        for (option, values) in &self.options {
+            if values.iter().all(|value| value.is_set_in(&entry)) {
+                debug!("{:?} -> Current state matches {:?}.", self.id, option);
+                return Ok(option.clone());
+            }
+        }
+
+        debug!("{:?} -> Current state does not match any option.", self.id);
+        let states = self
+            .elements()
+            .into_iter()
+            .map(|element| {
+                let current = entry.get(element);
+                let matches = self
+                    .options
+                    .iter()
+                    .filter(|(_, values)| {
+                        values.iter().any(|value| {
+                            value.element == element && same_value(current, value.value.as_deref())
+                        })
+                    })
+                    .map(|(option, _)| option.clone())
+                    .collect();
+                SettingState {
+                    setting: element.to_string(),
+                    core: None,
+                    value: Some(current.unwrap_or("<unset>").to_string()),
+                    matches,
+                }
+            })
+            .collect();
+        Ok(TweakOption::Mixed(states))
+    }
+
+    /// Sets the element values of the selected option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every element was changed and verified.
+    /// - `Err(anyhow::Error)` if any change fails, after putting back the elements
+    ///   already changed.
+    fn apply(&self, option: TweakOption) -> Result<()> {
+        debug!(
+            "Applying BCD tweak '{:?}' with option: {:?}.",
+            self.id, option
+        );
+
+        let values = self.options.get(&option).with_context(|| {
+            format!(
+                "{:?} -> No boot elements found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.write_values(values, "apply")
+    }
+
+    /// Sets the element values of the `Enabled(false)` option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every element was changed and verified.
+    /// - `Err(anyhow::Error)` if any change fails, after putting back the elements
+    ///   already changed.
+    fn revert(&self) -> Result<()> {
+        debug!("{:?} -> Reverting BCD tweak to Default.", self.id);
+
+        let values = self
+            .options
+            .get(&TweakOption::Enabled(false))
+            .with_context(|| {
+                format!(
+                    "{:?} -> No boot elements found for Default option.",
+                    self.id
+                )
+            })?;
+        self.write_values(values, "revert")
+    }
+
+    /// Captures every element the tweak changes, including those the entry does not set.
+    ///
+    /// # Returns
+    /// - `Ok(Some(TweakSnapshot::Bcd))` with one entry per element.
+    /// - `Err(anyhow::Error)` if the boot entry cannot be read.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        let entry = self.read_entry(backend::bcd().as_ref())?;
+        let values: Vec<BcdValueSnapshot> = self
+            .elements()
+            .into_iter()
+            .map(|element| BcdValueSnapshot {
+                entry: self.entry.clone(),
+                element: element.to_string(),
+                value: entry.get(element).map(str::to_string),
+            })
+            .collect();
+        debug!("{:?} -> Captured {} boot elements.", self.id, values.len());
+        Ok(Some(TweakSnapshot::Bcd(values)))
+    }
+
+    /// Puts back the elements captured by `snapshot`, deleting those that were not set.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every element was restored and verified.
+    /// - `Err(anyhow::Error)` if the snapshot is not a BCD snapshot or any change fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Bcd(values) = snapshot else {
+            anyhow::bail!("{:?} -> Backup is not a BCD snapshot", self.id);
+        };
+
+        let bcd = backend::bcd();
+        for saved in values {
+            let entry = bcd.read_entry(&saved.entry).with_context(|| {
+                format!("{:?} -> Failed to read boot entry {}", self.id, saved.entry)
+            })?;
+            let current = entry.get(&saved.element);
+            if !same_value(current, saved.value.as_deref()) {
+                self.write_element(
+                    bcd.as_ref(),
+                    &saved.element,
+                    current,
+                    saved.value.as_deref(),
+                )?;
+            }
+        }
+
+        debug!("{:?} -> Restored original boot elements.", self.id);
+        Ok(())
+    }
+
+    /// Lists each element the option changes, with its value before and after.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with one step per element.
+    /// - `Err(anyhow::Error)` if the option does not exist or the entry cannot be read.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let values = self.options.get(option).with_context(|| {
+            format!(
+                "{:?} -> No boot elements found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        let entry = self.read_entry(backend::bcd().as_ref())?;
+
+        let steps = values
+            .iter()
+            .map(|value| PlanStep::Bcd {
+                entry: self.entry.clone(),
+                element: value.element.clone(),
+                current: entry.get(&value.element).map(str::to_string),
+                new: value.value.clone(),
+            })
+            .collect();
+        Ok(TweakPlan { steps })
+    }
+
+    fn journaled(&self) -> bool {
+        true
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+
+    /// Checks that element names are single words, that values are not empty, and that
+    /// no option changes the same element twice.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (option, values) in &self.options {
+            for (i, value) in values.iter().enumerate() {
+                if value.element.is_empty()
+                    || !value.element.chars().all(|c| c.is_ascii_alphanumeric())
+                {
+                    problems.push(format!(
+                        "option {:?} changes '{}', which is not an element name",
+                        option, value.element
+                    ));
+                }
+                if value.value.as_deref().is_some_and(|v| v.trim().is_empty()) {
+                    problems.push(format!(
+                        "option {:?} sets {} to an empty value",
+                        option, value.element
+                    ));
+                }
+                if values[..i]
+                    .iter()
+                    .any(|other| other.element == value.element)
+                {
+                    problems.push(format!(
+                        "option {:?} changes {} more than once",
+                        option, value.element
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns every element any option changes.
+    fn settings(&self) -> Vec<TweakSetting> {
+        self.elements()
+            .into_iter()
+            .map(|element| TweakSetting::BcdElement {
+                entry: self.entry.clone(),
+                element: element.to_string(),
+            })
+            .collect()
+    }
+
+    /// Checks that the boot entry can be read, which also rules out systems other than
+    /// Windows.
+    fn unsupported(&self) -> Option<String> {
+        backend::bcd()
+            .read_entry(&self.entry)
+            .err()
+            .map(|e| format!("{:#}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_compare_like_bcdedit() {
+        let entry = BcdEntry {
+            elements: [("nx".to_string(), "OptIn".to_string())].into(),
+            ..Default::default()
+        };
+        assert!(BcdValue::set("nx", "optin").is_set_in(&entry));
+        assert!(BcdValue::set("NX", "OptIn").is_set_in(&entry));
+        assert!(!BcdValue::set("nx", "AlwaysOff").is_set_in(&entry));
+        assert!(!BcdValue::delete("nx").is_set_in(&entry));
+        assert!(BcdValue::delete("useplatformclock").is_set_in(&entry));
+        assert!(!BcdValue::set("useplatformclock", "No").is_set_in(&entry));
+    }
+}
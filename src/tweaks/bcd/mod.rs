@@ -0,0 +1,130 @@
+// src/tweaks/bcd/mod.rs
+
+//! Tweaks that change elements of the boot entry Windows started from.
+
+pub mod method;
+
+use indexmap::IndexMap;
+use method::{BcdTweak, BcdValue};
+
+use super::{option_map, Tweak, TweakCategory, TweakId, TweakOption};
+use crate::utils::bcd::CURRENT_ENTRY;
+
+pub fn all_bcd_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
+    vec![
+        (TweakId::DisableHPET, disable_dynamic_tick()),
+        (
+            TweakId::DisableDataExecutionPrevention,
+            disable_data_execution_prevention(),
+        ),
+        (TweakId::TscSyncPolicy, tsc_sync_policy()),
+        (TweakId::UsePlatformTick, use_platform_tick()),
+        (TweakId::HypervisorLaunchType, hypervisor_launch_type()),
+    ]
+}
+
+/// Returns combo box options named `Default`, which deletes `element`, and after each
+/// of `values`, which set it.
+fn element_options(element: &str, values: &[&str]) -> IndexMap<TweakOption, Vec<BcdValue>> {
+    let default = (
+        TweakOption::Option("Default".to_string()),
+        vec![BcdValue::delete(element)],
+    );
+    option_map(std::iter::once(default).chain(values.iter().map(|value| {
+        (
+            TweakOption::Option(value.to_string()),
+            vec![BcdValue::set(element, *value)],
+        )
+    })))
+}
+
+pub fn disable_dynamic_tick<'a>() -> Tweak<'a> {
+    Tweak::bcd_tweak(
+        "Disable Dynamic Tick",
+        "Disables the dynamic tick feature, which normally reduces timer interrupts during idle periods to conserve power. By disabling dynamic tick, the system maintains a constant rate of timer interrupts, improving performance in real-time applications by reducing latency and jitter. This tweak is useful in scenarios where consistent, low-latency processing is required, but it may increase power consumption as the CPU will not enter low-power states as frequently.",
+        TweakCategory::System,
+        BcdTweak {
+            id: TweakId::DisableHPET,
+            entry: CURRENT_ENTRY.to_string(),
+            options: option_map(vec![
+                // Both elements are unset on a fresh install
+                (
+                    TweakOption::Enabled(false),
+                    vec![
+                        BcdValue::delete("disabledynamictick"),
+                        BcdValue::delete("useplatformclock"),
+                    ],
+                ),
+                // Also removes the HPET forcing earlier versions of this tweak set
+                (
+                    TweakOption::Enabled(true),
+                    vec![
+                        BcdValue::set("disabledynamictick", "Yes"),
+                        BcdValue::delete("useplatformclock"),
+                    ],
+                ),
+            ]),
+        },
+    )
+}
+
+pub fn disable_data_execution_prevention<'a>() -> Tweak<'a> {
+    Tweak::bcd_tweak(
+        "Disable Data Execution Prevention",
+        "Disables Data Execution Prevention (DEP) by setting the `nx` boot configuration option to `AlwaysOff`. This may improve compatibility with older applications but can introduce security risks.",
+        TweakCategory::Security,
+        BcdTweak {
+            id: TweakId::DisableDataExecutionPrevention,
+            entry: CURRENT_ENTRY.to_string(),
+            options: option_map(vec![
+                (
+                    TweakOption::Enabled(false),
+                    vec![BcdValue::set("nx", "OptIn")],
+                ),
+                (
+                    TweakOption::Enabled(true),
+                    vec![BcdValue::set("nx", "AlwaysOff")],
+                ),
+            ]),
+        },
+    )
+}
+
+pub fn tsc_sync_policy<'a>() -> Tweak<'a> {
+    Tweak::bcd_tweak(
+        "TSC Sync Policy",
+        "Chooses how Windows synchronises the time stamp counters of the processors at boot. `Legacy` uses the older algorithm, while `Enhanced` keeps the counters closer together, which some timer benchmarks are sensitive to. `Default` lets Windows choose.",
+        TweakCategory::System,
+        BcdTweak {
+            id: TweakId::TscSyncPolicy,
+            entry: CURRENT_ENTRY.to_string(),
+            options: element_options("tscsyncpolicy", &["Legacy", "Enhanced"]),
+        },
+    )
+}
+
+pub fn use_platform_tick<'a>() -> Tweak<'a> {
+    Tweak::bcd_tweak(
+        "Platform Tick",
+        "Chooses the source of the timer tick. `Yes` always uses the platform timer, such as the HPET or the ACPI PM timer, while `No` lets Windows use a per-processor timer. `Default` lets Windows choose.",
+        TweakCategory::System,
+        BcdTweak {
+            id: TweakId::UsePlatformTick,
+            entry: CURRENT_ENTRY.to_string(),
+            options: element_options("useplatformtick", &["Yes", "No"]),
+        },
+    )
+}
+
+pub fn hypervisor_launch_type<'a>() -> Tweak<'a> {
+    Tweak::bcd_tweak(
+        "Hypervisor Launch Type",
+        "Chooses whether the Hyper-V hypervisor starts at boot. `Off` stops it, and with it virtualization-based security, WSL 2 and Windows Sandbox, removing the overhead of running Windows as a guest. `Auto` starts it when Hyper-V is installed.",
+        TweakCategory::Security,
+        BcdTweak {
+            id: TweakId::HypervisorLaunchType,
+            entry: CURRENT_ENTRY.to_string(),
+            options: element_options("hypervisorlaunchtype", &["Auto", "Off"]),
+        },
+    )
+}
@@ -0,0 +1,1117 @@
+// src/tweaks/definitions.rs
+
+//! Tweaks defined in TOML files instead of code.
+//!
+//! Every `*.toml` file in the user's tweaks directory may define any number of
+//! tweaks. Each tweak has exactly one method table, whose options map an option
+//! label (`Enabled`, `Disabled`, `Run`, or any other name for a combo box entry)
+//! to what that option does:
+//!
+//! ```toml
+//! [[tweak]]
+//! id = "DisableFooService"
+//! name = "Disable Foo"
+//! description = "Stops the Foo service from polling the sensors."
+//! category = "Services"
+//! requires_reboot = true
+//!
+//! [tweak.registry.options]
+//! Disabled = [{ path = "HKEY_LOCAL_MACHINE\\SYSTEM\\Foo", key = "Start", value = 2 }]
+//! Enabled = [{ path = "HKEY_LOCAL_MACHINE\\SYSTEM\\Foo", key = "Start", value = 4 }]
+//! ```
+//!
+//! Registry values are a DWORD when `value` is an integer, a string when it is a
+//! string and binary when it is an array of bytes. Leaving `value` out deletes the
+//! value.
+//!
+//! The other method tables are:
+//!
+//! - `[tweak.msr]` has `readable`, and options made of `{ index, bit, state }`. A
+//!   field of several bits is `{ index, bits = "9:7", value }`. A field of the
+//!   register database can be named instead, e.g.
+//!   `{ field = "PrefetchControl.L1Stride", state = true }`. An optional `cpus` list
+//!   limits the tweak to some processors, e.g.
+//!   `[{ vendor = "AMD", families = [0x19, 0x1A] }]`.
+//! - `[tweak.powershell]` has `read_script`, and options made of `{ script, state }`.
+//!   `state` is the boolean, integer or string the read script outputs, compared as
+//!   JSON. `timeout_secs`, `exit_codes` and `stderr_fails` change when the script
+//!   counts as having failed.
+//! - `[tweak.group_policy]` has `key`, and options that are either `"enabled"` or
+//!   `"disabled"`.
+
+use std::{collections::BTreeSet, fs, path::Path, str::FromStr, time::Duration};
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+
+use super::{
+    group_policy::method::{GroupPolicyTweak, GroupPolicyValue},
+    msr::{
+        method::{MSRTweak, MsrField},
+        registers,
+    },
+    powershell::method::{PowershellAction, PowershellState, PowershellTweak},
+    registry::method::{RegistryModification, RegistryTweak},
+    try_option_map, CustomTweakId, Tweak, TweakCategory, TweakId, TweakMethod, TweakOption,
+};
+use crate::utils::{
+    cpu::{self, CpuRange, CpuVendor},
+    powershell::ScriptPolicy,
+    registry::RegistryKeyValue,
+};
+
+/// Stands in for a definition's ID while the file is checked, before its ID is interned.
+const UNCHECKED_ID: TweakId = TweakId::Custom(CustomTweakId("<unchecked>"));
+
+/// Registry hives a definition's paths may start with.
+const REGISTRY_HIVES: [&str; 5] = [
+    "HKEY_LOCAL_MACHINE",
+    "HKEY_CURRENT_USER",
+    "HKEY_CLASSES_ROOT",
+    "HKEY_USERS",
+    "HKEY_CURRENT_CONFIG",
+];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionFile {
+    #[serde(default, rename = "tweak")]
+    tweaks: Vec<TweakDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TweakDefinition {
+    id: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    category: String,
+    #[serde(default)]
+    requires_reboot: bool,
+    registry: Option<RegistryDefinition>,
+    msr: Option<MsrDefinition>,
+    powershell: Option<PowershellDefinition>,
+    group_policy: Option<GroupPolicyDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryDefinition {
+    options: IndexMap<String, Vec<RegistryModificationDefinition>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegistryModificationDefinition {
+    path: String,
+    key: String,
+    value: Option<toml::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MsrDefinition {
+    #[serde(default = "default_readable")]
+    readable: bool,
+    options: IndexMap<String, Vec<MsrFieldDefinition>>,
+    #[serde(default)]
+    cpus: Vec<CpuDefinition>,
+}
+
+/// Processors an MSR tweak supports: a vendor, `"AMD"` or `"Intel"`, and optionally
+/// the first and last family and model, as CPUID reports them.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CpuDefinition {
+    vendor: String,
+    families: Option<[u32; 2]>,
+    models: Option<[u32; 2]>,
+}
+
+fn default_readable() -> bool {
+    true
+}
+
+/// Either a single bit (`bit` and `state`) or a field of several bits (`bits`, written
+/// `"high:low"` as in the processor manuals, and `value`) of MSR `index`, or a field of
+/// the register database named `Register.Field`, with a `state` or a `value`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MsrFieldDefinition {
+    index: Option<u32>,
+    field: Option<String>,
+    bit: Option<u32>,
+    state: Option<bool>,
+    bits: Option<String>,
+    value: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PowershellDefinition {
+    read_script: Option<String>,
+    options: IndexMap<String, PowershellActionDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PowershellActionDefinition {
+    script: String,
+    state: Option<toml::Value>,
+    timeout_secs: Option<u64>,
+    exit_codes: Option<Vec<i32>>,
+    #[serde(default)]
+    stderr_fails: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupPolicyDefinition {
+    key: String,
+    options: IndexMap<String, String>,
+}
+
+/// Loads every `*.toml` definition file in `dir`.
+///
+/// # Returns
+/// The tweaks that loaded, and one error per file that did not. A file with any
+/// invalid tweak contributes no tweaks at all.
+pub fn load_definitions(dir: &Path) -> (Vec<(TweakId, Tweak<'static>)>, Vec<anyhow::Error>) {
+    let mut tweaks = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut paths = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("toml"))
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            errors.push(anyhow::Error::new(e).context(format!(
+                "Failed to read tweaks directory '{}'",
+                dir.display()
+            )));
+            return (tweaks, errors);
+        }
+    };
+    paths.sort();
+
+    let mut loaded_ids = BTreeSet::new();
+    for path in paths {
+        let result = fs::read_to_string(&path)
+            .with_context(|| format!("{}: failed to read file", path.display()))
+            .and_then(|contents| {
+                parse_definitions(&path.display().to_string(), &contents, &mut loaded_ids)
+            });
+        match result {
+            Ok(file_tweaks) => tweaks.extend(file_tweaks),
+            Err(e) => {
+                tracing::error!("Failed to load tweak definitions: {:#}", e);
+                errors.push(e);
+            }
+        }
+    }
+
+    (tweaks, errors)
+}
+
+/// Parses the contents of one definition file.
+///
+/// The whole file is checked before anything is kept: only once every tweak in it is
+/// valid are its definitions leaked and its IDs interned.
+///
+/// # Parameters
+///
+/// - `file`: Name of the file, used in error messages.
+/// - `contents`: The TOML to parse.
+/// - `loaded_ids`: Lowercased IDs already loaded from other files. The IDs of this
+///   file are added to it if it loads.
+pub fn parse_definitions(
+    file: &str,
+    contents: &str,
+    loaded_ids: &mut BTreeSet<String>,
+) -> Result<Vec<(TweakId, Tweak<'static>)>> {
+    let definitions: DefinitionFile = toml::from_str(contents).with_context(|| file.to_string())?;
+    let context = |index: usize, definition: &TweakDefinition| {
+        format!("{}: tweak #{} ('{}')", file, index + 1, definition.id)
+    };
+
+    let mut file_ids = BTreeSet::new();
+    for (index, definition) in definitions.tweaks.iter().enumerate() {
+        validate_id(&definition.id, loaded_ids, &file_ids)
+            .with_context(|| context(index, definition))?;
+        file_ids.insert(definition.id.to_ascii_lowercase());
+
+        let problems = build_tweak(definition, UNCHECKED_ID)
+            .with_context(|| context(index, definition))?
+            .method
+            .validate();
+        if !problems.is_empty() {
+            return Err(anyhow::anyhow!("{}", problems.join("; ")))
+                .with_context(|| context(index, definition));
+        }
+    }
+
+    // Definitions of an accepted file live for the rest of the process, like the
+    // built-in tweaks' string literals
+    let definitions: &'static [TweakDefinition] = Box::leak(definitions.tweaks.into_boxed_slice());
+    let mut tweaks = Vec::new();
+    for (index, definition) in definitions.iter().enumerate() {
+        let id = TweakId::Custom(CustomTweakId::new(&definition.id).register());
+        let tweak = build_tweak(definition, id).with_context(|| context(index, definition))?;
+        tweaks.push((id, tweak.into_tweak()));
+    }
+    loaded_ids.extend(file_ids);
+    Ok(tweaks)
+}
+
+fn validate_id(id: &str, loaded_ids: &BTreeSet<String>, file_ids: &BTreeSet<String>) -> Result<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        anyhow::bail!("field 'id': must be non-empty and only contain letters, digits and '_'");
+    }
+    if TweakId::iter().any(|builtin| builtin.to_string().eq_ignore_ascii_case(id)) {
+        anyhow::bail!("field 'id': '{}' is already used by a built-in tweak", id);
+    }
+    let lowercase = id.to_ascii_lowercase();
+    if loaded_ids.contains(&lowercase) || file_ids.contains(&lowercase) {
+        anyhow::bail!("field 'id': '{}' is defined more than once", id);
+    }
+    Ok(())
+}
+
+/// A tweak built from a definition, borrowing its strings from the definition.
+struct DefinedTweak<'d> {
+    name: &'d str,
+    description: &'d str,
+    category: TweakCategory,
+    requires_reboot: bool,
+    method: DefinedMethod<'d>,
+}
+
+/// The method of a `DefinedTweak`. PowerShell actions are kept by value, as the tweak
+/// only holds references to them.
+enum DefinedMethod<'d> {
+    Registry(RegistryTweak<'d>),
+    Msr(MSRTweak),
+    Powershell {
+        id: TweakId,
+        read_script: Option<&'d str>,
+        options: IndexMap<TweakOption, PowershellAction<'d>>,
+    },
+    GroupPolicy(GroupPolicyTweak<'d>),
+}
+
+impl DefinedMethod<'_> {
+    /// Returns the problems `TweakMethod::validate` finds with the method.
+    fn validate(&self) -> Vec<String> {
+        match self {
+            DefinedMethod::Registry(method) => method.validate(),
+            DefinedMethod::Msr(method) => method.validate(),
+            DefinedMethod::Powershell {
+                id,
+                read_script,
+                options,
+            } => PowershellTweak {
+                id: *id,
+                read_script: *read_script,
+                options: options
+                    .iter()
+                    .map(|(option, action)| (option.clone(), action))
+                    .collect(),
+            }
+            .validate(),
+            DefinedMethod::GroupPolicy(method) => method.validate(),
+        }
+    }
+}
+
+impl DefinedTweak<'static> {
+    fn into_tweak(self) -> Tweak<'static> {
+        let DefinedTweak {
+            name,
+            description,
+            category,
+            requires_reboot,
+            method,
+        } = self;
+        match method {
+            DefinedMethod::Registry(method) => {
+                Tweak::registry_tweak(name, description, category, method, requires_reboot)
+            }
+            DefinedMethod::Msr(method) => {
+                let mut tweak = Tweak::msr_tweak(name, description, category, method);
+                tweak.requires_reboot = requires_reboot;
+                tweak
+            }
+            DefinedMethod::Powershell {
+                id,
+                read_script,
+                options,
+            } => Tweak::powershell_tweak(
+                name,
+                description,
+                category,
+                PowershellTweak {
+                    id,
+                    read_script,
+                    options: options
+                        .into_iter()
+                        .map(|(option, action)| {
+                            let action: &'static PowershellAction<'static> =
+                                Box::leak(Box::new(action));
+                            (option, action)
+                        })
+                        .collect(),
+                },
+                requires_reboot,
+            ),
+            DefinedMethod::GroupPolicy(method) => {
+                Tweak::group_policy_tweak(name, description, category, method, requires_reboot)
+            }
+        }
+    }
+}
+
+/// Builds the tweak `definition` describes, checking every field on the way.
+fn build_tweak(definition: &TweakDefinition, id: TweakId) -> Result<DefinedTweak<'_>> {
+    if definition.name.trim().is_empty() {
+        anyhow::bail!("field 'name': must not be empty");
+    }
+    let category = TweakCategory::from_str(&definition.category).map_err(|_| {
+        anyhow::anyhow!(
+            "field 'category': unknown category '{}', expected one of: {}",
+            definition.category,
+            TweakCategory::iter()
+                .map(|c| format!("{:?}", c))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
+
+    let methods = [
+        definition.registry.is_some(),
+        definition.msr.is_some(),
+        definition.powershell.is_some(),
+        definition.group_policy.is_some(),
+    ];
+    if methods.iter().filter(|defined| **defined).count() != 1 {
+        anyhow::bail!(
+            "exactly one of the fields 'registry', 'msr', 'powershell' or 'group_policy' must be set"
+        );
+    }
+
+    let method = if let Some(registry) = &definition.registry {
+        check_options("registry", registry.options.keys())?;
+        let mut options = Vec::new();
+        for (label, modifications) in &registry.options {
+            let modifications = modifications
+                .iter()
+                .enumerate()
+                .map(|(i, modification)| {
+                    build_registry_modification(modification)
+                        .with_context(|| format!("field 'registry.options.{}[{}]'", label, i))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            options.push((parse_option_label(label), modifications));
+        }
+        let options = try_option_map(options).context("field 'registry.options'")?;
+        DefinedMethod::Registry(RegistryTweak { id, options })
+    } else if let Some(msr) = &definition.msr {
+        check_options("msr", msr.options.keys())?;
+        let mut options = Vec::new();
+        for (label, fields) in &msr.options {
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| build_msr_field(field, &format!("msr.options.{}[{}]", label, i)))
+                .collect::<Result<Vec<_>>>()?;
+            options.push((parse_option_label(label), fields));
+        }
+        let options = try_option_map(options).context("field 'msr.options'")?;
+        DefinedMethod::Msr(MSRTweak {
+            id,
+            readable: msr.readable,
+            options,
+            supported_cpus: msr
+                .cpus
+                .iter()
+                .enumerate()
+                .map(|(i, cpu)| build_cpu_range(cpu, &format!("msr.cpus[{}]", i)))
+                .collect::<Result<_>>()?,
+            targets: Default::default(),
+        })
+    } else if let Some(powershell) = &definition.powershell {
+        check_options("powershell", powershell.options.keys())?;
+        let mut options = Vec::new();
+        for (label, action) in &powershell.options {
+            if action.script.trim().is_empty() {
+                anyhow::bail!(
+                    "field 'powershell.options.{}.script': must not be empty",
+                    label
+                );
+            }
+            let state = match &action.state {
+                None => None,
+                Some(toml::Value::Boolean(state)) => Some(PowershellState::Bool(*state)),
+                Some(toml::Value::Integer(state)) => Some(PowershellState::Integer(*state)),
+                Some(toml::Value::String(state)) => Some(PowershellState::String(state)),
+                Some(other) => anyhow::bail!(
+                    "field 'powershell.options.{}.state': expected a boolean, integer or string, found {}",
+                    label,
+                    other.type_str()
+                ),
+            };
+            if action.timeout_secs == Some(0) {
+                anyhow::bail!(
+                    "field 'powershell.options.{}.timeout_secs': must be at least 1",
+                    label
+                );
+            }
+            let policy = ScriptPolicy {
+                timeout: action
+                    .timeout_secs
+                    .map_or(ScriptPolicy::DEFAULT.timeout, Duration::from_secs),
+                exit_codes: action
+                    .exit_codes
+                    .as_deref()
+                    .unwrap_or(ScriptPolicy::DEFAULT.exit_codes),
+                stderr_fails: action.stderr_fails,
+            };
+            let action = PowershellAction {
+                script: &action.script,
+                state,
+                policy,
+            };
+            options.push((parse_option_label(label), action));
+        }
+        let options = try_option_map(options).context("field 'powershell.options'")?;
+        DefinedMethod::Powershell {
+            id,
+            read_script: powershell.read_script.as_deref(),
+            options,
+        }
+    } else if let Some(group_policy) = &definition.group_policy {
+        check_options("group_policy", group_policy.options.keys())?;
+        let mut options = Vec::new();
+        for (label, value) in &group_policy.options {
+            let value = match value.to_ascii_lowercase().as_str() {
+                "enabled" => GroupPolicyValue::Enabled,
+                "disabled" => GroupPolicyValue::Disabled,
+                _ => anyhow::bail!(
+                    "field 'group_policy.options.{}': expected \"enabled\" or \"disabled\", found \"{}\"",
+                    label,
+                    value
+                ),
+            };
+            options.push((parse_option_label(label), value));
+        }
+        let options = try_option_map(options).context("field 'group_policy.options'")?;
+        DefinedMethod::GroupPolicy(GroupPolicyTweak {
+            id,
+            key: &group_policy.key,
+            options,
+        })
+    } else {
+        unreachable!("exactly one method is defined")
+    };
+
+    Ok(DefinedTweak {
+        name: &definition.name,
+        description: &definition.description,
+        category,
+        requires_reboot: definition.requires_reboot,
+        method,
+    })
+}
+
+/// Checks that a method defines at least one option, and that a toggle has both states.
+/// Options defined more than once are caught when the options are built.
+fn check_options<'a>(method: &str, labels: impl Iterator<Item = &'a String>) -> Result<()> {
+    let options: Vec<TweakOption> = labels.map(|label| parse_option_label(label)).collect();
+    if options.is_empty() {
+        anyhow::bail!(
+            "field '{}.options': at least one option is required",
+            method
+        );
+    }
+    if options.contains(&TweakOption::Enabled(true))
+        != options.contains(&TweakOption::Enabled(false))
+    {
+        anyhow::bail!(
+            "field '{}.options': a toggle needs both an 'Enabled' and a 'Disabled' option",
+            method
+        );
+    }
+    Ok(())
+}
+
+fn build_registry_modification(
+    modification: &RegistryModificationDefinition,
+) -> Result<RegistryModification<'_>> {
+    let hive = modification.path.split('\\').next().unwrap_or_default();
+    if !REGISTRY_HIVES.iter().any(|h| h.eq_ignore_ascii_case(hive))
+        || !modification.path.contains('\\')
+    {
+        anyhow::bail!(
+            "field 'path': '{}' must start with one of {}",
+            modification.path,
+            REGISTRY_HIVES.join(", ")
+        );
+    }
+
+    let value = match &modification.value {
+        None => RegistryKeyValue::Deleted,
+        Some(toml::Value::Integer(v)) => RegistryKeyValue::Dword(
+            u32::try_from(*v)
+                .map_err(|_| anyhow::anyhow!("field 'value': {} does not fit in a DWORD", v))?,
+        ),
+        Some(toml::Value::String(v)) => RegistryKeyValue::String(v.clone()),
+        Some(toml::Value::Array(bytes)) => RegistryKeyValue::Binary(
+            bytes
+                .iter()
+                .map(|byte| {
+                    byte.as_integer()
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .context("field 'value': binary values must be an array of bytes (0-255)")
+                })
+                .collect::<Result<_>>()?,
+        ),
+        Some(other) => anyhow::bail!(
+            "field 'value': expected an integer (DWORD), a string or an array of bytes, found {}",
+            other.type_str()
+        ),
+    };
+
+    Ok(RegistryModification {
+        path: &modification.path,
+        key: &modification.key,
+        value,
+    })
+}
+
+/// Builds an MSR modification. `path` names the definition in error messages.
+fn build_msr_field(field: &MsrFieldDefinition, path: &str) -> Result<MsrField> {
+    if let Some(name) = &field.field {
+        return build_named_msr_field(name, field, path);
+    }
+    let index = field
+        .index
+        .with_context(|| format!("field '{}': set either 'index' or 'field'", path))?;
+
+    let (low_bit, high_bit, value) = match field {
+        MsrFieldDefinition {
+            bit: Some(bit),
+            state: Some(state),
+            bits: None,
+            value: None,
+            ..
+        } => (*bit, *bit, *state as u64),
+        MsrFieldDefinition {
+            bit: None,
+            state: None,
+            bits: Some(bits),
+            value: Some(value),
+            ..
+        } => {
+            let (high, low) = bits
+                .split_once(':')
+                .and_then(|(high, low)| {
+                    Some((
+                        high.trim().parse::<u32>().ok()?,
+                        low.trim().parse::<u32>().ok()?,
+                    ))
+                })
+                .with_context(|| {
+                    format!(
+                        "field '{}.bits': expected \"high:low\", e.g. \"9:7\", found '{}'",
+                        path, bits
+                    )
+                })?;
+            if high < low {
+                anyhow::bail!(
+                    "field '{}.bits': the high bit {} is below the low bit {}",
+                    path,
+                    high,
+                    low
+                );
+            }
+            (low, high, *value)
+        }
+        _ => anyhow::bail!(
+            "field '{}': set either 'bit' and 'state', or 'bits' and 'value'",
+            path
+        ),
+    };
+
+    let field_name = if field.bit.is_some() { "bit" } else { "bits" };
+    if high_bit >= 64 {
+        anyhow::bail!(
+            "field '{}.{}': {} is out of range, MSRs have 64 bits",
+            path,
+            field_name,
+            high_bit
+        );
+    }
+    let msr_field = MsrField::new(index, low_bit..=high_bit, value);
+    if msr_field.width() < 64 && value >> msr_field.width() != 0 {
+        anyhow::bail!(
+            "field '{}.value': {} does not fit in {} bits",
+            path,
+            value,
+            msr_field.width()
+        );
+    }
+    Ok(msr_field)
+}
+
+/// Builds an MSR modification of a field named in the register database, which takes
+/// a `state` if it is a single bit, or a `value`.
+fn build_named_msr_field(name: &str, field: &MsrFieldDefinition, path: &str) -> Result<MsrField> {
+    if field.index.is_some() || field.bit.is_some() || field.bits.is_some() {
+        anyhow::bail!(
+            "field '{}': 'field' names the bits itself, leave out 'index', 'bit' and 'bits'",
+            path
+        );
+    }
+    let (register, register_field) =
+        registers::lookup(name).with_context(|| format!("field '{}.field'", path))?;
+    let value = match (field.state, field.value) {
+        (Some(state), None) if register_field.width() == 1 => state as u64,
+        (Some(_), None) => anyhow::bail!(
+            "field '{}.state': {} has {} bits, set 'value' instead",
+            path,
+            name,
+            register_field.width()
+        ),
+        (None, Some(value)) => value,
+        _ => anyhow::bail!("field '{}': set either 'state' or 'value'", path),
+    };
+
+    let msr_field = MsrField::new(
+        register.index,
+        register_field.low_bit..=register_field.high_bit,
+        value,
+    );
+    if msr_field.width() < 64 && value >> msr_field.width() != 0 {
+        anyhow::bail!(
+            "field '{}.value': {} does not fit in {} bits",
+            path,
+            value,
+            msr_field.width()
+        );
+    }
+    Ok(msr_field)
+}
+
+/// Builds the range of processors described by `cpu`, whose path is used in errors.
+fn build_cpu_range(cpu: &CpuDefinition, path: &str) -> Result<CpuRange> {
+    let vendor = match cpu.vendor.to_ascii_lowercase().as_str() {
+        "amd" => CpuVendor::Amd,
+        "intel" => CpuVendor::Intel,
+        _ => anyhow::bail!(
+            "field '{}.vendor': expected \"AMD\" or \"Intel\", found '{}'",
+            path,
+            cpu.vendor
+        ),
+    };
+    let range = |bounds: Option<[u32; 2]>, field: &str| match bounds {
+        None => Ok(cpu::ANY),
+        Some([first, last]) if first <= last => Ok(first..=last),
+        Some([first, last]) => Err(anyhow::anyhow!(
+            "field '{}.{}': the first value 0x{:X} is above the last 0x{:X}",
+            path,
+            field,
+            first,
+            last
+        )),
+    };
+    Ok(CpuRange {
+        vendor,
+        families: range(cpu.families, "families")?,
+        models: range(cpu.models, "models")?,
+    })
+}
+
+/// Maps an option label from a definition file onto a `TweakOption`.
+fn parse_option_label(label: &str) -> TweakOption {
+    match label.to_ascii_lowercase().as_str() {
+        "enabled" => TweakOption::Enabled(true),
+        "disabled" => TweakOption::Enabled(false),
+        "run" => TweakOption::Run,
+        _ => TweakOption::Option(label.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tweaks::CUSTOM_TWEAK_NAMES, ui::TweakWidget};
+
+    fn parse(contents: &str) -> Result<Vec<(TweakId, Tweak<'static>)>> {
+        parse_definitions("test.toml", contents, &mut BTreeSet::new())
+    }
+
+    /// Parses a file that is expected to be invalid, returning the full error message.
+    fn parse_error(contents: &str, loaded_ids: &mut BTreeSet<String>) -> String {
+        match parse_definitions("test.toml", contents, loaded_ids) {
+            Ok(_) => panic!("definition unexpectedly parsed"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    #[test]
+    fn test_registry_definition() {
+        let tweaks = parse(
+            r#"
+            [[tweak]]
+            id = "TestRegistryTweak"
+            name = "Test registry tweak"
+            category = "system"
+            requires_reboot = true
+
+            [tweak.registry.options]
+            Disabled = [{ path = "HKEY_LOCAL_MACHINE\\SYSTEM\\Test", key = "Value" }]
+            Enabled = [
+                { path = "HKEY_LOCAL_MACHINE\\SYSTEM\\Test", key = "Value", value = 1 },
+                { path = "HKEY_LOCAL_MACHINE\\SYSTEM\\Test", key = "Name", value = "on" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(tweaks.len(), 1);
+        let (id, tweak) = &tweaks[0];
+        assert_eq!(id.to_string(), "TestRegistryTweak");
+        assert_eq!(TweakId::from_str("testregistrytweak").unwrap(), *id);
+        assert_eq!(tweak.category, TweakCategory::System);
+        assert!(tweak.requires_reboot);
+        assert_eq!(*tweak.widget, TweakWidget::Toggle);
+        assert_eq!(
+            tweak.options,
+            vec![TweakOption::Enabled(false), TweakOption::Enabled(true)]
+        );
+    }
+
+    #[test]
+    fn test_msr_and_group_policy_definitions() {
+        let tweaks = parse(
+            r#"
+            [[tweak]]
+            id = "TestMsrTweak"
+            name = "Test MSR tweak"
+            category = "Cpu"
+
+            [tweak.msr.options]
+            Disabled = [{ index = 0xC0011020, bit = 10, state = false }]
+            Enabled = [{ index = 0xC0011020, bit = 10, state = true }]
+
+            [[tweak]]
+            id = "TestMsrFieldTweak"
+            name = "Test MSR field tweak"
+            category = "Cpu"
+
+            [tweak.msr]
+            cpus = [{ vendor = "AMD", families = [0x19, 0x1A] }]
+
+            [tweak.msr.options]
+            Low = [{ index = 0xC0000108, bits = "9:7", value = 0 }]
+            High = [{ field = "PrefetchControl.PrefetchAggressivenessProfile", value = 3 }]
+
+            [[tweak]]
+            id = "TestPolicyTweak"
+            name = "Test policy tweak"
+            category = "Security"
+
+            [tweak.group_policy]
+            key = "SeLockMemoryPrivilege"
+            options = { Disabled = "disabled", Enabled = "enabled" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(tweaks.len(), 3);
+        assert_eq!(tweaks[0].1.category, TweakCategory::Cpu);
+        assert_eq!(
+            tweaks[1].1.options,
+            vec![
+                TweakOption::Option("Low".to_string()),
+                TweakOption::Option("High".to_string())
+            ]
+        );
+        assert_eq!(tweaks[2].1.category, TweakCategory::Security);
+    }
+
+    #[test]
+    fn test_powershell_combo_box_definition() {
+        let tweaks = parse(
+            r#"
+            [[tweak]]
+            id = "TestPowershellTweak"
+            name = "Test PowerShell tweak"
+            category = "Power"
+
+            [tweak.powershell]
+            read_script = "Get-Thing"
+
+            [tweak.powershell.options]
+            Low = { script = "Set-Thing Low", state = "Low" }
+            High = { script = "Set-Thing High", state = "High", timeout_secs = 5, exit_codes = [0, 3010] }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(*tweaks[0].1.widget, TweakWidget::SettingsComboBox);
+
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestBadState"
+            name = "Bad state"
+            category = "Power"
+
+            [tweak.powershell]
+            read_script = "Get-Thing"
+
+            [tweak.powershell.options]
+            Disabled = { script = "Set-Thing 0", state = 0 }
+            Enabled = { script = "Set-Thing 1", state = [1] }
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(
+            message.contains("powershell.options.Enabled.state") && message.contains("array"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_file_and_field() {
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestBadBit"
+            name = "Bad bit"
+            category = "Cpu"
+
+            [tweak.msr.options]
+            Disabled = [{ index = 0x10, bit = 64, state = false }]
+            Enabled = [{ index = 0x10, bit = 64, state = true }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(message.contains("test.toml"), "{}", message);
+        assert!(message.contains("TestBadBit"), "{}", message);
+        assert!(
+            message.contains("msr.options.Disabled[0].bit"),
+            "{}",
+            message
+        );
+
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestBadValue"
+            name = "Bad value"
+            category = "System"
+
+            [tweak.registry.options]
+            Run = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value", value = 1.5 }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(
+            message.contains("registry.options.Run[0]") && message.contains("field 'value'"),
+            "{}",
+            message
+        );
+
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestBadVendor"
+            name = "Bad vendor"
+            category = "Cpu"
+
+            [tweak.msr]
+            cpus = [{ vendor = "Cyrix" }]
+
+            [tweak.msr.options]
+            Disabled = [{ index = 0x10, bit = 1, state = false }]
+            Enabled = [{ index = 0x10, bit = 1, state = true }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(message.contains("msr.cpus[0].vendor"), "{}", message);
+
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestBadField"
+            name = "Bad field"
+            category = "Cpu"
+
+            [tweak.msr.options]
+            Disabled = [{ field = "PrefetchControl.L3Stride", state = false }]
+            Enabled = [{ field = "PrefetchControl.L3Stride", state = true }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(
+            message.contains("msr.options.Disabled[0].field") && message.contains("L3Stride"),
+            "{}",
+            message
+        );
+
+        let message = parse_error(
+            "[[tweak]]\nid = \"TestMissingName\"\ncategory = \"System\"\n",
+            &mut BTreeSet::new(),
+        );
+        assert!(
+            message.contains("test.toml") && message.contains("name"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_conflicting_ids_are_rejected() {
+        let builtin = parse_error(
+            r#"
+            [[tweak]]
+            id = "DisableHPET"
+            name = "Clash"
+            category = "System"
+            registry.options.Run = []
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(builtin.contains("built-in"), "{}", builtin);
+
+        let mut loaded_ids = BTreeSet::from(["testduplicate".to_string()]);
+        let duplicate = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestDuplicate"
+            name = "Duplicate"
+            category = "System"
+            registry.options.Run = []
+            "#,
+            &mut loaded_ids,
+        );
+        assert!(duplicate.contains("more than once"), "{}", duplicate);
+    }
+
+    #[test]
+    fn test_toggle_requires_both_states() {
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestHalfToggle"
+            name = "Half toggle"
+            category = "System"
+
+            [tweak.registry.options]
+            Enabled = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value", value = 1 }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(message.contains("'Disabled'"), "{}", message);
+    }
+
+    #[test]
+    fn test_rejected_file_registers_no_ids() {
+        let mut loaded_ids = BTreeSet::new();
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestRejectedFileGood"
+            name = "Good"
+            category = "System"
+            registry.options.Run = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value" }]
+
+            [[tweak]]
+            id = "TestRejectedFileBad"
+            name = "Bad"
+            category = "System"
+            registry.options.Run = [{ path = "HKEY_NOWHERE\\Test", key = "Value" }]
+            "#,
+            &mut loaded_ids,
+        );
+        assert!(message.contains("HKEY_NOWHERE"), "{}", message);
+
+        // Rejected by the method's own validation, after every field was read
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestRejectedFileTwice"
+            name = "Twice"
+            category = "System"
+            registry.options.Run = [
+                { path = "HKEY_CURRENT_USER\\Test", key = "Value" },
+                { path = "HKEY_CURRENT_USER\\Test", key = "Value", value = 1 },
+            ]
+            "#,
+            &mut loaded_ids,
+        );
+        assert!(message.contains("more than once"), "{}", message);
+
+        assert!(loaded_ids.is_empty());
+        let names = CUSTOM_TWEAK_NAMES.lock().unwrap();
+        for id in [
+            "TestRejectedFileGood",
+            "TestRejectedFileBad",
+            "TestRejectedFileTwice",
+        ] {
+            assert!(TweakId::from_str(id).is_err());
+            assert!(!names.contains(id), "{} was interned", id);
+        }
+    }
+
+    #[test]
+    fn test_ids_are_interned_once() {
+        let contents = r#"
+            [[tweak]]
+            id = "TestInternedId"
+            name = "Interned"
+            category = "System"
+            registry.options.Run = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value" }]
+            "#;
+        let first = parse(contents).unwrap();
+        let second = parse(contents).unwrap();
+        let (TweakId::Custom(first), TweakId::Custom(second)) = (first[0].0, second[0].0) else {
+            panic!("Definitions have custom IDs");
+        };
+        assert!(std::ptr::eq(first.as_str(), second.as_str()));
+        assert!(std::ptr::eq(
+            CustomTweakId::new("TestInternedId").as_str(),
+            first.as_str()
+        ));
+    }
+
+    #[test]
+    fn test_repeated_option_is_rejected() {
+        let message = parse_error(
+            r#"
+            [[tweak]]
+            id = "TestRepeatedOption"
+            name = "Repeated option"
+            category = "System"
+
+            [tweak.registry.options]
+            Disabled = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value" }]
+            Enabled = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value", value = 1 }]
+            enabled = [{ path = "HKEY_CURRENT_USER\\Test", key = "Value", value = 2 }]
+            "#,
+            &mut BTreeSet::new(),
+        );
+        assert!(
+            message
+                .contains("field 'registry.options': option 'Enabled' is defined more than once"),
+            "{}",
+            message
+        );
+    }
+}
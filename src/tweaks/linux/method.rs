@@ -0,0 +1,484 @@
+// src/tweaks/linux/method.rs
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use tracing::{debug, error};
+
+use crate::{
+    backend::{self, linux::selected_value, SysfsBackend},
+    backup::{SysfsValueSnapshot, TweakSnapshot},
+    journal::{record_step, JournalStep},
+    tweaks::{
+        default_option_of,
+        plan::{PlanStep, TweakPlan},
+        relations::TweakSetting,
+        SettingState, TweakId, TweakMethod, TweakOption,
+    },
+};
+
+/// A value to write to a sysfs or procfs file.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct SysfsValue {
+    /// Absolute path of the file. A `*` in a component matches any name, so
+    /// `/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor` is the governor of every
+    /// logical processor.
+    pub path: String,
+    pub value: String,
+}
+
+impl SysfsValue {
+    pub fn new(path: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// Returns whether `name` matches `pattern`, in which `*` matches any run of characters.
+fn wildcard_matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => name.strip_prefix(prefix).is_some_and(|remaining| {
+            (0..=remaining.len())
+                .filter(|&i| remaining.is_char_boundary(i))
+                .any(|i| wildcard_matches(rest, &remaining[i..]))
+        }),
+    }
+}
+
+/// Reads every file `pattern` matches.
+///
+/// # Returns
+///
+/// - `Ok(Vec<(String, String)>)` with the path and selected value of each file that
+///   exists, in path order. Empty if none does.
+/// - `Err(anyhow::Error)` if a directory or file cannot be read.
+fn read_matching(sysfs: &dyn SysfsBackend, pattern: &str) -> Result<Vec<(String, String)>> {
+    let mut paths = vec![String::new()];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut matched = Vec::new();
+        for dir in &paths {
+            if component.contains('*') {
+                let listing = if dir.is_empty() { "/" } else { dir.as_str() };
+                for name in sysfs.list(listing)? {
+                    if wildcard_matches(component, &name) {
+                        matched.push(format!("{}/{}", dir, name));
+                    }
+                }
+            } else {
+                matched.push(format!("{}/{}", dir, component));
+            }
+        }
+        paths = matched;
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        // A wildcard can match directories that lack the file, e.g. `cpufreq` for `cpu*`
+        if let Some(contents) = sysfs.read(&path)? {
+            files.push((path, selected_value(&contents).to_string()));
+        }
+    }
+    Ok(files)
+}
+
+/// Defines a set of Linux sysfs and procfs values, which in combination make up a
+/// single tweak. Each file is written as a whole, like `echo value > path`.
+#[derive(Debug)]
+pub struct SysfsTweak {
+    /// Unique ID for the tweak.
+    pub id: TweakId,
+    /// Mapping from `TweakOption` to the values written for it.
+    pub options: IndexMap<TweakOption, Vec<SysfsValue>>,
+}
+
+impl SysfsTweak {
+    /// Fails with the reason the system cannot run the tweak, e.g. because the kernel
+    /// lacks one of its files.
+    fn ensure_supported(&self) -> Result<()> {
+        match self.unsupported() {
+            Some(reason) => anyhow::bail!("{:?} -> {}", self.id, reason),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every distinct path any option writes, in the order the options list them.
+    fn patterns(&self) -> Vec<&str> {
+        let mut patterns: Vec<&str> = Vec::new();
+        for value in self.options.values().flatten() {
+            if !patterns.contains(&value.path.as_str()) {
+                patterns.push(&value.path);
+            }
+        }
+        patterns
+    }
+
+    /// Works out which file each value writes and what it holds now.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<(String, String, &str)>)` with the path, current value and new value of
+    ///   every file.
+    /// - `Err(anyhow::Error)` if any file cannot be read.
+    fn resolve<'v>(
+        &self,
+        sysfs: &dyn SysfsBackend,
+        values: &'v [SysfsValue],
+    ) -> Result<Vec<(String, String, &'v str)>> {
+        let mut files = Vec::new();
+        for value in values {
+            let matched = read_matching(sysfs, &value.path)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, value.path))?;
+            files.extend(
+                matched
+                    .into_iter()
+                    .map(|(path, current)| (path, current, value.value.as_str())),
+            );
+        }
+        Ok(files)
+    }
+
+    /// Writes a file, recording the change in the journal before it is made, and checks
+    /// that it reads back.
+    fn write_file(
+        &self,
+        sysfs: &dyn SysfsBackend,
+        path: &str,
+        current: &str,
+        new: &str,
+    ) -> Result<()> {
+        let step = JournalStep::Sysfs {
+            path: path.to_string(),
+            before: current.to_string(),
+            after: new.to_string(),
+        };
+        record_step(step, || {
+            sysfs
+                .write(path, new)
+                .with_context(|| format!("{:?} -> Failed to write '{}'", self.id, path))
+        })?;
+
+        let updated = sysfs
+            .read(path)
+            .with_context(|| format!("{:?} -> Failed to read '{}' after write", self.id, path))?
+            .unwrap_or_default();
+        if selected_value(&updated) != new {
+            anyhow::bail!("'{}' reads '{}' after writing '{}'", path, updated, new);
+        }
+        Ok(())
+    }
+
+    /// Writes `values`, all or nothing. If any write fails or does not read back, every
+    /// file written so far is put back to its original value. `action` names the
+    /// operation in messages.
+    fn write_values(&self, values: &[SysfsValue], action: &str) -> Result<()> {
+        self.ensure_supported()?;
+        let sysfs = backend::sysfs();
+        let files = self.resolve(sysfs.as_ref(), values)?;
+
+        let mut written: Vec<(&str, &str)> = Vec::new();
+        for (path, current, new) in &files {
+            if current == new {
+                continue;
+            }
+            written.push((path, current));
+            let Err(cause) = self.write_file(sysfs.as_ref(), path, current, new) else {
+                continue;
+            };
+            error!(
+                "{:?} -> Failed to {}: {:#}. Restoring the original values.",
+                self.id, action, cause
+            );
+
+            for (path, original) in written.iter().rev() {
+                let restored = sysfs.read(path).and_then(|current| {
+                    let current = current.unwrap_or_default();
+                    if selected_value(&current) == *original {
+                        Ok(())
+                    } else {
+                        self.write_file(sysfs.as_ref(), path, selected_value(&current), original)
+                    }
+                });
+                if let Err(e) = restored {
+                    error!("{:?} -> Failed to restore '{}': {:?}", self.id, path, e);
+                    return Err(cause.context(format!(
+                        "{:?} -> Failed to {}, and '{}' could not be restored",
+                        self.id, action, path
+                    )));
+                }
+            }
+            return Err(cause.context(format!(
+                "{:?} -> Failed to {}; every file was restored",
+                self.id, action
+            )));
+        }
+
+        tracing::info!(
+            "{:?} -> Successfully {} {} files.",
+            self.id,
+            if action == "revert" {
+                "reverted"
+            } else {
+                "applied"
+            },
+            files.len()
+        );
+        Ok(())
+    }
+}
+
+impl TweakMethod for SysfsTweak {
+    /// Reads every file the tweak writes and returns the option they match.
+    ///
+    /// # Returns
+    /// - `Ok(TweakOption)` indicating the current state, or `TweakOption::Mixed` with
+    ///   every file if no option matches.
+    /// - `Err(anyhow::Error)` if a file cannot be read.
+    fn initial_state(&self) -> Result<TweakOption> {
+        debug!(
+            "{:?} -> Determining the initial state of the sysfs tweak.",
+            self.id
+        );
+
+        self.ensure_supported()?;
+        let sysfs = backend::sysfs();
+
+        for (option, values) in &self.options {
+            if self
+                .resolve(sysfs.as_ref(), values)?
+                .iter()
+                .all(|(_, current, new)| current == new)
+            {
+                debug!("{:?} -> Current state matches {:?}.", self.id, option);
+                return Ok(option.clone());
+            }
+        }
+
+        debug!("{:?} -> Current state does not match any option.", self.id);
+        let mut states = Vec::new();
+        for pattern in self.patterns() {
+            let files = read_matching(sysfs.as_ref(), pattern)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, pattern))?;
+            for (path, current) in files {
+                let matches = self
+                    .options
+                    .iter()
+                    .filter(|(_, values)| {
+                        values
+                            .iter()
+                            .any(|value| value.path == pattern && value.value == current)
+                    })
+                    .map(|(option, _)| option.clone())
+                    .collect();
+                states.push(SettingState {
+                    setting: path,
+                    core: None,
+                    value: Some(current),
+                    matches,
+                });
+            }
+        }
+        Ok(TweakOption::Mixed(states))
+    }
+
+    /// Writes the values of the selected option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every file was written and verified.
+    /// - `Err(anyhow::Error)` if any write fails, after putting back the files already
+    ///   written.
+    fn apply(&self, option: TweakOption) -> Result<()> {
+        debug!(
+            "Applying sysfs tweak '{:?}' with option: {:?}.",
+            self.id, option
+        );
+
+        let values = self.options.get(&option).with_context(|| {
+            format!(
+                "{:?} -> No sysfs values found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.write_values(values, "apply")
+    }
+
+    /// Writes the values of the `Enabled(false)` option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every file was written and verified.
+    /// - `Err(anyhow::Error)` if any write fails, after putting back the files already
+    ///   written.
+    fn revert(&self) -> Result<()> {
+        debug!("{:?} -> Reverting sysfs tweak to Default.", self.id);
+
+        let values = self
+            .options
+            .get(&TweakOption::Enabled(false))
+            .with_context(|| {
+                format!("{:?} -> No sysfs values found for Default option.", self.id)
+            })?;
+        self.write_values(values, "revert")
+    }
+
+    /// Captures the value of every file the tweak writes.
+    ///
+    /// # Returns
+    /// - `Ok(Some(TweakSnapshot::Sysfs))` with one entry per file.
+    /// - `Err(anyhow::Error)` if any read fails.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        self.ensure_supported()?;
+        let sysfs = backend::sysfs();
+        let mut values = Vec::new();
+        for pattern in self.patterns() {
+            let files = read_matching(sysfs.as_ref(), pattern)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, pattern))?;
+            values.extend(
+                files
+                    .into_iter()
+                    .map(|(path, value)| SysfsValueSnapshot { path, value }),
+            );
+        }
+        debug!("{:?} -> Captured {} sysfs files.", self.id, values.len());
+        Ok(Some(TweakSnapshot::Sysfs(values)))
+    }
+
+    /// Writes back the values captured by `snapshot`.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every value was restored and verified.
+    /// - `Err(anyhow::Error)` if the snapshot is not a sysfs snapshot or any access fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Sysfs(values) = snapshot else {
+            anyhow::bail!("{:?} -> Backup is not a sysfs snapshot", self.id);
+        };
+
+        self.ensure_supported()?;
+        let sysfs = backend::sysfs();
+        for saved in values {
+            let current = sysfs
+                .read(&saved.path)
+                .with_context(|| format!("{:?} -> Failed to read '{}'", self.id, saved.path))?
+                .with_context(|| format!("{:?} -> '{}' no longer exists", self.id, saved.path))?;
+            if selected_value(&current) != saved.value {
+                self.write_file(
+                    sysfs.as_ref(),
+                    &saved.path,
+                    selected_value(&current),
+                    &saved.value,
+                )?;
+            }
+        }
+
+        debug!("{:?} -> Restored original sysfs values.", self.id);
+        Ok(())
+    }
+
+    /// Lists each file the option writes, with its value before and after.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with one step per file.
+    /// - `Err(anyhow::Error)` if the option does not exist or a file cannot be read.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let values = self.options.get(option).with_context(|| {
+            format!(
+                "{:?} -> No sysfs values found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.ensure_supported()?;
+
+        let steps = self
+            .resolve(backend::sysfs().as_ref(), values)?
+            .into_iter()
+            .map(|(path, current, new)| PlanStep::Sysfs {
+                path,
+                current: Some(current),
+                new: new.to_string(),
+            })
+            .collect();
+        Ok(TweakPlan { steps })
+    }
+
+    fn journaled(&self) -> bool {
+        true
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+
+    /// Checks that every path is an absolute path under `/sys` or `/proc`, that every
+    /// value is a single line, and that no option writes the same path twice.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (option, values) in &self.options {
+            for (i, value) in values.iter().enumerate() {
+                if !value.path.starts_with("/sys/") && !value.path.starts_with("/proc/") {
+                    problems.push(format!(
+                        "option {:?} writes '{}', which is not under /sys or /proc",
+                        option, value.path
+                    ));
+                }
+                if value.value.trim().is_empty() || value.value.contains('\n') {
+                    problems.push(format!(
+                        "option {:?} writes {:?} to '{}', which is not a single line",
+                        option, value.value, value.path
+                    ));
+                }
+                if values[..i].iter().any(|other| other.path == value.path) {
+                    problems.push(format!(
+                        "option {:?} writes '{}' more than once",
+                        option, value.path
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns every path any option writes.
+    fn settings(&self) -> Vec<TweakSetting> {
+        self.patterns()
+            .into_iter()
+            .map(|path| TweakSetting::SysfsFile {
+                path: path.to_string(),
+            })
+            .collect()
+    }
+
+    /// Checks that the kernel provides every file the tweak writes, which also rules
+    /// out systems other than Linux.
+    fn unsupported(&self) -> Option<String> {
+        let sysfs = backend::sysfs();
+        for pattern in self.patterns() {
+            match read_matching(sysfs.as_ref(), pattern) {
+                Ok(files) if files.is_empty() => {
+                    return Some(format!("This kernel does not provide {}", pattern))
+                }
+                Ok(_) => {}
+                Err(e) => return Some(format!("{:#}", e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_matches_any_run_of_characters() {
+        assert!(wildcard_matches("cpu*", "cpu12"));
+        assert!(wildcard_matches("cpu*", "cpufreq"));
+        assert!(wildcard_matches("state*", "state0"));
+        assert!(wildcard_matches("*", "anything"));
+        assert!(wildcard_matches("policy*.d", "policy3.d"));
+        assert!(!wildcard_matches("cpu*", "node0"));
+        assert!(!wildcard_matches("policy*.d", "policy3"));
+        assert!(wildcard_matches("swappiness", "swappiness"));
+    }
+}
@@ -0,0 +1,152 @@
+// src/tweaks/linux/mod.rs
+
+//! Tweaks for Linux installs, which write the kernel's sysfs and procfs files.
+
+pub mod method;
+
+use method::{SysfsTweak, SysfsValue};
+
+use super::{option_map, Tweak, TweakCategory, TweakId, TweakOption};
+
+pub fn all_linux_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
+    vec![
+        (TweakId::LinuxCpuGovernor, cpu_governor()),
+        (TweakId::LinuxTransparentHugepages, transparent_hugepages()),
+        (TweakId::LinuxLowSwappiness, low_swappiness()),
+        (
+            TweakId::LinuxDisableSchedAutogroup,
+            disable_sched_autogroup(),
+        ),
+        (TweakId::LinuxDisableRtThrottling, disable_rt_throttling()),
+        (
+            TweakId::LinuxDisableCpuIdleStates,
+            disable_cpu_idle_states(),
+        ),
+    ]
+}
+
+pub fn cpu_governor<'a>() -> Tweak<'a> {
+    const PATH: &str = "/sys/devices/system/cpu/cpu*/cpufreq/scaling_governor";
+    let options = ["performance", "schedutil", "powersave"].map(|governor| {
+        (
+            TweakOption::Option(governor.to_string()),
+            vec![SysfsValue::new(PATH, governor)],
+        )
+    });
+    Tweak::sysfs_tweak(
+        "CPU Frequency Governor (Linux)",
+        "Sets the cpufreq governor of every logical processor. `performance` holds the highest frequency, `schedutil` follows the scheduler's load estimate, and `powersave` holds the lowest frequency, or with intel_pstate and amd-pstate lets the hardware choose. Drivers that do not offer a governor reject it.",
+        TweakCategory::Power,
+        SysfsTweak {
+            id: TweakId::LinuxCpuGovernor,
+            options: option_map(options),
+        },
+    )
+}
+
+pub fn transparent_hugepages<'a>() -> Tweak<'a> {
+    const PATH: &str = "/sys/kernel/mm/transparent_hugepage/enabled";
+    let options = ["always", "madvise", "never"].map(|mode| {
+        (
+            TweakOption::Option(mode.to_string()),
+            vec![SysfsValue::new(PATH, mode)],
+        )
+    });
+    Tweak::sysfs_tweak(
+        "Transparent Hugepages (Linux)",
+        "Chooses when the kernel backs memory with 2 MB pages. `always` uses them for every large anonymous mapping, `madvise` only where a program asks with madvise(MADV_HUGEPAGE), and `never` not at all. Huge pages cut TLB misses in memory-bound benchmarks, at the cost of compaction stalls.",
+        TweakCategory::Memory,
+        SysfsTweak {
+            id: TweakId::LinuxTransparentHugepages,
+            options: option_map(options),
+        },
+    )
+}
+
+pub fn low_swappiness<'a>() -> Tweak<'a> {
+    const PATH: &str = "/proc/sys/vm/swappiness";
+    Tweak::sysfs_tweak(
+        "Low Swappiness (Linux)",
+        "Sets vm.swappiness to 1 instead of the default 60, so that the kernel drops page cache rather than swap out program memory, and only swaps to avoid running out of memory.",
+        TweakCategory::Memory,
+        SysfsTweak {
+            id: TweakId::LinuxLowSwappiness,
+            options: option_map(vec![
+                (
+                    TweakOption::Enabled(false),
+                    vec![SysfsValue::new(PATH, "60")],
+                ),
+                (
+                    TweakOption::Enabled(true),
+                    vec![SysfsValue::new(PATH, "1")],
+                ),
+            ]),
+        },
+    )
+}
+
+pub fn disable_sched_autogroup<'a>() -> Tweak<'a> {
+    const PATH: &str = "/proc/sys/kernel/sched_autogroup_enabled";
+    Tweak::sysfs_tweak(
+        "Disable Scheduler Autogroup (Linux)",
+        "Sets kernel.sched_autogroup_enabled to 0. Autogroup shares CPU time fairly between terminal sessions rather than between threads, so a benchmark started from one terminal competes as one group against everything else. Disabling it lets nice values and thread counts decide instead.",
+        TweakCategory::Kernel,
+        SysfsTweak {
+            id: TweakId::LinuxDisableSchedAutogroup,
+            options: option_map(vec![
+                (
+                    TweakOption::Enabled(false),
+                    vec![SysfsValue::new(PATH, "1")],
+                ),
+                (
+                    TweakOption::Enabled(true),
+                    vec![SysfsValue::new(PATH, "0")],
+                ),
+            ]),
+        },
+    )
+}
+
+pub fn disable_rt_throttling<'a>() -> Tweak<'a> {
+    const PATH: &str = "/proc/sys/kernel/sched_rt_runtime_us";
+    Tweak::sysfs_tweak(
+        "Disable Real-Time Throttling (Linux)",
+        "Sets kernel.sched_rt_runtime_us to -1, so that real-time threads may use the whole of every second instead of 950 ms of it. A real-time thread that never sleeps can then starve the rest of the system on its core.",
+        TweakCategory::Kernel,
+        SysfsTweak {
+            id: TweakId::LinuxDisableRtThrottling,
+            options: option_map(vec![
+                (
+                    TweakOption::Enabled(false),
+                    vec![SysfsValue::new(PATH, "950000")],
+                ),
+                (
+                    TweakOption::Enabled(true),
+                    vec![SysfsValue::new(PATH, "-1")],
+                ),
+            ]),
+        },
+    )
+}
+
+pub fn disable_cpu_idle_states<'a>() -> Tweak<'a> {
+    const PATH: &str = "/sys/devices/system/cpu/cpu*/cpuidle/state*/disable";
+    Tweak::sysfs_tweak(
+        "Disable CPU Idle States (Linux)",
+        "Disables every cpuidle state of every logical processor, so that idle cores poll instead of entering C-states. Removes C-state exit latency from benchmarks, at the cost of much higher idle power and temperature.",
+        TweakCategory::Power,
+        SysfsTweak {
+            id: TweakId::LinuxDisableCpuIdleStates,
+            options: option_map(vec![
+                (
+                    TweakOption::Enabled(false),
+                    vec![SysfsValue::new(PATH, "0")],
+                ),
+                (
+                    TweakOption::Enabled(true),
+                    vec![SysfsValue::new(PATH, "1")],
+                ),
+            ]),
+        },
+    )
+}
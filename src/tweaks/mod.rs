@@ -1,22 +1,50 @@
 // src/tweaks/mod.rs
 
+pub mod bcd;
+pub mod definitions;
 pub mod group_policy;
+pub mod linux;
 pub mod msr;
+pub mod pci;
+pub mod plan;
 pub mod powershell;
 pub mod registry;
+pub mod relations;
+pub mod validate;
 pub mod winapi;
 
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Error;
+use bcd::{all_bcd_tweaks, method::BcdTweak};
 use group_policy::{all_group_policy_tweaks, method::GroupPolicyTweak};
+use indexmap::IndexMap;
+use linux::{all_linux_tweaks, method::SysfsTweak};
 use msr::{all_msr_tweaks, method::MSRTweak};
+use once_cell::sync::Lazy;
+use pci::method::PciTweak;
+use plan::TweakPlan;
 use powershell::{all_powershell_tweaks, method::PowershellTweak};
 use registry::{all_registry_tweaks, method::RegistryTweak};
-use strum_macros::EnumIter;
+use relations::{TweakRelations, TweakSetting};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
 use winapi::all_winapi_tweaks;
 
-use crate::ui::TweakWidget;
+use crate::{
+    backup::TweakSnapshot,
+    ui::TweakWidget,
+    utils::{
+        cpu::{format_ranges, CoreSelection, LogicalCpu},
+        paths::tweaks_dir,
+    },
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd, EnumIter)]
 pub enum TweakId {
@@ -30,6 +58,8 @@ pub enum TweakId {
     UltimatePerformancePlan,
     NoLowDiskSpaceChecks,
     AdditionalKernelWorkerThreads,
+    /// Disable Dynamic Tick. Named after the HPET forcing it once did as well, and kept
+    /// so that saved profiles and backups still load.
     DisableHPET,
     DisableRamCompression,
     DisableApplicationTelemetry,
@@ -85,8 +115,95 @@ pub enum TweakId {
     DisableRepMovStosStreaming,
     DisableCoreWatchdogTimer,
     DisablePlatformFirstErrorHandling,
+    DisableIntelL2HardwarePrefetcher,
+    DisableIntelL2AdjacentLinePrefetcher,
+    DisableIntelDcuStreamerPrefetcher,
+    DisableIntelDcuIpPrefetcher,
+    DisableIntelC1e,
+    DisableIntelEist,
+    DisableIntelTurbo,
+    DisableIntelTsxRtm,
     AlchemyKernelTweak,
-    DisableDps
+    DisableDps,
+    TscSyncPolicy,
+    UsePlatformTick,
+    HypervisorLaunchType,
+    LinuxCpuGovernor,
+    LinuxTransparentHugepages,
+    LinuxLowSwappiness,
+    LinuxDisableSchedAutogroup,
+    LinuxDisableRtThrottling,
+    LinuxDisableCpuIdleStates,
+    /// A tweak loaded from a user definition file.
+    #[strum(disabled)]
+    Custom(CustomTweakId),
+}
+
+impl fmt::Display for TweakId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TweakId::Custom(id) => write!(f, "{}", id.as_str()),
+            id => write!(f, "{:?}", id),
+        }
+    }
+}
+
+impl FromStr for TweakId {
+    type Err = strum::ParseError;
+
+    /// Parses a tweak ID by name, ignoring ASCII case. Custom IDs are only found
+    /// once their definition has been loaded.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TweakId::iter()
+            .find(|id| id.to_string().eq_ignore_ascii_case(s))
+            .or_else(|| CustomTweakId::lookup(s).map(TweakId::Custom))
+            .ok_or(strum::ParseError::VariantNotFound)
+    }
+}
+
+/// Names of every custom tweak ID created in this process, each leaked only once.
+static CUSTOM_TWEAK_NAMES: Lazy<Mutex<BTreeSet<&'static str>>> =
+    Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+/// Names of every custom tweak ID registered in this process.
+static CUSTOM_TWEAK_IDS: Lazy<Mutex<BTreeSet<&'static str>>> =
+    Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+/// ID of a tweak defined in a user data file rather than in code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct CustomTweakId(&'static str);
+
+impl CustomTweakId {
+    /// Creates a custom tweak ID, reusing the name of an earlier ID with the same name.
+    /// It cannot be parsed by name until it is registered.
+    pub fn new(name: &str) -> Self {
+        let mut names = CUSTOM_TWEAK_NAMES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(interned) = names.get(name) {
+            return Self(interned);
+        }
+        let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+        names.insert(interned);
+        Self(interned)
+    }
+
+    /// Registers the ID, so that it can be parsed by name from then on.
+    pub fn register(self) -> Self {
+        let mut ids = CUSTOM_TWEAK_IDS.lock().unwrap_or_else(|e| e.into_inner());
+        ids.insert(self.0);
+        self
+    }
+
+    /// Finds a registered custom tweak ID by name, ignoring ASCII case.
+    pub fn lookup(name: &str) -> Option<Self> {
+        let ids = CUSTOM_TWEAK_IDS.lock().unwrap_or_else(|e| e.into_inner());
+        ids.iter()
+            .find(|id| id.eq_ignore_ascii_case(name))
+            .map(|id| Self(id))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
 }
 
 pub fn all_tweaks<'a>() -> BTreeMap<TweakId, Tweak<'a>> {
@@ -104,6 +221,10 @@ pub fn all_tweaks<'a>() -> BTreeMap<TweakId, Tweak<'a>> {
         tweaks.insert(id, tweak);
     }
 
+    for (id, tweak) in all_bcd_tweaks() {
+        tweaks.insert(id, tweak);
+    }
+
     for (id, tweak) in all_group_policy_tweaks() {
         tweaks.insert(id, tweak);
     }
@@ -112,9 +233,33 @@ pub fn all_tweaks<'a>() -> BTreeMap<TweakId, Tweak<'a>> {
         tweaks.insert(id, tweak);
     }
 
+    for (id, tweak) in all_linux_tweaks() {
+        tweaks.insert(id, tweak);
+    }
+
     tweaks
 }
 
+/// Returns the built-in tweaks together with those defined in the user's tweaks directory.
+///
+/// # Returns
+/// All tweaks that loaded, and an error for every definition file that could not be loaded.
+pub fn load_all_tweaks() -> (BTreeMap<TweakId, Tweak<'static>>, Vec<Error>) {
+    let mut tweaks = all_tweaks();
+
+    let dir = match tweaks_dir() {
+        Ok(dir) => dir,
+        Err(e) => return (tweaks, vec![e]),
+    };
+    let (custom_tweaks, errors) = definitions::load_definitions(&dir);
+    for (id, tweak) in custom_tweaks {
+        tracing::info!("{:?} -> Loaded custom tweak from '{}'.", id, dir.display());
+        tweaks.insert(id, tweak);
+    }
+
+    (tweaks, errors)
+}
+
 /// Represents a single tweak that can be applied to the system.
 pub struct Tweak<'a> {
     /// Display name of the tweak.
@@ -137,6 +282,8 @@ pub struct Tweak<'a> {
     pub requires_reboot: bool,
     /// If the tweak has been applied during this session, but still requires a reboot.
     pub pending_reboot: bool,
+    /// How the tweak interacts with other tweaks.
+    pub relations: TweakRelations,
 }
 
 /// Trait defining the behavior for all tweak methods.
@@ -147,15 +294,233 @@ pub trait TweakMethod: Send + Sync {
     /// Applies the tweak.
     fn apply(&self, option: TweakOption) -> Result<(), Error>;
 
+    /// Works out what setting the tweak to the given option would change, without
+    /// changing anything. `Enabled(false)` describes `revert`, since that is how a tweak
+    /// is disabled.
+    ///
+    /// The default describes the change in words only, for tweaks that cannot tell in advance.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan, Error> {
+        Ok(TweakPlan::described(format!(
+            "Apply {} (this tweak cannot describe its changes in advance)",
+            option
+        )))
+    }
+
     /// Reverts the tweak.
     fn revert(&self) -> Result<(), Error>;
+
+    /// Captures the current value of everything the tweak can change, so that it
+    /// can be put back later with `restore`.
+    ///
+    /// Returns `None` for tweaks whose previous state cannot be captured.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>, Error> {
+        Ok(None)
+    }
+
+    /// Puts back the values captured by `snapshot`.
+    fn restore(&self, _snapshot: &TweakSnapshot) -> Result<(), Error> {
+        Err(anyhow::anyhow!(
+            "This tweak cannot restore its original state"
+        ))
+    }
+
+    /// Checks the definition of the tweak for mistakes that would make it misbehave,
+    /// without touching the system.
+    ///
+    /// Returns a description of every problem found.
+    fn validate(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns whether the tweak records every modification it makes in the journal,
+    /// so that an interrupted change can be rolled back.
+    ///
+    /// Returns `false` by default.
+    fn journaled(&self) -> bool {
+        false
+    }
+
+    /// Returns the option that leaves the tweak as the system shipped it, if any.
+    ///
+    /// Returns `Enabled(false)` by default, which is what `revert` applies.
+    fn default_option(&self) -> Option<TweakOption> {
+        Some(TweakOption::Enabled(false))
+    }
+
+    /// Returns every setting the tweak can write, so that tweaks writing the same
+    /// setting can be found.
+    fn settings(&self) -> Vec<TweakSetting> {
+        Vec::new()
+    }
+
+    /// Explains why the tweak cannot be used on this system, e.g. because the processor
+    /// lacks the registers it changes.
+    ///
+    /// Returns `None` for tweaks that can be used, which is the default.
+    fn unsupported(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns the logical processors that changes and state reads are limited to, for
+    /// tweaks that are set on each logical processor separately.
+    ///
+    /// Returns `None` for tweaks that are not per core, which is the default.
+    fn targets(&self) -> Option<CoreSelection> {
+        None
+    }
+
+    /// Limits later changes and state reads to a subset of the logical processors.
+    /// Snapshots and restores still cover every logical processor.
+    fn set_targets(&self, _targets: CoreSelection) -> Result<(), Error> {
+        Err(anyhow::anyhow!("This tweak is not applied per core"))
+    }
+
+    /// Reads which option each logical processor is in, whether it is targeted or not.
+    ///
+    /// Returns an empty list for tweaks that are not per core, which is the default.
+    fn core_states(&self) -> Result<Vec<CoreState>, Error> {
+        Ok(Vec::new())
+    }
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum TweakOption {
     Run,
     Enabled(bool),
     Option(String),
+    /// The tweak matches none of its options, e.g. because it was only partly applied
+    /// or only applied on some cores. Holds the value of every setting that was read.
+    /// This is a state only and can never be applied.
+    Mixed(Vec<SettingState>),
+}
+
+/// The value one setting of a tweak was found in, recorded when the tweak as a whole
+/// matches none of its options.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct SettingState {
+    /// The setting, e.g. a registry value or an MSR bit.
+    pub setting: String,
+    /// The core the value was read on, for settings that are per core.
+    pub core: Option<usize>,
+    /// The value that was read, or `None` if the setting does not exist.
+    pub value: Option<String>,
+    /// The options that set the setting to this value.
+    pub matches: Vec<TweakOption>,
+}
+
+/// The state of a per-core tweak on one logical processor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreState {
+    pub cpu: LogicalCpu,
+    /// The option the processor's settings match, or `None` if they match none.
+    pub option: Option<TweakOption>,
+}
+
+/// Summarises the settings of a `Mixed` state, one line per setting, with the cores
+/// that read the same value grouped together.
+pub fn describe_mixed(states: &[SettingState]) -> Vec<String> {
+    // Each setting maps to the distinct values it was read in, with the cores that read them
+    let mut settings: IndexMap<&str, Vec<(&SettingState, Vec<usize>)>> = IndexMap::new();
+    for state in states {
+        let values = settings.entry(&state.setting).or_default();
+        match values
+            .iter_mut()
+            .find(|(first, _)| first.value == state.value && first.matches == state.matches)
+        {
+            Some((_, cores)) => cores.extend(state.core),
+            None => values.push((state, state.core.into_iter().collect())),
+        }
+    }
+
+    settings
+        .into_iter()
+        .map(|(setting, values)| {
+            let values = values
+                .into_iter()
+                .map(|(state, cores)| {
+                    let value = state.value.as_deref().unwrap_or("<absent>");
+                    let cores = match cores.len() {
+                        0 => String::new(),
+                        1 => format!(" on core {}", cores[0]),
+                        _ => format!(" on cores {}", format_ranges(&cores)),
+                    };
+                    let matches = if state.matches.is_empty() {
+                        "no option".to_string()
+                    } else {
+                        state
+                            .matches
+                            .iter()
+                            .map(|option| option.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    };
+                    format!("{}{} ({})", value, cores, matches)
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            format!("{}: {}", setting, values)
+        })
+        .collect()
+}
+
+impl fmt::Display for TweakOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TweakOption::Run => write!(f, "Run"),
+            TweakOption::Enabled(true) => write!(f, "Enabled"),
+            TweakOption::Enabled(false) => write!(f, "Disabled"),
+            TweakOption::Option(value) => write!(f, "{}", value),
+            TweakOption::Mixed(_) => write!(f, "Mixed"),
+        }
+    }
+}
+
+/// Builds the options of a tweak method, in the order given.
+///
+/// # Returns
+/// - `Ok(IndexMap)` mapping each option to what it does.
+/// - `Err(anyhow::Error)` naming an option listed more than once, which
+///   `IndexMap::from_iter` would silently collapse into the last of them.
+pub fn try_option_map<V>(
+    options: impl IntoIterator<Item = (TweakOption, V)>,
+) -> Result<IndexMap<TweakOption, V>, Error> {
+    let mut map = IndexMap::new();
+    for (option, value) in options {
+        if map.contains_key(&option) {
+            anyhow::bail!("option '{}' is defined more than once", option);
+        }
+        map.insert(option, value);
+    }
+    Ok(map)
+}
+
+/// Builds the options of a built-in tweak method, in the order given.
+///
+/// Panics if an option is listed more than once. Every built-in tweak is built by the
+/// tests, so such a mistake cannot ship.
+pub fn option_map<V>(
+    options: impl IntoIterator<Item = (TweakOption, V)>,
+) -> IndexMap<TweakOption, V> {
+    try_option_map(options).unwrap_or_else(|e| panic!("{:#}", e))
+}
+
+/// Finds the default among the options of a tweak method.
+///
+/// # Returns
+/// `Enabled(false)` for a toggle, the option named `Default` for a combo box, or `None`
+/// if the options include neither, e.g. when every option differs from the stock state.
+pub fn default_option_of<'o>(
+    options: impl IntoIterator<Item = &'o TweakOption>,
+) -> Option<TweakOption> {
+    let mut default = None;
+    for option in options {
+        match option {
+            TweakOption::Enabled(false) => return Some(option.clone()),
+            TweakOption::Option(name) if name == "Default" => default = Some(option.clone()),
+            _ => {}
+        }
+    }
+    default
 }
 
 #[derive(Debug)]
@@ -163,9 +528,12 @@ pub enum TweakStatus {
     Idle,
     Busy,
     Failed(String),
+    /// The tweak cannot be used on this system, for the given reason.
+    Unsupported(String),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, EnumString)]
+#[strum(ascii_case_insensitive)]
 pub enum TweakCategory {
     Action,
     Cpu,
@@ -220,6 +588,7 @@ impl<'a> Tweak<'a> {
             status: TweakStatus::Idle,
             state,
             pending_reboot: false,
+            relations: TweakRelations::default(),
         }
     }
 
@@ -249,6 +618,7 @@ impl<'a> Tweak<'a> {
             status: TweakStatus::Idle,
             state,
             pending_reboot: false,
+            relations: TweakRelations::default(),
         }
     }
 
@@ -278,6 +648,7 @@ impl<'a> Tweak<'a> {
             status: TweakStatus::Idle,
             state,
             pending_reboot: false,
+            relations: TweakRelations::default(),
         }
     }
 
@@ -300,6 +671,7 @@ impl<'a> Tweak<'a> {
             status: TweakStatus::Idle,
             state: TweakOption::Enabled(false),
             pending_reboot: false,
+            relations: TweakRelations::default(),
         }
     }
 
@@ -327,8 +699,113 @@ impl<'a> Tweak<'a> {
             status: TweakStatus::Idle,
             state: TweakOption::Enabled(false),
             pending_reboot: false,
+            relations: TweakRelations::default(),
         }
     }
+
+    pub fn pci_tweak(
+        name: &'a str,
+        description: &'a str,
+        category: TweakCategory,
+        method: PciTweak,
+    ) -> Self {
+        let options: Vec<TweakOption> = method.options.keys().cloned().collect();
+        let state = options[0].clone();
+        let widget = match state {
+            TweakOption::Enabled(_) => &TweakWidget::Toggle,
+            TweakOption::Option(_) => &TweakWidget::SettingsComboBox,
+            _ => &TweakWidget::Button,
+        };
+        Self {
+            name,
+            description,
+            category,
+            method: Arc::new(method),
+            options,
+            widget,
+            requires_reboot: false,
+            status: TweakStatus::Idle,
+            state,
+            pending_reboot: false,
+            relations: TweakRelations::default(),
+        }
+    }
+
+    pub fn sysfs_tweak(
+        name: &'a str,
+        description: &'a str,
+        category: TweakCategory,
+        method: SysfsTweak,
+    ) -> Self {
+        let options: Vec<TweakOption> = method.options.keys().cloned().collect();
+        let state = options[0].clone();
+        let widget = match state {
+            TweakOption::Enabled(_) => &TweakWidget::Toggle,
+            TweakOption::Option(_) => &TweakWidget::SettingsComboBox,
+            _ => &TweakWidget::Button,
+        };
+        Self {
+            name,
+            description,
+            category,
+            method: Arc::new(method),
+            options,
+            widget,
+            requires_reboot: false,
+            status: TweakStatus::Idle,
+            state,
+            pending_reboot: false,
+            relations: TweakRelations::default(),
+        }
+    }
+
+    /// Boot configuration changes take effect at the next boot, so these tweaks always
+    /// require a reboot.
+    pub fn bcd_tweak(
+        name: &'a str,
+        description: &'a str,
+        category: TweakCategory,
+        method: BcdTweak,
+    ) -> Self {
+        let options: Vec<TweakOption> = method.options.keys().cloned().collect();
+        let state = options[0].clone();
+        let widget = match state {
+            TweakOption::Enabled(_) => &TweakWidget::Toggle,
+            TweakOption::Option(_) => &TweakWidget::SettingsComboBox,
+            _ => &TweakWidget::Button,
+        };
+        Self {
+            name,
+            description,
+            category,
+            method: Arc::new(method),
+            options,
+            widget,
+            requires_reboot: true,
+            status: TweakStatus::Idle,
+            state,
+            pending_reboot: false,
+            relations: TweakRelations::default(),
+        }
+    }
+
+    /// Declares tweaks that must be enabled for this one to have any effect.
+    pub fn requires(mut self, ids: &[TweakId]) -> Self {
+        self.relations.requires.extend_from_slice(ids);
+        self
+    }
+
+    /// Declares tweaks that must not be enabled at the same time as this one.
+    pub fn conflicts_with(mut self, ids: &[TweakId]) -> Self {
+        self.relations.conflicts_with.extend_from_slice(ids);
+        self
+    }
+
+    /// Declares tweaks that change some of the same settings as this one.
+    pub fn overlaps(mut self, ids: &[TweakId]) -> Self {
+        self.relations.overlaps.extend_from_slice(ids);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -352,4 +829,48 @@ mod tests {
 
         assert!(unused_ids.is_empty(), "Unused IDs: {:?}", unused_ids);
     }
+
+    #[test]
+    fn test_repeated_option_is_rejected() {
+        let options = vec![
+            (TweakOption::Enabled(false), 0),
+            (TweakOption::Enabled(true), 1),
+            (TweakOption::Enabled(false), 2),
+        ];
+        let error = try_option_map(options.clone()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "option 'Disabled' is defined more than once"
+        );
+        assert!(std::panic::catch_unwind(|| option_map(options)).is_err());
+
+        let map = try_option_map(vec![(TweakOption::Run, 0)]).unwrap();
+        assert_eq!(map[&TweakOption::Run], 0);
+    }
+
+    #[test]
+    fn test_describe_mixed_groups_cores() {
+        let bit = |core, value: &str, option| SettingState {
+            setting: "MSR 0xC0011020 bit 4".to_string(),
+            core: Some(core),
+            value: Some(value.to_string()),
+            matches: vec![TweakOption::Enabled(option)],
+        };
+        let mut states: Vec<SettingState> = (0..4).map(|core| bit(core, "1", true)).collect();
+        states.extend([bit(4, "0", false), bit(5, "0", false), bit(6, "1", true)]);
+        states.push(SettingState {
+            setting: "HKEY_LOCAL_MACHINE\\SYSTEM\\Test\\Value".to_string(),
+            core: None,
+            value: None,
+            matches: vec![],
+        });
+
+        assert_eq!(
+            describe_mixed(&states),
+            vec![
+                "MSR 0xC0011020 bit 4: 1 on cores 0-3, 6 (Enabled); 0 on cores 4-5 (Disabled)",
+                "HKEY_LOCAL_MACHINE\\SYSTEM\\Test\\Value: <absent> (no option)",
+            ]
+        );
+    }
 }
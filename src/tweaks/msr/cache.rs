@@ -1,9 +1,10 @@
 // src/tweaks/msr/cache.rs
 
-use indexmap::IndexMap;
-
-use super::method::{MSRTweak, MsrState};
-use crate::tweaks::{Tweak, TweakCategory, TweakId, TweakOption};
+use super::{
+    method::{MSRTweak, MsrField},
+    zen, zen_4_and_later,
+};
+use crate::tweaks::{option_map, Tweak, TweakCategory, TweakId, TweakOption};
 
 pub fn disable_opcache<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
@@ -12,21 +13,15 @@ pub fn disable_opcache<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableOpCache,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1021,
-                        bit: 5,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_1021,
-                        bit: 5,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::bit(0xC001_1021, 5, false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::bit(0xC001_1021, 5, true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -38,33 +33,19 @@ pub fn disable_tlb_cache<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableTlbCache,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_0015,
-                        bit: 3,
-                        state: false,
-                    }]),
-                    (TweakOption::Option("Disabled".to_string()), vec![MsrState {
-                        index: 0xC001_0015,
-                        bit: 3,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("HWCR.TlbCacheDis", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("HWCR.TlbCacheDis", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
 
-// MSR0000_0C81 [L3 QoS Configuration] (L3::L3CRB::L3QosCfg1)
-// Reset: 0000_0000_0000_0000h.
-// QOS L3 Cache Allocation CDP mode enable (I vs. D). Contents are copied to ChL2QosCfg1 and ChL3QosCfg1_0.
-// _ccd[1:0]_lthree0; MSR0000_0C81
-// Bits Description
-// 63:1 Reserved.
-// 0 CDP. Read-write. Reset: 0. Code and Data Prioritization Technology enable
-
 pub fn enable_l3_code_data_prioritization<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
         "L3 Cache Code/Data Prioritization",
@@ -72,21 +53,15 @@ pub fn enable_l3_code_data_prioritization<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableL3CodeDataPrioritization,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0x0000_0C81,
-                        bit: 0,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0x0000_0C81,
-                        bit: 0,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("L3QosCfg1.CDP", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("L3QosCfg1.CDP", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
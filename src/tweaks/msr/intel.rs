@@ -0,0 +1,235 @@
+// src/tweaks/msr/intel.rs
+
+use super::method::{MSRTweak, MsrField};
+use crate::{
+    tweaks::{option_map, Tweak, TweakCategory, TweakId, TweakOption},
+    utils::cpu::CpuRange,
+};
+
+/// Family 6 models of the Intel Core and Xeon processors from Nehalem to Raptor Lake,
+/// which share the layout of the prefetcher, C1E, SpeedStep and turbo controls.
+#[rustfmt::skip]
+const CORE_MODELS: &[u32] = &[
+    // Nehalem and Westmere
+    0x1A, 0x1E, 0x1F, 0x25, 0x2C, 0x2E, 0x2F,
+    // Sandy Bridge and Ivy Bridge
+    0x2A, 0x2D, 0x3A, 0x3E,
+    // Haswell and Broadwell
+    0x3C, 0x3D, 0x3F, 0x45, 0x46, 0x47, 0x4F, 0x56,
+    // Skylake, Kaby Lake, Coffee Lake, Comet Lake and Cascade Lake
+    0x4E, 0x55, 0x5E, 0x8E, 0x9E, 0xA5, 0xA6,
+    // Ice Lake, Tiger Lake, Rocket Lake and Sapphire Rapids
+    0x6A, 0x6C, 0x7D, 0x7E, 0x8C, 0x8D, 0x8F, 0xA7,
+    // Alder Lake and Raptor Lake
+    0x97, 0x9A, 0xB7, 0xBA, 0xBF,
+];
+
+/// Family 6 models that may have IA32_TSX_CTRL, with the microcode that adds it:
+/// Cascade Lake, Ice Lake server, Sapphire Rapids, and the Coffee Lake, Whiskey Lake,
+/// Amber Lake and Comet Lake parts with TSX.
+const TSX_CTRL_MODELS: &[u32] = &[0x55, 0x6A, 0x6C, 0x8E, 0x8F, 0x9E, 0xA5, 0xA6];
+
+fn intel_models(models: &[u32]) -> Vec<CpuRange> {
+    models
+        .iter()
+        .map(|model| CpuRange::intel_models(0x6..=0x6, *model..=*model))
+        .collect()
+}
+
+/// Intel Core and Xeon processors from Nehalem to Raptor Lake.
+fn intel_core() -> Vec<CpuRange> {
+    intel_models(CORE_MODELS)
+}
+
+pub fn intel_msr_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
+    vec![
+        (
+            TweakId::DisableIntelL2HardwarePrefetcher,
+            disable_l2_hardware_prefetcher(),
+        ),
+        (
+            TweakId::DisableIntelL2AdjacentLinePrefetcher,
+            disable_l2_adjacent_line_prefetcher(),
+        ),
+        (
+            TweakId::DisableIntelDcuStreamerPrefetcher,
+            disable_dcu_streamer_prefetcher(),
+        ),
+        (
+            TweakId::DisableIntelDcuIpPrefetcher,
+            disable_dcu_ip_prefetcher(),
+        ),
+        (TweakId::DisableIntelC1e, disable_c1e()),
+        (TweakId::DisableIntelEist, disable_eist()),
+        (TweakId::DisableIntelTurbo, disable_turbo()),
+        (TweakId::DisableIntelTsxRtm, disable_tsx_rtm()),
+    ]
+}
+
+pub fn disable_l2_hardware_prefetcher<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Intel L2 Hardware Prefetcher Control",
+        "Disables the L2 hardware prefetcher, which fetches lines into L2 cache from detected memory access patterns. Disabling can improve benchmark consistency in latency-sensitive tests and reduce memory bandwidth wasted on unneeded lines.",
+        TweakCategory::Cpu,
+        MSRTweak {
+            id: TweakId::DisableIntelL2HardwarePrefetcher,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.L2HwPrefetcherDis", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.L2HwPrefetcherDis", true)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_l2_adjacent_line_prefetcher<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Intel L2 Adjacent Line Prefetcher Control",
+        "Disables the adjacent cache line prefetcher, which fetches the other 64-byte line of each 128-byte pair into L2 cache. Disabling can help memory benchmarks with scattered accesses, where the extra line is rarely used.",
+        TweakCategory::Cpu,
+        MSRTweak {
+            id: TweakId::DisableIntelL2AdjacentLinePrefetcher,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.L2AdjacentLinePrefetcherDis", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.L2AdjacentLinePrefetcherDis", true)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_dcu_streamer_prefetcher<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Intel L1 Streamer Prefetcher Control",
+        "Disables the DCU streamer prefetcher, which fetches the next line into L1 data cache when it detects ascending accesses. Disabling reduces L1 cache pollution in benchmarks that do not stream through memory.",
+        TweakCategory::Cpu,
+        MSRTweak {
+            id: TweakId::DisableIntelDcuStreamerPrefetcher,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.DcuStreamerPrefetcherDis", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.DcuStreamerPrefetcherDis", true)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_dcu_ip_prefetcher<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Intel L1 IP Prefetcher Control",
+        "Disables the DCU IP prefetcher, which tracks the stride of individual load instructions and fetches the next expected line into L1 data cache. Disabling can help in benchmarks without regular stride patterns.",
+        TweakCategory::Cpu,
+        MSRTweak {
+            id: TweakId::DisableIntelDcuIpPrefetcher,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.DcuIpPrefetcherDis", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("MISC_FEATURE_CONTROL.DcuIpPrefetcherDis", true)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_c1e<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Disable Intel C1E",
+        "Stops idle cores from being promoted from C1 to C1E, which drops them to their lowest voltage and frequency. Disabling C1E reduces the latency of waking an idle core at the cost of higher idle power.",
+        TweakCategory::Power,
+        MSRTweak {
+            id: TweakId::DisableIntelC1e,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("POWER_CTL.C1eEnable", true)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("POWER_CTL.C1eEnable", false)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_eist<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Disable Intel SpeedStep (EIST)",
+        "Disables Enhanced Intel SpeedStep Technology, so the operating system can no longer lower the cores' frequency and voltage. Keeps clocks at their base frequency for consistent benchmark runs. Many firmwares lock this setting, in which case the write is refused and nothing is changed.",
+        TweakCategory::Power,
+        MSRTweak {
+            id: TweakId::DisableIntelEist,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("IA32_MISC_ENABLE.EistEnable", true)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("IA32_MISC_ENABLE.EistEnable", false)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_turbo<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Disable Intel Turbo Boost",
+        "Disables Turbo Boost, capping the cores at their base frequency. Useful for repeatable benchmark runs and for testing stability and thermals without boost clocks.",
+        TweakCategory::Power,
+        MSRTweak {
+            id: TweakId::DisableIntelTurbo,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("IA32_MISC_ENABLE.TurboDisable", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("IA32_MISC_ENABLE.TurboDisable", true)]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_core(),
+            targets: Default::default(),
+        },
+    )
+}
+
+pub fn disable_tsx_rtm<'a>() -> Tweak<'a> {
+    Tweak::msr_tweak(
+        "Disable Intel TSX (MSR)",
+        "Disables Intel Transactional Synchronization Extensions through IA32_TSX_CTRL: RTM transactions always abort and CPUID stops reporting TSX. Takes effect immediately, without the restart the registry setting needs. Requires a processor and microcode that provide IA32_TSX_CTRL.",
+        TweakCategory::Security,
+        MSRTweak {
+            id: TweakId::DisableIntelTsxRtm,
+            options: option_map(
+                vec![
+                    (TweakOption::Enabled(false), vec![
+                        MsrField::named_bit("IA32_TSX_CTRL.RtmDisable", false),
+                        MsrField::named_bit("IA32_TSX_CTRL.TsxCpuidClear", false),
+                    ]),
+                    (TweakOption::Enabled(true), vec![
+                        MsrField::named_bit("IA32_TSX_CTRL.RtmDisable", true),
+                        MsrField::named_bit("IA32_TSX_CTRL.TsxCpuidClear", true),
+                    ]),
+                ]
+            ),
+            readable: true,
+            supported_cpus: intel_models(TSX_CTRL_MODELS),
+            targets: Default::default(),
+        },
+    )
+    // The registry setting turns TSX off at the next boot
+    .overlaps(&[TweakId::DisableIntelTSX])
+}
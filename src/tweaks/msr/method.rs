@@ -1,26 +1,189 @@
 // src/tweaks/msr.rs
 
+use std::{fmt, ops::RangeInclusive, sync::Mutex};
+
 use anyhow::{Context, Result};
 use indexmap::IndexMap;
 use tracing::{debug, error};
 
 use crate::{
-    tweaks::{TweakId, TweakMethod, TweakOption},
-    utils::{cpu::CPU_INFO, winring0::WINRING0_DRIVER},
+    backend::{self, MsrBackend},
+    backup::{MsrValueSnapshot, TweakSnapshot},
+    journal::{record_step, JournalStep},
+    tweaks::{
+        default_option_of,
+        msr::registers,
+        plan::{MsrFieldChange, PlanStep, TweakPlan},
+        relations::TweakSetting,
+        CoreState, SettingState, TweakId, TweakMethod, TweakOption,
+    },
+    utils::cpu::{
+        format_ranges, unsupported_reason, CoreSelection, CpuIdentity, CpuRange, CpuTopology,
+        LogicalCpu,
+    },
 };
 
-/// Represents a single MSR modification, including the MSR index, bit position,
-/// and the desired state (set to 1 or 0).
+/// Represents a single MSR modification: a field of one or more contiguous bits
+/// within an MSR and the value to write to it. A single bit is a field whose
+/// `low_bit` and `high_bit` are the same.
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
-pub struct MsrState {
+pub struct MsrField {
     /// MSR index (e.g., 0x10).
     pub index: u32,
-    /// Bit position within the MSR to modify.
-    pub bit: u32,
-    /// Desired state: `true` to set the bit to 1, `false` to clear it to 0.
-    pub state: bool,
+    /// Lowest bit of the field.
+    pub low_bit: u32,
+    /// Highest bit of the field, inclusive.
+    pub high_bit: u32,
+    /// Value of the field, with its lowest bit at `low_bit`.
+    pub value: u64,
+}
+
+impl MsrField {
+    /// Creates a modification that writes `value` to the bits in `bits`, e.g. `7..=9`.
+    pub fn new(index: u32, bits: RangeInclusive<u32>, value: u64) -> Self {
+        Self {
+            index,
+            low_bit: *bits.start(),
+            high_bit: *bits.end(),
+            value,
+        }
+    }
+
+    /// Creates a modification that sets a single bit to 1 (`true`) or clears it to 0 (`false`).
+    pub fn bit(index: u32, bit: u32, state: bool) -> Self {
+        Self::new(index, bit..=bit, state as u64)
+    }
+
+    /// Creates a modification that writes `value` to a field of the register database,
+    /// named `Register.Field`, e.g. `PrefetchControl.PrefetchAggressivenessProfile`.
+    ///
+    /// # Panics
+    /// If the database has no such field. Every built-in tweak is built by the tests.
+    pub fn named(path: &str, value: u64) -> Self {
+        let (register, field) = registers::lookup(path).unwrap_or_else(|e| panic!("{:#}", e));
+        Self::new(register.index, field.low_bit..=field.high_bit, value)
+    }
+
+    /// Creates a modification that sets or clears a single-bit field of the register
+    /// database, e.g. `PrefetchControl.L1Stride`.
+    pub fn named_bit(path: &str, state: bool) -> Self {
+        Self::named(path, state as u64)
+    }
+
+    /// Returns the number of bits in the field.
+    pub fn width(&self) -> u32 {
+        self.high_bit.saturating_sub(self.low_bit) + 1
+    }
+
+    /// Returns the field's bits within the MSR. Bits beyond bit 63 are left out.
+    pub fn mask(&self) -> u64 {
+        let ones = if self.width() >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << self.width()) - 1
+        };
+        ones.checked_shl(self.low_bit).unwrap_or(0)
+    }
+
+    /// Extracts the field's value from a full MSR value.
+    pub fn read(&self, msr_value: u64) -> u64 {
+        (msr_value & self.mask()) >> self.low_bit.min(63)
+    }
+
+    /// Returns `msr_value` with the field set to the modification's value and every
+    /// other bit unchanged.
+    pub fn write(&self, msr_value: u64) -> u64 {
+        let shifted = self.value.checked_shl(self.low_bit).unwrap_or(0);
+        (msr_value & !self.mask()) | (shifted & self.mask())
+    }
+
+    /// Returns whether the field holds the modification's value in `msr_value`.
+    pub fn matches(&self, msr_value: u64) -> bool {
+        self.read(msr_value) == self.value
+    }
+
+    /// Returns whether the field and `other` share any bit of the same MSR.
+    pub fn overlaps(&self, other: &MsrField) -> bool {
+        self.index == other.index
+            && self.low_bit <= other.high_bit
+            && other.low_bit <= self.high_bit
+    }
+
+    /// Describes the field's bits, e.g. `bit 5` or `bits 9:7`.
+    pub fn bits_label(&self) -> String {
+        if self.low_bit == self.high_bit {
+            format!("bit {}", self.low_bit)
+        } else {
+            format!("bits {}:{}", self.high_bit, self.low_bit)
+        }
+    }
+}
+
+/// What became of one logical processor when writing an MSR tweak failed part way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreOutcome {
+    /// It was never written.
+    Untouched,
+    /// It was written and then put back to its original values.
+    Restored,
+    /// It may still hold changed values, because putting the originals back failed.
+    Changed(String),
 }
 
+/// An MSR tweak that failed on one logical processor, with the state every targeted
+/// processor was left in once the others were put back.
+#[derive(Debug)]
+pub struct MsrWriteError {
+    pub id: TweakId,
+    /// The operation that failed, `apply` or `revert`.
+    pub action: String,
+    pub failed_on: LogicalCpu,
+    pub cause: anyhow::Error,
+    /// Every targeted processor, in order, and what it was left in.
+    pub cores: Vec<(LogicalCpu, CoreOutcome)>,
+}
+
+impl MsrWriteError {
+    /// Returns the numbers of the processors left in `outcome`, ignoring the reason a
+    /// processor is still changed.
+    pub fn cpus_left(&self, outcome: &CoreOutcome) -> Vec<usize> {
+        self.cores
+            .iter()
+            .filter(|(_, left)| std::mem::discriminant(left) == std::mem::discriminant(outcome))
+            .map(|(cpu, _)| cpu.number)
+            .collect()
+    }
+
+    /// Returns whether every processor is back to its original values.
+    pub fn fully_restored(&self) -> bool {
+        self.cpus_left(&CoreOutcome::Changed(String::new()))
+            .is_empty()
+    }
+}
+
+impl fmt::Display for MsrWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} -> Failed to {} on {}: {:#}.",
+            self.id, self.action, self.failed_on, self.cause
+        )?;
+        for (outcome, label) in [
+            (CoreOutcome::Restored, "restored"),
+            (CoreOutcome::Untouched, "untouched"),
+            (CoreOutcome::Changed(String::new()), "still changed"),
+        ] {
+            let cpus = self.cpus_left(&outcome);
+            if !cpus.is_empty() {
+                write!(f, " CPUs {} {}.", format_ranges(&cpus), label)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MsrWriteError {}
+
 /// Defines a set of MSR modifications, which in combination
 /// make up a single tweak.
 #[derive(Debug)]
@@ -30,34 +193,269 @@ pub struct MSRTweak {
     /// Indicates whether the tweak is readable.
     pub readable: bool,
     /// Mapping from `TweakOption` to a list of MSR modifications.
-    pub options: IndexMap<TweakOption, Vec<MsrState>>,
+    pub options: IndexMap<TweakOption, Vec<MsrField>>,
+    /// The processors whose MSRs the options are written for. Empty means any processor.
+    pub supported_cpus: Vec<CpuRange>,
+    /// The logical processors the tweak is applied on, every one unless the user
+    /// narrows it down.
+    pub targets: Mutex<CoreSelection>,
 }
 
 impl MSRTweak {
-    /// Creates a set mask by OR-ing all bits that need to be set.
-    fn create_set_mask(steps: &[&MsrState]) -> u64 {
-        steps
+    /// Fails with the reason the processor cannot run the tweak, since accessing an MSR
+    /// it does not implement faults.
+    fn ensure_supported(&self) -> Result<()> {
+        match self.unsupported() {
+            Some(reason) => anyhow::bail!("{:?} -> {}", self.id, reason),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `current_value` with every field of `fields` set to its value.
+    fn apply_fields(current_value: u64, fields: &[&MsrField]) -> u64 {
+        fields
             .iter()
-            .filter(|s| s.state)
-            .fold(0, |acc, s| acc | (1u64 << s.bit))
+            .fold(current_value, |value, field| field.write(value))
+    }
+
+    /// Writes an MSR on one logical processor, recording the change in the journal
+    /// before it is made.
+    fn write_msr(
+        &self,
+        msr: &dyn MsrBackend,
+        cpu: &LogicalCpu,
+        index: u32,
+        current_value: u64,
+        new_value: u64,
+    ) -> Result<()> {
+        let step = JournalStep::Msr {
+            core: cpu.number,
+            index,
+            before: current_value,
+            after: new_value,
+        };
+        record_step(step, || {
+            msr.write_msr(cpu.processor, index, new_value)
+                .with_context(|| {
+                    format!(
+                        "{:?} -> Failed to write MSR 0x{:X} on {}",
+                        self.id, index, cpu
+                    )
+                })
+        })
+    }
+
+    /// Returns the logical processors changes and state reads are limited to.
+    fn targeted_cpus(&self, topology: &CpuTopology) -> Result<Vec<LogicalCpu>> {
+        let targets = self
+            .targets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        targets
+            .select(topology)
+            .with_context(|| format!("{:?} -> Cannot apply on {}", self.id, targets))
+    }
+
+    /// Reads every MSR the tweak touches on each of the given logical processors.
+    fn read_values(&self, cpus: &[LogicalCpu]) -> Result<Vec<IndexMap<u32, u64>>> {
+        let touched_bits = self.touched_bits();
+
+        // Lock the MSR backend to prevent concurrent access
+        let msr = backend::msr()?;
+        let mut values = Vec::with_capacity(cpus.len());
+        for cpu in cpus {
+            let mut core_values = IndexMap::new();
+            for index in touched_bits.keys() {
+                let value = msr.read_msr(cpu.processor, *index).with_context(|| {
+                    format!(
+                        "{:?} -> Failed to read MSR 0x{:X} on {}",
+                        self.id, index, cpu
+                    )
+                })?;
+                core_values.insert(*index, value);
+            }
+            values.push(core_values);
+        }
+        Ok(values)
     }
 
-    /// Creates a clear mask by OR-ing all bits that need to be cleared.
-    fn create_clear_mask(steps: &[&MsrState]) -> u64 {
-        steps
+    /// Returns the first option whose every field holds its value in `core_values`.
+    fn matching_option(&self, core_values: &IndexMap<u32, u64>) -> Option<&TweakOption> {
+        self.options
             .iter()
-            .filter(|s| !s.state)
-            .fold(0, |acc, s| acc | (1u64 << s.bit))
+            .find(|(_, modifications)| {
+                modifications
+                    .iter()
+                    .all(|field| field.matches(core_values[&field.index]))
+            })
+            .map(|(option, _)| option)
+    }
+
+    /// Writes `modifications` on every targeted logical processor, all or nothing. The
+    /// original value of each MSR is saved on every processor before anything is
+    /// written, and if any write fails or does not read back, every processor written
+    /// so far is put back. `action` names the operation in messages.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every processor was written and verified.
+    /// - `Err(anyhow::Error)` wrapping an [`MsrWriteError`] that tells which processors
+    ///   were restored, untouched or left changed, if a write failed.
+    fn write_on_targets(&self, modifications: &[MsrField], action: &str) -> Result<()> {
+        self.ensure_supported()?;
+        let cpus = self.targeted_cpus(&CpuTopology::detect()?)?;
+
+        // Fields of the same MSR are written together
+        let mut fields_by_index: IndexMap<u32, Vec<&MsrField>> = IndexMap::new();
+        for field in modifications {
+            fields_by_index.entry(field.index).or_default().push(field);
+        }
+
+        // Lock the MSR backend to prevent concurrent access
+        let msr = backend::msr()?;
+
+        // Save the original words on every processor before writing any of them
+        let mut originals = Vec::with_capacity(cpus.len());
+        for cpu in &cpus {
+            let mut values = IndexMap::new();
+            for index in fields_by_index.keys() {
+                let value = msr.read_msr(cpu.processor, *index).with_context(|| {
+                    format!(
+                        "{:?} -> Failed to read MSR 0x{:X} on {}",
+                        self.id, index, cpu
+                    )
+                })?;
+                values.insert(*index, value);
+            }
+            originals.push(values);
+        }
+
+        for (position, (cpu, values)) in cpus.iter().zip(&originals).enumerate() {
+            let Err(cause) = self.write_core(&*msr, cpu, &fields_by_index, values) else {
+                continue;
+            };
+            error!(
+                "{:?} -> Failed to {} on {}: {:#}. Restoring the original values.",
+                self.id, action, cpu, cause
+            );
+
+            // The failed processor may be part written, so it is restored with the others
+            let mut cores = self.restore_cores(&*msr, &cpus[..=position], &originals);
+            cores.extend(
+                cpus[position + 1..]
+                    .iter()
+                    .map(|cpu| (*cpu, CoreOutcome::Untouched)),
+            );
+            return Err(MsrWriteError {
+                id: self.id,
+                action: action.to_string(),
+                failed_on: *cpu,
+                cause,
+                cores,
+            }
+            .into());
+        }
+
+        tracing::info!(
+            "{:?} -> Successfully {} MSRs {} on {} logical processors.",
+            self.id,
+            if action == "revert" {
+                "reverted"
+            } else {
+                "applied"
+            },
+            fields_by_index
+                .keys()
+                .map(|index| format!("0x{:X}", index))
+                .collect::<Vec<_>>()
+                .join(", "),
+            cpus.len()
+        );
+        Ok(())
     }
 
-    /// Applies the set and clear masks to the current MSR value.
-    fn apply_masks(current_value: u64, set_mask: u64, clear_mask: u64) -> u64 {
-        (current_value | set_mask) & !clear_mask
+    /// Writes the fields of every MSR on one logical processor, starting from its
+    /// original values, and checks that each reads back.
+    fn write_core(
+        &self,
+        msr: &dyn MsrBackend,
+        cpu: &LogicalCpu,
+        fields_by_index: &IndexMap<u32, Vec<&MsrField>>,
+        originals: &IndexMap<u32, u64>,
+    ) -> Result<()> {
+        for (index, fields) in fields_by_index {
+            let current_value = originals[index];
+            // Set the fields, leaving every other bit unchanged
+            let new_value = Self::apply_fields(current_value, fields);
+            self.write_msr(msr, cpu, *index, current_value, new_value)?;
+
+            let updated_value = msr.read_msr(cpu.processor, *index).with_context(|| {
+                format!(
+                    "{:?} -> Failed to read MSR 0x{:X} on {} after write",
+                    self.id, index, cpu
+                )
+            })?;
+            if updated_value != new_value {
+                anyhow::bail!(
+                    "MSR 0x{:X} on {} reads 0x{:016X} after writing 0x{:016X}",
+                    index,
+                    cpu,
+                    updated_value,
+                    new_value
+                );
+            }
+        }
+        Ok(())
     }
 
-    /// Reverts the set and clear masks to the expected value.
-    fn revert_masks(current_value: u64, set_mask: u64, clear_mask: u64) -> u64 {
-        (current_value | set_mask) & !clear_mask
+    /// Puts the original values back on each of `cpus`, whose saved values are the
+    /// matching entries of `originals`, and reports what each was left in.
+    fn restore_cores(
+        &self,
+        msr: &dyn MsrBackend,
+        cpus: &[LogicalCpu],
+        originals: &[IndexMap<u32, u64>],
+    ) -> Vec<(LogicalCpu, CoreOutcome)> {
+        cpus.iter()
+            .zip(originals)
+            .map(|(cpu, values)| {
+                let result = values.iter().try_for_each(|(index, original)| {
+                    let current = msr.read_msr(cpu.processor, *index)?;
+                    if current != *original {
+                        self.write_msr(msr, cpu, *index, current, *original)?;
+                        let restored = msr.read_msr(cpu.processor, *index)?;
+                        if restored != *original {
+                            anyhow::bail!(
+                                "MSR 0x{:X} reads 0x{:016X} after restoring 0x{:016X}",
+                                index,
+                                restored,
+                                original
+                            );
+                        }
+                    }
+                    Ok(())
+                });
+                match result {
+                    Ok(()) => (*cpu, CoreOutcome::Restored),
+                    Err(e) => {
+                        error!(
+                            "{:?} -> Failed to restore the original values on {}: {:?}",
+                            self.id, cpu, e
+                        );
+                        (*cpu, CoreOutcome::Changed(format!("{:#}", e)))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Returns, for each MSR index the tweak touches, a mask of every bit any option modifies.
+    fn touched_bits(&self) -> IndexMap<u32, u64> {
+        let mut masks = IndexMap::new();
+        for field in self.options.values().flatten() {
+            *masks.entry(field.index).or_insert(0) |= field.mask();
+        }
+        masks
     }
 }
 
@@ -65,7 +463,9 @@ impl TweakMethod for MSRTweak {
     /// Checks the current state of the MSR tweak and returns the corresponding `TweakOption`.
     ///
     /// # Returns
-    /// - `Ok(TweakOption)` indicating the current state.
+    /// - `Ok(TweakOption)` indicating the current state on the targeted cores, or
+    ///   `TweakOption::Mixed` with every bit on every targeted core if no option matches
+    ///   on all of them.
     /// - `Err(anyhow::Error)` if the operation fails.
     fn initial_state(&self) -> Result<TweakOption> {
         debug!(
@@ -73,53 +473,69 @@ impl TweakMethod for MSRTweak {
             self.id
         );
 
+        self.ensure_supported()?;
         if !self.readable {
             return Ok(TweakOption::Enabled(false));
         }
 
-        // Lock the WinRing0 driver to prevent concurrent access
-        let winring0 = WINRING0_DRIVER
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock WinRing0: {:?}", e))?;
+        // Read every MSR the tweak touches once per targeted logical processor
+        let cpus = self.targeted_cpus(&CpuTopology::detect()?)?;
+        let values = self.read_values(&cpus)?;
 
         // Iterate through all possible options to find which one matches the current state
         for (option, modifications) in &self.options {
-            let mut all_match = true;
-
-            for step in modifications {
-                for core_id in 0..CPU_INFO.cores {
-                    let current_value =
-                        winring0.read_msr(core_id, step.index).with_context(|| {
-                            format!(
-                                "{:?} -> Failed to read MSR 0x{:X} on core {}",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-
-                    let bit_state = ((current_value >> step.bit) & 1) == 1;
-                    if bit_state != step.state {
-                        all_match = false;
-                        break;
-                    }
-                }
-
-                if !all_match {
-                    break;
-                }
-            }
-
+            let all_match = values.iter().all(|core_values| {
+                modifications
+                    .iter()
+                    .all(|field| field.matches(core_values[&field.index]))
+            });
             if all_match {
                 tracing::debug!("{:?} -> Current state matches {:?}.", self.id, option);
                 return Ok(option.clone());
             }
         }
 
-        // If no matching option is found, consider it as Default
         tracing::debug!(
-            "{:?} -> Current state does not match any custom options. Reverting to Default.",
+            "{:?} -> Current state does not match any option on every core.",
             self.id
         );
-        Ok(TweakOption::Enabled(false))
+        // Every distinct field any option writes, in the order the options list them
+        let mut fields: Vec<&MsrField> = Vec::new();
+        for field in self.options.values().flatten() {
+            if !fields.iter().any(|seen| {
+                (seen.index, seen.low_bit, seen.high_bit)
+                    == (field.index, field.low_bit, field.high_bit)
+            }) {
+                fields.push(field);
+            }
+        }
+        let mut states = Vec::new();
+        for field in fields {
+            for (cpu, core_values) in cpus.iter().zip(&values) {
+                let value = field.read(core_values[&field.index]);
+                states.push(SettingState {
+                    setting: format!("MSR 0x{:08X} {}", field.index, field.bits_label()),
+                    core: Some(cpu.number),
+                    value: Some(value.to_string()),
+                    matches: self
+                        .options
+                        .iter()
+                        .filter(|(_, modifications)| {
+                            modifications.iter().any(|modification| {
+                                (
+                                    modification.index,
+                                    modification.low_bit,
+                                    modification.high_bit,
+                                    modification.value,
+                                ) == (field.index, field.low_bit, field.high_bit, value)
+                            })
+                        })
+                        .map(|(option, _)| option.clone())
+                        .collect(),
+                });
+            }
+        }
+        Ok(TweakOption::Mixed(states))
     }
 
     /// Applies the MSR tweak based on the selected `TweakOption`.
@@ -142,86 +558,7 @@ impl TweakMethod for MSRTweak {
             "{:?} -> No MSR modifications found for option: {:?}",
             self.id, option
         ))?;
-
-        // Lock the WinRing0 driver to prevent concurrent access
-        let winring0 = WINRING0_DRIVER
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock WinRing0: {:?}", e))?;
-
-        // Wrap the apply logic in a closure to handle errors and perform rollback
-        let result: Result<(), anyhow::Error> = (|| -> Result<(), anyhow::Error> {
-            for step in modifications {
-                for core_id in 0..CPU_INFO.cores {
-                    // Read current MSR value
-                    let current_value =
-                        winring0.read_msr(core_id, step.index).with_context(|| {
-                            format!(
-                                "{:?} -> Failed to read MSR 0x{:X} on core {}",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-
-                    // Create masks
-                    let set_mask = MSRTweak::create_set_mask(&[step]);
-                    let clear_mask = MSRTweak::create_clear_mask(&[step]);
-
-                    // Apply masks
-                    let new_value = MSRTweak::apply_masks(current_value, set_mask, clear_mask);
-
-                    // Write back the modified MSR value
-                    winring0
-                        .write_msr(core_id, step.index, new_value)
-                        .with_context(|| {
-                            format!(
-                                "{:?} -> Failed to write MSR 0x{:X} on core {}",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-
-                    // Verify the changes
-                    let updated_value =
-                        winring0.read_msr(core_id, step.index).with_context(|| {
-                            format!(
-                                "{:?} -> Failed to read MSR 0x{:X} on core {} after write",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-                    if updated_value != new_value {
-                        tracing::error!(
-                            "{:?} -> Failed to apply MSR 0x{:X} on core {}. Expected: 0x{:016X}, Got: 0x{:016X}",
-                            self.id,
-                            step.index,
-                            core_id,
-                            new_value,
-                            updated_value
-                        );
-                        return Err(anyhow::anyhow!(
-                            "Failed to apply MSR 0x{:X} on core {}",
-                            step.index,
-                            core_id
-                        ));
-                    }
-                }
-
-                tracing::info!(
-                    "{:?} -> Successfully applied MSR 0x{:X} on all cores.",
-                    self.id,
-                    step.index
-                );
-            }
-
-            Ok(())
-        })();
-
-        if let Err(e) = result {
-            // An error occurred during apply
-            error!(
-                "{:?} -> Error occurred during apply: {}. Attempting rollback.",
-                self.id, e
-            );
-
-            return Err(e);
-        }
+        self.write_on_targets(modifications, "apply")?;
 
         debug!(
             "{:?} -> Successfully applied MSR tweak with option: {:?}.",
@@ -246,91 +583,337 @@ impl TweakMethod for MSRTweak {
                     "{:?} -> No MSR modifications found for Default option.",
                     self.id
                 ))?;
+        self.write_on_targets(default_modifications, "revert")?;
 
-        // Lock the WinRing0 driver to prevent concurrent access
-        let winring0 = WINRING0_DRIVER
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to lock WinRing0: {:?}", e))?;
-
-        // Wrap the revert logic in a closure to handle errors and perform rollback
-        let result: Result<(), anyhow::Error> = (|| -> Result<(), anyhow::Error> {
-            for step in default_modifications {
-                for core_id in 0..CPU_INFO.cores {
-                    // Read current MSR value
-                    let current_value =
-                        winring0.read_msr(core_id, step.index).with_context(|| {
-                            format!(
-                                "{:?} -> Failed to read MSR 0x{:X} on core {}",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-
-                    // Create masks for reverting
-                    let set_mask = MSRTweak::create_clear_mask(&[step]);
-                    let clear_mask = MSRTweak::create_set_mask(&[step]);
-
-                    // Apply masks to revert
-                    let new_value = MSRTweak::revert_masks(current_value, set_mask, clear_mask);
-
-                    // Write back the reverted MSR value
-                    winring0
-                        .write_msr(core_id, step.index, new_value)
-                        .with_context(|| {
-                            format!(
-                                "{:?} -> Failed to write MSR 0x{:X} on core {}",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-
-                    // Verify the revert
-                    let updated_value =
-                        winring0.read_msr(core_id, step.index).with_context(|| {
-                            format!(
-                                "{:?} -> Failed to read MSR 0x{:X} on core {} after revert",
-                                self.id, step.index, core_id
-                            )
-                        })?;
-                    if updated_value != new_value {
-                        tracing::error!(
-                            "{:?} -> Failed to revert MSR 0x{:X} on core {}. Expected: 0x{:016X}, Got: 0x{:016X}",
-                            self.id,
-                            step.index,
-                            core_id,
-                            new_value,
-                            updated_value
-                        );
-                        return Err(anyhow::anyhow!(
-                            "Failed to revert MSR 0x{:X} on core {}",
-                            step.index,
-                            core_id
-                        ));
-                    }
-                }
+        debug!(
+            "{:?} -> Successfully reverted MSR tweak to Default.",
+            self.id
+        );
+        Ok(())
+    }
 
-                tracing::info!(
-                    "{:?} -> Successfully reverted MSR 0x{:X} on all cores.",
-                    self.id,
-                    step.index
-                );
+    /// Captures the full value of every MSR the tweak touches, on every core.
+    ///
+    /// # Returns
+    /// - `Ok(Some(TweakSnapshot::Msr))` with one entry per core and MSR.
+    /// - `Ok(None)` if the tweak's MSRs cannot be read.
+    /// - `Err(anyhow::Error)` if any read fails.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        self.ensure_supported()?;
+        if !self.readable {
+            return Ok(None);
+        }
+
+        let topology = CpuTopology::detect()?;
+
+        // Lock the MSR backend to prevent concurrent access
+        let msr = backend::msr()?;
+
+        let mut values = Vec::new();
+        for index in self.touched_bits().keys() {
+            for cpu in &topology.cpus {
+                let value = msr.read_msr(cpu.processor, *index).with_context(|| {
+                    format!(
+                        "{:?} -> Failed to read MSR 0x{:X} on {}",
+                        self.id, index, cpu
+                    )
+                })?;
+                values.push(MsrValueSnapshot {
+                    core: cpu.number,
+                    index: *index,
+                    value,
+                });
             }
+        }
 
-            Ok(())
-        })();
+        debug!("{:?} -> Captured {} MSR values.", self.id, values.len());
+        Ok(Some(TweakSnapshot::Msr(values)))
+    }
 
-        if let Err(e) = result {
-            // An error occurred during revert
-            error!(
-                "{:?} -> Error occurred during revert: {}. Attempting rollback.",
-                self.id, e
-            );
+    /// Restores the bits this tweak controls to the values captured by `snapshot`.
+    ///
+    /// Only the bits the tweak's options modify are written back, so changes other
+    /// tweaks have since made to the same MSR are preserved.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every value was restored and verified.
+    /// - `Err(anyhow::Error)` if the snapshot is not an MSR snapshot or any access fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Msr(values) = snapshot else {
+            anyhow::bail!("{:?} -> Backup is not an MSR snapshot", self.id);
+        };
+
+        self.ensure_supported()?;
+        let masks = self.touched_bits();
+        let topology = CpuTopology::detect()?;
+
+        // Lock the MSR backend to prevent concurrent access
+        let msr = backend::msr()?;
+
+        for saved in values {
+            let Some(mask) = masks.get(&saved.index) else {
+                continue;
+            };
+            let cpu = topology
+                .cpu(saved.core)
+                .with_context(|| format!("{:?} -> Backup is from another processor", self.id))?;
+
+            let current_value = msr.read_msr(cpu.processor, saved.index).with_context(|| {
+                format!(
+                    "{:?} -> Failed to read MSR 0x{:X} on {}",
+                    self.id, saved.index, cpu
+                )
+            })?;
+            let new_value = (current_value & !mask) | (saved.value & mask);
+
+            self.write_msr(&*msr, cpu, saved.index, current_value, new_value)?;
+
+            let updated_value = msr.read_msr(cpu.processor, saved.index).with_context(|| {
+                format!(
+                    "{:?} -> Failed to read MSR 0x{:X} on {} after restore",
+                    self.id, saved.index, cpu
+                )
+            })?;
+            if updated_value != new_value {
+                return Err(anyhow::anyhow!(
+                    "Failed to restore MSR 0x{:X} on {}",
+                    saved.index,
+                    cpu
+                ));
+            }
+        }
 
-            return Err(e);
+        debug!("{:?} -> Restored original MSR values.", self.id);
+        Ok(())
+    }
+
+    /// Lists, for every targeted core, each MSR the option modifies with the bits it sets and the
+    /// value before and after. Values are left out for MSRs the tweak cannot read.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with one step per MSR index and core.
+    /// - `Err(anyhow::Error)` if the option does not exist or an MSR cannot be read.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let modifications = self.options.get(option).with_context(|| {
+            format!(
+                "{:?} -> No MSR modifications found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.ensure_supported()?;
+
+        let mut by_index: IndexMap<u32, Vec<&MsrField>> = IndexMap::new();
+        for step in modifications {
+            by_index.entry(step.index).or_default().push(step);
         }
 
-        debug!(
-            "{:?} -> Successfully reverted MSR tweak to Default.",
-            self.id
-        );
+        let cpus = self.targeted_cpus(&CpuTopology::detect()?)?;
+        let msr = backend::msr()?;
+
+        let mut steps = Vec::new();
+        for cpu in &cpus {
+            for (index, fields) in &by_index {
+                let (current, new) = if self.readable {
+                    let current = msr.read_msr(cpu.processor, *index).with_context(|| {
+                        format!(
+                            "{:?} -> Failed to read MSR 0x{:X} on {}",
+                            self.id, index, cpu
+                        )
+                    })?;
+                    let new = MSRTweak::apply_fields(current, fields);
+                    (Some(current), Some(new))
+                } else {
+                    (None, None)
+                };
+                steps.push(PlanStep::Msr {
+                    core: cpu.number,
+                    index: *index,
+                    fields: fields
+                        .iter()
+                        .map(|field| MsrFieldChange {
+                            low_bit: field.low_bit,
+                            high_bit: field.high_bit,
+                            value: field.value,
+                        })
+                        .collect(),
+                    current,
+                    new,
+                });
+            }
+        }
+        Ok(TweakPlan { steps })
+    }
+
+    fn journaled(&self) -> bool {
+        true
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+
+    /// Checks that every field lies within the MSR, that its value fits, that no
+    /// option writes the same bit twice, and that every processor range is the right
+    /// way round.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for range in &self.supported_cpus {
+            if range.families.is_empty() || range.models.is_empty() {
+                problems.push(format!(
+                    "supported processors {:?} match no processor",
+                    range
+                ));
+            }
+        }
+        for (option, fields) in &self.options {
+            for (i, field) in fields.iter().enumerate() {
+                if field.low_bit > field.high_bit {
+                    problems.push(format!(
+                        "option {:?} writes bits {}:{} of MSR 0x{:X}, whose high bit is below its low bit",
+                        option, field.high_bit, field.low_bit, field.index
+                    ));
+                    continue;
+                }
+                if field.high_bit >= 64 {
+                    problems.push(format!(
+                        "option {:?} writes {} of MSR 0x{:X}, but MSRs only have 64 bits",
+                        option,
+                        field.bits_label(),
+                        field.index
+                    ));
+                    continue;
+                }
+                if field.width() < 64 && field.value >> field.width() != 0 {
+                    problems.push(format!(
+                        "option {:?} writes {} to {} of MSR 0x{:X}, which does not fit in {} bits",
+                        option,
+                        field.value,
+                        field.bits_label(),
+                        field.index,
+                        field.width()
+                    ));
+                }
+                if fields[..i].iter().any(|other| other.overlaps(field)) {
+                    problems.push(format!(
+                        "option {:?} sets {} of MSR 0x{:X} more than once",
+                        option,
+                        field.bits_label(),
+                        field.index
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns every MSR bit any option writes.
+    fn settings(&self) -> Vec<TweakSetting> {
+        self.touched_bits()
+            .into_iter()
+            .flat_map(|(index, mask)| {
+                (0..64)
+                    .filter(move |bit| mask & (1u64 << bit) != 0)
+                    .map(move |bit| TweakSetting::MsrBit { index, bit })
+            })
+            .collect()
+    }
+
+    /// Checks the processor against the ones the tweak supports, then that a driver can
+    /// reach the registers.
+    fn unsupported(&self) -> Option<String> {
+        unsupported_reason(&self.supported_cpus, &CpuIdentity::detect())
+            .or_else(backend::hardware_unavailable)
+    }
+
+    fn targets(&self) -> Option<CoreSelection> {
+        Some(
+            self.targets
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+
+    /// Checks the selection against the topology before narrowing the tweak down to it.
+    fn set_targets(&self, targets: CoreSelection) -> Result<()> {
+        targets
+            .select(&CpuTopology::detect()?)
+            .with_context(|| format!("{:?} -> Cannot apply on {}", self.id, targets))?;
+        debug!("{:?} -> Applying on {}.", self.id, targets);
+        *self.targets.lock().unwrap_or_else(|e| e.into_inner()) = targets;
         Ok(())
     }
+
+    /// Reads the tweak's MSRs on every logical processor and matches them to an option.
+    /// Tweaks whose MSRs cannot be read report no option on any processor.
+    fn core_states(&self) -> Result<Vec<CoreState>> {
+        self.ensure_supported()?;
+        let topology = CpuTopology::detect()?;
+        if !self.readable {
+            return Ok(topology
+                .cpus
+                .into_iter()
+                .map(|cpu| CoreState { cpu, option: None })
+                .collect());
+        }
+
+        let values = self.read_values(&topology.cpus)?;
+        Ok(topology
+            .cpus
+            .into_iter()
+            .zip(&values)
+            .map(|(cpu, core_values)| CoreState {
+                cpu,
+                option: self.matching_option(core_values).cloned(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_write_only_changes_its_bits() {
+        let field = MsrField::new(0xC000_0108, 7..=9, 2);
+        assert_eq!(field.mask(), 0x380);
+        // Reset value 3C0h: MasterEnable set and the profile at 7h
+        assert_eq!(field.write(0x3C0), 0x140);
+        assert_eq!(field.read(0x140), 2);
+        assert!(field.matches(0x140));
+        assert!(!field.matches(0x3C0));
+    }
+
+    #[test]
+    fn test_single_bit_field() {
+        let field = MsrField::bit(0xC001_0015, 3, true);
+        assert_eq!(field.width(), 1);
+        assert_eq!(field.write(0), 0x8);
+        assert_eq!(MsrField::bit(0xC001_0015, 3, false).write(0xF), 0x7);
+        assert_eq!(field.bits_label(), "bit 3");
+        assert_eq!(MsrField::new(0x10, 7..=9, 0).bits_label(), "bits 9:7");
+    }
+
+    #[test]
+    fn test_overlapping_and_oversized_fields_are_reported() {
+        let tweak = MSRTweak {
+            id: TweakId::AggressivePrefetchProfile,
+            readable: true,
+            options: IndexMap::from_iter(vec![(
+                TweakOption::Option("Level 8".to_string()),
+                vec![
+                    MsrField::new(0xC000_0108, 7..=9, 8),
+                    MsrField::bit(0xC000_0108, 8, true),
+                ],
+            )]),
+            supported_cpus: Vec::new(),
+            targets: Mutex::default(),
+        };
+        let problems = tweak.validate();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].contains("does not fit in 3 bits"));
+        assert!(problems[1].contains("more than once"));
+    }
 }
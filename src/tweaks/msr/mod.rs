@@ -1,21 +1,50 @@
 // src/tweaks/msr/mod.rs
 
 use cache::{disable_opcache, disable_tlb_cache, enable_l3_code_data_prioritization};
-use indexmap::IndexMap;
-use method::{MSRTweak, MsrState};
+pub use intel::intel_msr_tweaks;
+use method::{MSRTweak, MsrField};
 use prefetch::{
     disable_l1_region_prefetcher, disable_l1_stream_prefetcher, disable_l1_stride_prefetcher,
     disable_l2_stream_prefetcher, disable_up_down_prefetcher, prefetch_aggressiveness_profile,
 };
 
-use super::{Tweak, TweakCategory, TweakId, TweakOption};
+use super::{option_map, Tweak, TweakCategory, TweakId, TweakOption};
+use crate::utils::cpu::CpuRange;
 
 mod cache;
+mod intel;
 pub mod method;
 mod prefetch;
+pub mod registers;
 
-pub fn all_msr_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
+/// Every Zen processor: family 17h (Zen to Zen 2), 19h (Zen 3 and Zen 4) and 1Ah (Zen 5).
+/// For the registers AMD has kept the same since the first Zen.
+fn zen() -> Vec<CpuRange> {
+    vec![CpuRange::amd(0x17..=0x1A)]
+}
+
+/// Zen 3 and later, families 19h and 1Ah.
+fn zen_3_and_later() -> Vec<CpuRange> {
+    vec![CpuRange::amd(0x19..=0x1A)]
+}
+
+/// Zen 4 and later: the Zen 4 models of family 19h, and family 1Ah. Most tweaks here
+/// follow the Family 19h Zen 4 PPR, and many of the bits it documents moved or did not
+/// exist in earlier generations.
+fn zen_4_and_later() -> Vec<CpuRange> {
     vec![
+        // Genoa
+        CpuRange::amd_models(0x19..=0x19, 0x10..=0x1F),
+        // Raphael and Phoenix
+        CpuRange::amd_models(0x19..=0x19, 0x60..=0x7F),
+        // Bergamo and Siena
+        CpuRange::amd_models(0x19..=0x19, 0xA0..=0xAF),
+        CpuRange::amd(0x1A..=0x1A),
+    ]
+}
+
+pub fn all_msr_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
+    let mut tweaks = vec![
         (TweakId::AutomaticIbrsEnable, automatic_ibrs_enable()),
         (
             TweakId::AggressivePrefetchProfile,
@@ -102,44 +131,11 @@ pub fn all_msr_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
             TweakId::DisablePlatformFirstErrorHandling,
             disable_platform_first_error_handling(),
         ),
-    ]
+    ];
+    tweaks.extend(intel_msr_tweaks());
+    tweaks
 }
 
-// MSRC000_0080 [Extended Feature Enable] (Core::X86::Msr::EFER)
-// SKINIT Execution: 0000_0000_0000_0000h.
-// _ccd[11:0]_lthree0_core[15:0]_thread[1:0]; MSRC000_0080
-// Bits Description
-// 63:22 Reserved.
-// 21 AutomaticIBRSEn: Automatic IBRS Enable. Read-write. Reset: 0. 0=IBRS protection is not enabled unless
-// (SPEC_CTRL[IBRS] == 1). 1=IBRS protection is enabled for any process running at (CPL == 0) or ((ASID ==
-// 0) && SEV-SNP).
-// 20 UAIE: Upper Address Ignore Enable. Read-write. Reset: 0. Upper Address Ignore suppresses canonical faults
-// for most data access virtual addresses, which allows software to use the upper bits of a virtual address as tags.
-// 19 Reserved.
-// 18 IntWbinvdEn. Read-write. Reset: 0. Interruptible wbinvd, wbnoinvd enable.
-// 17:16 Reserved.
-// 15 TCE: translation cache extension enable. Read-write. Reset: 0. 1=Translation cache extension is enabled. PDC
-// entries related to the linear address of the INVLPG instruction are invalidated. If this bit is 0 all PDC entries are
-// invalidated by the INVLPG instruction.
-// 14 FFXSE: fast FXSAVE/FRSTOR enable. Read-write. Reset: 0. 1=Enables the fast FXSAVE/FRSTOR
-// mechanism. A 64-bit operating system may enable the fast FXSAVE/FRSTOR mechanism if
-// (Core::X86::Cpuid::FeatureExtIdEdx[FFXSR] == 1). This bit is set once by the operating system and its value is
-// not changed afterwards.
-// 13 LMSLE: long mode segment limit enable. Read-only,Error-on-write-1. Reset: Fixed,0. 1=Enables the long
-// mode segment limit check mechanism.
-// 12 SVME: secure virtual machine (SVM) enable. Reset: Fixed,0. 1=SVM features are enabled.
-// AccessType: Core::X86::Msr::VM_CR[SvmeDisable] ? Read-only,Error-on-write-1 : Read-write.
-// 11 NXE: no-execute page enable. Read-write. Reset: 0. 1=The no-execute page protection feature is enabled.
-// 10 LMA: long mode active. Read-only. Reset: 0. 1=Indicates that long mode is active. When writing the EFER
-// register the value of this bit must be preserved. Software must read the EFER register to determine the value of
-// LMA, change any other bits as required and then write the EFER register. An attempt to write a value that differs
-// from the state determined by hardware results in a #GP fault.
-// 9 Reserved.
-// 8 LME: long mode enable. Read-write. Reset: 0. 1=Long mode is enabled.
-// 7:1 Reserved.
-// 0 SYSCALL: system call extension enable. Read-write. Reset: 0. 1=SYSCALL and SYSRET instructions are
-// enabled. This adds the SYSCALL and SYSRET instructions which can be used in flat addressed operating
-// systems as low latency system calls and returns.
 pub fn automatic_ibrs_enable<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
         "Automatic IBRS Control",
@@ -147,21 +143,15 @@ pub fn automatic_ibrs_enable<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::AutomaticIbrsEnable,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 21,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 21,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("EFER.AutomaticIBRSEn", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("EFER.AutomaticIBRSEn", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -173,21 +163,15 @@ pub fn enable_interruptible_wbinvd<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableInterruptibleWbinvd,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 18,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 18,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("EFER.IntWbinvdEn", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("EFER.IntWbinvdEn", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_3_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -199,21 +183,15 @@ pub fn translation_cache_extension_enable<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableTranslationCacheExtension,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 15,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 15,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("EFER.TCE", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("EFER.TCE", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
@@ -225,84 +203,19 @@ pub fn enable_fast_fxsave_frstor<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableFastFxsaveFrstor,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 14,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0080,
-                        bit: 14,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("EFER.FFXSE", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("EFER.FFXSE", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
 
-// MSRC001_0010 [System Configuration] (Core::X86::Msr::SYS_CFG)
-// Reset: 0000_0000_0000_0000h.
-// If Core::X86::Msr::SYS_CFG[SecureNestedPagingEn] is set, writes to this register are ignored.
-// _ccd[11:0]_lthree0_core[15:0]; MSRC001_0010
-// Bits Description
-// 63:27 Reserved.
-// 26 HMKEE: Host Multi-Key Encryption Enable. Read,Write-1-only. Reset: 0. Used with SYS_CFG[SMEE] to
-// select secure memory encryption mode. See SYS_CFG[SMEE] for a table listing the available memory
-// encryption modes.
-// 25 VmplEn. Reset: 0. VM permission levels enable.
-// AccessType: Core::X86::Msr::SYS_CFG[SecureNestedPagingEn] ? Read-only : Read-write.
-// 24 SecureNestedPagingEn. Read,Write-1-only. Reset: 0. Enable Secure Nested Paging (SNP).
-// 23 SMEE: Secure Memory Encryption Enable. Read,Write-1-only. Reset: 0.
-// Description: Used with SYS_CFG[HMKEE] to select secure memory encryption mode. See the table below for
-// the available memory encryption modes.
-// HMKEE SMEE Description
-// 0 0 No encryption.
-// 0 1 Enables SME and SEV memory encryption.
-// 1 0 Enables SME-HMK memory encryption.
-// 1 1 Not supported. Results in #GP.
-// 22 Tom2ForceMemTypeWB: top of memory 2 memory type write back. Read-write. Reset: 0. 1=The default
-// memory type of memory between 4GB and Core::X86::Msr::TOM2 is write back instead of the memory type
-// defined by Core::X86::Msr::MTRRdefType[MemType]. For this bit to have any effect,
-// Core::X86::Msr::MTRRdefType[MtrrDefTypeEn] must be 1. MTRRs and PAT can be used to override this
-// memory type.
-// 21 MtrrTom2En: MTRR top of memory 2 enable. Read-write. Reset: 0. 0=Core::X86::Msr::TOM2 is disabled. 1=
-// Core::X86::Msr::TOM2 is enabled.
-// 20 MtrrVarDramEn: MTRR variable DRAM enable. Read-write. Reset: 0. Init: BIOS,1.
-// 0=Core::X86::Msr::TOP_MEM and IORRs are disabled. 1=These registers are enabled.
-// 19 MtrrFixDramModEn: MTRR fixed RdDram and WrDram modification enable. Read-write. Reset: 0.
-// 0=Core::X86::Msr::MtrrFix_64K through Core::X86::Msr::MtrrFix_4K_7 [RdDram,WrDram] read values is
-// masked 00b; writing does not change the hidden value. 1=Core::X86::Msr::MtrrFix_64K through
-// Core::X86::Msr::MtrrFix_4K_7 [RdDram,WrDram] access type is Read-write. Not shared between threads.
-// Controls access to Core::X86::Msr::MtrrFix_64K through Core::X86::Msr::MtrrFix_4K_7 [RdDram ,WrDram].
-// This bit should be set to 1 during BIOS initialization of the fixed MTRRs, then cleared to 0 for operation.
-// 18 MtrrFixDramEn: MTRR fixed RdDram and WrDram attributes enable. Read-write. Reset: 0. Init: BIOS,1.
-// 1=Enables the RdDram and WrDram attributes in Core::X86::Msr::MtrrFix_64K through
-// Core::X86::Msr::MtrrFix_4K_7.
-// 17:0 Reserved.
-
-// MSR0000_02FF [MTRR Default Memory Type] (Core::X86::Msr::MTRRdefType)
-// See Core::X86::Msr::MtrrVarBase for general MTRR information.
-// _ccd[11:0]_lthree0_core[15:0]; MSR0000_02FF
-// Bits Description
-// 63:12 Reserved.
-// 11 MtrrDefTypeEn: variable and fixed MTRR enable. Read-write. Reset: 0. 0=Fixed and variable MTRRs are not
-// enabled. 1=Core::X86::Msr::MtrrVarBase, and Core::X86::Msr::MtrrFix_64K through
-// Core::X86::Msr::MtrrFix_4K_7 are enabled.
-// 10 MtrrDefTypeFixEn: fixed MTRR enable. Read-write. Reset: 0. 0=Core::X86::Msr::MtrrFix_64K through
-// Core::X86::Msr::MtrrFix_4K_7 are not enabled. 1=Core::X86::Msr::MtrrFix_64K through
-// Core::X86::Msr::MtrrFix_4K_7 are enabled. This field is ignored (and the fixed MTRRs are not enabled) if
-// Core::X86::Msr::MTRRdefType[MtrrDefTypeEn] == 0.
-// 9:8 Reserved.
-// 7:0 MemType: memory type. Read-write. Reset: 00h.
-// Description: If MtrrDefTypeEn == 1 then MemType specifies the memory type for memory space that is not
-// specified by either the fixed or variable range MTRRs. If MtrrDefTypeEn == 0 then the default memory type for
-// all of memory is UC.
-// Valid encodings are {00000b, Core
-
 pub fn enable_mtrr_fixed_dram_modification<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
         "Enable MTRR Fixed DRAM Modification",
@@ -310,21 +223,15 @@ pub fn enable_mtrr_fixed_dram_modification<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableMtrrFixedDramModification,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_0010,
-                        bit: 19,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_0010,
-                        bit: 19,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("SYS_CFG.MtrrFixDramModEn", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("SYS_CFG.MtrrFixDramModEn", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
@@ -336,33 +243,20 @@ pub fn enable_mtrr_fixed_dram_attributes<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::EnableMtrrFixedDramAttributes,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_0010,
-                        bit: 18,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_0010,
-                        bit: 18,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("SYS_CFG.MtrrFixDramEn", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("SYS_CFG.MtrrFixDramEn", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
+    .requires(&[TweakId::EnableMtrrFixedDramModification])
 }
 
-// MSR0000_0DA0 [Extended Supervisor State] (Core::X86::Msr::XSS)
-// _ccd[11:0]_lthree0_core[15:0]_thread[1:0]; MSR0000_0DA0
-// Bits Description
-// 63:13 Reserved.
-// 12 CET_S. Read-write. Reset: 0. System Control-flow Enforcement Technology.
-// 11 CET_U. Read-write. Reset: 0. User Control-flow Enforcement Technology.
-// 10:0 Reserved.
-
 pub fn disable_control_flow_enforcement<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
         "Disable Control-Flow Enforcement",
@@ -370,21 +264,15 @@ pub fn disable_control_flow_enforcement<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisbleControlFlowEnforcement,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0x0000_0DA0,
-                        bit: 12,
-                        state: false,
-                    }]),
-                    (TweakOption::Option("Disabled".to_string()), vec![MsrState {
-                        index: 0x0000_0DA0,
-                        bit: 12,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("XSS.CET_S", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("XSS.CET_S", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_3_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -396,25 +284,22 @@ pub fn disable_mca_status_write_enable<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableMcaStatusWriteEnable,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_0015,
-                        bit: 18,
-                        state: false,
-                    }]),
-                    (TweakOption::Option("Disabled".to_string()), vec![MsrState {
-                        index: 0xC001_0015,
-                        bit: 18,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("HWCR.McStatusWrEn", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("HWCR.McStatusWrEn", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
 
+// The community tuning table the tweaks below follow. Most of these bits are not in
+// the public PPR, so the tweaks write them by index and bit rather than by name.
+
 // Setting                             MSRs       Bit    Write
 
 // Streaming Stores Disabled                       |   0xC0011020  |   28  |   1
@@ -454,21 +339,15 @@ pub fn disable_streaming_stores<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableStreamingStores,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1020,
-                        bit: 28,
-                        state: false,
-                    }]),
-                    (TweakOption::Option("Disabled".to_string()), vec![MsrState {
-                        index: 0xC001_1020,
-                        bit: 28,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::bit(0xC001_1020, 28, false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::bit(0xC001_1020, 28, true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -480,21 +359,15 @@ pub fn disable_redirect_for_return<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableRedirectForReturn,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1029,
-                        bit: 14,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_1029,
-                        bit: 14,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::bit(0xC001_1029, 14, false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::bit(0xC001_1029, 14, true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -506,47 +379,25 @@ pub fn cpu_speculative_store_modes<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::SpeculativeStoreModes,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Option("Balanced".to_string()), vec![
-                        MsrState {
-                            index: 0xC001_10EC,
-                            bit: 0,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_10E5,
-                            bit: 26,
-                            state: false,
-                        },
+                        MsrField::bit(0xC001_10EC, 0, false),
+                        MsrField::bit(0xC001_10E5, 26, false),
                     ]),
                     (TweakOption::Option("Less Speculative".to_string()), vec![
-                        MsrState {
-                            index: 0xC001_10EC,
-                            bit: 0,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_10E5,
-                            bit: 26,
-                            state: true,
-                        },
+                        MsrField::bit(0xC001_10EC, 0, false),
+                        MsrField::bit(0xC001_10E5, 26, true),
                     ]),
                     (TweakOption::Option("More Speculative".to_string()), vec![
-                        MsrState {
-                            index: 0xC001_10EC,
-                            bit: 0,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_10E5,
-                            bit: 26,
-                            state: true,
-                        },
+                        MsrField::bit(0xC001_10EC, 0, true),
+                        MsrField::bit(0xC001_10E5, 26, true),
                     ]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -558,75 +409,29 @@ pub fn disable_avx512<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableAvx512,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false), vec![
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 16,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 17,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 21,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 28,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 30,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 31,
-                            state: false,
-                        },
+                        MsrField::bit(0xC001_1022, 16, false),
+                        MsrField::bit(0xC001_1022, 17, false),
+                        MsrField::bit(0xC001_1022, 21, false),
+                        MsrField::bit(0xC001_1022, 28, false),
+                        MsrField::bit(0xC001_1022, 30, false),
+                        MsrField::bit(0xC001_1022, 31, false),
                     ]),
                     (TweakOption::Enabled(true), vec![
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 16,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 17,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 21,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 28,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 30,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_1022,
-                            bit: 31,
-                            state: true,
-                        },
+                        MsrField::bit(0xC001_1022, 16, true),
+                        MsrField::bit(0xC001_1022, 17, true),
+                        MsrField::bit(0xC001_1022, 21, true),
+                        MsrField::bit(0xC001_1022, 28, true),
+                        MsrField::bit(0xC001_1022, 30, true),
+                        MsrField::bit(0xC001_1022, 31, true),
                     ]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -638,21 +443,15 @@ pub fn disable_fast_short_rep_movsb<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableFastShortRepMovsb,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_10DF,
-                        bit: 36,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_10DF,
-                        bit: 36,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::bit(0xC001_10DF, 36, false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::bit(0xC001_10DF, 36, true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -664,21 +463,15 @@ pub fn disable_enhanced_rep_movsb_stosb<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableEnhancedRepMovsbStosb,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1002,
-                        bit: 9,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC001_1002,
-                        bit: 9,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("Cpuid7Features.ERMS", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("Cpuid7Features.ERMS", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -690,21 +483,15 @@ pub fn disable_rep_mov_stos_streaming<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableRepMovStosStreaming,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1000,
-                        bit: 15,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC001_1000,
-                        bit: 15,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::bit(0xC001_1000, 15, false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::bit(0xC001_1000, 15, true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -716,35 +503,23 @@ pub fn disable_core_watchdog_timer<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableCoreWatchdogTimer,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![
-                        MsrState {
-                            index: 0xC001_0074,
-                            bit: 0,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC001_0074,
-                            bit: 3,
-                            state: false,
-                        },
+                    (TweakOption::Enabled(true), vec![
+                        MsrField::named_bit("CpuWdtCfg.CpuWdtEn", false),
+                        // The lowest bit of CpuWdtCountSel
+                        MsrField::bit(0xC001_0074, 3, false),
                     ]),
                     (TweakOption::Enabled(false), vec![
-                        MsrState {
-                            index: 0xC001_0074,
-                            bit: 0,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC001_0074,
-                            bit: 3,
-                            state: true,
-                        },
+                        MsrField::named_bit("CpuWdtCfg.CpuWdtEn", true),
+                        // The lowest bit of CpuWdtCountSel
+                        MsrField::bit(0xC001_0074, 3, true),
                     ]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
@@ -756,35 +531,21 @@ pub fn disable_platform_first_error_handling<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisablePlatformFirstErrorHandling,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![
-                        MsrState {
-                            index: 0xC000_0410,
-                            bit: 5,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC000_0410,
-                            bit: 12,
-                            state: false,
-                        },
+                    (TweakOption::Enabled(true), vec![
+                        MsrField::bit(0xC000_0410, 5, false),
+                        MsrField::bit(0xC000_0410, 12, false),
                     ]),
                     (TweakOption::Enabled(false), vec![
-                        MsrState {
-                            index: 0xC000_0410,
-                            bit: 5,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0410,
-                            bit: 12,
-                            state: true,
-                        },
+                        MsrField::bit(0xC000_0410, 5, true),
+                        MsrField::bit(0xC000_0410, 12, true),
                     ]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen(),
+            targets: Default::default(),
         },
     )
 }
@@ -1,38 +1,10 @@
 // src/tweaks/msr/prefetch.rs
 
-// MSRC000_0108 [Prefetch Control] (Core::X86::Msr::PrefetchControl)
-// Reset: 0000_0000_0000_03C0h.
-// _ccd[11:0]_lthree0_core[15:0]; MSRC000_0108
-// Bits Description
-// 63:10 Reserved.
-// 9:7 PrefetchAggressivenessProfile. Read-write. Reset: 7h. When MasterEnable is set, selects a prefetch
-// aggressiveness profile.
-// ValidValues:
-// Value Description
-// 0h Level 0, least aggressive prefetch profile.
-// 1h Level 1
-// 2h Level 2
-// 3h Level 3, most aggressive prefetch profile.
-// 6h-4h Reserved.
-// 7h Default used by hardware. Not software accessible.
-// 6 MasterEnable. Read-write. Reset: 1. Enable prefetch aggressiveness profiles.
-// 5 UpDown. Read-write. Reset: 0. Disable prefetcher that uses memory access history to determine whether to fetch
-// the next or previous line into L2 cache for all memory accesses.
-// 4 Reserved.
-// 3 L2Stream. Read-write. Reset: 0. Disable prefetcher that uses history of memory access patterns to fetch
-// additional sequential lines into L2 cache.
-// 2 L1Region. Read-write. Reset: 0. Disable prefetcher that uses memory access history to fetch additional lines into
-// L1 cache when the data access for a given instruction tends to be followed by a consistent pattern of other
-// accesses within a localized region.
-// 1 L1Stride. Read-write. Reset: 0. Disable stride prefetcher that uses memory access history of individual
-// instructions to fetch additional lines into L1 cache when each access is a constant distance from the previous.
-// 0 L1Stream. Read-write. Reset: 0. Disable stream prefetcher that uses history of memory access patterns to fetch
-// additional sequential lines into L1 cache.
-
-use indexmap::IndexMap;
-
-use super::method::{MSRTweak, MsrState};
-use crate::tweaks::{Tweak, TweakCategory, TweakId, TweakOption};
+use super::{
+    method::{MSRTweak, MsrField},
+    zen_4_and_later,
+};
+use crate::tweaks::{option_map, Tweak, TweakCategory, TweakId, TweakOption};
 
 pub fn prefetch_aggressiveness_profile<'a>() -> Tweak<'a> {
     Tweak::msr_tweak(
@@ -41,112 +13,50 @@ pub fn prefetch_aggressiveness_profile<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::AggressivePrefetchProfile,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Level 0".to_string()),
                     vec![
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 6,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 7,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 8,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 9,
-                            state: false,
-                        },
+                        MsrField::named_bit("PrefetchControl.MasterEnable", true),
+                        MsrField::named("PrefetchControl.PrefetchAggressivenessProfile", 0),
                     ],
                 ),
                 (
                     TweakOption::Option("Level 1".to_string()),
                     vec![
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 6,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 7,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 8,
-                            state: false,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 9,
-                            state: false,
-                        },
+                        MsrField::named_bit("PrefetchControl.MasterEnable", true),
+                        MsrField::named("PrefetchControl.PrefetchAggressivenessProfile", 1),
                     ],
                 ),
                 (
                     TweakOption::Option("Level 2".to_string()),
                     vec![
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 6,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 7,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 8,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 9,
-                            state: false,
-                        },
+                        MsrField::named_bit("PrefetchControl.MasterEnable", true),
+                        MsrField::named("PrefetchControl.PrefetchAggressivenessProfile", 2),
                     ],
                 ),
                 (
                     TweakOption::Option("Level 3".to_string()),
                     vec![
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 6,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 7,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 8,
-                            state: true,
-                        },
-                        MsrState {
-                            index: 0xC000_0108,
-                            bit: 9,
-                            state: true,
-                        },
+                        MsrField::named_bit("PrefetchControl.MasterEnable", true),
+                        MsrField::named("PrefetchControl.PrefetchAggressivenessProfile", 3),
                     ],
                 )
 
             ]),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
+    // The individual prefetcher toggles live in the same MSR
+    .overlaps(&[
+        TweakId::DisableUpDownPrefetcher,
+        TweakId::DisableL2StreamPrefetcher,
+        TweakId::DisableL1RegionPrefetcher,
+        TweakId::DisableL1StridePrefetcher,
+        TweakId::DisableL1StreamPrefetcher,
+    ])
 }
 
 pub fn disable_up_down_prefetcher<'a>() -> Tweak<'a> {
@@ -156,21 +66,15 @@ pub fn disable_up_down_prefetcher<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableUpDownPrefetcher,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 5,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 5,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("PrefetchControl.UpDown", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("PrefetchControl.UpDown", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -182,21 +86,15 @@ pub fn disable_l2_stream_prefetcher<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableL2StreamPrefetcher,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 3,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 3,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("PrefetchControl.L2Stream", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("PrefetchControl.L2Stream", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -208,21 +106,15 @@ pub fn disable_l1_region_prefetcher<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableL1RegionPrefetcher,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 2,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 2,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("PrefetchControl.L1Region", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("PrefetchControl.L1Region", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -234,21 +126,15 @@ pub fn disable_l1_stride_prefetcher<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableL1StridePrefetcher,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 1,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 1,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("PrefetchControl.L1Stride", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("PrefetchControl.L1Stride", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -260,21 +146,15 @@ pub fn disable_l1_stream_prefetcher<'a>() -> Tweak<'a> {
         TweakCategory::Cpu,
         MSRTweak {
             id: TweakId::DisableL1StreamPrefetcher,
-            options: IndexMap::from_iter(
+            options: option_map(
                 vec![
-                    (TweakOption::Enabled(false), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 0,
-                        state: false,
-                    }]),
-                    (TweakOption::Enabled(true), vec![MsrState {
-                        index: 0xC000_0108,
-                        bit: 0,
-                        state: true,
-                    }]),
+                    (TweakOption::Enabled(false), vec![MsrField::named_bit("PrefetchControl.L1Stream", false)]),
+                    (TweakOption::Enabled(true), vec![MsrField::named_bit("PrefetchControl.L1Stream", true)]),
                 ]
             ),
             readable: true,
+            supported_cpus: zen_4_and_later(),
+            targets: Default::default(),
         },
     )
 }
@@ -0,0 +1,477 @@
+// src/tweaks/msr/registers.rs
+
+//! The layout of the AMD and Intel MSRs the tweaks use, so that fields can be referred
+//! to by name, e.g. `PrefetchControl.L1Stride`, instead of by index and bit.
+//!
+//! AMD names, reset values and access types follow the Family 19h Zen 4 PPR where it
+//! documents the register. Registers the public PPR leaves out are named after their
+//! index, as the PPR does, and the bits tweaks write in them come from the community
+//! tuning table in `msr/mod.rs`. Those bits have no name, so they cannot be looked up,
+//! and the inspector names them after the tweak. Intel registers follow volume 4 of
+//! the Software Developer's Manual; the firmware sets most of their fields at boot.
+
+use anyhow::{Context, Result};
+
+/// How software may access a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    ReadWrite,
+    ReadOnly,
+    /// Can be set but not cleared again until reset.
+    ReadWrite1Only,
+}
+
+/// A named range of bits within a register.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RegisterField {
+    pub name: &'static str,
+    /// Lowest bit of the field.
+    pub low_bit: u32,
+    /// Highest bit of the field, inclusive.
+    pub high_bit: u32,
+    /// Value of the field after reset, when it is documented.
+    pub reset: Option<u64>,
+    pub access: Access,
+    pub description: &'static str,
+}
+
+impl RegisterField {
+    /// Returns the number of bits in the field.
+    pub fn width(&self) -> u32 {
+        self.high_bit - self.low_bit + 1
+    }
+
+    /// Returns the field's bits within the register.
+    pub fn mask(&self) -> u64 {
+        (u64::MAX >> (64 - self.width())) << self.low_bit
+    }
+
+    /// Extracts the field's value from a full register value.
+    pub fn read(&self, value: u64) -> u64 {
+        (value & self.mask()) >> self.low_bit
+    }
+}
+
+/// A model-specific register and its named fields. Bits outside every field and the
+/// undocumented mask are reserved.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MsrRegister {
+    pub name: &'static str,
+    pub index: u32,
+    pub description: &'static str,
+    pub fields: &'static [RegisterField],
+    /// Bits tweaks write that no public manual describes. They have no name and no
+    /// documented reset value.
+    pub undocumented: u64,
+}
+
+impl MsrRegister {
+    /// Returns the field called `name`.
+    pub fn field(&self, name: &str) -> Option<&'static RegisterField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the field holding `bit`, if it is not reserved.
+    pub fn field_at(&self, bit: u32) -> Option<&'static RegisterField> {
+        self.fields
+            .iter()
+            .find(|field| field.low_bit <= bit && bit <= field.high_bit)
+    }
+
+    /// Returns the value of the register after reset, if every field documents one and
+    /// no bit is undocumented. Reserved bits read as zero.
+    pub fn reset_value(&self) -> Option<u64> {
+        if self.undocumented != 0 {
+            return None;
+        }
+        self.fields.iter().try_fold(0, |value, field| {
+            Some(value | (field.reset? << field.low_bit))
+        })
+    }
+}
+
+/// Returns the register called `name`.
+pub fn register(name: &str) -> Option<&'static MsrRegister> {
+    REGISTERS.iter().find(|register| register.name == name)
+}
+
+/// Returns the register at MSR `index`.
+pub fn register_at(index: u32) -> Option<&'static MsrRegister> {
+    REGISTERS.iter().find(|register| register.index == index)
+}
+
+/// Looks up a field by its full name, `Register.Field`.
+///
+/// # Returns
+/// - `Ok((register, field))` if the database has the field.
+/// - `Err(anyhow::Error)` if the name is malformed or names no known register or field.
+pub fn lookup(path: &str) -> Result<(&'static MsrRegister, &'static RegisterField)> {
+    let (register_name, field_name) = path.split_once('.').with_context(|| {
+        format!(
+            "'{}' is not a field name, expected 'Register.Field', e.g. 'PrefetchControl.L1Stride'",
+            path
+        )
+    })?;
+    let register = register(register_name)
+        .with_context(|| format!("'{}' is not a known MSR", register_name))?;
+    let field = register
+        .field(field_name)
+        .with_context(|| format!("MSR '{}' has no field '{}'", register_name, field_name))?;
+    Ok((register, field))
+}
+
+/// A field whose reset value and access type are documented.
+const fn documented(
+    name: &'static str,
+    bits: (u32, u32),
+    reset: u64,
+    access: Access,
+    description: &'static str,
+) -> RegisterField {
+    RegisterField {
+        name,
+        low_bit: bits.1,
+        high_bit: bits.0,
+        reset: Some(reset),
+        access,
+        description,
+    }
+}
+
+/// A read-write field the manual names without a reset value, usually because the
+/// firmware sets it at boot.
+const fn without_reset(
+    name: &'static str,
+    bits: (u32, u32),
+    description: &'static str,
+) -> RegisterField {
+    RegisterField {
+        name,
+        low_bit: bits.1,
+        high_bit: bits.0,
+        reset: None,
+        access: Access::ReadWrite,
+        description,
+    }
+}
+
+use Access::{ReadOnly, ReadWrite, ReadWrite1Only};
+
+/// Every register in the database. Field bits are written `(high, low)`, as in the PPR.
+#[rustfmt::skip]
+pub static REGISTERS: &[MsrRegister] = &[
+    MsrRegister {
+        name: "EFER",
+        index: 0xC000_0080,
+        description: "Extended Feature Enable",
+        fields: &[
+            documented("SYSCALL", (0, 0), 0, ReadWrite, "System call extension enable. 1=SYSCALL and SYSRET are enabled."),
+            documented("LME", (8, 8), 0, ReadWrite, "Long mode enable."),
+            documented("LMA", (10, 10), 0, ReadOnly, "Long mode active. Writes must preserve it."),
+            documented("NXE", (11, 11), 0, ReadWrite, "No-execute page enable."),
+            documented("SVME", (12, 12), 0, ReadWrite, "Secure virtual machine enable. Read-only when VM_CR[SvmeDisable] is set."),
+            documented("LMSLE", (13, 13), 0, ReadOnly, "Long mode segment limit enable. Error on writing 1."),
+            documented("FFXSE", (14, 14), 0, ReadWrite, "Fast FXSAVE/FRSTOR enable. Set once by a 64-bit operating system."),
+            documented("TCE", (15, 15), 0, ReadWrite, "Translation cache extension enable. 1=INVLPG only invalidates the PDC entries of its linear address."),
+            documented("IntWbinvdEn", (18, 18), 0, ReadWrite, "Interruptible WBINVD and WBNOINVD enable."),
+            documented("UAIE", (20, 20), 0, ReadWrite, "Upper address ignore enable. Suppresses canonical faults for most data accesses."),
+            documented("AutomaticIBRSEn", (21, 21), 0, ReadWrite, "Automatic IBRS enable. 1=IBRS protection is enabled at CPL 0."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "PrefetchControl",
+        index: 0xC000_0108,
+        description: "Prefetch Control",
+        fields: &[
+            documented("L1Stream", (0, 0), 0, ReadWrite, "Disable the stream prefetcher that fetches additional sequential lines into L1."),
+            documented("L1Stride", (1, 1), 0, ReadWrite, "Disable the stride prefetcher that fetches lines into L1 when each access of an instruction is a constant distance from the previous."),
+            documented("L1Region", (2, 2), 0, ReadWrite, "Disable the prefetcher that fetches lines into L1 when accesses of an instruction follow a consistent pattern within a region."),
+            documented("L2Stream", (3, 3), 0, ReadWrite, "Disable the stream prefetcher that fetches additional sequential lines into L2."),
+            documented("UpDown", (5, 5), 0, ReadWrite, "Disable the prefetcher that fetches the next or previous line into L2."),
+            documented("MasterEnable", (6, 6), 1, ReadWrite, "Enable prefetch aggressiveness profiles."),
+            documented("PrefetchAggressivenessProfile", (9, 7), 7, ReadWrite, "Prefetch aggressiveness, from 0 (least) to 3 (most), when MasterEnable is set. 7 is the hardware default and cannot be written."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "SYS_CFG",
+        index: 0xC001_0010,
+        description: "System Configuration. Writes are ignored while SecureNestedPagingEn is set.",
+        fields: &[
+            documented("MtrrFixDramEn", (18, 18), 0, ReadWrite, "Enables the RdDram and WrDram attributes of the fixed MTRRs."),
+            documented("MtrrFixDramModEn", (19, 19), 0, ReadWrite, "Makes the RdDram and WrDram attributes of the fixed MTRRs writable. Cleared after the BIOS sets up the MTRRs."),
+            documented("MtrrVarDramEn", (20, 20), 0, ReadWrite, "Enables TOP_MEM and the IORRs."),
+            documented("MtrrTom2En", (21, 21), 0, ReadWrite, "Enables TOM2."),
+            documented("Tom2ForceMemTypeWB", (22, 22), 0, ReadWrite, "Makes memory between 4GB and TOM2 write back by default."),
+            documented("SMEE", (23, 23), 0, ReadWrite1Only, "Secure memory encryption enable."),
+            documented("SecureNestedPagingEn", (24, 24), 0, ReadWrite1Only, "Secure Nested Paging enable."),
+            documented("VmplEn", (25, 25), 0, ReadWrite, "VM permission levels enable. Read-only while SecureNestedPagingEn is set."),
+            documented("HMKEE", (26, 26), 0, ReadWrite1Only, "Host multi-key encryption enable."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "HWCR",
+        index: 0xC001_0015,
+        description: "Hardware Configuration",
+        fields: &[
+            documented("TlbCacheDis", (3, 3), 0, ReadWrite, "Disable the performance improvement that assumes page table entries are in cacheable write-back DRAM."),
+            documented("McStatusWrEn", (18, 18), 0, ReadWrite, "Machine check status write enable. 1=MCA status registers are writable."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "CpuWdtCfg",
+        index: 0xC001_0074,
+        description: "CPU Watchdog Timer Configuration. Named as in the Family 15h BKDG; the Zen PPRs leave it out.",
+        fields: &[
+            without_reset("CpuWdtEn", (0, 0), "Enables the core watchdog timer."),
+            without_reset("CpuWdtTimeBase", (2, 1), "Time base of the watchdog count."),
+            without_reset("CpuWdtCountSel", (6, 3), "Number of time base periods before the watchdog fires."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "McaIntrCfg",
+        index: 0xC000_0410,
+        description: "MCA Interrupt Configuration",
+        fields: &[],
+        // Cleared by "Platform First Error Handling" in the community tuning table
+        undocumented: 1 << 5 | 1 << 12,
+    },
+    MsrRegister {
+        name: "L3QosCfg1",
+        index: 0x0000_0C81,
+        description: "L3 QoS Configuration",
+        fields: &[documented(
+            "CDP",
+            (0, 0),
+            0,
+            ReadWrite,
+            "Code and Data Prioritization enable, splitting L3 allocation between instructions and data.",
+        )],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "XSS",
+        index: 0x0000_0DA0,
+        description: "Extended Supervisor State",
+        fields: &[
+            documented("CET_U", (11, 11), 0, ReadWrite, "User control-flow enforcement."),
+            documented("CET_S", (12, 12), 0, ReadWrite, "Supervisor control-flow enforcement."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "MSRC001_1000",
+        index: 0xC001_1000,
+        description: "Not in the public PPR",
+        fields: &[],
+        // Set by "REP-MOV/STOS Streaming (Disable)" in the community tuning table
+        undocumented: 1 << 15,
+    },
+    MsrRegister {
+        name: "Cpuid7Features",
+        index: 0xC001_1002,
+        description: "Features reported by CPUID Fn0000_0007 EBX",
+        fields: &[without_reset(
+            "ERMS",
+            (9, 9),
+            "Enhanced REP MOVSB/STOSB. Cleared to hide it from software.",
+        )],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "LsCfg",
+        index: 0xC001_1020,
+        description: "Load-Store Configuration. The public PPR documents none of its bits.",
+        fields: &[],
+        // Set by "Streaming Stores Disabled" in the community tuning table
+        undocumented: 1 << 28,
+    },
+    MsrRegister {
+        name: "IcCfg",
+        index: 0xC001_1021,
+        description: "Instruction Cache Configuration. The public PPR documents none of its bits.",
+        fields: &[],
+        // Set by "Disable OpCache" in the community tuning table
+        undocumented: 1 << 5,
+    },
+    MsrRegister {
+        name: "DcCfg",
+        index: 0xC001_1022,
+        description: "Data Cache Configuration. The public PPR documents none of its bits.",
+        fields: &[],
+        // Set together by "Disable AVX512" in the community tuning table
+        undocumented: 1 << 16 | 1 << 17 | 1 << 21 | 1 << 28 | 1 << 30 | 1 << 31,
+    },
+    MsrRegister {
+        name: "DeCfg",
+        index: 0xC001_1029,
+        description: "Decode Configuration. The public PPR documents none of its bits.",
+        fields: &[],
+        // Set by "RedirectForReturnDis" in the community tuning table
+        undocumented: 1 << 14,
+    },
+    MsrRegister {
+        name: "MSRC001_10DF",
+        index: 0xC001_10DF,
+        description: "Not in the public PPR",
+        fields: &[],
+        // Cleared by "Fast Short REP MOVSB (Disable)" in the community tuning table
+        undocumented: 1 << 36,
+    },
+    MsrRegister {
+        name: "MSRC001_10E5",
+        index: 0xC001_10E5,
+        description: "Not in the public PPR",
+        fields: &[],
+        // Set by the less and more speculative "CPU Speculative Store Modes" in the
+        // community tuning table
+        undocumented: 1 << 26,
+    },
+    MsrRegister {
+        name: "MSRC001_10EC",
+        index: 0xC001_10EC,
+        description: "Not in the public PPR",
+        fields: &[],
+        // Set by the more speculative "CPU Speculative Store Modes" in the community
+        // tuning table
+        undocumented: 1 << 0,
+    },
+    MsrRegister {
+        name: "IA32_TSX_CTRL",
+        index: 0x0000_0122,
+        description: "TSX Control. Present when IA32_ARCH_CAPABILITIES[TSX_CTRL] is set.",
+        fields: &[
+            documented("RtmDisable", (0, 0), 0, ReadWrite, "1=XBEGIN always aborts, so RTM transactions never run."),
+            documented("TsxCpuidClear", (1, 1), 0, ReadWrite, "1=CPUID no longer reports RTM and HLE."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "IA32_MISC_ENABLE",
+        index: 0x0000_01A0,
+        description: "Miscellaneous Enables. SDM volume 4, table 2-2.",
+        fields: &[
+            without_reset("EistEnable", (16, 16), "Enhanced Intel SpeedStep Technology Enable. Read-only when the firmware locks it."),
+            without_reset("TurboDisable", (38, 38), "Turbo Mode Disable. 1=Turbo Boost is disabled and CPUID stops reporting it."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "MISC_FEATURE_CONTROL",
+        index: 0x0000_01A4,
+        description: "Hardware prefetcher control of Intel Core and Xeon processors",
+        fields: &[
+            documented("L2HwPrefetcherDis", (0, 0), 0, ReadWrite, "Disable the L2 hardware prefetcher, which fetches lines into L2 from memory access patterns."),
+            documented("L2AdjacentLinePrefetcherDis", (1, 1), 0, ReadWrite, "Disable the L2 adjacent cache line prefetcher, which fetches the other line of each 128-byte pair."),
+            documented("DcuStreamerPrefetcherDis", (2, 2), 0, ReadWrite, "Disable the DCU streamer prefetcher, which fetches the next line into L1."),
+            documented("DcuIpPrefetcherDis", (3, 3), 0, ReadWrite, "Disable the DCU IP prefetcher, which fetches lines into L1 from the stride of each load instruction."),
+        ],
+        undocumented: 0,
+    },
+    MsrRegister {
+        name: "POWER_CTL",
+        index: 0x0000_01FC,
+        description: "Power Control, MSR_POWER_CTL in the SDM volume 4 tables of each Core generation",
+        fields: &[without_reset(
+            "C1eEnable",
+            (1, 1),
+            "C1E Enable. 1=Cores entering C1 are promoted to C1E, the lowest voltage and frequency.",
+        )],
+        undocumented: 0,
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::tweaks::{msr::all_msr_tweaks, relations::TweakSetting};
+
+    #[test]
+    fn test_registers_are_well_formed() {
+        let mut names = BTreeSet::new();
+        let mut indices = BTreeSet::new();
+        for register in REGISTERS {
+            assert!(
+                names.insert(register.name),
+                "{} is listed twice",
+                register.name
+            );
+            assert!(
+                indices.insert(register.index),
+                "{} shares its index",
+                register.name
+            );
+
+            let mut taken = 0u64;
+            let mut field_names = BTreeSet::new();
+            for field in register.fields {
+                let path = format!("{}.{}", register.name, field.name);
+                assert!(field_names.insert(field.name), "{} is listed twice", path);
+                assert!(
+                    field.low_bit <= field.high_bit && field.high_bit < 64,
+                    "{}",
+                    path
+                );
+                assert_eq!(taken & field.mask(), 0, "{} overlaps another field", path);
+                taken |= field.mask();
+                if let Some(reset) = field.reset {
+                    assert_eq!(reset & !(field.mask() >> field.low_bit), 0, "{}", path);
+                }
+            }
+            assert_eq!(
+                taken & register.undocumented,
+                0,
+                "{} has undocumented bits within a field",
+                register.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_name() {
+        let (prefetch, field) = lookup("PrefetchControl.L1Stride").unwrap();
+        assert_eq!(prefetch.index, 0xC000_0108);
+        assert_eq!((field.low_bit, field.high_bit), (1, 1));
+        assert_eq!(prefetch.reset_value(), Some(0x3C0));
+        assert_eq!(
+            prefetch.field_at(8).unwrap().name,
+            "PrefetchAggressivenessProfile"
+        );
+        assert_eq!(register_at(0xC000_0080).unwrap().name, "EFER");
+        assert_eq!(register("CpuWdtCfg").unwrap().reset_value(), None);
+
+        let dc_cfg = register_at(0xC001_1022).unwrap();
+        assert_eq!(dc_cfg.field_at(16), None);
+        assert_eq!(dc_cfg.reset_value(), None);
+
+        assert!(lookup("PrefetchControl").is_err());
+        assert!(lookup("PrefetchControl.L3Stride").is_err());
+        assert!(lookup("NoSuchMsr.L1Stride").is_err());
+    }
+
+    #[test]
+    fn test_every_msr_tweak_bit_is_listed() {
+        for (id, tweak) in all_msr_tweaks() {
+            for setting in tweak.method.settings() {
+                let TweakSetting::MsrBit { index, bit } = setting else {
+                    continue;
+                };
+                let register = register_at(index)
+                    .unwrap_or_else(|| panic!("{:?}: MSR 0x{:X} is not listed", id, index));
+                assert!(
+                    register.field_at(bit).is_some() || register.undocumented >> bit & 1 == 1,
+                    "{:?}: bit {} of {} is reserved",
+                    id,
+                    bit,
+                    register.name
+                );
+            }
+        }
+    }
+}
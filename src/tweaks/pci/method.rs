@@ -0,0 +1,572 @@
+// src/tweaks/pci/method.rs
+
+use std::ops::RangeInclusive;
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use tracing::{debug, error};
+
+use crate::{
+    backend::{self, PciAccess, PciRegister},
+    backup::{PciValueSnapshot, TweakSnapshot},
+    journal::{record_step, JournalStep},
+    tweaks::{
+        default_option_of,
+        plan::{MsrFieldChange, PlanStep, TweakPlan},
+        relations::TweakSetting,
+        SettingState, TweakId, TweakMethod, TweakOption,
+    },
+    utils::cpu::{unsupported_reason, CpuIdentity, CpuRange},
+};
+
+/// A field of one or more contiguous bits within a 32-bit PCI configuration space or
+/// SMN register, and the value to write to it.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct PciField {
+    pub register: PciRegister,
+    /// Lowest bit of the field.
+    pub low_bit: u32,
+    /// Highest bit of the field, inclusive.
+    pub high_bit: u32,
+    /// Value of the field, with its lowest bit at `low_bit`.
+    pub value: u32,
+}
+
+impl PciField {
+    /// Creates a modification that writes `value` to the bits in `bits`, e.g. `7..=9`.
+    pub fn new(register: PciRegister, bits: RangeInclusive<u32>, value: u32) -> Self {
+        Self {
+            register,
+            low_bit: *bits.start(),
+            high_bit: *bits.end(),
+            value,
+        }
+    }
+
+    /// Creates a modification that sets a single bit to 1 (`true`) or clears it to 0 (`false`).
+    pub fn bit(register: PciRegister, bit: u32, state: bool) -> Self {
+        Self::new(register, bit..=bit, state as u32)
+    }
+
+    /// Returns the number of bits in the field.
+    pub fn width(&self) -> u32 {
+        self.high_bit.saturating_sub(self.low_bit) + 1
+    }
+
+    /// Returns the field's bits within the register. Bits beyond bit 31 are left out.
+    pub fn mask(&self) -> u32 {
+        let ones = if self.width() >= 32 {
+            u32::MAX
+        } else {
+            (1u32 << self.width()) - 1
+        };
+        ones.checked_shl(self.low_bit).unwrap_or(0)
+    }
+
+    /// Extracts the field's value from a full register value.
+    pub fn read(&self, register_value: u32) -> u32 {
+        (register_value & self.mask()) >> self.low_bit.min(31)
+    }
+
+    /// Returns `register_value` with the field set to the modification's value and
+    /// every other bit unchanged.
+    pub fn write(&self, register_value: u32) -> u32 {
+        let shifted = self.value.checked_shl(self.low_bit).unwrap_or(0);
+        (register_value & !self.mask()) | (shifted & self.mask())
+    }
+
+    /// Returns whether the field holds the modification's value in `register_value`.
+    pub fn matches(&self, register_value: u32) -> bool {
+        self.read(register_value) == self.value
+    }
+
+    /// Returns whether the field and `other` share any bit of the same register.
+    pub fn overlaps(&self, other: &PciField) -> bool {
+        self.register == other.register
+            && self.low_bit <= other.high_bit
+            && other.low_bit <= self.high_bit
+    }
+
+    /// Describes the field's bits, e.g. `bit 5` or `bits 9:7`.
+    pub fn bits_label(&self) -> String {
+        if self.low_bit == self.high_bit {
+            format!("bit {}", self.low_bit)
+        } else {
+            format!("bits {}:{}", self.high_bit, self.low_bit)
+        }
+    }
+}
+
+/// Defines a set of PCI configuration space and SMN register modifications, which in
+/// combination make up a single tweak. Unlike MSRs, these registers are shared by the
+/// whole processor, so they are written once rather than per core.
+#[derive(Debug)]
+pub struct PciTweak {
+    /// Unique ID for the tweak.
+    pub id: TweakId,
+    /// Mapping from `TweakOption` to a list of register modifications.
+    pub options: IndexMap<TweakOption, Vec<PciField>>,
+    /// The processors whose registers the options are written for. Empty means any processor.
+    pub supported_cpus: Vec<CpuRange>,
+}
+
+impl PciTweak {
+    /// Fails with the reason the processor cannot run the tweak, since other processors
+    /// have other devices, or nothing, at the same addresses.
+    fn ensure_supported(&self) -> Result<()> {
+        match self.unsupported() {
+            Some(reason) => anyhow::bail!("{:?} -> {}", self.id, reason),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns, for each register the tweak touches, a mask of every bit any option modifies.
+    fn touched_bits(&self) -> IndexMap<PciRegister, u32> {
+        let mut masks = IndexMap::new();
+        for field in self.options.values().flatten() {
+            *masks.entry(field.register).or_insert(0) |= field.mask();
+        }
+        masks
+    }
+
+    /// Reads every register the tweak touches.
+    fn read_values(&self, pci: &PciAccess) -> Result<IndexMap<PciRegister, u32>> {
+        self.touched_bits()
+            .keys()
+            .map(|register| {
+                let value = pci
+                    .read(*register)
+                    .with_context(|| format!("{:?} -> Failed to read {}", self.id, register))?;
+                Ok((*register, value))
+            })
+            .collect()
+    }
+
+    /// Writes a register, recording the change in the journal before it is made, and
+    /// checks that it reads back.
+    fn write_register(
+        &self,
+        pci: &PciAccess,
+        register: PciRegister,
+        current_value: u32,
+        new_value: u32,
+    ) -> Result<()> {
+        let step = JournalStep::Pci {
+            register,
+            before: current_value,
+            after: new_value,
+        };
+        record_step(step, || {
+            pci.write(register, new_value)
+                .with_context(|| format!("{:?} -> Failed to write {}", self.id, register))
+        })?;
+
+        let updated_value = pci
+            .read(register)
+            .with_context(|| format!("{:?} -> Failed to read {} after write", self.id, register))?;
+        if updated_value != new_value {
+            anyhow::bail!(
+                "{} reads 0x{:08X} after writing 0x{:08X}",
+                register,
+                updated_value,
+                new_value
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes `modifications`, all or nothing. If any write fails or does not read back,
+    /// every register written so far is put back to its original value. `action` names
+    /// the operation in messages.
+    fn write_fields(&self, modifications: &[PciField], action: &str) -> Result<()> {
+        self.ensure_supported()?;
+
+        // Fields of the same register are written together
+        let mut fields_by_register: IndexMap<PciRegister, Vec<&PciField>> = IndexMap::new();
+        for field in modifications {
+            fields_by_register
+                .entry(field.register)
+                .or_default()
+                .push(field);
+        }
+
+        // Lock the PCI backend so that SMN accesses do not interleave
+        let pci = backend::pci()?;
+
+        let mut written: Vec<(PciRegister, u32)> = Vec::new();
+        for (register, fields) in &fields_by_register {
+            let result = pci
+                .read(*register)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, register))
+                .and_then(|current_value| {
+                    // Set the fields, leaving every other bit unchanged
+                    let new_value = fields
+                        .iter()
+                        .fold(current_value, |value, field| field.write(value));
+                    written.push((*register, current_value));
+                    self.write_register(&pci, *register, current_value, new_value)
+                });
+            let Err(cause) = result else {
+                continue;
+            };
+            error!(
+                "{:?} -> Failed to {}: {:#}. Restoring the original values.",
+                self.id, action, cause
+            );
+
+            for (register, original) in written.iter().rev() {
+                let restored = pci.read(*register).and_then(|current| {
+                    if current == *original {
+                        Ok(())
+                    } else {
+                        self.write_register(&pci, *register, current, *original)
+                    }
+                });
+                if let Err(e) = restored {
+                    error!("{:?} -> Failed to restore {}: {:?}", self.id, register, e);
+                    return Err(cause.context(format!(
+                        "{:?} -> Failed to {}, and {} could not be restored",
+                        self.id, action, register
+                    )));
+                }
+            }
+            return Err(cause.context(format!(
+                "{:?} -> Failed to {}; every register was restored",
+                self.id, action
+            )));
+        }
+
+        tracing::info!(
+            "{:?} -> Successfully {} {}.",
+            self.id,
+            if action == "revert" {
+                "reverted"
+            } else {
+                "applied"
+            },
+            fields_by_register
+                .keys()
+                .map(|register| register.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Ok(())
+    }
+}
+
+impl TweakMethod for PciTweak {
+    /// Reads every register the tweak touches and returns the option they match.
+    ///
+    /// # Returns
+    /// - `Ok(TweakOption)` indicating the current state, or `TweakOption::Mixed` with
+    ///   every field if no option matches.
+    /// - `Err(anyhow::Error)` if a register cannot be read.
+    fn initial_state(&self) -> Result<TweakOption> {
+        debug!(
+            "{:?} -> Determining the initial state of the PCI tweak.",
+            self.id
+        );
+
+        self.ensure_supported()?;
+        let values = self.read_values(&backend::pci()?)?;
+
+        for (option, modifications) in &self.options {
+            if modifications
+                .iter()
+                .all(|field| field.matches(values[&field.register]))
+            {
+                debug!("{:?} -> Current state matches {:?}.", self.id, option);
+                return Ok(option.clone());
+            }
+        }
+
+        debug!("{:?} -> Current state does not match any option.", self.id);
+        // Every distinct field any option writes, in the order the options list them
+        let mut fields: Vec<&PciField> = Vec::new();
+        for field in self.options.values().flatten() {
+            if !fields.iter().any(|seen| {
+                (seen.register, seen.low_bit, seen.high_bit)
+                    == (field.register, field.low_bit, field.high_bit)
+            }) {
+                fields.push(field);
+            }
+        }
+        let states = fields
+            .into_iter()
+            .map(|field| {
+                let value = field.read(values[&field.register]);
+                SettingState {
+                    setting: format!("{} {}", field.register, field.bits_label()),
+                    core: None,
+                    value: Some(value.to_string()),
+                    matches: self
+                        .options
+                        .iter()
+                        .filter(|(_, modifications)| {
+                            modifications.iter().any(|modification| {
+                                (
+                                    modification.register,
+                                    modification.low_bit,
+                                    modification.high_bit,
+                                    modification.value,
+                                ) == (field.register, field.low_bit, field.high_bit, value)
+                            })
+                        })
+                        .map(|(option, _)| option.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(TweakOption::Mixed(states))
+    }
+
+    /// Writes the fields of the selected option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every register was written and verified.
+    /// - `Err(anyhow::Error)` if any access fails, after putting back the registers
+    ///   already written.
+    fn apply(&self, option: TweakOption) -> Result<()> {
+        debug!(
+            "Applying PCI tweak '{:?}' with option: {:?}.",
+            self.id, option
+        );
+
+        let modifications = self.options.get(&option).with_context(|| {
+            format!(
+                "{:?} -> No PCI modifications found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.write_fields(modifications, "apply")
+    }
+
+    /// Writes the fields of the `Enabled(false)` option.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every register was written and verified.
+    /// - `Err(anyhow::Error)` if any access fails, after putting back the registers
+    ///   already written.
+    fn revert(&self) -> Result<()> {
+        debug!("{:?} -> Reverting PCI tweak to Default.", self.id);
+
+        let modifications = self
+            .options
+            .get(&TweakOption::Enabled(false))
+            .with_context(|| {
+                format!(
+                    "{:?} -> No PCI modifications found for Default option.",
+                    self.id
+                )
+            })?;
+        self.write_fields(modifications, "revert")
+    }
+
+    /// Captures the full value of every register the tweak touches.
+    ///
+    /// # Returns
+    /// - `Ok(Some(TweakSnapshot::Pci))` with one entry per register.
+    /// - `Err(anyhow::Error)` if any read fails.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        self.ensure_supported()?;
+        let values = self.read_values(&backend::pci()?)?;
+        debug!("{:?} -> Captured {} PCI registers.", self.id, values.len());
+        Ok(Some(TweakSnapshot::Pci(
+            values
+                .into_iter()
+                .map(|(register, value)| PciValueSnapshot { register, value })
+                .collect(),
+        )))
+    }
+
+    /// Restores the bits this tweak controls to the values captured by `snapshot`,
+    /// preserving every other bit of the registers.
+    ///
+    /// # Returns
+    /// - `Ok(())` if every value was restored and verified.
+    /// - `Err(anyhow::Error)` if the snapshot is not a PCI snapshot or any access fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Pci(values) = snapshot else {
+            anyhow::bail!("{:?} -> Backup is not a PCI snapshot", self.id);
+        };
+
+        self.ensure_supported()?;
+        let masks = self.touched_bits();
+        let pci = backend::pci()?;
+
+        for saved in values {
+            let Some(mask) = masks.get(&saved.register) else {
+                continue;
+            };
+            let current_value = pci
+                .read(saved.register)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, saved.register))?;
+            let new_value = (current_value & !mask) | (saved.value & mask);
+            self.write_register(&pci, saved.register, current_value, new_value)?;
+        }
+
+        debug!("{:?} -> Restored original PCI register values.", self.id);
+        Ok(())
+    }
+
+    /// Lists each register the option modifies, with the bits it sets and the value
+    /// before and after.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with one step per register.
+    /// - `Err(anyhow::Error)` if the option does not exist or a register cannot be read.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let modifications = self.options.get(option).with_context(|| {
+            format!(
+                "{:?} -> No PCI modifications found for option: {:?}",
+                self.id, option
+            )
+        })?;
+        self.ensure_supported()?;
+
+        let mut by_register: IndexMap<PciRegister, Vec<&PciField>> = IndexMap::new();
+        for field in modifications {
+            by_register.entry(field.register).or_default().push(field);
+        }
+
+        let pci = backend::pci()?;
+        let mut steps = Vec::new();
+        for (register, fields) in by_register {
+            let current = pci
+                .read(register)
+                .with_context(|| format!("{:?} -> Failed to read {}", self.id, register))?;
+            let new = fields
+                .iter()
+                .fold(current, |value, field| field.write(value));
+            steps.push(PlanStep::Pci {
+                register,
+                fields: fields
+                    .iter()
+                    .map(|field| MsrFieldChange {
+                        low_bit: field.low_bit,
+                        high_bit: field.high_bit,
+                        value: field.value as u64,
+                    })
+                    .collect(),
+                current: Some(current),
+                new: Some(new),
+            });
+        }
+        Ok(TweakPlan { steps })
+    }
+
+    fn journaled(&self) -> bool {
+        true
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+
+    /// Checks that every configuration space offset is an aligned DWORD, that every
+    /// field lies within its register and its value fits, and that no option writes the
+    /// same bit twice.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for range in &self.supported_cpus {
+            if range.families.is_empty() || range.models.is_empty() {
+                problems.push(format!(
+                    "supported processors {:?} match no processor",
+                    range
+                ));
+            }
+        }
+        for (option, fields) in &self.options {
+            for (i, field) in fields.iter().enumerate() {
+                if let PciRegister::Config { offset, .. } = field.register {
+                    if !offset.is_multiple_of(4) || offset >= 0x1000 {
+                        problems.push(format!(
+                            "option {:?} writes {}, which is not a DWORD of configuration space",
+                            option, field.register
+                        ));
+                    }
+                }
+                if field.low_bit > field.high_bit {
+                    problems.push(format!(
+                        "option {:?} writes bits {}:{} of {}, whose high bit is below its low bit",
+                        option, field.high_bit, field.low_bit, field.register
+                    ));
+                    continue;
+                }
+                if field.high_bit >= 32 {
+                    problems.push(format!(
+                        "option {:?} writes {} of {}, but the register only has 32 bits",
+                        option,
+                        field.bits_label(),
+                        field.register
+                    ));
+                    continue;
+                }
+                if field.width() < 32 && field.value >> field.width() != 0 {
+                    problems.push(format!(
+                        "option {:?} writes {} to {} of {}, which does not fit in {} bits",
+                        option,
+                        field.value,
+                        field.bits_label(),
+                        field.register,
+                        field.width()
+                    ));
+                }
+                if fields[..i].iter().any(|other| other.overlaps(field)) {
+                    problems.push(format!(
+                        "option {:?} sets {} of {} more than once",
+                        option,
+                        field.bits_label(),
+                        field.register
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns every register bit any option writes.
+    fn settings(&self) -> Vec<TweakSetting> {
+        self.touched_bits()
+            .into_iter()
+            .flat_map(|(register, mask)| {
+                (0..32)
+                    .filter(move |bit| mask & (1u32 << bit) != 0)
+                    .map(move |bit| TweakSetting::PciBit { register, bit })
+            })
+            .collect()
+    }
+
+    /// Checks the processor against the ones the tweak supports, then that a driver can
+    /// reach the registers.
+    fn unsupported(&self) -> Option<String> {
+        unsupported_reason(&self.supported_cpus, &CpuIdentity::detect())
+            .or_else(backend::hardware_unavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::PciAddress;
+
+    #[test]
+    fn test_field_write_only_changes_its_bits() {
+        let register = PciRegister::Config {
+            address: PciAddress::new(0, 0x18, 3),
+            offset: 0xE8,
+        };
+        let field = PciField::new(register, 28..=31, 0xA);
+        assert_eq!(field.mask(), 0xF000_0000);
+        assert_eq!(field.write(0x1234_5678), 0xA234_5678);
+        assert_eq!(field.read(0xA234_5678), 0xA);
+        assert!(!field.matches(0x1234_5678));
+        assert_eq!(
+            PciField::bit(register, 31, false).write(u32::MAX),
+            0x7FFF_FFFF
+        );
+    }
+}
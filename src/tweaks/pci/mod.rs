@@ -0,0 +1,6 @@
+// src/tweaks/pci/mod.rs
+
+//! Tweaks that write PCI configuration space, either directly or through the AMD
+//! System Management Network (SMN).
+
+pub mod method;
@@ -0,0 +1,345 @@
+// src/tweaks/plan.rs
+
+//! Dry-run descriptions of what changing a tweak will do.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{backend::PciRegister, utils::registry::RegistryKeyValue};
+
+/// Everything a change to a tweak will do, worked out without making it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TweakPlan {
+    pub steps: Vec<PlanStep>,
+}
+
+impl TweakPlan {
+    /// Creates a plan made of a single step described in words.
+    pub fn described(description: impl Into<String>) -> Self {
+        Self {
+            steps: vec![PlanStep::Other {
+                description: description.into(),
+            }],
+        }
+    }
+
+    /// Returns whether carrying out the plan would leave the system as it is.
+    pub fn is_noop(&self) -> bool {
+        self.steps.iter().all(PlanStep::is_noop)
+    }
+}
+
+impl fmt::Display for TweakPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.steps.is_empty() {
+            return write!(f, "Nothing to do");
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single operation a change will carry out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlanStep {
+    /// A registry value is written or, if `new` is `None`, deleted.
+    RegistryValue {
+        path: String,
+        key: String,
+        /// `None` if the value does not exist.
+        current: Option<RegistryKeyValue>,
+        new: Option<RegistryKeyValue>,
+    },
+    /// An MSR is read, modified and written back on one core.
+    Msr {
+        core: usize,
+        index: u32,
+        fields: Vec<MsrFieldChange>,
+        /// `None` if the MSR cannot be read ahead of time.
+        current: Option<u64>,
+        new: Option<u64>,
+    },
+    /// A PCI configuration space or SMN register is read, modified and written back.
+    Pci {
+        register: PciRegister,
+        fields: Vec<MsrFieldChange>,
+        /// `None` if the register cannot be read ahead of time.
+        current: Option<u32>,
+        new: Option<u32>,
+    },
+    /// A Linux sysfs or procfs file is written.
+    Sysfs {
+        path: String,
+        /// `None` if the file does not exist.
+        current: Option<String>,
+        new: String,
+    },
+    /// An element of a boot entry is set or deleted.
+    Bcd {
+        entry: String,
+        element: String,
+        /// `None` if the entry does not set the element.
+        current: Option<String>,
+        /// `None` deletes the element.
+        new: Option<String>,
+    },
+    /// A PowerShell script is run.
+    Powershell { script: String },
+    /// A control is sent to a Windows service.
+    Service {
+        name: String,
+        control: ServiceControl,
+    },
+    /// A user right is granted to or revoked from the current user.
+    UserRight {
+        privilege: String,
+        grant: bool,
+        /// `None` if the current assignment cannot be read.
+        currently_granted: Option<bool>,
+    },
+    /// Anything else, described in words.
+    Other { description: String },
+}
+
+impl PlanStep {
+    /// Returns whether the step would leave the system as it is.
+    pub fn is_noop(&self) -> bool {
+        match self {
+            PlanStep::RegistryValue { current, new, .. } => current == new,
+            PlanStep::Msr { current, new, .. } => current.is_some() && current == new,
+            PlanStep::Pci { current, new, .. } => current.is_some() && current == new,
+            PlanStep::Sysfs { current, new, .. } => current.as_ref() == Some(new),
+            PlanStep::Bcd { current, new, .. } => match (current, new) {
+                (Some(current), Some(new)) => current.eq_ignore_ascii_case(new),
+                (current, new) => current == new,
+            },
+            PlanStep::UserRight {
+                grant,
+                currently_granted,
+                ..
+            } => *currently_granted == Some(*grant),
+            PlanStep::Powershell { .. } | PlanStep::Service { .. } | PlanStep::Other { .. } => {
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unchanged = if self.is_noop() { " (unchanged)" } else { "" };
+        match self {
+            PlanStep::RegistryValue {
+                path,
+                key,
+                current,
+                new,
+            } => {
+                let current = current
+                    .as_ref()
+                    .map_or("<absent>".to_string(), |v| v.to_string());
+                match new {
+                    Some(new) => write!(
+                        f,
+                        "Registry: set {}\\{} from {} to {}{}",
+                        path, key, current, new, unchanged
+                    ),
+                    None => write!(
+                        f,
+                        "Registry: delete {}\\{} (currently {}){}",
+                        path, key, current, unchanged
+                    ),
+                }
+            }
+            PlanStep::Msr {
+                core,
+                index,
+                fields,
+                current,
+                new,
+            } => {
+                let bits = fields
+                    .iter()
+                    .map(|change| change.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match (current, new) {
+                    (Some(current), Some(new)) => write!(
+                        f,
+                        "MSR 0x{:08X} on core {}: {} (0x{:016X} -> 0x{:016X}){}",
+                        index, core, bits, current, new, unchanged
+                    ),
+                    _ => write!(f, "MSR 0x{:08X} on core {}: {}", index, core, bits),
+                }
+            }
+            PlanStep::Pci {
+                register,
+                fields,
+                current,
+                new,
+            } => {
+                let bits = fields
+                    .iter()
+                    .map(|change| change.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match (current, new) {
+                    (Some(current), Some(new)) => write!(
+                        f,
+                        "{}: {} (0x{:08X} -> 0x{:08X}){}",
+                        register, bits, current, new, unchanged
+                    ),
+                    _ => write!(f, "{}: {}", register, bits),
+                }
+            }
+            PlanStep::Sysfs { path, current, new } => write!(
+                f,
+                "Linux: set {} from {} to {}{}",
+                path,
+                current.as_deref().unwrap_or("<absent>"),
+                new,
+                unchanged
+            ),
+            PlanStep::Bcd {
+                entry,
+                element,
+                current,
+                new,
+            } => write!(
+                f,
+                "Boot: set {} {} from {} to {}{}",
+                entry,
+                element,
+                current.as_deref().unwrap_or("<unset>"),
+                new.as_deref().unwrap_or("<unset>"),
+                unchanged
+            ),
+            PlanStep::Powershell { script } => write!(f, "PowerShell: run `{}`", script.trim()),
+            PlanStep::Service { name, control } => {
+                write!(f, "Service: {} {}", control, name)
+            }
+            PlanStep::UserRight {
+                privilege, grant, ..
+            } => write!(
+                f,
+                "User right: {} {} for the current user{}",
+                if *grant { "grant" } else { "revoke" },
+                privilege,
+                unchanged
+            ),
+            PlanStep::Other { description } => write!(f, "{}", description),
+        }
+    }
+}
+
+/// The value a field of one or more bits of an MSR or PCI register will be set to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MsrFieldChange {
+    pub low_bit: u32,
+    /// Inclusive.
+    pub high_bit: u32,
+    pub value: u64,
+}
+
+impl fmt::Display for MsrFieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.low_bit == self.high_bit {
+            write!(f, "bit {} = {}", self.low_bit, self.value)
+        } else {
+            write!(
+                f,
+                "bits {}:{} = {}",
+                self.high_bit, self.low_bit, self.value
+            )
+        }
+    }
+}
+
+/// A control sent to a Windows service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceControl {
+    Stop,
+}
+
+impl fmt::Display for ServiceControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceControl::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_rendering() {
+        let plan = TweakPlan {
+            steps: vec![
+                PlanStep::RegistryValue {
+                    path: "HKEY_LOCAL_MACHINE\\SYSTEM\\Test".to_string(),
+                    key: "Value".to_string(),
+                    current: Some(RegistryKeyValue::Dword(0)),
+                    new: Some(RegistryKeyValue::Dword(1)),
+                },
+                PlanStep::RegistryValue {
+                    path: "HKEY_LOCAL_MACHINE\\SYSTEM\\Test".to_string(),
+                    key: "Other".to_string(),
+                    current: None,
+                    new: None,
+                },
+                PlanStep::Msr {
+                    core: 2,
+                    index: 0xC001_1020,
+                    fields: vec![
+                        MsrFieldChange {
+                            low_bit: 4,
+                            high_bit: 4,
+                            value: 1,
+                        },
+                        MsrFieldChange {
+                            low_bit: 7,
+                            high_bit: 9,
+                            value: 2,
+                        },
+                    ],
+                    current: Some(0),
+                    new: Some(0x110),
+                },
+            ],
+        };
+
+        assert!(!plan.is_noop());
+        assert_eq!(
+            plan.to_string(),
+            "Registry: set HKEY_LOCAL_MACHINE\\SYSTEM\\Test\\Value from Dword(0) to Dword(1)\n\
+             Registry: delete HKEY_LOCAL_MACHINE\\SYSTEM\\Test\\Other (currently <absent>) (unchanged)\n\
+             MSR 0xC0011020 on core 2: bit 4 = 1, bits 9:7 = 2 (0x0000000000000000 -> 0x0000000000000110)"
+        );
+    }
+
+    #[test]
+    fn test_plan_serializes_with_step_kinds() {
+        let plan = TweakPlan {
+            steps: vec![PlanStep::Service {
+                name: "SysMain".to_string(),
+                control: ServiceControl::Stop,
+            }],
+        };
+        assert_eq!(
+            serde_json::to_value(&plan).unwrap(),
+            serde_json::json!({
+                "steps": [{ "kind": "service", "name": "SysMain", "control": "stop" }]
+            })
+        );
+    }
+}
@@ -1,31 +1,106 @@
-// src/tweaks/powershell.rs
-
-use std::collections::HashMap;
+// src/tweaks/powershell/method.rs
 
 use anyhow::{anyhow, Context, Result};
+use indexmap::IndexMap;
+use serde_json::Value;
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    tweaks::{TweakId, TweakMethod, TweakOption},
-    utils::powershell::execute_powershell_script,
+    backup::TweakSnapshot,
+    tweaks::{
+        default_option_of,
+        plan::{PlanStep, TweakPlan},
+        SettingState, TweakId, TweakMethod, TweakOption,
+    },
+    utils::powershell::{execute_powershell_script, execute_powershell_script_with, ScriptPolicy},
 };
 
+/// Names the read script's output in the settings of a `Mixed` state.
+const READ_SCRIPT_SETTING: &str = "PowerShell read script";
+
 #[derive(Debug)]
 pub struct PowershellTweak<'a> {
     /// The unique ID of the tweak
     pub id: TweakId,
-    /// PowerShell script to read the current state of the tweak.
+    /// PowerShell script to read the current state of the tweak. Its output is passed
+    /// through `ConvertTo-Json`, so it should output values such as `$true` or
+    /// `(Get-Service DPS).Status.ToString()`, not text describing them.
     pub read_script: Option<&'a str>,
     /// Mapping of tweak options to their corresponding PowerShell tweak states.
-    pub options: HashMap<TweakOption, &'a PowershellAction<'a>>,
+    pub options: IndexMap<TweakOption, &'a PowershellAction<'a>>,
 }
 
 #[derive(Debug)]
 pub struct PowershellAction<'a> {
     /// PowerShell script to apply or revert the tweak.
     pub script: &'a str,
-    /// The target state string to compare against the current state.
-    pub state: Option<&'a str>,
+    /// What the read script outputs while this option is in effect.
+    pub state: Option<PowershellState<'a>>,
+    /// How long `script` may run, and which of its results count as success.
+    pub policy: ScriptPolicy<'a>,
+}
+
+/// A value the JSON output of a read script is compared with.
+///
+/// A JSON array matches if it is not empty and every element matches, as a script that
+/// outputs one value per item, e.g. per network adapter, produces an array.
+#[derive(Debug, Clone, Copy)]
+pub enum PowershellState<'a> {
+    Bool(bool),
+    Integer(i64),
+    /// A string, compared ignoring ASCII case as PowerShell does.
+    String(&'a str),
+    /// Any output the function accepts, e.g. an object with several properties.
+    Matches(fn(&Value) -> bool),
+}
+
+impl PowershellState<'_> {
+    /// Returns whether the read script's output is this state.
+    pub fn matches(&self, output: &Value) -> bool {
+        if let Value::Array(items) = output {
+            return !items.is_empty() && items.iter().all(|item| self.matches(item));
+        }
+        match self {
+            PowershellState::Bool(expected) => output.as_bool() == Some(*expected),
+            PowershellState::Integer(expected) => {
+                output.as_i64() == Some(*expected) || output.as_f64() == Some(*expected as f64)
+            }
+            PowershellState::String(expected) => output
+                .as_str()
+                .is_some_and(|value| value.eq_ignore_ascii_case(expected)),
+            PowershellState::Matches(predicate) => predicate(output),
+        }
+    }
+
+    /// Returns whether `other` is the same fixed value. Predicates never are, as they
+    /// cannot be compared.
+    fn same_value(&self, other: &PowershellState) -> bool {
+        match (self, other) {
+            (PowershellState::Bool(a), PowershellState::Bool(b)) => a == b,
+            (PowershellState::Integer(a), PowershellState::Integer(b)) => a == b,
+            (PowershellState::String(a), PowershellState::String(b)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the script actually run to read a tweak's state: `read_script`, with its
+/// output converted to compact JSON.
+pub fn json_read_script(read_script: &str) -> String {
+    format!(
+        "& {{\n{}\n}} | ConvertTo-Json -Compress -Depth 4",
+        read_script.trim()
+    )
+}
+
+/// Parses the output of a read script run through `json_read_script`. A script that
+/// outputs nothing gives `null`.
+fn parse_output(output: &str) -> Result<Value> {
+    if output.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(output.trim())
+        .with_context(|| format!("Read script output is not JSON: '{}'", output.trim()))
 }
 
 impl PowershellTweak<'_> {
@@ -33,7 +108,7 @@ impl PowershellTweak<'_> {
     ///
     /// # Returns
     ///
-    /// - `Ok(Some(String))` with the current state if `read_script` is defined and succeeds.
+    /// - `Ok(Some(String))` with the JSON output if `read_script` is defined and succeeds.
     /// - `Ok(None)` if no `read_script` is defined.
     /// - `Err(anyhow::Error)` if the script execution fails.
     fn read_current_state(&self) -> Result<Option<String>> {
@@ -43,13 +118,13 @@ impl PowershellTweak<'_> {
                 self.id
             );
 
-            // Execute the PowerShell script using the custom function
-            let output = execute_powershell_script(script).with_context(|| {
-                format!(
-                    "{:?} -> Failed to execute read PowerShell script '{}'",
-                    self.id, script
-                )
-            })?;
+            let output =
+                execute_powershell_script(&json_read_script(script)).with_context(|| {
+                    format!(
+                        "{:?} -> Failed to execute read PowerShell script '{}'",
+                        self.id, script
+                    )
+                })?;
 
             debug!(
                 "{:?} -> PowerShell script output: {}",
@@ -72,17 +147,20 @@ impl TweakMethod for PowershellTweak<'_> {
     /// Determines the initial state by reading the current state and matching it against defined options.
     ///
     /// # Returns
-    /// - `Ok(TweakOption)` representing the current active option.
-    /// - `Err(anyhow::Error)` if reading the state fails.
+    /// - `Ok(TweakOption)` representing the current active option, or `TweakOption::Mixed`
+    ///   holding the read script's output if it matches no option.
+    /// - `Err(anyhow::Error)` if reading the state fails or its output is not JSON.
     fn initial_state(&self) -> Result<TweakOption> {
         if let Some(current_state) = self.read_current_state()? {
             info!(
                 "{:?} -> Checking current state of PowerShell tweak.",
                 self.id
             );
+            let output = parse_output(&current_state)
+                .with_context(|| format!("{:?} -> Failed to read the state", self.id))?;
             for (option, tweak_state) in &self.options {
                 if let Some(state) = tweak_state.state {
-                    if current_state.contains(state) {
+                    if state.matches(&output) {
                         debug!(
                             "{:?} -> Current state matches option {:?}.",
                             self.id, option
@@ -92,10 +170,15 @@ impl TweakMethod for PowershellTweak<'_> {
                 }
             }
             warn!(
-                "{:?} -> Current state does not match any defined option. Assuming default.",
-                self.id
+                "{:?} -> Current state {} does not match any defined option.",
+                self.id, output
             );
-            Ok(TweakOption::Enabled(false))
+            Ok(TweakOption::Mixed(vec![SettingState {
+                setting: READ_SCRIPT_SETTING.to_string(),
+                core: None,
+                value: Some(output.to_string()),
+                matches: Vec::new(),
+            }]))
         } else {
             warn!(
                 "{:?} -> No read script defined for PowerShell tweak. Assuming default state.",
@@ -123,12 +206,14 @@ impl TweakMethod for PowershellTweak<'_> {
             );
 
             // Execute the PowerShell script using the custom function
-            let output = execute_powershell_script(tweak_state.script).with_context(|| {
-                format!(
-                    "{:?} -> Failed to execute apply PowerShell script '{}'",
-                    self.id, tweak_state.script
-                )
-            })?;
+            let output =
+                execute_powershell_script_with(tweak_state.script, &tweak_state.policy, None)
+                    .with_context(|| {
+                        format!(
+                            "{:?} -> Failed to execute apply PowerShell script '{}'",
+                            self.id, tweak_state.script
+                        )
+                    })?;
 
             debug!(
                 "{:?} -> Apply script executed successfully. Output: {}",
@@ -161,12 +246,14 @@ impl TweakMethod for PowershellTweak<'_> {
             );
 
             // Execute the PowerShell script using the custom function
-            let output = execute_powershell_script(default_state.script).with_context(|| {
-                format!(
-                    "{:?} -> Failed to execute revert PowerShell script '{}'",
-                    self.id, default_state.script
-                )
-            })?;
+            let output =
+                execute_powershell_script_with(default_state.script, &default_state.policy, None)
+                    .with_context(|| {
+                    format!(
+                        "{:?} -> Failed to execute revert PowerShell script '{}'",
+                        self.id, default_state.script
+                    )
+                })?;
 
             debug!(
                 "{:?} -> Revert script executed successfully. Output: {}",
@@ -182,4 +269,129 @@ impl TweakMethod for PowershellTweak<'_> {
             Ok(())
         }
     }
+
+    /// Captures the output of the read script.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(TweakSnapshot::Powershell))` with the read script's output.
+    /// - `Ok(None)` if no `read_script` is defined.
+    /// - `Err(anyhow::Error)` if the script execution fails.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        Ok(self.read_current_state()?.map(TweakSnapshot::Powershell))
+    }
+
+    /// Restores the state captured by `snapshot` by running the script of the option
+    /// whose state matches the captured JSON output.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the matching script executes successfully.
+    /// - `Err(anyhow::Error)` if no option matches the captured output or the script fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Powershell(saved_state) = snapshot else {
+            return Err(anyhow!(
+                "{:?} -> Backup is not a PowerShell snapshot",
+                self.id
+            ));
+        };
+
+        let output = parse_output(saved_state)
+            .with_context(|| format!("{:?} -> Failed to read the backup", self.id))?;
+        let (option, action) = self
+            .options
+            .iter()
+            .find(|(_, action)| action.state.is_some_and(|state| state.matches(&output)))
+            .with_context(|| {
+                format!(
+                    "{:?} -> No option reproduces the original state '{}'",
+                    self.id, saved_state
+                )
+            })?;
+
+        info!(
+            "{:?} -> Restoring original state using the script of option {:?}.",
+            self.id, option
+        );
+        execute_powershell_script_with(action.script, &action.policy, None).with_context(|| {
+            format!(
+                "{:?} -> Failed to execute restore PowerShell script '{}'",
+                self.id, action.script
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Lists the script the option runs.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with a single script step.
+    /// - `Err(anyhow::Error)` if the option is not found.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let action = self
+            .options
+            .get(option)
+            .ok_or_else(|| anyhow!("{:?} -> Option {:?} not found in options.", self.id, option))?;
+        Ok(TweakPlan {
+            steps: vec![PlanStep::Powershell {
+                script: action.script.to_string(),
+            }],
+        })
+    }
+
+    /// Checks that the current state maps onto at most one option, as far as can be told
+    /// without running the read script.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let states: Vec<(&TweakOption, PowershellState)> = self
+            .options
+            .iter()
+            .filter_map(|(option, action)| action.state.map(|state| (option, state)))
+            .collect();
+
+        if self.read_script.is_none() && !states.is_empty() {
+            problems.push("options define states, but there is no read script".to_string());
+        }
+        for (i, (option, state)) in states.iter().enumerate() {
+            if matches!(state, PowershellState::String(value) if value.is_empty()) {
+                problems.push(format!("option {:?} has an empty state", option));
+            }
+            for (other_option, other_state) in &states[i + 1..] {
+                if state.same_value(other_state) {
+                    problems.push(format!(
+                        "options {:?} and {:?} have the same state {:?}",
+                        option, other_option, state
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_states_match_typed_json() {
+        assert!(PowershellState::Bool(false).matches(&json!(false)));
+        assert!(!PowershellState::Bool(false).matches(&json!("False")));
+        assert!(PowershellState::Integer(0).matches(&json!(0)));
+        assert!(!PowershellState::Integer(0).matches(&Value::Null));
+        assert!(PowershellState::String("stopped").matches(&json!("Stopped")));
+        // Whole values are compared, not substrings
+        assert!(!PowershellState::String("Enabled").matches(&json!("NotEnabled")));
+        assert!(PowershellState::String("Yes").matches(&json!(["Yes", "yes"])));
+        assert!(!PowershellState::String("Yes").matches(&json!(["Yes", "No"])));
+        assert!(!PowershellState::String("Yes").matches(&json!([])));
+        assert!(PowershellState::Matches(|v| v["Count"] == 0).matches(&json!({ "Count": 0 })));
+    }
 }
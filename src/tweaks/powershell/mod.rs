@@ -2,25 +2,20 @@
 
 pub mod method;
 
-use std::collections::HashMap;
+use std::time::Duration;
 
-use method::{PowershellAction, PowershellTweak};
+use method::{PowershellAction, PowershellState, PowershellTweak};
 
-use super::{Tweak, TweakCategory, TweakOption};
-use crate::tweaks::TweakId;
+use super::{option_map, Tweak, TweakCategory, TweakOption};
+use crate::{tweaks::TweakId, utils::powershell::ScriptPolicy};
 
 pub fn all_powershell_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
     vec![
         (TweakId::ProcessIdleTasks, process_idle_tasks()),
-        (TweakId::DisableHPET, disable_hpet()),
         (TweakId::DisableRamCompression, disable_ram_compression()),
         (TweakId::DisableLocalFirewall, disable_local_firewall()),
         (TweakId::DisableSuccessAuditing, disable_success_auditing()),
         (TweakId::DisablePagefile, disable_pagefile()),
-        (
-            TweakId::DisableDataExecutionPrevention,
-            disable_data_execution_prevention(),
-        ),
         (TweakId::DisableSuperfetch, disable_superfetch()),
         (TweakId::DisableCpuIdleStates, disable_cpu_idle_states()),
         (TweakId::DisableDps, disable_dps()),
@@ -28,6 +23,11 @@ pub fn all_powershell_tweaks<'a>() -> Vec<(TweakId, Tweak<'a>)> {
 }
 
 pub fn process_idle_tasks<'a>() -> Tweak<'a> {
+    // Works through every queued maintenance task, which can take minutes
+    const POLICY: ScriptPolicy = ScriptPolicy {
+        timeout: Duration::from_secs(15 * 60),
+        ..ScriptPolicy::DEFAULT
+    };
     Tweak::powershell_tweak(
         "Process Idle Tasks",
         "Runs the Process Idle Tasks command to optimize system performance by processing idle tasks and background maintenance activities.",
@@ -35,12 +35,12 @@ pub fn process_idle_tasks<'a>() -> Tweak<'a> {
         PowershellTweak {
             id: TweakId::ProcessIdleTasks,
             read_script: None,
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Run,
                     &PowershellAction {
                         script:"rundll32.exe advapi32.dll,ProcessIdleTasks",
-            state: None})],
+            state: None, policy: POLICY })],
             ),
 
         },
@@ -48,51 +48,6 @@ pub fn process_idle_tasks<'a>() -> Tweak<'a> {
     )
 }
 
-pub fn disable_hpet<'a>() -> Tweak<'a> {
-    Tweak::powershell_tweak(
-        "Disable Dynamic Tick",
-        "Disables the dynamic tick feature, which normally reduces timer interrupts during idle periods to conserve power. By disabling dynamic tick, the system maintains a constant rate of timer interrupts, improving performance in real-time applications by reducing latency and jitter. This tweak is useful in scenarios where consistent, low-latency processing is required, but it may increase power consumption as the CPU will not enter low-power states as frequently.",
-        TweakCategory::System,
-        PowershellTweak {
-            id: TweakId::DisableHPET,
-            read_script: Some(
-                r#"bcdedit /enum | Select-String "useplatformclock" | ForEach-Object {
-                    if (($_ -split '\s+')[1] -eq 'Yes') {
-                        'True'
-                    } else {
-                        'False'
-                    }
-                } | Select-Object -Unique"#
-            ),
-            options: HashMap::from_iter(
-                vec![
-                    (
-                        TweakOption::Enabled(false),
-                        &PowershellAction {
-                            script: r#"
-                                bcdedit /deletevalue useplatformclock
-                                bcdedit /set disabledynamictick yes
-                            "#,
-                            state: Some("True"),
-                        }
-                    ),
-                    (
-                        TweakOption::Enabled(true),
-                        &PowershellAction {
-                            script: r#"
-                                bcdedit /set useplatformclock true
-                                bcdedit /set disabledynamictick no
-                            "#,
-                            state: Some("False"),
-                        },
-                    ),
-                ],
-            ),
-        },
-        true,
-    )
-}
-
 pub fn disable_ram_compression<'a>() -> Tweak<'a> {
     Tweak::powershell_tweak(
         "Disable RAM Compression",
@@ -101,27 +56,21 @@ pub fn disable_ram_compression<'a>() -> Tweak<'a> {
         PowershellTweak {
             id: TweakId::DisableRamCompression,
             read_script: Some(
-                r#"
-                $ramCompression = Get-MMAgent | Select-Object -ExpandProperty MemoryCompression
-
-                if ($ramCompression -eq $true) {
-                    "Enabled"
-                } else {
-                    "Disabled"
-                }
-                "#.trim(),
+                "(Get-MMAgent).MemoryCompression",
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(true),
                     &PowershellAction {
                         script: r#"Disable-MMAgent -mc"#,
-                        state: Some("True"),
+                        state: Some(PowershellState::Bool(false)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(false),
                     &PowershellAction {
                         script: r#"Enable-MMAgent -mc"#,
-                        state: Some("False"),
+                        state: Some(PowershellState::Bool(true)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
@@ -138,28 +87,21 @@ pub fn disable_local_firewall<'a>() -> Tweak<'a> {
         PowershellTweak {
             id: TweakId::DisableLocalFirewall,
             read_script: Some(
-                r#"
-                $firewallState = netsh advfirewall show allprofiles state | Select-String "State" | ForEach-Object { $_.Line }
-
-                if ($firewallState -match "off") {
-                    "Off"
-                } else {
-                    "On"
-                }
-                "#
-                .trim(),
+                "Get-NetFirewallProfile | ForEach-Object { [bool]$_.Enabled }",
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false),
                     &PowershellAction {
                         script: "netsh advfirewall set allprofiles state on",
-                        state: Some("On"),
+                        state: Some(PowershellState::Bool(true)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true),
                     &PowershellAction {
                         script: "netsh advfirewall set allprofiles state off",
-                        state: Some("Off"),
+                        state: Some(PowershellState::Bool(false)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
@@ -176,27 +118,22 @@ pub fn disable_success_auditing<'a>() -> Tweak<'a> {
         PowershellTweak {
             id: TweakId::DisableSuccessAuditing,
             read_script: Some(
-                r#"
-                $auditSettings = AuditPol /get /category:* | Where-Object { $_ -match "No Auditing" }
-
-                if ($auditSettings.Count -gt 0) {
-                    "Enabled"
-                } else {
-                    "Disabled"
-                }
-                "#.trim(),
+                "@(AuditPol /get /category:* | Select-String 'Success').Count",
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false),
                     &PowershellAction {
                         script: "Auditpol /set /category:* /Success:enable",
-                        state: Some("Disabled"),
+                        // Some category still audits successes
+                        state: Some(PowershellState::Matches(|count| count.as_i64() > Some(0))),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true),
                     &PowershellAction {
                         script: "Auditpol /set /category:* /Success:disable",
-                        state: Some("Enabled"),
+                        state: Some(PowershellState::Integer(0)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
@@ -214,21 +151,19 @@ pub fn disable_pagefile<'a>() -> Tweak<'a> {
             id: TweakId::DisablePagefile,
             read_script: Some(
                 r#"
-                $pagefileSettings = Get-WmiObject -Class Win32_PageFileUsage | Select-Object -ExpandProperty AllocatedBaseSize
-
-                if ($pagefileSettings -eq 0) {
-                    "Disabled"
-                } else {
-                    "Enabled"
+                [pscustomobject]@{
+                    Automatic = (Get-CimInstance -ClassName Win32_ComputerSystem).AutomaticManagedPagefile
+                    PageFiles = @(Get-CimInstance -ClassName Win32_PageFileSetting).Count
                 }
                 "#.trim(),
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false), // Keep pagefile enabled
                     &PowershellAction {
                         script: "wmic computersystem set AutomaticManagedPagefile=True",
-                        state: Some("Enabled"),  // Changed to match actual state
+                        state: Some(PowershellState::Matches(|pagefile| pagefile["Automatic"] == true)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true), // Disable pagefile
                     &PowershellAction {
@@ -236,45 +171,10 @@ pub fn disable_pagefile<'a>() -> Tweak<'a> {
 wmic computersystem set AutomaticManagedPagefile=False
 wmic pagefileset delete
 ",
-                        state: Some("Disabled"),  // Changed to match actual state
-                    }),
-                ],
-            ),
-        },
-        true, // requires reboot
-    )
-}
-
-pub fn disable_data_execution_prevention<'a>() -> Tweak<'a> {
-    Tweak::powershell_tweak(
-        "Disable Data Execution Prevention",
-        "Disables Data Execution Prevention (DEP) by setting the `nx` boot configuration option to `AlwaysOff`. This may improve compatibility with older applications but can introduce security risks.",
-        TweakCategory::Security,
-        PowershellTweak {
-            id: TweakId::DisableDataExecutionPrevention,
-            read_script: Some(
-                r#"
-                $depSettings = bcdedit /enum | Select-String 'nx'
-
-                if ($depSettings -match 'AlwaysOff') {
-                    "Disabled"
-                } else {
-                    "Enabled"
-                }
-                "#
-                .trim(),
-            ),
-            options: HashMap::from_iter(
-                vec![
-                    (TweakOption::Enabled(false),
-                    &PowershellAction {
-                        script: "bcdedit.exe /set nx OptIn",
-                        state: Some("Enabled"),
-                    }),
-                    (TweakOption::Enabled(true),
-                    &PowershellAction {
-                        script: "bcdedit.exe /set nx AlwaysOff",
-                        state: Some("Disabled"),
+                        state: Some(PowershellState::Matches(|pagefile| {
+                            pagefile["Automatic"] == false && pagefile["PageFiles"] == 0
+                        })),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
@@ -291,28 +191,21 @@ pub fn disable_superfetch<'a>() -> Tweak<'a> {
         PowershellTweak {
             id: TweakId::DisableSuperfetch,
             read_script: Some(
-                r#"
-                $superfetchStatus = Get-Service -Name SysMain | Select-Object -ExpandProperty Status
-
-                if ($superfetchStatus -eq "Running") {
-                    "Enabled"
-                } else {
-                    "Disabled"
-                }
-                "#
-                .trim(),
+                r#"(Get-Service -Name "SysMain").StartType.ToString()"#,
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false),
                     &PowershellAction {
                         script: r#"Set-Service -Name "SysMain" -StartupType Automatic -Status Running"#,
-                        state: Some("Disabled"),
+                        state: Some(PowershellState::String("Automatic")),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true),
                     &PowershellAction {
                         script: r#"Stop-Service -Force -Name "SysMain"; Set-Service -Name "SysMain" -StartupType Disabled"#,
-                        state: Some("Enabled"),
+                        state: Some(PowershellState::String("Disabled")),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
 
                 ],
@@ -320,6 +213,8 @@ pub fn disable_superfetch<'a>() -> Tweak<'a> {
         },
         true, // requires reboot
     )
+    // SysMain is one of the services KillAllNonCriticalServices stops
+    .overlaps(&[TweakId::KillAllNonCriticalServices])
 }
 
 fn disable_cpu_idle_states<'a>() -> Tweak<'a> {
@@ -337,12 +232,12 @@ powercfg /QH |
         $_.Context.PostContext |
         Select-String 'Current AC Power Setting Index:' |
         ForEach-Object {
-            ($_ -split ':')[1].Trim()
+            [Convert]::ToInt32(($_ -split ':')[1].Trim(), 16)
         }
     }
                 "#.trim(),
             ),
-            options: HashMap::from_iter(
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false),
                     &PowershellAction {
@@ -350,7 +245,8 @@ powercfg /QH |
                         powercfg.exe /setacvalueindex SCHEME_CURRENT SUB_PROCESSOR IdleDisable 0
                         powercfg.exe /setactive SCHEME_CURRENT
                         "#,
-                        state: Some("0x00000000"),
+                        state: Some(PowershellState::Integer(0)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true),
                     &PowershellAction {
@@ -358,7 +254,8 @@ powercfg /QH |
                         powercfg.exe /setacvalueindex SCHEME_CURRENT SUB_PROCESSOR IdleDisable 1
                         powercfg.exe /setactive SCHEME_CURRENT
                         "#,
-                        state: Some("0x00000001"),
+                        state: Some(PowershellState::Integer(1)),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
@@ -374,22 +271,24 @@ fn disable_dps<'a>() -> Tweak<'a> {
         TweakCategory::System,
         PowershellTweak {
             id: TweakId::DisableDps,
-            read_script: Some(r#"(Get-Service -Name "DPS").Status"#),
-            options: HashMap::from_iter(
+            read_script: Some(r#"(Get-Service -Name "DPS").Status.ToString()"#),
+            options: option_map(
                 vec![
                     (TweakOption::Enabled(false),
                     &PowershellAction {
                         script: r#"Start-Service -Force -Name "DPS""#,
-                        state: Some("Running"),
+                        state: Some(PowershellState::String("Running")),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                     (TweakOption::Enabled(true),
                     &PowershellAction {
                         script: r#"Stop-Service -Name "DPS" -Force"#,
-                        state: Some("Stopped"),
+                        state: Some(PowershellState::String("Stopped")),
+                        policy: ScriptPolicy::DEFAULT,
                     }),
                 ],
             ),
         },
         false, // does not require reboot
     )
-}
\ No newline at end of file
+}
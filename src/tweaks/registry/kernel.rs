@@ -1,9 +1,8 @@
 // src/tweaks/registry/kernel.rs
-use indexmap::IndexMap;
 
 use super::{
     method::{RegistryModification, RegistryTweak},
-    Tweak, TweakCategory, TweakOption,
+    option_map, Tweak, TweakCategory, TweakOption,
 };
 use crate::{tweaks::TweakId, utils::registry::RegistryKeyValue};
 
@@ -17,7 +16,7 @@ pub fn thread_dpc_disable<'a>() -> Tweak<'a> {
         TweakCategory::Kernel,
         RegistryTweak {
             id: TweakId::ThreadDpcDisable,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -53,7 +52,7 @@ pub fn additional_kernel_worker_threads<'a>() -> Tweak<'a> {
         TweakCategory::Kernel,
         RegistryTweak {
             id: TweakId::AdditionalKernelWorkerThreads,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Default".to_string()),
                     vec![
@@ -177,7 +176,7 @@ pub fn alchemy_kernel_tweak<'a>() -> Tweak<'a> {
         TweakCategory::Kernel,
         RegistryTweak {
             id: TweakId::AlchemyKernelTweak,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![
@@ -1,14 +1,23 @@
 // src/tweaks/registry.rs
 
+use std::collections::HashSet;
+
 use anyhow::{Context, Result};
 use indexmap::IndexMap;
 use tracing::{debug, error, trace};
 
 use crate::{
-    tweaks::{TweakId, TweakMethod, TweakOption},
+    backup::{RegistryValueSnapshot, TweakSnapshot},
+    journal::{record_step, JournalStep},
+    tweaks::{
+        default_option_of,
+        plan::{PlanStep, TweakPlan},
+        relations::TweakSetting,
+        SettingState, TweakId, TweakMethod, TweakOption,
+    },
     utils::registry::{
-        create_or_modify_registry_value, delete_registry_value, read_registry_value,
-        RegistryKeyValue,
+        create_or_modify_registry_value, delete_registry_value, parse_registry_path,
+        read_registry_value, try_read_registry_value, RegistryKeyValue,
     },
 };
 
@@ -35,6 +44,18 @@ pub struct RegistryTweak<'a> {
 }
 
 impl RegistryTweak<'_> {
+    /// Returns every distinct `(path, key)` pair modified by any of the tweak's options.
+    fn touched_values(&self) -> Vec<(&str, &str)> {
+        let mut values = Vec::new();
+        for modification in self.options.values().flatten() {
+            let value = (modification.path, modification.key);
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        values
+    }
+
     /// Reads the current values of all registry modifications for a given option.
     ///
     ///
@@ -104,72 +125,85 @@ impl RegistryTweak<'_> {
         Ok(true)
     }
 
-    /// Rolls back modifications by applying the `Default` option's modifications.
+    /// Sets a registry value, or deletes it if `value` is `None`, recording the change
+    /// in the journal before it is made.
     ///
-    /// # Parameters
+    /// # Returns
     ///
-    /// - `operation`: A string indicating the operation ("apply" or "revert") for logging purposes.
+    /// - `Ok(())` if the value was written.
+    /// - `Err(anyhow::Error)` if the change could not be journaled or written.
+    fn write_value(&self, path: &str, key: &str, value: Option<&RegistryKeyValue>) -> Result<()> {
+        let step = JournalStep::Registry {
+            path: path.to_string(),
+            key: key.to_string(),
+            before: try_read_registry_value(path, key)?,
+            after: value.cloned(),
+        };
+        record_step(step, || match value {
+            Some(value) => create_or_modify_registry_value(path, key, value),
+            None => delete_registry_value(path, key),
+        })
+    }
+
+    /// Reads the current value of every registry value any option modifies.
     ///
     /// # Returns
     ///
-    /// - `Ok(())` if all rollback operations succeed.
-    /// - `Err(anyhow::Error)` if any rollback operation fails.
-    fn rollback(&self, operation: &str) -> Result<()> {
-        debug!(
-            "{:?} -> Initiating rollback for {} operation by applying Default modifications.",
-            self.id, operation
-        );
-
-        let default_modifications =
-            self.options
-                .get(&TweakOption::Enabled(false))
-                .context(format!(
-                    "{:?} -> No registry modifications found for Default option during rollback.",
-                    self.id
-                ))?;
-
-        for modification in default_modifications {
-            match &modification.value {
-                RegistryKeyValue::Deleted => {
-                    // Delete the registry value
-                    delete_registry_value(modification.path, modification.key).with_context(
-                        || {
-                            format!(
-                                "{:?} -> Failed to delete value '{}' in '{}' during rollback.",
-                                self.id, modification.key, modification.path
-                            )
-                        },
-                    )?;
-                    debug!(
-                        "{:?} -> Deleted value '{}' in '{}'.",
-                        self.id, modification.key, modification.path
-                    );
-                }
-                _ => {
-                    // Apply the default value using the helper function
-                    create_or_modify_registry_value(
-                        modification.path,
-                        modification.key,
-                        &modification.value,
+    /// - `Ok(Vec<RegistryValueSnapshot>)` with one entry per value, `None` where it does not exist.
+    /// - `Err(anyhow::Error)` if any value cannot be read.
+    fn capture_values(&self) -> Result<Vec<RegistryValueSnapshot>> {
+        self.touched_values()
+            .into_iter()
+            .map(|(path, key)| {
+                let value = try_read_registry_value(path, key).with_context(|| {
+                    format!(
+                        "{:?} -> Failed to read value '{}' in '{}'",
+                        self.id, key, path
                     )
+                })?;
+                Ok(RegistryValueSnapshot {
+                    path: path.to_string(),
+                    key: key.to_string(),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes back values captured by `capture_values`, deleting values that did not exist.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every value was written.
+    /// - `Err(anyhow::Error)` if any write fails.
+    fn write_values(&self, values: &[RegistryValueSnapshot]) -> Result<()> {
+        for saved in values {
+            match &saved.value {
+                Some(value) => self
+                    .write_value(&saved.path, &saved.key, Some(value))
                     .with_context(|| {
                         format!(
-                            "{:?} -> Failed to set default value '{}' in '{}' during rollback.",
-                            self.id, modification.key, modification.path
+                            "{:?} -> Failed to restore value '{}' in '{}'",
+                            self.id, saved.key, saved.path
                         )
-                    })?;
-                    debug!(
-                        "{:?} -> Set default value '{}' to {:?} in '{}'.",
-                        self.id, modification.key, modification.value, modification.path
-                    );
+                    })?,
+                None => {
+                    if try_read_registry_value(&saved.path, &saved.key)?.is_some() {
+                        self.write_value(&saved.path, &saved.key, None)
+                            .with_context(|| {
+                                format!(
+                                    "{:?} -> Failed to delete value '{}' in '{}'",
+                                    self.id, saved.key, saved.path
+                                )
+                            })?;
+                    }
                 }
             }
+            debug!(
+                "{:?} -> Restored value '{}' in '{}' to {:?}.",
+                self.id, saved.key, saved.path, saved.value
+            );
         }
-
-        debug!(
-            "{:?} -> Successfully rolled back {} operation.",
-            self.id, operation
-        );
         Ok(())
     }
 }
@@ -178,7 +212,8 @@ impl TweakMethod for RegistryTweak<'_> {
     /// Checks the current state of the registry tweak and returns the corresponding `TweakOption`.
     ///
     /// # Returns
-    /// - `Ok(TweakOption)` indicating the current state.
+    /// - `Ok(TweakOption)` indicating the current state, or `TweakOption::Mixed` with
+    ///   every value that was read if no option matches.
     /// - `Err(anyhow::Error)` if the operation fails.
     fn initial_state(&self) -> Result<TweakOption, anyhow::Error> {
         debug!(
@@ -186,47 +221,64 @@ impl TweakMethod for RegistryTweak<'_> {
             self.id
         );
 
+        let current_values = self.capture_values()?;
+        let current_value = |path: &str, key: &str| {
+            current_values
+                .iter()
+                .find(|saved| saved.path == path && saved.key == key)
+                .and_then(|saved| saved.value.as_ref())
+        };
+        // A `Deleted` modification matches a value that does not exist
+        let matches = |modification: &RegistryModification, current: Option<&RegistryKeyValue>| {
+            match &modification.value {
+                RegistryKeyValue::Deleted => current.is_none(),
+                value => current == Some(value),
+            }
+        };
+
         // Iterate through all possible options to find which one matches the current state
         for (option, modifications) in &self.options {
-            let mut all_match = true;
-            for modification in modifications {
-                match &modification.value {
-                    RegistryKeyValue::Deleted => {
-                        // For Deleted, ensure the value does not exist
-                        let exists =
-                            read_registry_value(modification.path, modification.key)?.is_some();
-                        if exists {
-                            all_match = false;
-                            break;
-                        }
-                    }
-                    _ => {
-                        // For other types, compare the current value with the expected value
-                        let current = read_registry_value(modification.path, modification.key)?;
-                        if current != Some(modification.value.clone()) {
-                            all_match = false;
-                            break;
-                        }
-                    }
-                }
-            }
+            let all_match = modifications.iter().all(|modification| {
+                matches(
+                    modification,
+                    current_value(modification.path, modification.key),
+                )
+            });
             if all_match {
                 tracing::debug!("{:?} -> Current state matches {:?}.", self.id, option);
                 return Ok(option.clone());
             }
         }
 
-        // If no matching option is found, consider it as Default
         tracing::debug!(
-            "{:?} -> Current state does not match any custom options. Reverting to Default.",
-            self.id
+            "{:?} -> Current state does not match any option: {:?}",
+            self.id,
+            current_values
         );
-        Ok(self
-            .options
-            .keys()
-            .next()
-            .context("No keys found in options")?
-            .clone())
+        let states = current_values
+            .iter()
+            .map(|saved| {
+                let current = saved.value.as_ref();
+                SettingState {
+                    setting: format!("{}\\{}", saved.path, saved.key),
+                    core: None,
+                    value: current.map(|value| value.to_string()),
+                    matches: self
+                        .options
+                        .iter()
+                        .filter(|(_, modifications)| {
+                            modifications.iter().any(|modification| {
+                                modification.path == saved.path
+                                    && modification.key == saved.key
+                                    && matches(modification, current)
+                            })
+                        })
+                        .map(|(option, _)| option.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(TweakOption::Mixed(states))
     }
 
     /// Applies the registry tweak based on the selected `TweakOption`.
@@ -250,20 +302,22 @@ impl TweakMethod for RegistryTweak<'_> {
         ))?;
         let mut applied_modifications = Vec::new();
 
+        // Capture the current values so that a failed apply can put them back exactly
+        let previous_values = self.capture_values()?;
+
         // Wrap the apply logic in a closure to handle errors and perform rollback
         let result: Result<(), anyhow::Error> = (|| -> Result<(), anyhow::Error> {
             for modification in modifications {
                 match &modification.value {
                     RegistryKeyValue::Deleted => {
                         // Delete the registry value
-                        delete_registry_value(modification.path, modification.key).with_context(
-                            || {
+                        self.write_value(modification.path, modification.key, None)
+                            .with_context(|| {
                                 format!(
                                     "{:?} -> Failed to delete value '{}' in '{}'",
                                     self.id, modification.key, modification.path
                                 )
-                            },
-                        )?;
+                            })?;
                         debug!(
                             "{:?} -> Deleted value '{}' in '{}'.",
                             self.id, modification.key, modification.path
@@ -271,10 +325,10 @@ impl TweakMethod for RegistryTweak<'_> {
                     }
                     _ => {
                         // Apply the tweak value using the helper function
-                        create_or_modify_registry_value(
+                        self.write_value(
                             modification.path,
                             modification.key,
-                            &modification.value,
+                            Some(&modification.value),
                         )
                         .with_context(|| {
                             format!(
@@ -302,8 +356,8 @@ impl TweakMethod for RegistryTweak<'_> {
                 self.id, e
             );
 
-            // Attempt to rollback by applying Default modifications
-            if let Err(rollback_err) = self.rollback("apply") {
+            // Attempt to rollback by restoring the values captured before the apply
+            if let Err(rollback_err) = self.write_values(&previous_values) {
                 // Rollback failed
                 error!(
                     "{:?} -> Failed to rollback after apply error: {}",
@@ -349,14 +403,13 @@ impl TweakMethod for RegistryTweak<'_> {
             match &modification.value {
                 RegistryKeyValue::Deleted => {
                     // Delete the registry value
-                    delete_registry_value(modification.path, modification.key).with_context(
-                        || {
+                    self.write_value(modification.path, modification.key, None)
+                        .with_context(|| {
                             format!(
                                 "{:?} -> Failed to delete value '{}' in '{}'",
                                 self.id, modification.key, modification.path
                             )
-                        },
-                    )?;
+                        })?;
                     debug!(
                         "{:?} -> Deleted value '{}' in '{}'.",
                         self.id, modification.key, modification.path
@@ -364,10 +417,10 @@ impl TweakMethod for RegistryTweak<'_> {
                 }
                 _ => {
                     // Apply the default value using the helper function
-                    create_or_modify_registry_value(
+                    self.write_value(
                         modification.path,
                         modification.key,
-                        &modification.value,
+                        Some(&modification.value),
                     )
                     .with_context(|| {
                         format!(
@@ -389,4 +442,111 @@ impl TweakMethod for RegistryTweak<'_> {
         );
         Ok(())
     }
+
+    /// Captures the current value of every registry value any option modifies.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(TweakSnapshot::Registry))` with one entry per value.
+    /// - `Err(anyhow::Error)` if any value cannot be read.
+    fn snapshot(&self) -> Result<Option<TweakSnapshot>> {
+        let values = self.capture_values()?;
+        debug!(
+            "{:?} -> Captured {} registry values.",
+            self.id,
+            values.len()
+        );
+        Ok(Some(TweakSnapshot::Registry(values)))
+    }
+
+    /// Writes back the registry values captured by `snapshot`.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if every value was restored.
+    /// - `Err(anyhow::Error)` if the snapshot is not a registry snapshot or any write fails.
+    fn restore(&self, snapshot: &TweakSnapshot) -> Result<()> {
+        let TweakSnapshot::Registry(values) = snapshot else {
+            anyhow::bail!("{:?} -> Backup is not a registry snapshot", self.id);
+        };
+        self.write_values(values)
+    }
+
+    /// Lists every registry value the option writes, with its current and new value.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(TweakPlan)` with one step per modification.
+    /// - `Err(anyhow::Error)` if the option does not exist or a current value cannot be read.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let modifications = self.options.get(option).with_context(|| {
+            format!(
+                "{:?} -> No registry modifications found for option: {:?}",
+                self.id, option
+            )
+        })?;
+
+        let steps = modifications
+            .iter()
+            .map(|modification| {
+                let current = try_read_registry_value(modification.path, modification.key)
+                    .with_context(|| {
+                        format!(
+                            "{:?} -> Failed to read value '{}' in '{}'",
+                            self.id, modification.key, modification.path
+                        )
+                    })?;
+                let new = match &modification.value {
+                    RegistryKeyValue::Deleted => None,
+                    value => Some(value.clone()),
+                };
+                Ok(PlanStep::RegistryValue {
+                    path: modification.path.to_string(),
+                    key: modification.key.to_string(),
+                    current,
+                    new,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(TweakPlan { steps })
+    }
+
+    fn journaled(&self) -> bool {
+        true
+    }
+
+    fn default_option(&self) -> Option<TweakOption> {
+        default_option_of(self.options.keys())
+    }
+
+    /// Checks that every path parses and that no option writes the same value twice.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (option, modifications) in &self.options {
+            let mut seen = HashSet::new();
+            for modification in modifications {
+                if let Err(e) = parse_registry_path(modification.path) {
+                    problems.push(format!("option {:?}: {:#}", option, e));
+                }
+                if !seen.insert((modification.path, modification.key)) {
+                    problems.push(format!(
+                        "option {:?} modifies '{}' in '{}' more than once",
+                        option, modification.key, modification.path
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Returns every registry value any option writes.
+    fn settings(&self) -> Vec<TweakSetting> {
+        self.touched_values()
+            .into_iter()
+            .map(|(path, key)| TweakSetting::RegistryValue {
+                path: path.to_lowercase(),
+                key: key.to_lowercase(),
+            })
+            .collect()
+    }
 }
@@ -1,10 +1,9 @@
 //  src/tweaks/registry/mod.rs
 
-use indexmap::IndexMap;
 use kernel::{additional_kernel_worker_threads, alchemy_kernel_tweak, thread_dpc_disable};
 use method::{RegistryModification, RegistryTweak};
 
-use super::{Tweak, TweakCategory, TweakOption};
+use super::{option_map, Tweak, TweakCategory, TweakOption};
 use crate::{tweaks::TweakId, utils::registry::RegistryKeyValue};
 
 mod kernel;
@@ -84,7 +83,7 @@ pub fn enable_large_system_cache<'a>() -> Tweak<'a> {
         TweakCategory::Memory,
         RegistryTweak {
             id: TweakId::LargeSystemCache,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -117,7 +116,7 @@ pub fn system_responsiveness<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::SystemResponsiveness,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Default".to_string()),
                     vec![RegistryModification {
@@ -155,7 +154,7 @@ pub fn disable_hw_acceleration<'a>() -> Tweak<'a> {
         TweakCategory::Graphics,
         RegistryTweak {
             id: TweakId::DisableHWAcceleration,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -200,7 +199,7 @@ pub fn win32_priority_separation<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::Win32PrioritySeparation,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Default".to_string()),
                     vec![RegistryModification {
@@ -323,7 +322,7 @@ pub fn disable_core_parking<'a>() -> Tweak<'a> {
         TweakCategory::Power,
         RegistryTweak {
             id: TweakId::DisableCoreParking,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -353,7 +352,7 @@ pub fn disable_low_disk_space_checks<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::NoLowDiskSpaceChecks,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -388,7 +387,7 @@ pub fn disable_windows_error_reporting<'a>() -> Tweak<'a> {
         TweakCategory::Telemetry,
         RegistryTweak {
             id: TweakId::DisableWindowsErrorReporting,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -418,7 +417,7 @@ pub fn dont_verify_random_drivers<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::DontVerifyRandomDrivers,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -451,7 +450,7 @@ pub fn disable_driver_paging<'a>() -> Tweak<'a> {
         TweakCategory::Memory,
         RegistryTweak {
             id: TweakId::DisableDriverPaging,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -461,7 +460,7 @@ pub fn disable_driver_paging<'a>() -> Tweak<'a> {
                     }],
                 ),
                 (
-                    TweakOption::Enabled(false),
+                    TweakOption::Enabled(true),
                     vec![RegistryModification {
                         path: "HKEY_LOCAL_MACHINE\\SYSTEM\\ControlSet001\\Control\\Session Manager\\Memory Management",
                         key: "DisablePagingExecutive",
@@ -486,7 +485,7 @@ pub fn configure_prefetcher_service<'a>() -> Tweak<'a> {
         TweakCategory::Services,
         RegistryTweak {
             id: TweakId::DisablePrefetcher,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Default".to_string()),
                     vec![RegistryModification {
@@ -516,7 +515,7 @@ pub fn configure_prefetcher_service<'a>() -> Tweak<'a> {
                     vec![RegistryModification {
                         path: "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Memory Management\\PrefetchParameters",
                         key: "EnablePrefetcher",
-                        value: RegistryKeyValue::Dword(1),
+                        value: RegistryKeyValue::Dword(0),
                     }],
                 ),
 
@@ -538,7 +537,7 @@ pub fn disable_application_telemetry<'a>() -> Tweak<'a> {
         TweakCategory::Telemetry,
         RegistryTweak {
             id: TweakId::DisableApplicationTelemetry,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -572,7 +571,7 @@ pub fn svc_host_split_threshold<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::SvcHostSplitThreshold,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Option("Default".to_string()),
                     vec![RegistryModification {
@@ -610,7 +609,7 @@ pub fn disable_windows_defender<'a>() -> Tweak<'a> {
         TweakCategory::Security,
         RegistryTweak {
             id: TweakId::DisableWindowsDefender,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -642,7 +641,7 @@ pub fn disable_page_file_encryption<'a>() -> Tweak<'a> {
         TweakCategory::Memory,
         RegistryTweak {
             id: TweakId::DisablePageFileEncryption,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -663,6 +662,8 @@ pub fn disable_page_file_encryption<'a>() -> Tweak<'a> {
         },
         true, // Requires reboot 
     )
+    // Without a page file there is nothing to encrypt
+    .conflicts_with(&[TweakId::DisablePagefile])
 }
 
 pub fn disable_intel_tsx<'a>() -> Tweak<'a> {
@@ -677,7 +678,7 @@ pub fn disable_intel_tsx<'a>() -> Tweak<'a> {
         TweakCategory::Security,
         RegistryTweak {
             id: TweakId::DisableIntelTSX,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -711,7 +712,7 @@ pub fn disable_windows_maintenance<'a>() -> Tweak<'a> {
         TweakCategory::System,
         RegistryTweak {
             id: TweakId::DisableWindowsMaintenance,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -743,7 +744,7 @@ pub fn disable_speculative_execution_mitigations<'a>() -> Tweak<'a> {
         TweakCategory::Security,
         RegistryTweak {
             id: TweakId::SpeculativeExecutionMitigations,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![
@@ -807,7 +808,7 @@ pub fn high_performance_visual_settings<'a>() -> Tweak<'a> {
         TweakCategory::Graphics,
         RegistryTweak {
             id: TweakId::HighPerformanceVisualSettings,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![
@@ -960,7 +961,7 @@ pub fn split_large_caches<'a>() -> Tweak<'a> {
         TweakCategory::Memory,
         RegistryTweak {
             id: TweakId::SplitLargeCaches,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -1000,7 +1001,7 @@ pub fn disable_protected_services<'a>() -> Tweak<'a> {
         TweakCategory::Services,
         RegistryTweak {
             id: TweakId::DisableProtectedServices,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![
@@ -1084,7 +1085,7 @@ pub fn disable_security_accounts_manager<'a>() -> Tweak<'a> {
         TweakCategory::Services,
         RegistryTweak {
             id: TweakId::DisableSecurityAccountsManager,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -1119,7 +1120,7 @@ pub fn disable_paging_combining<'a>() -> Tweak<'a> {
         TweakCategory::Memory,
         RegistryTweak {
             id: TweakId::DisablePagingCombining,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(false),
                     vec![RegistryModification {
@@ -1162,7 +1163,7 @@ pub fn enable_mcsss<'a>() -> Tweak<'a> {
         TweakCategory::Services,
         RegistryTweak {
             id: TweakId::EnableMcsss,
-            options: IndexMap::from_iter(vec![
+            options: option_map(vec![
                 (
                     TweakOption::Enabled(true),
                     vec![
@@ -1210,6 +1211,7 @@ pub fn enable_mcsss<'a>() -> Tweak<'a> {
         },
         true, // Correctly requires reboot
     )
+    .overlaps(&[TweakId::SystemResponsiveness])
 }
 
 #[cfg(test)]
@@ -0,0 +1,513 @@
+// src/tweaks/relations.rs
+
+//! How tweaks interact with each other.
+//!
+//! A tweak can declare that it `requires` another tweak to be enabled, that it
+//! `conflicts_with` a tweak that must not be enabled at the same time, or that it
+//! `overlaps` a tweak that changes some of the same settings. Conflicts and overlaps
+//! only need to be declared on one of the two tweaks.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Tweak, TweakId, TweakOption};
+use crate::backend::PciRegister;
+
+/// Relations a tweak declares with other tweaks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TweakRelations {
+    /// Tweaks that must be enabled for this one to have any effect.
+    pub requires: Vec<TweakId>,
+    /// Tweaks that must not be enabled at the same time as this one.
+    pub conflicts_with: Vec<TweakId>,
+    /// Tweaks that change some of the same settings as this one, so they must not be
+    /// applied at the same time and may undo each other.
+    pub overlaps: Vec<TweakId>,
+}
+
+/// A single setting a tweak writes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TweakSetting {
+    /// A registry value. The path is lowercased, as registry paths are case-insensitive.
+    RegistryValue { path: String, key: String },
+    /// A single bit of an MSR.
+    MsrBit { index: u32, bit: u32 },
+    /// A single bit of a PCI configuration space or SMN register.
+    PciBit { register: PciRegister, bit: u32 },
+    /// A Linux sysfs or procfs file, or every file a wildcard path matches.
+    SysfsFile { path: String },
+    /// An element of a boot entry.
+    BcdElement { entry: String, element: String },
+}
+
+/// What changing a tweak would mean for the tweaks related to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeCheck {
+    /// Enabled tweaks that conflict with the change. The change must not be made.
+    pub conflicts: Vec<TweakId>,
+    /// Required tweaks that are not enabled.
+    pub missing_requirements: Vec<TweakId>,
+    /// Changed tweaks that write some of the same settings.
+    pub overlaps: Vec<TweakId>,
+    /// Enabled tweaks that require the tweak being put back to its default. The change
+    /// must not be made.
+    pub required_by: Vec<TweakId>,
+}
+
+impl ChangeCheck {
+    /// Returns whether the change must not be made.
+    pub fn is_blocked(&self) -> bool {
+        !self.conflicts.is_empty() || !self.required_by.is_empty()
+    }
+
+    /// Describes why the change is blocked, or what to look out for.
+    ///
+    /// # Returns
+    /// One message per kind of relation that applies.
+    pub fn messages(&self, tweaks: &BTreeMap<TweakId, Tweak<'_>>) -> Vec<String> {
+        let names = |ids: &[TweakId]| {
+            ids.iter()
+                .map(|id| {
+                    tweaks
+                        .get(id)
+                        .map_or(id.to_string(), |t| t.name.to_string())
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut messages = Vec::new();
+        if !self.conflicts.is_empty() {
+            messages.push(format!("Conflicts with {}", names(&self.conflicts)));
+        }
+        if !self.required_by.is_empty() {
+            messages.push(format!("Required by {}", names(&self.required_by)));
+        }
+        if !self.missing_requirements.is_empty() {
+            messages.push(format!(
+                "Has no effect unless {} is enabled",
+                names(&self.missing_requirements)
+            ));
+        }
+        if !self.overlaps.is_empty() {
+            messages.push(format!(
+                "Changes some of the same settings as {}",
+                names(&self.overlaps)
+            ));
+        }
+        messages
+    }
+}
+
+/// Returns whether a tweak in the given state has been changed from its stock state,
+/// i.e. whether the state is one of its options other than its default.
+///
+/// `Run` and `Mixed` do not count, as neither leaves a known option applied.
+fn is_active(tweak: &Tweak<'_>, state: &TweakOption) -> bool {
+    !matches!(state, TweakOption::Run | TweakOption::Mixed(_))
+        && tweak.options.contains(state)
+        && tweak.method.default_option().as_ref() != Some(state)
+}
+
+/// Returns every tweak related to `id` by `relation`, declared on either side.
+pub fn related(
+    tweaks: &BTreeMap<TweakId, Tweak<'_>>,
+    id: TweakId,
+    relation: impl Fn(&TweakRelations) -> &Vec<TweakId>,
+) -> BTreeSet<TweakId> {
+    let mut ids: BTreeSet<TweakId> = tweaks
+        .get(&id)
+        .map(|tweak| relation(&tweak.relations).iter().copied().collect())
+        .unwrap_or_default();
+    for (other_id, other) in tweaks {
+        if relation(&other.relations).contains(&id) {
+            ids.insert(*other_id);
+        }
+    }
+    ids.remove(&id);
+    ids
+}
+
+/// Returns the current state of every tweak.
+pub fn current_states(tweaks: &BTreeMap<TweakId, Tweak<'_>>) -> BTreeMap<TweakId, TweakOption> {
+    tweaks
+        .iter()
+        .map(|(id, tweak)| (*id, tweak.state.clone()))
+        .collect()
+}
+
+/// Checks a change against the relations of the tweak.
+///
+/// # Parameters
+///
+/// - `tweaks`: Every tweak, for their declared relations.
+/// - `states`: The state each tweak will be in once the change is made, apart from
+///   the tweak being changed.
+/// - `id`: The tweak being changed.
+/// - `option`: The option it is changed to.
+pub fn check_change(
+    tweaks: &BTreeMap<TweakId, Tweak<'_>>,
+    states: &BTreeMap<TweakId, TweakOption>,
+    id: TweakId,
+    option: &TweakOption,
+) -> ChangeCheck {
+    let active = |other: &TweakId| {
+        tweaks
+            .get(other)
+            .zip(states.get(other))
+            .is_some_and(|(tweak, state)| is_active(tweak, state))
+    };
+    // Putting a tweak back to its default only matters to the tweaks that require it
+    if tweaks
+        .get(&id)
+        .is_some_and(|tweak| !is_active(tweak, option))
+    {
+        return ChangeCheck {
+            required_by: tweaks
+                .iter()
+                .filter(|(other, tweak)| tweak.relations.requires.contains(&id) && active(other))
+                .map(|(other, _)| *other)
+                .collect(),
+            ..Default::default()
+        };
+    }
+
+    ChangeCheck {
+        conflicts: related(tweaks, id, |r| &r.conflicts_with)
+            .into_iter()
+            .filter(|other| active(other))
+            .collect(),
+        missing_requirements: tweaks
+            .get(&id)
+            .map(|tweak| {
+                tweak
+                    .relations
+                    .requires
+                    .iter()
+                    .copied()
+                    .filter(|other| !active(other))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        overlaps: related(tweaks, id, |r| &r.overlaps)
+            .into_iter()
+            .filter(|other| active(other))
+            .collect(),
+        required_by: Vec::new(),
+    }
+}
+
+/// Checks a batch of changes against the states the whole batch leaves behind.
+///
+/// # Returns
+/// Every change that must not be made, with the check that blocked it.
+pub fn blocked_changes(
+    tweaks: &BTreeMap<TweakId, Tweak<'_>>,
+    changes: &[(TweakId, TweakOption)],
+) -> Vec<(TweakId, ChangeCheck)> {
+    let mut states = current_states(tweaks);
+    states.extend(changes.iter().cloned());
+
+    changes
+        .iter()
+        .map(|(id, option)| (*id, check_change(tweaks, &states, *id, option)))
+        .filter(|(_, check)| check.is_blocked())
+        .collect()
+}
+
+/// Splits a set of changes into sequences that can be applied concurrently.
+///
+/// Changes to related tweaks end up in the same sequence, so they are never applied
+/// at the same time. Within a sequence, tweaks being put back to their default come
+/// first, so that a conflicting tweak is disabled before the other one is enabled,
+/// followed by the other changes, each after the tweaks it requires.
+pub fn order_changes(
+    tweaks: &BTreeMap<TweakId, Tweak<'_>>,
+    changes: Vec<(TweakId, TweakOption)>,
+) -> Vec<Vec<(TweakId, TweakOption)>> {
+    let changed: BTreeSet<TweakId> = changes.iter().map(|(id, _)| *id).collect();
+    let neighbours = |id: TweakId| -> BTreeSet<TweakId> {
+        let mut ids = related(tweaks, id, |r| &r.requires);
+        ids.extend(related(tweaks, id, |r| &r.conflicts_with));
+        ids.extend(related(tweaks, id, |r| &r.overlaps));
+        ids.retain(|other| changed.contains(other));
+        ids
+    };
+
+    // Group the changed tweaks into connected components
+    let mut group_of: BTreeMap<TweakId, usize> = BTreeMap::new();
+    let mut group_count = 0;
+    for id in &changed {
+        if group_of.contains_key(id) {
+            continue;
+        }
+        let mut stack = vec![*id];
+        while let Some(current) = stack.pop() {
+            if group_of.insert(current, group_count).is_none() {
+                stack.extend(neighbours(current));
+            }
+        }
+        group_count += 1;
+    }
+
+    let mut groups: Vec<Vec<(TweakId, TweakOption)>> = vec![Vec::new(); group_count];
+    for (id, option) in changes {
+        groups[group_of[&id]].push((id, option));
+    }
+
+    for group in &mut groups {
+        let (mut ordered, mut enabling): (Vec<_>, Vec<_>) =
+            group.drain(..).partition(|(id, option)| {
+                tweaks
+                    .get(id)
+                    .is_some_and(|tweak| !is_active(tweak, option))
+            });
+
+        // Repeatedly take the first change whose requirements are already ordered
+        while !enabling.is_empty() {
+            let ready = enabling.iter().position(|(id, _)| {
+                tweaks.get(id).is_none_or(|tweak| {
+                    tweak
+                        .relations
+                        .requires
+                        .iter()
+                        .all(|required| !enabling.iter().any(|(other, _)| other == required))
+                })
+            });
+            // A requirement cycle cannot be ordered, so keep the given order
+            let next = ready.unwrap_or(0);
+            ordered.push(enabling.remove(next));
+        }
+        *group = ordered;
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::tweaks::all_tweaks;
+
+    #[test]
+    fn test_shared_settings_are_declared() {
+        let tweaks = all_tweaks();
+        let mut writers: BTreeMap<TweakSetting, Vec<TweakId>> = BTreeMap::new();
+        for (id, tweak) in &tweaks {
+            for setting in tweak.method.settings() {
+                writers.entry(setting).or_default().push(*id);
+            }
+        }
+
+        let mut undeclared = Vec::new();
+        for (setting, ids) in &writers {
+            for (i, id) in ids.iter().enumerate() {
+                for other in &ids[i + 1..] {
+                    let declared = related(&tweaks, *id, |r| &r.overlaps).contains(other)
+                        || related(&tweaks, *id, |r| &r.conflicts_with).contains(other);
+                    if !declared {
+                        undeclared.push(format!("{} and {} both write {:?}", id, other, setting));
+                    }
+                }
+            }
+        }
+
+        assert!(
+            undeclared.is_empty(),
+            "Tweaks share settings without declaring an overlap:\n{}",
+            undeclared.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_relations_refer_to_other_tweaks() {
+        let tweaks = all_tweaks();
+        for (id, tweak) in &tweaks {
+            let relations = &tweak.relations;
+            for other in relations
+                .requires
+                .iter()
+                .chain(&relations.conflicts_with)
+                .chain(&relations.overlaps)
+            {
+                assert_ne!(id, other, "{} is related to itself", id);
+                assert!(tweaks.contains_key(other), "{} refers to {}", id, other);
+            }
+        }
+    }
+
+    #[test]
+    fn test_conflicts_block_only_when_enabled() {
+        let tweaks = all_tweaks();
+        let mut states = current_states(&tweaks);
+
+        states.insert(TweakId::DisablePagefile, TweakOption::Enabled(false));
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::DisablePageFileEncryption,
+            &TweakOption::Enabled(true),
+        );
+        assert!(!check.is_blocked());
+
+        states.insert(TweakId::DisablePagefile, TweakOption::Enabled(true));
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::DisablePageFileEncryption,
+            &TweakOption::Enabled(true),
+        );
+        assert_eq!(check.conflicts, vec![TweakId::DisablePagefile]);
+
+        // A batch is checked against the states it leaves behind
+        let blocked = blocked_changes(
+            &tweaks,
+            &[
+                (TweakId::DisablePagefile, TweakOption::Enabled(true)),
+                (
+                    TweakId::DisablePageFileEncryption,
+                    TweakOption::Enabled(true),
+                ),
+            ],
+        );
+        assert_eq!(blocked.len(), 2, "{:?}", blocked);
+
+        // Turning the tweak off is never blocked
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::DisablePageFileEncryption,
+            &TweakOption::Enabled(false),
+        );
+        assert!(!check.is_blocked());
+    }
+
+    #[test]
+    fn test_requirements_block_reverts_only_when_required() {
+        let tweaks = all_tweaks();
+        let mut states = current_states(&tweaks);
+
+        states.insert(
+            TweakId::EnableMtrrFixedDramAttributes,
+            TweakOption::Enabled(false),
+        );
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::EnableMtrrFixedDramModification,
+            &TweakOption::Enabled(false),
+        );
+        assert_eq!(check, ChangeCheck::default());
+
+        states.insert(
+            TweakId::EnableMtrrFixedDramAttributes,
+            TweakOption::Enabled(true),
+        );
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::EnableMtrrFixedDramModification,
+            &TweakOption::Enabled(false),
+        );
+        assert_eq!(
+            check.required_by,
+            vec![TweakId::EnableMtrrFixedDramAttributes]
+        );
+        assert!(check.is_blocked());
+
+        // Reverting both together leaves nothing that needs the requirement
+        let blocked = blocked_changes(
+            &tweaks,
+            &[
+                (
+                    TweakId::EnableMtrrFixedDramModification,
+                    TweakOption::Enabled(false),
+                ),
+                (
+                    TweakId::EnableMtrrFixedDramAttributes,
+                    TweakOption::Enabled(false),
+                ),
+            ],
+        );
+        assert!(blocked.is_empty(), "{:?}", blocked);
+    }
+
+    #[test]
+    fn test_combo_box_at_default_is_inactive() {
+        let tweaks = all_tweaks();
+        let mut states = current_states(&tweaks);
+        let default = TweakOption::Option("Default".to_string());
+
+        states.insert(TweakId::SystemResponsiveness, default.clone());
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::EnableMcsss,
+            &TweakOption::Enabled(true),
+        );
+        assert_eq!(check, ChangeCheck::default());
+
+        states.insert(
+            TweakId::SystemResponsiveness,
+            TweakOption::Option("0".to_string()),
+        );
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::EnableMcsss,
+            &TweakOption::Enabled(true),
+        );
+        assert_eq!(check.overlaps, vec![TweakId::SystemResponsiveness]);
+
+        // Putting the combo box back to its default is never checked
+        let check = check_change(&tweaks, &states, TweakId::SystemResponsiveness, &default);
+        assert_eq!(check, ChangeCheck::default());
+
+        // A tweak without a default is inactive until one of its options is applied
+        states.insert(
+            TweakId::AggressivePrefetchProfile,
+            TweakOption::Mixed(Vec::new()),
+        );
+        let check = check_change(
+            &tweaks,
+            &states,
+            TweakId::DisableL1StridePrefetcher,
+            &TweakOption::Enabled(true),
+        );
+        assert_eq!(check, ChangeCheck::default());
+    }
+
+    #[test]
+    fn test_related_changes_are_ordered_together() {
+        let tweaks = all_tweaks();
+        let groups = order_changes(
+            &tweaks,
+            vec![
+                (
+                    TweakId::EnableMtrrFixedDramAttributes,
+                    TweakOption::Enabled(true),
+                ),
+                (TweakId::DisableHPET, TweakOption::Enabled(true)),
+                (TweakId::DisablePagefile, TweakOption::Enabled(false)),
+                (
+                    TweakId::EnableMtrrFixedDramModification,
+                    TweakOption::Enabled(true),
+                ),
+            ],
+        );
+
+        assert_eq!(groups.len(), 3, "{:?}", groups);
+        let mtrr = groups
+            .iter()
+            .find(|group| group.len() == 2)
+            .expect("the MTRR tweaks should be grouped");
+        assert_eq!(
+            mtrr.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![
+                TweakId::EnableMtrrFixedDramModification,
+                TweakId::EnableMtrrFixedDramAttributes
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// src/tweaks/validate.rs
+
+//! Static checks over tweak definitions.
+//!
+//! An option listed twice never gets this far: option maps are built with
+//! [`option_map`](super::option_map) or [`try_option_map`](super::try_option_map),
+//! which refuse it.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::{Tweak, TweakId, TweakOption};
+use crate::ui::TweakWidget;
+
+/// A problem found in the definition of a tweak.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionIssue {
+    pub id: TweakId,
+    pub message: String,
+}
+
+impl fmt::Display for DefinitionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.message)
+    }
+}
+
+/// Checks a single tweak definition.
+///
+/// # Returns
+/// Every problem found, or an empty list if the definition is valid.
+pub fn validate_tweak(id: TweakId, tweak: &Tweak<'_>) -> Vec<DefinitionIssue> {
+    let mut messages = Vec::new();
+
+    if tweak.options.is_empty() {
+        messages.push("has no options".to_string());
+    }
+
+    let is_toggle = *tweak.widget == TweakWidget::Toggle
+        || tweak
+            .options
+            .iter()
+            .any(|option| matches!(option, TweakOption::Enabled(_)));
+    if is_toggle {
+        for state in [true, false] {
+            if !tweak.options.contains(&TweakOption::Enabled(state)) {
+                messages.push(format!(
+                    "is a toggle but has no {:?} option",
+                    TweakOption::Enabled(state)
+                ));
+            }
+        }
+    }
+
+    messages.extend(tweak.method.validate());
+
+    messages
+        .into_iter()
+        .map(|message| DefinitionIssue { id, message })
+        .collect()
+}
+
+/// Checks every tweak in a catalog.
+///
+/// # Returns
+/// Every problem found, ordered by tweak ID.
+pub fn validate_tweaks(tweaks: &BTreeMap<TweakId, Tweak<'_>>) -> Vec<DefinitionIssue> {
+    tweaks
+        .iter()
+        .flat_map(|(id, tweak)| validate_tweak(*id, tweak))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+    use crate::{
+        tweaks::{
+            all_tweaks,
+            msr::method::{MSRTweak, MsrField},
+            registry::method::{RegistryModification, RegistryTweak},
+            TweakCategory,
+        },
+        utils::registry::RegistryKeyValue,
+    };
+
+    #[test]
+    fn test_builtin_tweaks_are_valid() {
+        let issues = validate_tweaks(&all_tweaks());
+        assert!(
+            issues.is_empty(),
+            "Invalid tweak definitions:\n{}",
+            issues
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_half_toggle_is_reported() {
+        let tweak = Tweak::msr_tweak(
+            "Test",
+            "",
+            TweakCategory::Cpu,
+            MSRTweak {
+                id: TweakId::DisableCoreWatchdogTimer,
+                readable: true,
+                options: IndexMap::from_iter(vec![(
+                    TweakOption::Enabled(false),
+                    vec![MsrField::bit(0xC001_0074, 0, false)],
+                )]),
+                supported_cpus: Vec::new(),
+                targets: Default::default(),
+            },
+        );
+
+        let issues = validate_tweak(TweakId::DisableCoreWatchdogTimer, &tweak);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(issues[0].message.contains("Enabled(true)"));
+    }
+
+    #[test]
+    fn test_method_problems_are_reported() {
+        let msr = Tweak::msr_tweak(
+            "Test",
+            "",
+            TweakCategory::Cpu,
+            MSRTweak {
+                id: TweakId::DisableAvx512,
+                readable: true,
+                options: IndexMap::from_iter(vec![
+                    (
+                        TweakOption::Enabled(false),
+                        vec![MsrField::bit(0xC001_1022, 64, false)],
+                    ),
+                    (TweakOption::Enabled(true), vec![]),
+                ]),
+                supported_cpus: Vec::new(),
+                targets: Default::default(),
+            },
+        );
+        let issues = validate_tweak(TweakId::DisableAvx512, &msr);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(issues[0].message.contains("64 bits"));
+
+        let registry = Tweak::registry_tweak(
+            "Test",
+            "",
+            TweakCategory::System,
+            RegistryTweak {
+                id: TweakId::DisableHPET,
+                options: IndexMap::from_iter(vec![(
+                    TweakOption::Run,
+                    vec![RegistryModification {
+                        path: "HKEY_NOWHERE\\Test",
+                        key: "Value",
+                        value: RegistryKeyValue::Dword(1),
+                    }],
+                )]),
+            },
+            false,
+        );
+        let issues = validate_tweak(TweakId::DisableHPET, &registry);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(issues[0].message.contains("HKEY_NOWHERE"));
+    }
+}
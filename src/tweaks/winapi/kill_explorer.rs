@@ -1,31 +1,19 @@
 // src/tweaks/definitions/kill_explorer.rs
 
-use std::{ffi::OsStr, os::windows::ffi::OsStrExt};
-
 use anyhow::Error;
-use tracing::{error, info};
-use windows::{
-    core::{PCWSTR, PWSTR},
-    Win32::{
-        Foundation::CloseHandle,
-        System::{
-            Diagnostics::ToolHelp::{
-                CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
-                TH32CS_SNAPPROCESS,
-            },
-            Threading::{
-                CreateProcessW, OpenProcess, TerminateProcess, PROCESS_CREATION_FLAGS,
-                PROCESS_INFORMATION, PROCESS_TERMINATE, STARTUPINFOW,
-            },
-        },
+use tracing::info;
+
+use crate::{
+    backend,
+    tweaks::{
+        plan::{PlanStep, TweakPlan},
+        TweakId, TweakMethod, TweakOption,
     },
-};
-use winreg::{
-    enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WRITE},
-    RegKey,
+    utils::registry::{create_or_modify_registry_value, try_read_registry_value, RegistryKeyValue},
 };
 
-use crate::tweaks::{TweakId, TweakMethod, TweakOption};
+const WINLOGON_KEY_PATH: &str =
+    r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows NT\CurrentVersion\Winlogon";
 
 /// Struct implementing the TweakMethod trait for killing Explorer.
 pub struct KillExplorerTweak {
@@ -75,6 +63,34 @@ impl TweakMethod for KillExplorerTweak {
         Ok(())
     }
 
+    /// Describes terminating Explorer, or starting it again when disabling, and the
+    /// change to AutoRestartShell.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan, Error> {
+        let enable = *option != TweakOption::Enabled(false);
+        let auto_restart_shell = PlanStep::RegistryValue {
+            path: WINLOGON_KEY_PATH.to_string(),
+            key: "AutoRestartShell".to_string(),
+            current: try_read_registry_value(WINLOGON_KEY_PATH, "AutoRestartShell")?,
+            new: Some(RegistryKeyValue::Dword(if enable { 0 } else { 1 })),
+        };
+        let steps = if enable {
+            vec![
+                PlanStep::Other {
+                    description: "Terminate every explorer.exe process".to_string(),
+                },
+                auto_restart_shell,
+            ]
+        } else {
+            vec![
+                auto_restart_shell,
+                PlanStep::Other {
+                    description: "Start explorer.exe".to_string(),
+                },
+            ]
+        };
+        Ok(TweakPlan { steps })
+    }
+
     /// Reverts the tweak: Allows Explorer to restart and starts it.
     fn revert(&self) -> Result<(), Error> {
         info!(
@@ -96,177 +112,35 @@ impl TweakMethod for KillExplorerTweak {
 
 /// Checks if any instances of explorer.exe are currently running.
 fn is_explorer_running() -> Result<bool, Error> {
-    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)? };
-    let mut entry = PROCESSENTRY32W {
-        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
-        ..Default::default()
-    };
-
-    let mut found = false;
-
-    if unsafe { Process32FirstW(snapshot, &mut entry).is_ok() } {
-        loop {
-            // Convert the wide string to a Rust String, trimming null terminators.
-            let exe_name_utf16: Vec<u16> = entry
-                .szExeFile
-                .iter()
-                .take_while(|&&c| c != 0)
-                .cloned()
-                .collect();
-            let exe_name = String::from_utf16_lossy(&exe_name_utf16);
-
-            if exe_name.eq_ignore_ascii_case("explorer.exe") {
-                found = true;
-                break;
-            }
-
-            if !unsafe { Process32NextW(snapshot, &mut entry).is_ok() } {
-                break;
-            }
-        }
-    }
-
-    if let Err(e) = unsafe { CloseHandle(snapshot) } {
-        error!("Failed to close snapshot handle: {:?}", e);
-    }
-
-    Ok(found)
+    backend::processes().is_running("explorer.exe")
 }
 
 /// Checks if the AutoRestartShell registry key is enabled.
 fn is_auto_restart_enabled() -> Result<bool, Error> {
-    // Access the HKEY_LOCAL_MACHINE hive.
-    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-    let key_path = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Winlogon";
-    let key = hklm.open_subkey_with_flags(key_path, KEY_READ)?;
-
-    // Retrieve the value of AutoRestartShell.
-    let auto_restart_shell: Result<u32, _> = key.get_value("AutoRestartShell");
-    match auto_restart_shell {
-        Ok(value) => Ok(value == 1),
-        Err(_) => Ok(false), // Default to false if the key is not set.
-    }
+    // Default to false if the value is not set.
+    Ok(matches!(
+        try_read_registry_value(WINLOGON_KEY_PATH, "AutoRestartShell")?,
+        Some(RegistryKeyValue::Dword(1))
+    ))
 }
 
 /// Sets the AutoRestartShell registry key.
 ///
 /// - `enable`: If `true`, sets AutoRestartShell to 1. Otherwise, sets it to 0.
 fn set_auto_restart_shell(enable: bool) -> Result<(), Error> {
-    // Access the HKEY_LOCAL_MACHINE hive.
-    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-    let key_path = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Winlogon";
-    let (key, _) = hklm.create_subkey_with_flags(key_path, KEY_WRITE)?;
-
-    // Set the value of AutoRestartShell.
-    key.set_value("AutoRestartShell", &if enable { 1u32 } else { 0u32 })?;
-
-    Ok(())
+    create_or_modify_registry_value(
+        WINLOGON_KEY_PATH,
+        "AutoRestartShell",
+        &RegistryKeyValue::Dword(if enable { 1 } else { 0 }),
+    )
 }
 
 /// Terminates all running instances of explorer.exe.
 fn kill_explorer_processes() -> Result<(), Error> {
-    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)? };
-    let mut entry = PROCESSENTRY32W {
-        dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
-        ..Default::default()
-    };
-
-    if unsafe { Process32FirstW(snapshot, &mut entry).is_ok() } {
-        loop {
-            // Convert the wide string to a Rust String, trimming null terminators.
-            let exe_name_utf16: Vec<u16> = entry
-                .szExeFile
-                .iter()
-                .take_while(|&&c| c != 0)
-                .cloned()
-                .collect();
-            let exe_name = String::from_utf16_lossy(&exe_name_utf16);
-
-            if exe_name.eq_ignore_ascii_case("explorer.exe") {
-                // Open the process with termination rights.
-                let process_handle =
-                    unsafe { OpenProcess(PROCESS_TERMINATE, false, entry.th32ProcessID)? };
-
-                if !process_handle.is_invalid() {
-                    // Terminate the process.
-                    let success = unsafe { TerminateProcess(process_handle, 0).is_ok() };
-                    if success {
-                        info!("Terminated explorer.exe with PID {}", entry.th32ProcessID);
-                    } else {
-                        error!(
-                            "Failed to terminate explorer.exe with PID {}",
-                            entry.th32ProcessID
-                        );
-                    }
-
-                    // Close the process handle.
-                    if let Err(e) = unsafe { CloseHandle(process_handle) } {
-                        error!("Failed to close process handle: {:?}", e);
-                    }
-                } else {
-                    error!(
-                        "Failed to open explorer.exe with PID {}",
-                        entry.th32ProcessID
-                    );
-                }
-            }
-
-            if !unsafe { Process32NextW(snapshot, &mut entry).is_ok() } {
-                break;
-            }
-        }
-    }
-
-    if let Err(e) = unsafe { CloseHandle(snapshot) } {
-        error!("Failed to close snapshot handle: {:?}", e);
-    }
-
-    Ok(())
+    backend::processes().terminate("explorer.exe")
 }
 
 /// Starts the explorer.exe process.
 fn start_explorer() -> Result<(), Error> {
-    let application_name = "C:\\Windows\\explorer.exe";
-    let application_name_wide: Vec<u16> = OsStr::new(application_name)
-        .encode_wide()
-        .chain(std::iter::once(0)) // Null terminator.
-        .collect();
-
-    let mut startup_info: STARTUPINFOW = unsafe { std::mem::zeroed() };
-    startup_info.cb = std::mem::size_of::<STARTUPINFOW>() as u32;
-    let mut process_info: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
-
-    // Create the process.
-    let result = unsafe {
-        CreateProcessW(
-            PCWSTR(application_name_wide.as_ptr()),
-            PWSTR(std::ptr::null_mut()), // lpCommandLine is optional when lpApplicationName is provided.
-            None,
-            None,
-            false,
-            PROCESS_CREATION_FLAGS(0),
-            None,
-            None,
-            &startup_info,
-            &mut process_info,
-        )
-    };
-
-    if result.is_err() {
-        return Err(Error::msg("Failed to start explorer.exe"));
-    }
-
-    // Close process and thread handles to prevent handle leaks.
-    unsafe {
-        if CloseHandle(process_info.hProcess).is_err() {
-            if let Err(e) = CloseHandle(process_info.hThread) {
-                error!("Failed to close thread handle: {:?}", e);
-            }
-        }
-        if let Err(e) = CloseHandle(process_info.hThread) {
-            error!("Failed to close thread handle: {:?}", e);
-        }
-    }
-
-    Ok(())
+    backend::processes().start("C:\\Windows\\explorer.exe")
 }
@@ -44,10 +44,13 @@ pub fn ultimate_performance_plan<'a>() -> Tweak<'a> {
 pub fn low_res_mode<'a>() -> Tweak<'a> {
     let method = LowResMode::default();
 
-    let formatted_description = format!(
+    let formatted_description = match &method.target_state {
+        Some(target) => format!(
             "Sets the display to lower resolution and refresh rate to reduce GPU load and improve performance -> {}x{} @{}hz.",
-            method.target_state.width, method.target_state.height, method.target_state.refresh_rate
-        );
+            target.width, target.height, target.refresh_rate
+        ),
+        None => "Sets the display to lower resolution and refresh rate to reduce GPU load and improve performance.".to_string(),
+    };
     let description: &'a str = Box::leak(formatted_description.into_boxed_str());
 
     Tweak::winapi(
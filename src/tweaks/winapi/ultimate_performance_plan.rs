@@ -1,41 +1,59 @@
 // src/tweaks/definitions/ultimate_performance_plan.rs
 
-use anyhow::Result;
-use windows::core::GUID;
+use anyhow::{Context, Result};
 
 use crate::{
     power::{
         duplicate_power_scheme, get_active_power_scheme, get_all_power_schemes,
         set_active_power_scheme, PowerScheme,
     },
-    tweaks::{TweakId, TweakMethod, TweakOption},
+    tweaks::{plan::TweakPlan, TweakId, TweakMethod, TweakOption},
 };
 
-const ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID: GUID =
-    GUID::from_u128(0xe9a42b02_d5df_448d_aa00_03f14749eb61);
+const ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID: u128 = 0xe9a42b02_d5df_448d_aa00_03f14749eb61;
+const ULTIMATE_PERFORMANCE_POWER_SCHEME_NAME: &str = "Ultimate Performance";
 
 pub struct UltimatePerformancePlan {
     id: TweakId,
-    default_power_plan: PowerScheme,
+    /// The power plan that was active at startup, which reverting switches back to.
+    default_power_plan: Option<PowerScheme>,
 }
 
 impl UltimatePerformancePlan {
     pub fn new() -> Self {
+        let id = TweakId::UltimatePerformancePlan;
+        let default_power_plan = get_active_power_scheme()
+            .map_err(|e| {
+                tracing::warn!("{:?} -> Failed to read the active power plan: {:?}", id, e)
+            })
+            .ok();
         Self {
-            id: TweakId::UltimatePerformancePlan,
-            default_power_plan: get_active_power_scheme().unwrap(),
+            id,
+            default_power_plan,
         }
     }
+
+    /// Returns the power plan that was active at startup.
+    fn default_power_plan(&self) -> Result<&PowerScheme> {
+        self.default_power_plan.as_ref().with_context(|| {
+            format!(
+                "{:?} -> The power plan that was active at startup is unknown",
+                self.id
+            )
+        })
+    }
 }
 
 impl TweakMethod for UltimatePerformancePlan {
+    /// Checks whether the active power plan is Ultimate Performance, either the
+    /// built-in scheme or a copy of it.
     fn initial_state(&self) -> Result<TweakOption> {
         tracing::debug!("{:?}-> Checking initial state", self.id);
-        if self.default_power_plan.guid == ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID {
-            Ok(TweakOption::Enabled(true))
-        } else {
-            Ok(TweakOption::Enabled(false))
-        }
+        let active = get_active_power_scheme()?;
+        Ok(TweakOption::Enabled(
+            active.guid == ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID
+                || active.name == ULTIMATE_PERFORMANCE_POWER_SCHEME_NAME,
+        ))
     }
 
     fn apply(&self, _option: TweakOption) -> Result<()> {
@@ -46,21 +64,21 @@ impl TweakMethod for UltimatePerformancePlan {
             .find(|scheme| scheme.guid == ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID)
         {
             Some(scheme) => {
-                set_active_power_scheme(&scheme.guid)
+                set_active_power_scheme(scheme.guid)
                     .expect("Failed to set ultimate performance power scheme");
             }
             None => {
                 // create the ultimate performance power plan
-                duplicate_power_scheme(&ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID)
+                duplicate_power_scheme(ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID)
                     .expect("Failed to duplicate ultimate performance power plan");
                 let available_schemes =
                     get_all_power_schemes().expect("Failed to get power schemes");
                 match available_schemes
                     .iter()
-                    .find(|scheme| scheme.name == "Ultimate Performance")
+                    .find(|scheme| scheme.name == ULTIMATE_PERFORMANCE_POWER_SCHEME_NAME)
                 {
                     Some(scheme) => {
-                        set_active_power_scheme(&scheme.guid).expect("Failed to set power scheme");
+                        set_active_power_scheme(scheme.guid).expect("Failed to set power scheme");
                     }
                     None => {
                         return Err(anyhow::anyhow!(
@@ -75,8 +93,34 @@ impl TweakMethod for UltimatePerformancePlan {
         Ok(())
     }
 
+    /// Describes the power plan switch.
+    fn plan(&self, option: &TweakOption) -> Result<TweakPlan> {
+        let current = get_active_power_scheme()?;
+        let description = if *option == TweakOption::Enabled(false) {
+            format!(
+                "Power: switch from the '{}' power plan to '{}'",
+                current.name,
+                self.default_power_plan()?.name
+            )
+        } else if get_all_power_schemes()?
+            .iter()
+            .any(|scheme| scheme.guid == ULTIMATE_PERFORMANCE_POWER_SCHEME_GUID)
+        {
+            format!(
+                "Power: switch from the '{}' power plan to 'Ultimate Performance'",
+                current.name
+            )
+        } else {
+            format!(
+                "Power: create the 'Ultimate Performance' power plan and switch to it from '{}'",
+                current.name
+            )
+        };
+        Ok(TweakPlan::described(description))
+    }
+
     fn revert(&self) -> Result<(), anyhow::Error> {
-        set_active_power_scheme(&self.default_power_plan.guid)?;
+        set_active_power_scheme(self.default_power_plan()?.guid)?;
         tracing::debug!("{:?}-> Reverted Ultimate Performance power plan", self.id);
         Ok(())
     }
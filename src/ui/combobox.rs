@@ -6,7 +6,8 @@ use crate::{
     tweaks::{TweakId, TweakOption},
 };
 
-/// A simple wrapper around egui's ComboBox
+/// A simple wrapper around egui's ComboBox. A selected index past the last option
+/// shows the tweak as "Mixed".
 pub struct SettingsComboBox<'a> {
     id_source: String,
     selected_index: &'a mut usize,
@@ -42,7 +43,10 @@ impl Widget for SettingsComboBox<'_> {
             options_text: options,
         } = self;
 
-        let selected_text = options.get(*selected_index).cloned().unwrap();
+        let selected_text = options
+            .get(*selected_index)
+            .cloned()
+            .unwrap_or_else(|| "Mixed".to_string());
 
         let original_selected_index = *selected_index;
 
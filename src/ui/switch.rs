@@ -6,6 +6,7 @@ const TOGGLE_DIMENSIONS: Vec2 = vec2(2.0, 1.0);
 pub struct ToggleSwitch<'a> {
     state: &'a mut bool,
     has_error: bool,
+    is_mixed: bool,
 }
 
 impl<'a> ToggleSwitch<'a> {
@@ -13,6 +14,7 @@ impl<'a> ToggleSwitch<'a> {
         Self {
             state,
             has_error: false,
+            is_mixed: false,
         }
     }
 
@@ -20,6 +22,12 @@ impl<'a> ToggleSwitch<'a> {
         self.has_error = has_error;
         self
     }
+
+    /// Draws the switch half way, for a tweak that is neither fully on nor off.
+    pub fn with_mixed(mut self, is_mixed: bool) -> Self {
+        self.is_mixed = is_mixed;
+        self
+    }
 }
 
 impl eframe::egui::Widget for ToggleSwitch<'_> {
@@ -36,7 +44,11 @@ impl eframe::egui::Widget for ToggleSwitch<'_> {
 
         // Draw the toggle based on the current state.
         if ui.is_rect_visible(rect) {
-            let how_on = ui.ctx().animate_bool_responsive(response.id, *self.state);
+            let how_on = if self.is_mixed {
+                0.5
+            } else {
+                ui.ctx().animate_bool_responsive(response.id, *self.state)
+            };
             let mut visuals = ui.style().interact_selectable(&response, *self.state);
 
             // Modify the visuals if there's an error
@@ -0,0 +1,264 @@
+// src/utils/bcd.rs
+
+//! Boot entries of the boot configuration data (BCD) store, as `bcdedit /enum /v`
+//! prints them.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+
+/// The entry Windows booted from, which `bcdedit` accepts in place of its GUID.
+pub const CURRENT_ENTRY: &str = "{current}";
+
+/// Elements holding a boolean, whose value `bcdedit` prints in the display language,
+/// e.g. `Ja` or `Nein` in German. Enumerated values such as `OptIn` are not translated.
+const BOOLEAN_ELEMENTS: &[&str] = &[
+    "bootdebug",
+    "bootems",
+    "bootlog",
+    "debug",
+    "disabledynamictick",
+    "disableelamdrivers",
+    "ems",
+    "nocrashautoreboot",
+    "nointegritychecks",
+    "novesa",
+    "novga",
+    "quietboot",
+    "recoveryenabled",
+    "sos",
+    "testsigning",
+    "usefirmwarepcisettings",
+    "useplatformclock",
+    "useplatformtick",
+    "vga",
+];
+
+/// "No" in each display language of Windows. Any other value of a boolean element
+/// means yes.
+const NEGATIVES: &[&str] = &[
+    "No",
+    "Nein",
+    "Non",
+    "Não",
+    "Nee",
+    "Nej",
+    "Nei",
+    "Ei",
+    "Nie",
+    "Ne",
+    "Nem",
+    "Nu",
+    "Nē",
+    "Hayır",
+    "Όχι",
+    "Нет",
+    "Ні",
+    "Не",
+    "いいえ",
+    "아니요",
+    "否",
+    "לא",
+    "لا",
+    "ไม่",
+];
+
+/// One entry of the BCD store, e.g. a Windows Boot Loader.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BcdEntry {
+    /// The heading of the entry, e.g. `Windows Boot Loader`.
+    pub kind: String,
+    /// The GUID of the entry in braces, or a well-known name such as `{bootmgr}`.
+    pub identifier: String,
+    /// The value of every element set on the entry, by element name in lower case.
+    /// Elements holding a list, such as `displayorder`, have one item per line, and
+    /// booleans are `Yes` or `No` whatever the display language.
+    pub elements: BTreeMap<String, String>,
+}
+
+impl BcdEntry {
+    /// Returns the value of an element, or `None` if the entry does not set it.
+    pub fn get(&self, element: &str) -> Option<&str> {
+        self.elements
+            .get(&element.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// Parses the output of `bcdedit /enum /v`.
+///
+/// Each entry is a heading underlined with dashes, followed by one element per line:
+/// its name, then its value after a run of spaces. A line starting with whitespace
+/// continues the list of the element above it. The first element is the identifier,
+/// whose name is translated in other display languages, as are the values of boolean
+/// elements; those are read back as `Yes` or `No`.
+///
+/// # Returns
+///
+/// - `Ok(Vec<BcdEntry>)` with every entry, in the order printed.
+/// - `Err(anyhow::Error)` if an element appears outside an entry.
+pub fn parse_bcdedit_enum(output: &str) -> Result<Vec<BcdEntry>> {
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    let mut entries: Vec<BcdEntry> = Vec::new();
+    let mut last_element: Option<String> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() || is_underline(line) {
+            continue;
+        }
+        if lines.get(i + 1).is_some_and(|next| is_underline(next)) {
+            entries.push(BcdEntry {
+                kind: line.trim().to_string(),
+                ..Default::default()
+            });
+            last_element = None;
+            continue;
+        }
+        let Some(entry) = entries.last_mut() else {
+            bail!("Element '{}' appears before any boot entry", line.trim());
+        };
+
+        if line.starts_with(char::is_whitespace) {
+            let Some(element) = &last_element else {
+                bail!("Value '{}' does not follow an element", line.trim());
+            };
+            let value = entry.elements.entry(element.clone()).or_default();
+            value.push('\n');
+            value.push_str(line.trim());
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once(char::is_whitespace)
+            .map_or((*line, ""), |(name, value)| (name, value.trim()));
+        if entry.identifier.is_empty() {
+            entry.identifier = value.to_string();
+            last_element = None;
+            continue;
+        }
+        let name = name.to_ascii_lowercase();
+        let value = if BOOLEAN_ELEMENTS.contains(&name.as_str()) {
+            english_boolean(value)
+        } else {
+            value
+        };
+        entry.elements.insert(name.clone(), value.to_string());
+        last_element = Some(name);
+    }
+    Ok(entries)
+}
+
+/// Returns the value of a boolean element as `bcdedit` prints it in English.
+fn english_boolean(value: &str) -> &'static str {
+    let value = value.to_lowercase();
+    if NEGATIVES.iter().any(|no| no.to_lowercase() == value) {
+        "No"
+    } else {
+        "Yes"
+    }
+}
+
+/// Returns whether a line underlines an entry heading.
+fn is_underline(line: &str) -> bool {
+    !line.is_empty() && line.chars().all(|c| c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `bcdedit /enum /v` on a Windows 11 install with a few timer elements set.
+    const ENUM_OUTPUT: &str = r"
+Windows Boot Manager
+--------------------
+identifier              {9dea862c-5cdd-4e70-acc1-f32b344d4795}
+device                  partition=\Device\HarddiskVolume1
+path                    \EFI\Microsoft\Boot\bootmgfw.efi
+description             Windows Boot Manager
+locale                  en-US
+default                 {b2721d73-1db4-4c62-bf78-c548a880142d}
+displayorder            {b2721d73-1db4-4c62-bf78-c548a880142d}
+                        {a5a30fa2-3d06-4e9f-b5f4-a01df9d1fcba}
+timeout                 30
+
+Windows Boot Loader
+-------------------
+identifier              {b2721d73-1db4-4c62-bf78-c548a880142d}
+device                  partition=C:
+path                    \Windows\system32\winload.efi
+description             Windows 11
+locale                  en-US
+recoveryenabled         Yes
+osdevice                partition=C:
+systemroot              \Windows
+nx                      OptIn
+bootmenupolicy          Standard
+hypervisorlaunchtype    Off
+useplatformclock        Yes
+disabledynamictick      Yes
+tscsyncpolicy           Enhanced
+";
+
+    #[test]
+    fn test_bcdedit_output_is_parsed_into_entries() {
+        let entries = parse_bcdedit_enum(ENUM_OUTPUT).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let manager = &entries[0];
+        assert_eq!(manager.kind, "Windows Boot Manager");
+        assert_eq!(manager.identifier, "{9dea862c-5cdd-4e70-acc1-f32b344d4795}");
+        assert_eq!(
+            manager.get("displayorder"),
+            Some("{b2721d73-1db4-4c62-bf78-c548a880142d}\n{a5a30fa2-3d06-4e9f-b5f4-a01df9d1fcba}")
+        );
+        assert_eq!(manager.get("timeout"), Some("30"));
+
+        let loader = &entries[1];
+        assert_eq!(loader.kind, "Windows Boot Loader");
+        assert_eq!(loader.get("description"), Some("Windows 11"));
+        assert_eq!(loader.get("path"), Some(r"\Windows\system32\winload.efi"));
+        assert_eq!(loader.get("nx"), Some("OptIn"));
+        assert_eq!(loader.get("UsePlatformClock"), Some("Yes"));
+        assert_eq!(loader.get("tscsyncpolicy"), Some("Enhanced"));
+        assert_eq!(loader.get("useplatformtick"), None);
+        assert!(!loader.elements.contains_key("identifier"));
+
+        // The identifier is the first element whatever its name, which is translated
+        let german = "Windows-Start-Manager\n---------------------\nBezeichner              {bootmgr}\ntimeout                 30\n";
+        let entries = parse_bcdedit_enum(german).unwrap();
+        assert_eq!(entries[0].identifier, "{bootmgr}");
+        assert_eq!(entries[0].get("timeout"), Some("30"));
+
+        assert!(parse_bcdedit_enum("").unwrap().is_empty());
+        assert!(parse_bcdedit_enum("nx OptIn\n").is_err());
+    }
+
+    #[test]
+    fn test_translated_booleans_are_read_in_english() {
+        let german = r"
+Windows-Startladeprogramm
+-------------------------
+Bezeichner              {current}
+description             Windows 11
+recoveryenabled         Ja
+nx                      OptIn
+useplatformclock        Ja
+disabledynamictick      Nein
+useplatformtick         nein
+";
+        let entry = &parse_bcdedit_enum(german).unwrap()[0];
+        assert_eq!(entry.identifier, "{current}");
+        assert_eq!(entry.get("recoveryenabled"), Some("Yes"));
+        assert_eq!(entry.get("useplatformclock"), Some("Yes"));
+        assert_eq!(entry.get("disabledynamictick"), Some("No"));
+        assert_eq!(entry.get("useplatformtick"), Some("No"));
+        // Only booleans are translated
+        assert_eq!(entry.get("nx"), Some("OptIn"));
+        assert_eq!(entry.get("description"), Some("Windows 11"));
+
+        let french = "Chargeur de démarrage Windows\n-----------------------------\nidentificateur          {current}\nuseplatformclock        Non\nbootlog                 Oui\n";
+        let entry = &parse_bcdedit_enum(french).unwrap()[0];
+        assert_eq!(entry.get("useplatformclock"), Some("No"));
+        assert_eq!(entry.get("bootlog"), Some("Yes"));
+    }
+}
@@ -1,26 +1,706 @@
 // src/utils/cpu.rs
 
-use once_cell::sync::Lazy;
+use std::{collections::BTreeSet, fmt, ops::RangeInclusive};
 
-#[derive(Debug, Clone, Copy)]
+use anyhow::{Context, Result};
+use raw_cpuid::{CpuId, CpuIdReader};
+use serde::{Deserialize, Serialize};
+
+use crate::backend;
+
+/// The processor the program runs on: what it is, and how its logical processors are laid out.
+#[derive(Debug, Clone)]
 pub struct CpuInfo {
-    pub cores: usize,
-    pub _threads: usize,
+    pub identity: CpuIdentity,
+    pub topology: CpuTopology,
 }
 
-impl Default for CpuInfo {
-    fn default() -> Self {
-        Self::new()
+impl CpuInfo {
+    /// Identifies the processor and reads its topology through the installed backends.
+    pub fn detect() -> Result<Self> {
+        Ok(Self {
+            identity: CpuIdentity::detect(),
+            topology: CpuTopology::detect()?,
+        })
     }
 }
 
-impl CpuInfo {
-    pub fn new() -> Self {
-        CpuInfo {
-            cores: num_cpus::get(),
-            _threads: num_cpus::get_physical(),
+/// The address Windows gives a logical processor: its processor group, and its index
+/// within that group. A group holds at most 64 logical processors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProcessorNumber {
+    pub group: u16,
+    pub index: u8,
+}
+
+impl fmt::Display for ProcessorNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "group {} index {}", self.group, self.index)
+    }
+}
+
+/// One logical processor, and where it sits in the processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogicalCpu {
+    /// Position among every logical processor in the system, counting group by group.
+    /// This is the CPU number shown in plans, journals and backups.
+    pub number: usize,
+    pub processor: ProcessorNumber,
+    /// The physical core, shared with any SMT sibling.
+    pub core: usize,
+    /// The core complex: the cores sharing an L3 cache.
+    pub ccx: usize,
+    /// The core complex die.
+    pub ccd: usize,
+}
+
+impl fmt::Display for LogicalCpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CPU {} ({})", self.number, self.processor)
+    }
+}
+
+/// A set of logical processors within one processor group, as Windows reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupMask {
+    pub group: u16,
+    pub mask: u64,
+}
+
+impl GroupMask {
+    /// Returns whether the set holds the logical processor.
+    pub fn contains(&self, processor: ProcessorNumber) -> bool {
+        self.group == processor.group
+            && processor.index < 64
+            && self.mask & (1u64 << processor.index) != 0
+    }
+}
+
+/// Every logical processor in the system, in `number` order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuTopology {
+    pub cpus: Vec<LogicalCpu>,
+}
+
+impl CpuTopology {
+    /// Reads the topology through the installed backend.
+    pub fn detect() -> Result<Self> {
+        backend::topology().topology()
+    }
+
+    /// Lays out the logical processors of each physical core and of each L3 cache, as
+    /// Windows reports them. Processors are numbered group by group, and every
+    /// `ccx_per_ccd` L3 caches in order make up one die.
+    pub fn from_masks(
+        cores: &[GroupMask],
+        l3_caches: &[Vec<GroupMask>],
+        ccx_per_ccd: usize,
+    ) -> Self {
+        let mut processors: Vec<(ProcessorNumber, usize)> = cores
+            .iter()
+            .enumerate()
+            .flat_map(|(core, mask)| {
+                (0..64u8)
+                    .filter(move |index| mask.mask & (1u64 << index) != 0)
+                    .map(move |index| {
+                        (
+                            ProcessorNumber {
+                                group: mask.group,
+                                index,
+                            },
+                            core,
+                        )
+                    })
+            })
+            .collect();
+        processors.sort();
+
+        let cpus = processors
+            .into_iter()
+            .enumerate()
+            .map(|(number, (processor, core))| {
+                let ccx = l3_caches
+                    .iter()
+                    .position(|masks| masks.iter().any(|mask| mask.contains(processor)))
+                    .unwrap_or(0);
+                LogicalCpu {
+                    number,
+                    processor,
+                    core,
+                    ccx,
+                    ccd: ccx / ccx_per_ccd.max(1),
+                }
+            })
+            .collect();
+        Self { cpus }
+    }
+
+    /// A topology of `ccds` dies, each with `ccx_per_ccd` core complexes of
+    /// `cores_per_ccx` cores running `threads_per_core` threads. Processors fill groups
+    /// of 64 in order, for tests and fakes.
+    pub fn uniform(
+        ccds: usize,
+        ccx_per_ccd: usize,
+        cores_per_ccx: usize,
+        threads_per_core: usize,
+    ) -> Self {
+        let address = |number: usize| ProcessorNumber {
+            group: (number / 64) as u16,
+            index: (number % 64) as u8,
+        };
+        let masks = |first: usize, count: usize| {
+            let mut masks: Vec<GroupMask> = Vec::new();
+            for processor in (first..first + count).map(address) {
+                match masks.last_mut() {
+                    Some(mask) if mask.group == processor.group => {
+                        mask.mask |= 1u64 << processor.index
+                    }
+                    _ => masks.push(GroupMask {
+                        group: processor.group,
+                        mask: 1u64 << processor.index,
+                    }),
+                }
+            }
+            masks
+        };
+
+        let threads_per_ccx = cores_per_ccx * threads_per_core;
+        let cores: Vec<GroupMask> = (0..ccds * ccx_per_ccd * cores_per_ccx)
+            .flat_map(|core| masks(core * threads_per_core, threads_per_core))
+            .collect();
+        let l3_caches: Vec<Vec<GroupMask>> = (0..ccds * ccx_per_ccd)
+            .map(|ccx| masks(ccx * threads_per_ccx, threads_per_ccx))
+            .collect();
+        Self::from_masks(&cores, &l3_caches, ccx_per_ccd)
+    }
+
+    /// Returns the logical processor with the given number.
+    pub fn cpu(&self, number: usize) -> Result<&LogicalCpu> {
+        self.cpus.get(number).with_context(|| {
+            format!(
+                "CPU {} does not exist; the system has {}",
+                number,
+                self.cpus.len()
+            )
+        })
+    }
+
+    /// Returns the number of core complex dies.
+    pub fn ccd_count(&self) -> usize {
+        self.cpus.iter().map(|cpu| cpu.ccd + 1).max().unwrap_or(0)
+    }
+}
+
+/// The logical processors a per-core tweak is applied on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CoreSelection {
+    /// Every logical processor.
+    #[default]
+    All,
+    /// Every logical processor of the given core complex dies.
+    Ccds(BTreeSet<usize>),
+    /// The logical processors with the given numbers.
+    Cpus(BTreeSet<usize>),
+}
+
+impl CoreSelection {
+    /// Describes a set of logical processors by the simplest selection that covers
+    /// exactly them: every processor, whole dies, or the processors themselves.
+    pub fn from_cpus(topology: &CpuTopology, cpus: &BTreeSet<usize>) -> Self {
+        if topology.cpus.iter().all(|cpu| cpus.contains(&cpu.number)) {
+            return CoreSelection::All;
+        }
+        let ccds: BTreeSet<usize> = topology
+            .cpus
+            .iter()
+            .filter(|cpu| cpus.contains(&cpu.number))
+            .map(|cpu| cpu.ccd)
+            .collect();
+        let whole_ccds = topology
+            .cpus
+            .iter()
+            .all(|cpu| ccds.contains(&cpu.ccd) == cpus.contains(&cpu.number));
+        if whole_ccds && !ccds.is_empty() {
+            CoreSelection::Ccds(ccds)
+        } else {
+            CoreSelection::Cpus(cpus.clone())
+        }
+    }
+
+    /// Returns the selected logical processors, in `number` order.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<LogicalCpu>)` with at least one processor.
+    /// - `Err(anyhow::Error)` if the selection names a die or processor the system does
+    ///   not have, or selects nothing.
+    pub fn select(&self, topology: &CpuTopology) -> Result<Vec<LogicalCpu>> {
+        let cpus: Vec<LogicalCpu> = match self {
+            CoreSelection::All => topology.cpus.clone(),
+            CoreSelection::Ccds(ccds) => {
+                let count = topology.ccd_count();
+                if let Some(ccd) = ccds.iter().find(|ccd| **ccd >= count) {
+                    anyhow::bail!("CCD {} does not exist; the processor has {}", ccd, count);
+                }
+                topology
+                    .cpus
+                    .iter()
+                    .filter(|cpu| ccds.contains(&cpu.ccd))
+                    .copied()
+                    .collect()
+            }
+            CoreSelection::Cpus(numbers) => numbers
+                .iter()
+                .map(|number| topology.cpu(*number).copied())
+                .collect::<Result<_>>()?,
+        };
+        if cpus.is_empty() {
+            anyhow::bail!("No logical processors are selected");
+        }
+        Ok(cpus)
+    }
+}
+
+impl fmt::Display for CoreSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let numbers =
+            |set: &BTreeSet<usize>| format_ranges(&set.iter().copied().collect::<Vec<_>>());
+        match self {
+            CoreSelection::All => write!(f, "all cores"),
+            CoreSelection::Ccds(ccds) if ccds.len() == 1 => write!(f, "CCD {}", numbers(ccds)),
+            CoreSelection::Ccds(ccds) => write!(f, "CCDs {}", numbers(ccds)),
+            CoreSelection::Cpus(cpus) if cpus.len() == 1 => write!(f, "CPU {}", numbers(cpus)),
+            CoreSelection::Cpus(cpus) => write!(f, "CPUs {}", numbers(cpus)),
         }
     }
 }
 
-pub static CPU_INFO: Lazy<CpuInfo> = Lazy::new(CpuInfo::new);
+/// Formats a list of numbers as ranges, e.g. `0-3, 6`.
+pub fn format_ranges(numbers: &[usize]) -> String {
+    let mut numbers = numbers.to_vec();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for number in numbers {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == number => *end = number,
+            _ => ranges.push((number, number)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Parses a list of numbers and ranges, e.g. `0-3,6`, as written by `format_ranges`.
+pub fn parse_ranges(list: &str) -> Result<BTreeSet<usize>> {
+    let mut numbers = BTreeSet::new();
+    for part in list
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let parse = |number: &str| {
+            number
+                .trim()
+                .parse::<usize>()
+                .with_context(|| format!("'{}' is not a number", number.trim()))
+        };
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    anyhow::bail!("Range '{}' ends before it starts", part);
+                }
+                numbers.extend(start..=end);
+            }
+            None => {
+                numbers.insert(parse(part)?);
+            }
+        }
+    }
+    if numbers.is_empty() {
+        anyhow::bail!("No numbers given");
+    }
+    Ok(numbers)
+}
+
+/// The manufacturer of a processor, from its CPUID vendor string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuVendor {
+    Amd,
+    Intel,
+    Other,
+}
+
+impl fmt::Display for CpuVendor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CpuVendor::Amd => "AMD",
+            CpuVendor::Intel => "Intel",
+            CpuVendor::Other => "Unknown vendor",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The vendor, family, model and stepping a processor reports through CPUID.
+/// Family and model include the extended family and model fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuIdentity {
+    pub vendor: CpuVendor,
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+}
+
+impl CpuIdentity {
+    /// Identifies the processor through the installed CPUID backend.
+    pub fn detect() -> Self {
+        let cpuid = backend::cpuid();
+        Self::from_cpuid(&CpuId::with_cpuid_reader(move |leaf, subleaf| {
+            cpuid.cpuid(leaf, subleaf)
+        }))
+    }
+
+    /// Returns how many core complexes, each with its own L3 cache, share a die. Zen and
+    /// Zen 2 have two; later Zen generations and other processors have one.
+    pub fn ccx_per_ccd(&self) -> usize {
+        if self.vendor == CpuVendor::Amd && self.family == 0x17 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Identifies a processor from its CPUID leaves.
+    ///
+    /// # Returns
+    /// The identity, with family, model and stepping 0 if leaf 1 is not available.
+    pub fn from_cpuid<R: CpuIdReader>(cpuid: &CpuId<R>) -> Self {
+        let vendor = match cpuid.get_vendor_info().as_ref().map(|info| info.as_str()) {
+            Some("AuthenticAMD") => CpuVendor::Amd,
+            Some("GenuineIntel") => CpuVendor::Intel,
+            _ => CpuVendor::Other,
+        };
+        let (family, model, stepping) = cpuid
+            .get_feature_info()
+            .map(|info| {
+                (
+                    info.family_id() as u32,
+                    info.model_id() as u32,
+                    info.stepping_id() as u32,
+                )
+            })
+            .unwrap_or_default();
+        Self {
+            vendor,
+            family,
+            model,
+            stepping,
+        }
+    }
+}
+
+impl fmt::Display for CpuIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} family {:X}h model {:X}h stepping {:X}h",
+            self.vendor, self.family, self.model, self.stepping
+        )
+    }
+}
+
+/// Matches every family or every model in a `CpuRange`.
+pub const ANY: RangeInclusive<u32> = 0..=u32::MAX;
+
+/// A vendor together with the families and models of its processors that something
+/// supports, e.g. AMD family 19h models 60h-7Fh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuRange {
+    pub vendor: CpuVendor,
+    pub families: RangeInclusive<u32>,
+    pub models: RangeInclusive<u32>,
+}
+
+impl CpuRange {
+    /// Every model of the given AMD families.
+    pub fn amd(families: RangeInclusive<u32>) -> Self {
+        Self::amd_models(families, ANY)
+    }
+
+    /// The given models of the given AMD families.
+    pub fn amd_models(families: RangeInclusive<u32>, models: RangeInclusive<u32>) -> Self {
+        Self {
+            vendor: CpuVendor::Amd,
+            families,
+            models,
+        }
+    }
+
+    /// The given models of the given Intel families.
+    pub fn intel_models(families: RangeInclusive<u32>, models: RangeInclusive<u32>) -> Self {
+        Self {
+            vendor: CpuVendor::Intel,
+            families,
+            models,
+        }
+    }
+
+    /// Returns whether the processor falls within the range.
+    pub fn contains(&self, cpu: &CpuIdentity) -> bool {
+        self.vendor == cpu.vendor
+            && self.families.contains(&cpu.family)
+            && self.models.contains(&cpu.model)
+    }
+}
+
+/// Formats a family or model range in hexadecimal, as `19h` or `19h-1Ah`.
+fn hex_range(range: &RangeInclusive<u32>) -> String {
+    if range.start() == range.end() {
+        format!("{:X}h", range.start())
+    } else {
+        format!("{:X}h-{:X}h", range.start(), range.end())
+    }
+}
+
+impl fmt::Display for CpuRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.vendor)?;
+        if self.families != ANY {
+            write!(f, " family {}", hex_range(&self.families))?;
+        }
+        if self.models != ANY {
+            write!(f, " models {}", hex_range(&self.models))?;
+        }
+        Ok(())
+    }
+}
+
+/// Explains why something limited to `supported` processors cannot be used on `cpu`.
+///
+/// # Returns
+/// - `None` if `supported` is empty, meaning any processor, or one of its ranges contains `cpu`.
+/// - `Some(String)` naming the processors supported and the one detected otherwise.
+pub fn unsupported_reason(supported: &[CpuRange], cpu: &CpuIdentity) -> Option<String> {
+    if supported.is_empty() || supported.iter().any(|range| range.contains(cpu)) {
+        return None;
+    }
+    // Ranges of the same families are named once, with their models listed together
+    let mut groups: Vec<(&CpuRange, Vec<String>)> = Vec::new();
+    for range in supported {
+        match groups.last_mut() {
+            Some((first, models))
+                if range.models != ANY
+                    && first.models != ANY
+                    && first.vendor == range.vendor
+                    && first.families == range.families =>
+            {
+                models.push(hex_range(&range.models))
+            }
+            _ => groups.push((range, vec![hex_range(&range.models)])),
+        }
+    }
+    let supported: Vec<String> = groups
+        .into_iter()
+        .map(|(range, models)| match models.len() {
+            1 => range.to_string(),
+            _ => format!(
+                "{} family {} models {}",
+                range.vendor,
+                hex_range(&range.families),
+                models.join("/")
+            ),
+        })
+        .collect();
+    Some(format!(
+        "Only supported on {}, but this processor is {}",
+        supported.join(", "),
+        cpu
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{fake::FakeCpuid, CpuidBackend};
+
+    /// Identifies a processor from recorded leaves 0 and 1.
+    fn identify(leaves: &[(u32, u32, [u32; 4])]) -> CpuIdentity {
+        let cpuid = FakeCpuid::new(leaves);
+        CpuIdentity::from_cpuid(&CpuId::with_cpuid_reader(|leaf, subleaf| {
+            cpuid.cpuid(leaf, subleaf)
+        }))
+    }
+
+    // Ryzen 9 3900X (Zen 2)
+    const RYZEN_3900X: &[(u32, u32, [u32; 4])] = &[
+        (0x0, 0, [0x0000_0010, 0x6874_7541, 0x444D_4163, 0x6974_6E65]),
+        (0x1, 0, [0x0087_0F10, 0x0018_0800, 0x7ED8_320B, 0x178B_FBFF]),
+    ];
+
+    // Ryzen 7 5800X3D (Zen 3)
+    const RYZEN_5800X3D: &[(u32, u32, [u32; 4])] = &[
+        (0x0, 0, [0x0000_0010, 0x6874_7541, 0x444D_4163, 0x6974_6E65]),
+        (0x1, 0, [0x00A2_0F12, 0x0010_0800, 0x7EF8_320B, 0x178B_FBFF]),
+    ];
+
+    // Core i9-12900K (Alder Lake)
+    const CORE_12900K: &[(u32, u32, [u32; 4])] = &[
+        (0x0, 0, [0x0000_0020, 0x756E_6547, 0x6C65_746E, 0x4965_6E69]),
+        (0x1, 0, [0x0009_0672, 0x0080_0800, 0x7FFA_FBFF, 0xBFEB_FBFF]),
+    ];
+
+    #[test]
+    fn test_identifies_recorded_processors() {
+        assert_eq!(
+            identify(RYZEN_3900X),
+            CpuIdentity {
+                vendor: CpuVendor::Amd,
+                family: 0x17,
+                model: 0x71,
+                stepping: 0,
+            }
+        );
+        assert_eq!(
+            identify(RYZEN_5800X3D),
+            CpuIdentity {
+                vendor: CpuVendor::Amd,
+                family: 0x19,
+                model: 0x21,
+                stepping: 2,
+            }
+        );
+        assert_eq!(
+            identify(CORE_12900K),
+            CpuIdentity {
+                vendor: CpuVendor::Intel,
+                family: 0x6,
+                model: 0x97,
+                stepping: 2,
+            }
+        );
+        assert_eq!(identify(&[]).vendor, CpuVendor::Other);
+    }
+
+    #[test]
+    fn test_uniform_topology_spans_processor_groups() {
+        // Threadripper 3990X: 8 dies of 2 complexes of 4 cores, 128 threads
+        let topology = CpuTopology::uniform(8, 2, 4, 2);
+        assert_eq!(topology.cpus.len(), 128);
+
+        let cpu = topology.cpu(65).unwrap();
+        assert_eq!(cpu.processor, ProcessorNumber { group: 1, index: 1 });
+        assert_eq!((cpu.core, cpu.ccx, cpu.ccd), (32, 8, 4));
+        assert_eq!(cpu.to_string(), "CPU 65 (group 1 index 1)");
+        assert!(topology.cpu(128).is_err());
+        assert_eq!(topology.ccd_count(), 8);
+    }
+
+    #[test]
+    fn test_topology_from_uneven_groups() {
+        // Windows splits 96 threads into two groups of 48; each core has two threads and
+        // each L3 cache six cores. Group 1's processors come after all of group 0's.
+        let group_masks = |group: u16, first: u8, count: u8| GroupMask {
+            group,
+            mask: ((1u64 << count) - 1) << first,
+        };
+        let mut cores = Vec::new();
+        let mut l3_caches = Vec::new();
+        for group in [1, 0] {
+            for core in 0..24 {
+                cores.push(group_masks(group, core * 2, 2));
+            }
+            for ccx in 0..4 {
+                l3_caches.push(vec![group_masks(group, ccx * 12, 12)]);
+            }
+        }
+        let topology = CpuTopology::from_masks(&cores, &l3_caches, 1);
+
+        assert_eq!(topology.cpus.len(), 96);
+        let first = topology.cpu(0).unwrap();
+        assert_eq!(first.processor, ProcessorNumber { group: 0, index: 0 });
+        // Group 0 was reported second, so its cores and caches come after group 1's
+        assert_eq!((first.core, first.ccx), (24, 4));
+        let cpu = topology.cpu(48 + 13).unwrap();
+        assert_eq!(
+            cpu.processor,
+            ProcessorNumber {
+                group: 1,
+                index: 13
+            }
+        );
+        assert_eq!((cpu.core, cpu.ccx, cpu.ccd), (6, 1, 1));
+    }
+
+    #[test]
+    fn test_core_selection_on_dual_ccd() {
+        // Ryzen 9 7950X: 2 dies of 8 cores with SMT
+        let topology = CpuTopology::uniform(2, 1, 8, 2);
+        let second_ccd = BTreeSet::from([1]);
+        let cpus = CoreSelection::Ccds(second_ccd.clone())
+            .select(&topology)
+            .unwrap();
+        assert_eq!(cpus.len(), 16);
+        assert!(cpus.iter().all(|cpu| cpu.ccd == 1 && cpu.number >= 16));
+
+        // Whole dies are described as dies, anything else processor by processor
+        let numbers: BTreeSet<usize> = (16..32).collect();
+        assert_eq!(
+            CoreSelection::from_cpus(&topology, &numbers),
+            CoreSelection::Ccds(second_ccd)
+        );
+        assert_eq!(
+            CoreSelection::from_cpus(&topology, &(0..32).collect()),
+            CoreSelection::All
+        );
+        let some = parse_ranges("0-3, 6").unwrap();
+        assert_eq!(
+            CoreSelection::from_cpus(&topology, &some).to_string(),
+            "CPUs 0-3, 6"
+        );
+
+        assert!(CoreSelection::Ccds(BTreeSet::from([2]))
+            .select(&topology)
+            .is_err());
+        assert!(CoreSelection::Cpus(BTreeSet::from([32]))
+            .select(&topology)
+            .is_err());
+        assert!(parse_ranges("3-1").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_reason_names_both_processors() {
+        let zen_3 = [CpuRange::amd(0x19..=0x1A)];
+        assert_eq!(unsupported_reason(&zen_3, &identify(RYZEN_5800X3D)), None);
+        assert_eq!(unsupported_reason(&[], &identify(CORE_12900K)), None);
+        assert_eq!(
+            unsupported_reason(&zen_3, &identify(CORE_12900K)).unwrap(),
+            "Only supported on AMD family 19h-1Ah, but this processor is Intel family 6h model 97h stepping 2h"
+        );
+
+        let zen_4 = [CpuRange::amd_models(0x19..=0x19, 0x60..=0x7F)];
+        assert!(unsupported_reason(&zen_4, &identify(RYZEN_5800X3D))
+            .unwrap()
+            .contains("AMD family 19h models 60h-7Fh"));
+
+        let coffee_lake = [
+            CpuRange::intel_models(0x6..=0x6, 0x8E..=0x8E),
+            CpuRange::intel_models(0x6..=0x6, 0x9E..=0x9E),
+            CpuRange::amd(0x19..=0x19),
+        ];
+        assert!(unsupported_reason(&coffee_lake, &identify(CORE_12900K))
+            .unwrap()
+            .starts_with("Only supported on Intel family 6h models 8Eh/9Eh, AMD family 19h,"));
+        assert!(unsupported_reason(&zen_4, &identify(RYZEN_3900X)).is_some());
+    }
+}
@@ -1,4 +1,6 @@
+pub mod bcd;
 pub mod cpu;
+pub mod paths;
 pub mod powershell;
 pub mod registry;
 pub mod services;
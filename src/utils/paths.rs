@@ -0,0 +1,98 @@
+// src/utils/paths.rs
+
+use std::{ffi::OsString, fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+const APP_DIR_NAME: &str = "oc_tool";
+
+/// Returns the directory the app keeps its persistent data in, creating it if needed.
+///
+/// This is `%APPDATA%\oc_tool` on Windows. On Linux it is `$XDG_DATA_HOME/oc_tool`, or
+/// `$HOME/.local/share/oc_tool` when `XDG_DATA_HOME` is not set. The data never falls
+/// back to the working directory, so that profiles, backups and the journal are found
+/// wherever the app is started from.
+///
+/// # Returns
+///
+/// - `Ok(PathBuf)` with the directory.
+/// - `Err(anyhow::Error)` if none of those variables is set or the directory cannot be
+///   created.
+pub fn app_data_dir() -> Result<PathBuf> {
+    let dir = data_home(|name| std::env::var_os(name))?.join(APP_DIR_NAME);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create data directory '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+/// Returns the directory per-user application data goes in, looking environment
+/// variables up through `var`. Empty and relative paths count as not set.
+fn data_home(var: impl Fn(&str) -> Option<OsString>) -> Result<PathBuf> {
+    let dir = |name: &str| var(name).map(PathBuf::from).filter(|dir| dir.is_absolute());
+    if cfg!(target_os = "linux") {
+        dir("XDG_DATA_HOME")
+            .or_else(|| dir("HOME").map(|home| home.join(".local/share")))
+            .context("Cannot find the data directory: neither XDG_DATA_HOME nor HOME is set")
+    } else {
+        dir("APPDATA").context("Cannot find the data directory: APPDATA is not set")
+    }
+}
+
+/// Returns the directory saved tweak profiles are stored in, `profiles` in the data
+/// directory, creating it if needed.
+pub fn profiles_dir() -> Result<PathBuf> {
+    let dir = app_data_dir()?.join("profiles");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create profiles directory '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+/// Returns the directory user-defined tweak files are loaded from, creating it if needed.
+pub fn tweaks_dir() -> Result<PathBuf> {
+    let dir = app_data_dir()?.join("tweaks");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create tweaks directory '{}'", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(target_os = "linux"))]
+    fn test_data_home_is_app_data() {
+        let app_data = r"C:\Users\me\AppData\Roaming";
+        let home = data_home(|name| (name == "APPDATA").then(|| app_data.into()));
+        assert_eq!(home.unwrap(), PathBuf::from(app_data));
+
+        assert!(data_home(|_| None).is_err());
+        assert!(data_home(|_| Some("".into())).is_err());
+        assert!(data_home(|_| Some("AppData".into())).is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_data_home_follows_xdg() {
+        let home = data_home(|name| match name {
+            "XDG_DATA_HOME" => Some("/home/me/.data".into()),
+            "HOME" => Some("/home/me".into()),
+            _ => None,
+        });
+        assert_eq!(home.unwrap(), PathBuf::from("/home/me/.data"));
+
+        // An empty or relative XDG_DATA_HOME is ignored, as the specification says
+        for xdg in ["", "data"] {
+            let home = data_home(|name| match name {
+                "XDG_DATA_HOME" => Some(xdg.into()),
+                "HOME" => Some("/home/me".into()),
+                _ => None,
+            });
+            assert_eq!(home.unwrap(), PathBuf::from("/home/me/.local/share"));
+        }
+
+        // Never the working directory
+        assert!(data_home(|_| None).is_err());
+        assert!(data_home(|name| (name == "APPDATA").then(|| "/tmp".into())).is_err());
+    }
+}
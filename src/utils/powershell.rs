@@ -1,29 +1,85 @@
 // src/utils/powershell.rs
 
-use std::ffi::CString;
-
-use anyhow::Context;
-use windows::{
-    core::PSTR,
-    Win32::{
-        Foundation::{
-            CloseHandle, SetHandleInformation, HANDLE, HANDLE_FLAGS, HANDLE_FLAG_INHERIT, TRUE,
-            WAIT_OBJECT_0,
-        },
-        Security::SECURITY_ATTRIBUTES,
-        Storage::FileSystem::ReadFile,
-        System::{
-            Pipes::CreatePipe,
-            Threading::{
-                CreateProcessA, WaitForSingleObject, CREATE_NO_WINDOW, INFINITE,
-                PROCESS_INFORMATION, STARTF_USESHOWWINDOW, STARTF_USESTDHANDLES, STARTUPINFOA,
-            },
-        },
-        UI::WindowsAndMessaging::SW_HIDE,
-    },
+use std::{
+    collections::hash_map::RandomState,
+    fs::{self, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 
-/// Executes a PowerShell script using Windows APIs and captures stdout and stderr separately.
+use anyhow::{bail, Context};
+
+use crate::backend::{self, CancelToken, ScriptOptions, ScriptOutput};
+
+/// How long a script may run unless its caller says otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The longest command line `CreateProcess` accepts, in characters.
+const MAX_COMMAND_LINE: usize = 32_767;
+
+/// Runs before every script. PowerShell writes progress bars to standard error as
+/// CLIXML when its output is redirected, which would read as error output.
+const SCRIPT_PREAMBLE: &str = "$ProgressPreference = 'SilentlyContinue'\n";
+
+/// Starts every PowerShell command line, before the script or the file holding it.
+const POWERSHELL: &str =
+    "powershell -NoProfile -NonInteractive -ExecutionPolicy Bypass -WindowStyle Hidden";
+
+/// How long a script may run, and which of its results count as success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptPolicy<'a> {
+    /// Kills the script once it has run this long.
+    pub timeout: Duration,
+    /// Exit codes that mean the script succeeded.
+    pub exit_codes: &'a [i32],
+    /// Whether anything written to standard error fails the script, whatever its exit
+    /// code. Off by default, as tools such as `bcdedit` and `powercfg` write harmless
+    /// warnings there.
+    pub stderr_fails: bool,
+}
+
+impl ScriptPolicy<'_> {
+    /// Succeeds on exit code 0 within `DEFAULT_TIMEOUT`, logging any standard error.
+    pub const DEFAULT: ScriptPolicy<'static> = ScriptPolicy {
+        timeout: DEFAULT_TIMEOUT,
+        exit_codes: &[0],
+        stderr_fails: false,
+    };
+
+    /// Checks the output of a finished script.
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(())` if the script succeeded.
+    /// - `Err(anyhow::Error)` with the exit code and standard error otherwise.
+    pub fn check(&self, output: &ScriptOutput) -> anyhow::Result<()> {
+        if !self.exit_codes.contains(&output.exit_code) {
+            bail!(
+                "PowerShell script exited with code {}: {}",
+                output.exit_code,
+                output.stderr
+            );
+        }
+        if !output.stderr.is_empty() {
+            if self.stderr_fails {
+                bail!("PowerShell script error: {}", output.stderr);
+            }
+            tracing::warn!("PowerShell script error output: {}", output.stderr);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScriptPolicy<'_> {
+    fn default() -> Self {
+        ScriptPolicy::DEFAULT
+    }
+}
+
+/// Executes a PowerShell script and captures its output.
 ///
 /// # Arguments
 ///
@@ -31,174 +87,273 @@ use windows::{
 ///
 /// # Returns
 ///
-/// * `Ok((stdout, stderr))` containing the standard output and standard error.
-/// * `Err(anyhow::Error)` if the script execution fails.
+/// * `Ok(String)` containing the standard output.
+/// * `Err(anyhow::Error)` if the script fails under `ScriptPolicy::DEFAULT`.
 pub fn execute_powershell_script(script: &str) -> anyhow::Result<String> {
-    // Step 1: Create security attributes to allow handle inheritance
-    let sa = SECURITY_ATTRIBUTES {
-        nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
-        bInheritHandle: TRUE,
-        lpSecurityDescriptor: std::ptr::null_mut(),
-    };
-
-    // Step 2: Create pipes for stdout and stderr
-    let mut stdout_read: HANDLE = HANDLE(std::ptr::null_mut());
-    let mut stdout_write: HANDLE = HANDLE(std::ptr::null_mut());
-    let mut stderr_read: HANDLE = HANDLE(std::ptr::null_mut());
-    let mut stderr_write: HANDLE = HANDLE(std::ptr::null_mut());
-
-    unsafe {
-        // Create stdout pipe
-        CreatePipe(&mut stdout_read, &mut stdout_write, Some(&sa), 0)
-            .ok()
-            .context("Failed to create stdout pipe")?;
-
-        // Ensure the read handle is not inherited
-        SetHandleInformation(stdout_read, HANDLE_FLAG_INHERIT.0, HANDLE_FLAGS(0))
-            .ok()
-            .context("Failed to set handle information for stdout_read")?;
-
-        // Create stderr pipe
-        CreatePipe(&mut stderr_read, &mut stderr_write, Some(&sa), 0)
-            .ok()
-            .context("Failed to create stderr pipe")?;
-
-        // Ensure the read handle is not inherited
-        SetHandleInformation(stderr_read, HANDLE_FLAG_INHERIT.0, HANDLE_FLAGS(0))
-            .ok()
-            .context("Failed to set handle information for stderr_read")?;
-    }
-
-    // Step 3: Set up the STARTUPINFOA structure
-    let startup_info = STARTUPINFOA {
-        cb: std::mem::size_of::<STARTUPINFOA>() as u32,
-        dwFlags: STARTF_USESHOWWINDOW | STARTF_USESTDHANDLES,
-        wShowWindow: SW_HIDE.0 as u16,
-        hStdOutput: stdout_write,
-        hStdError: stderr_write,
-        ..Default::default()
-    };
-    // Optionally, you can redirect stdin if needed:
-    // startup_info.hStdInput = stdin_read;
+    execute_powershell_script_with(script, &ScriptPolicy::DEFAULT, None)
+}
 
-    // Step 4: Prepare the command line
-    let command_line_str = format!(
-        "powershell -NoProfile -ExecutionPolicy Bypass -WindowStyle Hidden -Command \"{}\"",
-        script.replace('"', r#"\""#) // Escape double quotes in the script
+/// Executes a PowerShell script, judging its result by `policy`.
+///
+/// # Arguments
+///
+/// * `script` - The PowerShell script to execute.
+/// * `policy` - The timeout, and which results count as success.
+/// * `cancel` - Kills the script when cancelled.
+///
+/// # Returns
+///
+/// * `Ok(String)` containing the standard output.
+/// * `Err(anyhow::Error)` if the script could not be run, timed out, was cancelled or
+///   failed under `policy`.
+pub fn execute_powershell_script_with(
+    script: &str,
+    policy: &ScriptPolicy,
+    cancel: Option<&CancelToken>,
+) -> anyhow::Result<String> {
+    let output = backend::shell().run_powershell(
+        script,
+        &ScriptOptions {
+            timeout: Some(policy.timeout),
+            cancel: cancel.cloned(),
+        },
+    )?;
+    tracing::debug!(
+        "PowerShell script exited with code {} after {:?}",
+        output.exit_code,
+        output.duration
     );
+    policy.check(&output)?;
+    Ok(output.stdout)
+}
 
-    // Convert to CString to ensure it's null-terminated
-    let command_line_cstr = CString::new(command_line_str)
-        .with_context(|| "Failed to convert command line to CString")?;
-
-    // Obtain a mutable pointer and cast it to *mut u8
-    let command_line_ptr = command_line_cstr.as_ptr() as *mut u8;
-
-    // Wrap it in PSTR
-    let command_line = PSTR(command_line_ptr);
-
-    // Step 5: Initialize PROCESS_INFORMATION
-    let mut process_info = PROCESS_INFORMATION::default();
-
-    // Step 6: Create the PowerShell process
-    let success = unsafe {
-        CreateProcessA(
-            None,              // lpApplicationName
-            command_line,      // lpCommandLine
-            None,              // lpProcessAttributes
-            None,              // lpThreadAttributes
-            TRUE,              // bInheritHandles
-            CREATE_NO_WINDOW,  // dwCreationFlags
-            None,              // lpEnvironment
-            None,              // lpCurrentDirectory
-            &startup_info,     // lpStartupInfo
-            &mut process_info, // lpProcessInformation
-        )
-    };
+/// A command line that runs a script in a hidden PowerShell process, and the temporary
+/// file holding the script if it was too long to pass on the command line. The file is
+/// removed on drop, so the command must be kept until the process has exited.
+#[derive(Debug)]
+pub struct PowershellCommand {
+    pub command_line: String,
+    script_file: Option<PathBuf>,
+}
 
-    if success.is_err() {
-        tracing::error!("Failed to create PowerShell process.");
-        return Err(anyhow::anyhow!("Failed to execute PowerShell script"));
+impl Drop for PowershellCommand {
+    fn drop(&mut self) {
+        if let Some(path) = &self.script_file {
+            if let Err(e) = fs::remove_file(path) {
+                tracing::warn!("{:?} -> Failed to remove the script file: {}", path, e);
+            }
+        }
     }
+}
 
-    // Close the write ends of the pipes in the parent process
-    unsafe {
-        let _ = CloseHandle(stdout_write);
-        let _ = CloseHandle(stderr_write);
+/// Returns the command that runs `script` in a hidden PowerShell process.
+///
+/// The script is passed through `-EncodedCommand` as the Base64 of its UTF-16LE text, so
+/// quotes, `$`, backticks and non-ASCII characters reach PowerShell unchanged, and the
+/// command line itself is plain ASCII. The encoding nearly triples the script's length,
+/// so a script of more than about 12000 characters is instead written to a temporary
+/// `.ps1` file and run with `-File`.
+///
+/// # Returns
+///
+/// * `Ok(PowershellCommand)` with the command line.
+/// * `Err(anyhow::Error)` if the script is too long for a command line and could not be
+///   written to a file.
+pub fn powershell_command_line(script: &str) -> anyhow::Result<PowershellCommand> {
+    let script = format!("{}{}", SCRIPT_PREAMBLE, script);
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let command_line = format!("{} -EncodedCommand {}", POWERSHELL, base64(&utf16));
+    if command_line.len() <= MAX_COMMAND_LINE {
+        return Ok(PowershellCommand {
+            command_line,
+            script_file: None,
+        });
     }
 
-    // Step 7: Read from stdout and stderr
-    let stdout =
-        read_from_pipe(stdout_read).context("Failed to read stdout from PowerShell script")?;
-    let stderr =
-        read_from_pipe(stderr_read).context("Failed to read stderr from PowerShell script")?;
+    let path = script_file_path();
+    create_script_file(&path, &script).with_context(|| {
+        format!(
+            "{:?} -> Failed to write a PowerShell script of {} characters encoded",
+            path,
+            command_line.len()
+        )
+    })?;
+    Ok(PowershellCommand {
+        command_line: format!("{} -File \"{}\"", POWERSHELL, path.display()),
+        script_file: Some(path),
+    })
+}
 
-    // Step 8: Wait for the process to complete
-    let wait_result = unsafe { WaitForSingleObject(process_info.hProcess, INFINITE) };
+/// Returns a path in the temporary directory for a new script file. The name ends in a
+/// random number, so that no other process can create the file ahead of this one.
+fn script_file_path() -> PathBuf {
+    static SCRIPT_FILES: AtomicUsize = AtomicUsize::new(0);
+    // RandomState is keyed from the operating system's random number generator
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(SCRIPT_FILES.fetch_add(1, Ordering::Relaxed));
+    std::env::temp_dir().join(format!(
+        "oc_tool_script_{}_{:016x}.ps1",
+        std::process::id(),
+        hasher.finish()
+    ))
+}
 
-    if wait_result != WAIT_OBJECT_0 {
-        tracing::error!(
-            "WaitForSingleObject failed with return value: {:?}",
-            wait_result
-        );
-        // Clean up handles before returning
-        unsafe {
-            let _ = CloseHandle(process_info.hProcess);
-            let _ = CloseHandle(process_info.hThread);
-            let _ = CloseHandle(stdout_read);
-            let _ = CloseHandle(stderr_read);
+/// Writes `script` to a new file at `path`. Fails if anything already exists there,
+/// rather than writing through it, as the script is run elevated.
+fn create_script_file(path: &Path, script: &str) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .context("Failed to create the script file")?;
+    // Windows PowerShell reads a file without a byte order mark in the ANSI code page
+    file.write_all(format!("\u{FEFF}{}", script).as_bytes())
+        .context("Failed to write the script file")
+}
+
+/// Encodes bytes as standard, padded Base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
         }
-        return Err(anyhow::anyhow!("Failed to wait for PowerShell process"));
     }
+    encoded
+}
 
-    // Step 9: Close process and thread handles
-    unsafe {
-        let _ = CloseHandle(process_info.hProcess);
-        let _ = CloseHandle(process_info.hThread);
-        let _ = CloseHandle(stdout_read);
-        let _ = CloseHandle(stderr_read);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Step 10: Check if there was any error output
-    if !stderr.is_empty() {
-        tracing::error!("PowerShell script error output: {}", stderr.trim());
+    /// Reverses `powershell_command_line`, returning the script PowerShell would run.
+    fn decode_command_line(command_line: &str) -> String {
+        let (_, encoded) = command_line.rsplit_once(' ').unwrap();
+        let mut bytes = Vec::new();
+        for chunk in encoded.as_bytes().chunks(4) {
+            let mut group = 0u32;
+            let mut len = 0;
+            for (i, c) in chunk.iter().enumerate() {
+                if *c == b'=' {
+                    continue;
+                }
+                let value = match c {
+                    b'A'..=b'Z' => c - b'A',
+                    b'a'..=b'z' => c - b'a' + 26,
+                    b'0'..=b'9' => c - b'0' + 52,
+                    b'+' => 62,
+                    b'/' => 63,
+                    _ => panic!("'{}' is not Base64", *c as char),
+                };
+                group |= (value as u32) << (18 - 6 * i);
+                len = i;
+            }
+            bytes.extend(group.to_be_bytes()[1..=len].iter());
+        }
+        let utf16: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&utf16).unwrap()
     }
 
-    // You can choose to handle stderr separately or include it in the output
-    // Here, we'll return only stdout for successful execution
-    if !stderr.is_empty() {
-        return Err(anyhow::anyhow!(
-            "PowerShell script error: {}",
-            stderr.trim()
-        ));
-    }
+    #[test]
+    fn test_command_line_carries_scripts_unchanged() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(&[0xFB, 0xFF]), "+/8=");
 
-    Ok(stdout.trim().to_string())
-}
+        for script in [
+            "",
+            r#"[bool](bcdedit /enum '{current}' | Select-String '^useplatformclock\s+Yes')"#,
+            r#"Set-Service -Name "SysMain" -StartupType Automatic"#,
+            r#"Write-Output "It's `"quoted`" \" twice" '$env:TEMP'"#,
+            "$x = @{ A = 1 }; $x.A\r\n`t\"\"\"",
+            "Write-Output 'Überhitzung – 温度'",
+        ] {
+            let command = powershell_command_line(script).unwrap();
+            assert_eq!(command.script_file, None);
+            assert!(command.command_line.is_ascii());
+            assert!(!command.command_line.contains('"'));
+            assert_eq!(
+                decode_command_line(&command.command_line),
+                format!("{}{}", SCRIPT_PREAMBLE, script)
+            );
+        }
+    }
 
-// Helper function to read from a pipe handle until EOF
-fn read_from_pipe(handle: HANDLE) -> anyhow::Result<String> {
-    let mut buffer = [0u8; 4096];
-    let mut output = Vec::new();
-    let mut bytes_read: u32 = 0;
+    #[test]
+    fn test_long_scripts_run_from_a_file() {
+        let script = format!("Write-Output '{}'", "Ü".repeat(MAX_COMMAND_LINE / 2));
+        let command = powershell_command_line(&script).unwrap();
+        let path = command.script_file.clone().unwrap();
+        assert_eq!(
+            command.command_line,
+            format!("{} -File \"{}\"", POWERSHELL, path.display())
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("\u{FEFF}{}{}", SCRIPT_PREAMBLE, script)
+        );
 
-    loop {
-        let success =
-            unsafe { ReadFile(handle, Some(&mut buffer), Some(&mut bytes_read), None).is_ok() };
+        // Each command has its own file, removed once the command is dropped
+        let other = powershell_command_line(&script).unwrap();
+        assert_ne!(other.script_file, command.script_file);
+        drop(command);
+        assert!(!path.exists());
+        assert!(other.script_file.as_ref().unwrap().exists());
+    }
 
-        if !success {
-            // You might want to handle specific errors here
-            break;
-        }
+    #[test]
+    fn test_existing_script_files_are_not_reused() {
+        let path = script_file_path();
+        assert_ne!(script_file_path(), path);
 
-        if bytes_read == 0 {
-            // EOF reached
-            break;
-        }
+        fs::write(&path, "Write-Output 'planted'").unwrap();
+        assert!(create_script_file(&path, "Write-Output 'ours'").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Write-Output 'planted'");
+        fs::remove_file(&path).unwrap();
 
-        output.extend_from_slice(&buffer[..bytes_read as usize]);
+        create_script_file(&path, "Write-Output 'ours'").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{FEFF}Write-Output 'ours'"
+        );
+        fs::remove_file(&path).unwrap();
     }
 
-    let output_str = String::from_utf8_lossy(&output).to_string();
-    Ok(output_str)
+    #[test]
+    fn test_policy_judges_exit_code_and_stderr() {
+        let warning = ScriptOutput {
+            stderr: "The parameter is incorrect.".to_string(),
+            ..Default::default()
+        };
+        assert!(ScriptPolicy::DEFAULT.check(&warning).is_ok());
+        let strict = ScriptPolicy {
+            stderr_fails: true,
+            ..ScriptPolicy::DEFAULT
+        };
+        assert!(strict.check(&warning).is_err());
+
+        let failed = ScriptOutput {
+            exit_code: 1,
+            ..Default::default()
+        };
+        assert!(ScriptPolicy::DEFAULT.check(&failed).is_err());
+        let lenient = ScriptPolicy {
+            exit_codes: &[0, 1],
+            ..ScriptPolicy::DEFAULT
+        };
+        assert!(lenient.check(&failed).is_ok());
+    }
 }
@@ -1,6 +1,7 @@
 // src/utils/windows.rs
 
 use anyhow::Result as AnyResult;
+#[cfg(windows)]
 use windows::{
     core::PWSTR as CorePWSTR,
     Win32::{
@@ -21,6 +22,7 @@ use super::powershell::execute_powershell_script;
 ///
 /// - `true` if the process is elevated.
 /// - `false` otherwise.
+#[cfg(windows)]
 pub fn is_elevated() -> bool {
     let mut handle: HANDLE = HANDLE::default();
     if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut handle).is_ok() } {
@@ -51,6 +53,12 @@ pub fn is_elevated() -> bool {
     false
 }
 
+/// Elevation is a Windows concept, so the process is never considered elevated.
+#[cfg(not(windows))]
+pub fn is_elevated() -> bool {
+    false
+}
+
 /// Initiates a system reboot.
 ///
 /// Returns:
@@ -72,6 +80,7 @@ pub fn reboot_into_bios() -> AnyResult<()> {
 }
 
 /// Retrieves the current username using the Windows API.
+#[cfg(windows)]
 pub fn get_current_username() -> String {
     let mut buffer = [0u16; 256]; // Create a buffer for the username
     let mut size = buffer.len() as u32;
@@ -85,6 +94,14 @@ pub fn get_current_username() -> String {
     }
 }
 
+/// Retrieves the current username from the environment.
+#[cfg(not(windows))]
+pub fn get_current_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,121 @@
+// tests/bcd_tweaks.rs
+
+//! Runs the boot configuration tweaks against a fake BCD store: reading elements that
+//! are not set, planning and applying combo box options, and restoring a snapshot.
+
+use oc_tool::{
+    backend::{fake::FakeSystem, BcdBackend},
+    tweaks::{all_tweaks, describe_mixed, TweakId, TweakOption},
+};
+
+fn default() -> TweakOption {
+    TweakOption::Option("Default".to_string())
+}
+
+fn enhanced() -> TweakOption {
+    TweakOption::Option("Enhanced".to_string())
+}
+
+#[test]
+fn test_fresh_store_reads_as_default() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweaks = all_tweaks();
+
+    // A fresh install sets nx, and leaves the timer elements to their defaults
+    let dep = &tweaks[&TweakId::DisableDataExecutionPrevention];
+    assert!(dep.requires_reboot);
+    assert_eq!(
+        dep.method.initial_state().unwrap(),
+        TweakOption::Enabled(false)
+    );
+    let tsc = &tweaks[&TweakId::TscSyncPolicy].method;
+    assert_eq!(tsc.initial_state().unwrap(), default());
+    let dynamic_tick = &tweaks[&TweakId::DisableHPET].method;
+    assert_eq!(
+        dynamic_tick.initial_state().unwrap(),
+        TweakOption::Enabled(false)
+    );
+}
+
+#[test]
+fn test_mixed_state_lists_unset_elements() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let dynamic_tick = &all_tweaks()[&TweakId::DisableHPET].method;
+
+    // The HPET forced on by hand matches neither option
+    system
+        .bcd
+        .set("{current}", "useplatformclock", "Yes")
+        .unwrap();
+    let TweakOption::Mixed(states) = dynamic_tick.initial_state().unwrap() else {
+        panic!("A forced useplatformclock matched an option");
+    };
+    assert_eq!(
+        describe_mixed(&states),
+        vec![
+            "disabledynamictick: <unset> (Disabled)".to_string(),
+            "useplatformclock: Yes (no option)".to_string(),
+        ]
+    );
+
+    dynamic_tick.apply(TweakOption::Enabled(true)).unwrap();
+    assert_eq!(
+        dynamic_tick.initial_state().unwrap(),
+        TweakOption::Enabled(true)
+    );
+    let elements = system.bcd.elements();
+    assert_eq!(
+        elements.get("disabledynamictick").map(String::as_str),
+        Some("Yes")
+    );
+    assert_eq!(elements.get("useplatformclock"), None);
+}
+
+#[test]
+fn test_combo_box_option_is_planned_and_applied() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tsc = &all_tweaks()[&TweakId::TscSyncPolicy].method;
+
+    assert_eq!(
+        tsc.plan(&enhanced()).unwrap().to_string(),
+        "Boot: set {current} tscsyncpolicy from <unset> to Enhanced"
+    );
+    tsc.apply(enhanced()).unwrap();
+    assert_eq!(tsc.initial_state().unwrap(), enhanced());
+    assert_eq!(
+        system
+            .bcd
+            .elements()
+            .get("tscsyncpolicy")
+            .map(String::as_str),
+        Some("Enhanced")
+    );
+}
+
+#[test]
+fn test_restoring_deletes_elements_the_snapshot_found_unset() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tsc = &all_tweaks()[&TweakId::TscSyncPolicy].method;
+
+    let snapshot = tsc.snapshot().unwrap().unwrap();
+    let before = system.state().unwrap();
+    tsc.apply(enhanced()).unwrap();
+    tsc.restore(&snapshot).unwrap();
+    assert_eq!(system.state().unwrap(), before);
+    assert_eq!(tsc.initial_state().unwrap(), default());
+}
+
+#[test]
+fn test_values_compare_ignoring_case() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let dep = &all_tweaks()[&TweakId::DisableDataExecutionPrevention].method;
+
+    // As bcdedit does
+    system.bcd.set("{current}", "nx", "alwaysoff").unwrap();
+    assert_eq!(dep.initial_state().unwrap(), TweakOption::Enabled(true));
+}
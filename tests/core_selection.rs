@@ -0,0 +1,116 @@
+// tests/core_selection.rs
+
+//! Applies an MSR tweak on one CCD of a dual-CCD processor, and saves and re-applies
+//! it through a profile.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    profile::Profile,
+    tweaks::{all_tweaks, TweakId, TweakMethod, TweakOption},
+    utils::cpu::{CoreSelection, CpuTopology},
+};
+
+const PREFETCH_CONTROL: u32 = 0xC000_0108;
+const L1_STRIDE: u64 = 1 << 1;
+
+const ID: TweakId = TweakId::DisableL1StridePrefetcher;
+
+/// Returns a Ryzen 9 7950X: 2 dies of 8 cores with SMT.
+fn ryzen_7950x() -> FakeSystem {
+    FakeSystem::with_topology(CpuTopology::uniform(2, 1, 8, 2))
+}
+
+fn second_ccd() -> CoreSelection {
+    CoreSelection::Ccds(BTreeSet::from([1]))
+}
+
+/// Returns the tweak, pointed at the second die.
+fn on_second_ccd() -> Arc<dyn TweakMethod> {
+    let method = all_tweaks()[&ID].method.clone();
+    assert_eq!(method.targets(), Some(CoreSelection::All));
+    method.set_targets(second_ccd()).unwrap();
+    method
+}
+
+#[test]
+fn test_only_the_selected_ccd_is_changed() {
+    let system = ryzen_7950x();
+    let _backends = system.install();
+    let method = on_second_ccd();
+
+    // The state is read on the second die alone
+    method.apply(TweakOption::Enabled(true)).unwrap();
+    for cpu in &system.topology.0.cpus {
+        let expected = if cpu.ccd == 1 { L1_STRIDE } else { 0 };
+        assert_eq!(system.msr.get(cpu.processor, PREFETCH_CONTROL), expected);
+    }
+    assert_eq!(method.initial_state().unwrap(), TweakOption::Enabled(true));
+    assert_eq!(
+        method
+            .plan(&TweakOption::Enabled(false))
+            .unwrap()
+            .steps
+            .len(),
+        16
+    );
+}
+
+#[test]
+fn test_every_core_reports_its_own_state() {
+    let system = ryzen_7950x();
+    let _backends = system.install();
+    let method = on_second_ccd();
+    method.apply(TweakOption::Enabled(true)).unwrap();
+
+    let states = method.core_states().unwrap();
+    assert_eq!(states.len(), 32);
+    for state in &states {
+        assert_eq!(
+            state.option,
+            Some(TweakOption::Enabled(state.cpu.ccd == 1)),
+            "{}",
+            state.cpu
+        );
+    }
+}
+
+#[test]
+fn test_missing_ccd_is_refused() {
+    let system = ryzen_7950x();
+    let _backends = system.install();
+    let method = on_second_ccd();
+
+    assert!(method
+        .set_targets(CoreSelection::Ccds(BTreeSet::from([2])))
+        .is_err());
+    assert_eq!(method.targets(), Some(second_ccd()));
+}
+
+#[test]
+fn test_profile_points_the_tweak_back_at_its_ccd() {
+    let system = ryzen_7950x();
+    let _backends = system.install();
+    let mut tweaks = all_tweaks();
+    let method = tweaks[&ID].method.clone();
+    method.set_targets(second_ccd()).unwrap();
+
+    tweaks.get_mut(&ID).unwrap().state = TweakOption::Enabled(true);
+    let profile = Profile::capture("Bench", &tweaks);
+    assert_eq!(profile.targets, BTreeMap::from([(ID, second_ccd())]));
+    let profile = Profile::from_json(&profile.to_json().unwrap()).unwrap();
+
+    method.set_targets(CoreSelection::All).unwrap();
+    tweaks.get_mut(&ID).unwrap().state = method.initial_state().unwrap();
+
+    let changes = profile.changes(&tweaks);
+    assert_eq!(changes, vec![(ID, TweakOption::Enabled(true))]);
+    assert!(profile.set_targets(&tweaks, &changes).is_empty());
+    assert_eq!(method.targets(), Some(second_ccd()));
+    method.apply(TweakOption::Enabled(true)).unwrap();
+    assert_eq!(system.state().unwrap().msrs.len(), 16);
+}
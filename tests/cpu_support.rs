@@ -0,0 +1,122 @@
+// tests/cpu_support.rs
+
+//! Checks that MSR tweaks refuse to run on processors they were not written for.
+
+use std::sync::Arc;
+
+use oc_tool::{
+    backend::fake::{FakeCpuid, FakeSystem},
+    tweaks::{
+        msr::{all_msr_tweaks, intel_msr_tweaks},
+        TweakId, TweakOption,
+    },
+};
+
+// Core i9-12900K (Alder Lake)
+const CORE_12900K: &[(u32, u32, [u32; 4])] = &[
+    (0x0, 0, [0x0000_0020, 0x756E_6547, 0x6C65_746E, 0x4965_6E69]),
+    (0x1, 0, [0x0009_0672, 0x0080_0800, 0x7FFA_FBFF, 0xBFEB_FBFF]),
+];
+
+// Ryzen 9 3900X (Zen 2)
+const RYZEN_3900X: &[(u32, u32, [u32; 4])] = &[
+    (0x0, 0, [0x0000_0010, 0x6874_7541, 0x444D_4163, 0x6974_6E65]),
+    (0x1, 0, [0x0087_0F10, 0x0018_0800, 0x7ED8_320B, 0x178B_FBFF]),
+];
+
+fn fake_system(leaves: &[(u32, u32, [u32; 4])]) -> FakeSystem {
+    let mut system = FakeSystem::new(4);
+    system.cpuid = Arc::new(FakeCpuid::new(leaves));
+    system
+}
+
+const IA32_MISC_ENABLE: u32 = 0x1A0;
+const TURBO_DISABLE: u64 = 1 << 38;
+
+fn is_intel(id: TweakId) -> bool {
+    intel_msr_tweaks()
+        .iter()
+        .any(|(intel_id, _)| *intel_id == id)
+}
+
+#[test]
+fn test_amd_tweaks_refuse_intel_processors() {
+    // No AMD MSR tweak runs on Intel, and none touches an MSR trying
+    let system = fake_system(CORE_12900K);
+    let _backends = system.install();
+    for (id, tweak) in all_msr_tweaks()
+        .into_iter()
+        .filter(|(id, _)| !is_intel(*id))
+    {
+        let reason = tweak
+            .method
+            .unsupported()
+            .unwrap_or_else(|| panic!("{:?} -> Supported on an Intel processor", id));
+        assert!(reason.contains("Intel family 6h model 97h"), "{}", reason);
+
+        let error = tweak
+            .method
+            .apply(tweak.options[0].clone())
+            .expect_err("applying an unsupported tweak must fail");
+        assert!(format!("{:#}", error).contains(&reason));
+        assert!(tweak.method.initial_state().is_err());
+    }
+    assert!(system.state().unwrap().msrs.is_empty());
+}
+
+#[test]
+fn test_intel_tweaks_run_on_intel_processors() {
+    let system = fake_system(CORE_12900K);
+    let _backends = system.install();
+    // Apart from TSX control, which Alder Lake does not have
+    for (id, tweak) in intel_msr_tweaks() {
+        assert_eq!(
+            tweak.method.unsupported().is_none(),
+            id != TweakId::DisableIntelTsxRtm,
+            "{:?}",
+            id
+        );
+    }
+    let (_, turbo) = intel_msr_tweaks()
+        .into_iter()
+        .find(|(id, _)| *id == TweakId::DisableIntelTurbo)
+        .unwrap();
+    turbo.method.apply(TweakOption::Enabled(true)).unwrap();
+    assert_eq!(
+        turbo.method.initial_state().unwrap(),
+        TweakOption::Enabled(true)
+    );
+    let msrs = system.state().unwrap().msrs;
+    assert_eq!(msrs.len(), 4);
+    assert!(msrs
+        .iter()
+        .all(|((_, index), value)| *index == IA32_MISC_ENABLE && *value == TURBO_DISABLE));
+}
+
+#[test]
+fn test_zen_2_lacks_the_registers_added_by_zen_4() {
+    let _backends = fake_system(RYZEN_3900X).install();
+    let supported = |id: TweakId| {
+        all_msr_tweaks()
+            .into_iter()
+            .find(|(tweak_id, _)| *tweak_id == id)
+            .map(|(_, tweak)| tweak.method.unsupported().is_none())
+            .unwrap()
+    };
+    assert!(supported(TweakId::EnableFastFxsaveFrstor));
+    assert!(!supported(TweakId::AutomaticIbrsEnable));
+}
+
+#[test]
+fn test_zen_4_supports_every_amd_tweak() {
+    // The default fake processor is a Zen 4
+    let _backends = FakeSystem::new(4).install();
+    for (id, tweak) in all_msr_tweaks() {
+        if is_intel(id) {
+            assert!(tweak.method.unsupported().is_some(), "{:?}", id);
+            continue;
+        }
+        assert_eq!(tweak.method.unsupported(), None, "{:?}", id);
+        assert!(tweak.method.initial_state().is_ok(), "{:?}", id);
+    }
+}
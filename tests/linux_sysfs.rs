@@ -0,0 +1,88 @@
+// tests/linux_sysfs.rs
+
+//! Runs the Linux tweaks against a fake sysfs tree: a governor set on only some
+//! processors, a multiple choice file, and a kernel without cpuidle.
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    tweaks::{all_tweaks, describe_mixed, TweakId, TweakOption},
+};
+
+const GOVERNOR: &str = "/sys/devices/system/cpu/cpu2/cpufreq/scaling_governor";
+
+#[test]
+fn test_governor_set_on_one_processor_is_mixed() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let governor = &all_tweaks()[&TweakId::LinuxCpuGovernor].method;
+
+    system.sysfs.set(GOVERNOR, "performance");
+    let TweakOption::Mixed(states) = governor.initial_state().unwrap() else {
+        panic!("A governor set on one processor is not mixed");
+    };
+    assert_eq!(
+        describe_mixed(&states)[2],
+        "/sys/devices/system/cpu/cpu2/cpufreq/scaling_governor: performance (performance)"
+    );
+}
+
+#[test]
+fn test_plan_marks_unchanged_processors() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let governor = &all_tweaks()[&TweakId::LinuxCpuGovernor].method;
+    let performance = TweakOption::Option("performance".to_string());
+
+    system.sysfs.set(GOVERNOR, "performance");
+    let plan = governor.plan(&performance).unwrap();
+    assert_eq!(
+        plan.to_string().lines().nth(2),
+        Some(
+            "Linux: set /sys/devices/system/cpu/cpu2/cpufreq/scaling_governor \
+             from performance to performance (unchanged)"
+        )
+    );
+    governor.apply(performance.clone()).unwrap();
+    assert_eq!(governor.initial_state().unwrap(), performance);
+}
+
+#[test]
+fn test_only_the_bracketed_choice_counts() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let hugepages = &all_tweaks()[&TweakId::LinuxTransparentHugepages].method;
+
+    assert_eq!(
+        hugepages.initial_state().unwrap(),
+        TweakOption::Option("madvise".to_string())
+    );
+    system.sysfs.set(
+        "/sys/kernel/mm/transparent_hugepage/enabled",
+        "always madvise [never]",
+    );
+    assert_eq!(
+        hugepages.initial_state().unwrap(),
+        TweakOption::Option("never".to_string())
+    );
+}
+
+#[test]
+fn test_kernel_without_cpuidle_has_nothing_to_disable() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let idle_states = &all_tweaks()[&TweakId::LinuxDisableCpuIdleStates].method;
+
+    assert_eq!(idle_states.unsupported(), None);
+    for cpu in 0..4 {
+        system
+            .sysfs
+            .remove(&format!("/sys/devices/system/cpu/cpu{}/cpuidle", cpu));
+    }
+    assert_eq!(
+        idle_states.unsupported().as_deref(),
+        Some("This kernel does not provide /sys/devices/system/cpu/cpu*/cpuidle/state*/disable")
+    );
+    let before = system.state().unwrap();
+    assert!(idle_states.apply(TweakOption::Enabled(true)).is_err());
+    assert_eq!(system.state().unwrap(), before);
+}
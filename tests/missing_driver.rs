@@ -0,0 +1,62 @@
+// tests/missing_driver.rs
+
+//! Checks that without a hardware driver, MSR tweaks report why they are unavailable
+//! and fail cleanly, while every other tweak keeps working.
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    tweaks::{all_tweaks, TweakId, TweakOption},
+};
+
+/// Returns a system whose hardware driver cannot be loaded.
+fn without_driver() -> FakeSystem {
+    let system = FakeSystem::new(4);
+    system
+        .driver
+        .set_unavailable(Some("WinRing0x64.dll was not found"));
+    system
+}
+
+#[test]
+fn test_msr_tweaks_report_the_missing_driver() {
+    let system = without_driver();
+    let _backends = system.install();
+    let msr_tweak = &all_tweaks()[&TweakId::DisableL1StridePrefetcher];
+
+    let reason = msr_tweak.method.unsupported().expect("no driver is loaded");
+    assert!(
+        reason.contains("WinRing0x64.dll was not found"),
+        "{}",
+        reason
+    );
+    let error = msr_tweak
+        .method
+        .apply(TweakOption::Enabled(true))
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("WinRing0x64.dll was not found"));
+}
+
+#[test]
+fn test_other_tweaks_work_without_a_driver() {
+    let system = without_driver();
+    let _backends = system.install();
+    let registry_tweak = &all_tweaks()[&TweakId::DisableHPET];
+
+    assert_eq!(registry_tweak.method.unsupported(), None);
+    registry_tweak
+        .method
+        .apply(TweakOption::Enabled(true))
+        .unwrap();
+}
+
+#[test]
+fn test_msr_tweaks_work_once_the_driver_loads() {
+    let system = without_driver();
+    let _backends = system.install();
+    let msr_tweak = &all_tweaks()[&TweakId::DisableL1StridePrefetcher];
+    assert!(msr_tweak.method.unsupported().is_some());
+
+    system.driver.set_unavailable(None);
+    assert_eq!(msr_tweak.method.unsupported(), None);
+    msr_tweak.method.apply(TweakOption::Enabled(true)).unwrap();
+}
@@ -0,0 +1,85 @@
+// tests/msr_inspector.rs
+
+//! Reads an MSR that differs between cores, edits a field of it and restores it, as the
+//! MSR inspector does.
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    inspector::{plan_write, write_field, MsrInspection},
+    tweaks::msr::method::MsrField,
+    utils::cpu::CpuTopology,
+};
+
+const PSTATE_CONTROL: u32 = 0xC001_0062;
+
+/// Returns a four core system whose third core has a different P-state selected.
+fn odd_core_out() -> FakeSystem {
+    let system = FakeSystem::with_topology(CpuTopology::uniform(1, 1, 4, 1));
+    for cpu in &system.topology.0.cpus {
+        system.msr.set(cpu.processor, PSTATE_CONTROL, 0x8000_0000);
+    }
+    system.msr.set(
+        system.topology.0.cpus[2].processor,
+        PSTATE_CONTROL,
+        0x8000_0002,
+    );
+    system
+}
+
+fn values(system: &FakeSystem) -> Vec<u64> {
+    system
+        .topology
+        .0
+        .cpus
+        .iter()
+        .map(|cpu| system.msr.get(cpu.processor, PSTATE_CONTROL))
+        .collect()
+}
+
+#[test]
+fn test_differing_bits_are_found() {
+    let system = odd_core_out();
+    let _backends = system.install();
+
+    let inspection = MsrInspection::read(PSTATE_CONTROL, &system.topology.0.cpus).unwrap();
+    assert_eq!(inspection.common_value(), Some(0x8000_0000));
+    assert_eq!(inspection.differing_bits(), 0b10);
+}
+
+#[test]
+fn test_previewing_a_write_changes_nothing() {
+    let system = odd_core_out();
+    let _backends = system.install();
+    let cpus = &system.topology.0.cpus;
+
+    let field = MsrField::new(PSTATE_CONTROL, 0..=2, 0b101);
+    let plan = plan_write(&field, &cpus[1..3]).unwrap();
+    assert_eq!(plan[1].current, 0x8000_0002);
+    assert_eq!(plan[1].new, 0x8000_0005);
+    assert_eq!(
+        values(&system),
+        [0x8000_0000, 0x8000_0000, 0x8000_0002, 0x8000_0000]
+    );
+}
+
+#[test]
+fn test_write_and_restore_touch_only_the_field_and_cores() {
+    let system = odd_core_out();
+    let _backends = system.install();
+    let cpus = &system.topology.0.cpus;
+
+    // Writing leaves the other bits and the other cores alone
+    let field = MsrField::new(PSTATE_CONTROL, 0..=2, 0b101);
+    let written = write_field(&field, &cpus[1..3]).unwrap();
+    assert_eq!(
+        values(&system),
+        [0x8000_0000, 0x8000_0005, 0x8000_0005, 0x8000_0000]
+    );
+
+    // Restoring puts back what each core had
+    written.restore().unwrap();
+    assert_eq!(
+        values(&system),
+        [0x8000_0000, 0x8000_0000, 0x8000_0002, 0x8000_0000]
+    );
+}
@@ -0,0 +1,124 @@
+// tests/msr_rollback.rs
+
+//! Checks that an MSR tweak failing on one core puts every core it already wrote back
+//! to its original value, and reports what each core was left in.
+
+use std::sync::Arc;
+
+use oc_tool::{
+    backend::fake::{FakeSystem, MsrFault},
+    tweaks::{
+        all_tweaks,
+        msr::method::{CoreOutcome, MsrWriteError},
+        TweakId, TweakMethod, TweakOption,
+    },
+    utils::cpu::CpuTopology,
+};
+
+const PREFETCH_CONTROL: u32 = 0xC000_0108;
+const L1_STRIDE: u64 = 1 << 1;
+
+/// Gives every core a different original word, so that restoring it is visible.
+fn original(number: usize) -> u64 {
+    0x3C0 | ((number as u64) << 16)
+}
+
+/// Returns a 16 core system with each core's original word set.
+fn system() -> FakeSystem {
+    let system = FakeSystem::with_topology(CpuTopology::uniform(2, 1, 8, 1));
+    for cpu in &system.topology.0.cpus {
+        system
+            .msr
+            .set(cpu.processor, PREFETCH_CONTROL, original(cpu.number));
+    }
+    system
+}
+
+fn l1_stride() -> Arc<dyn TweakMethod> {
+    all_tweaks()[&TweakId::DisableL1StridePrefetcher]
+        .method
+        .clone()
+}
+
+#[test]
+fn test_rejected_write_restores_every_core() {
+    let system = system();
+    let _backends = system.install();
+    let cpus = &system.topology.0.cpus;
+    let method = l1_stride();
+
+    // Core 12 rejects the write, after cores 0 to 11 took it
+    system
+        .msr
+        .set_fault(cpus[12].processor, Some(MsrFault::RejectWrites));
+    let error = method.apply(TweakOption::Enabled(true)).unwrap_err();
+    let report = error
+        .downcast_ref::<MsrWriteError>()
+        .expect("a failed apply reports every core");
+    assert_eq!(report.failed_on.number, 12);
+    assert_eq!(
+        report.cpus_left(&CoreOutcome::Restored),
+        (0..=12).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        report.cpus_left(&CoreOutcome::Untouched),
+        (13..16).collect::<Vec<_>>()
+    );
+    assert!(report.fully_restored());
+    let message = format!("{:#}", error);
+    assert!(
+        message.contains("CPUs 0-12 restored") && message.contains("CPUs 13-15 untouched"),
+        "{}",
+        message
+    );
+    for cpu in cpus {
+        assert_eq!(
+            system.msr.get(cpu.processor, PREFETCH_CONTROL),
+            original(cpu.number),
+            "{}",
+            cpu
+        );
+    }
+}
+
+#[test]
+fn test_ignored_write_fails_verification() {
+    let system = system();
+    let _backends = system.install();
+    let method = l1_stride();
+
+    system.msr.set_fault(
+        system.topology.0.cpus[5].processor,
+        Some(MsrFault::IgnoreWrites),
+    );
+    let error = method.apply(TweakOption::Enabled(true)).unwrap_err();
+    let report = error.downcast_ref::<MsrWriteError>().unwrap();
+    assert_eq!(report.failed_on.number, 5);
+    assert_eq!(
+        report.cpus_left(&CoreOutcome::Untouched),
+        (6..16).collect::<Vec<_>>()
+    );
+    assert_eq!(method.initial_state().unwrap(), TweakOption::Enabled(false));
+}
+
+#[test]
+fn test_apply_succeeds_once_every_core_behaves() {
+    let system = system();
+    let _backends = system.install();
+    let cpus = &system.topology.0.cpus;
+    let method = l1_stride();
+
+    system
+        .msr
+        .set_fault(cpus[5].processor, Some(MsrFault::RejectWrites));
+    method.apply(TweakOption::Enabled(true)).unwrap_err();
+
+    system.msr.set_fault(cpus[5].processor, None);
+    method.apply(TweakOption::Enabled(true)).unwrap();
+    for cpu in cpus {
+        assert_eq!(
+            system.msr.get(cpu.processor, PREFETCH_CONTROL),
+            original(cpu.number) | L1_STRIDE
+        );
+    }
+}
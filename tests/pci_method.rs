@@ -0,0 +1,110 @@
+// tests/pci_method.rs
+
+//! Applies and reverts a tweak made of a PCI configuration space field and an SMN field,
+//! and checks that a register that ignores writes leaves everything as it was.
+
+use oc_tool::{
+    backend::{fake::FakeSystem, PciAddress, PciRegister},
+    tweaks::{
+        option_map,
+        pci::method::{PciField, PciTweak},
+        CustomTweakId, TweakId, TweakMethod, TweakOption,
+    },
+};
+
+/// A register of the data fabric's function 3, in configuration space.
+const CONFIG: PciRegister = PciRegister::Config {
+    address: PciAddress::new(0, 0x18, 3),
+    offset: 0x64,
+};
+/// A register reached through the SMN index/data pair.
+const SMN: PciRegister = PciRegister::Smn {
+    address: 0x0005_A000,
+};
+
+/// Returns a system whose registers hold bits the tweak must leave alone.
+fn system() -> FakeSystem {
+    let system = FakeSystem::new(4);
+    system.pci.set(CONFIG, 0x8000_0001);
+    system.pci.set(SMN, 0x0000_0300);
+    system
+}
+
+fn tweak() -> PciTweak {
+    let tweak = PciTweak {
+        id: TweakId::Custom(CustomTweakId::new("TestPciTweak").register()),
+        options: option_map(vec![
+            (
+                TweakOption::Enabled(false),
+                vec![
+                    PciField::bit(CONFIG, 4, false),
+                    PciField::new(SMN, 8..=9, 3),
+                ],
+            ),
+            (
+                TweakOption::Enabled(true),
+                vec![PciField::bit(CONFIG, 4, true), PciField::new(SMN, 8..=9, 1)],
+            ),
+        ]),
+        supported_cpus: Vec::new(),
+    };
+    assert!(tweak.validate().is_empty(), "{:?}", tweak.validate());
+    tweak
+}
+
+#[test]
+fn test_plan_shows_both_registers_without_changing_them() {
+    let system = system();
+    let _backends = system.install();
+    let tweak = tweak();
+
+    assert_eq!(tweak.initial_state().unwrap(), TweakOption::Enabled(false));
+    let plan = tweak.plan(&TweakOption::Enabled(true)).unwrap();
+    assert_eq!(
+        plan.to_string(),
+        "PCI 00:18.3 offset 0x64: bit 4 = 1 (0x80000001 -> 0x80000011)\n\
+         SMN 0x0005A000: bits 9:8 = 1 (0x00000300 -> 0x00000100)"
+    );
+    assert_eq!(system.pci.get(CONFIG), 0x8000_0001);
+}
+
+#[test]
+fn test_apply_leaves_the_other_bits_alone() {
+    let system = system();
+    let _backends = system.install();
+    let tweak = tweak();
+
+    tweak.apply(TweakOption::Enabled(true)).unwrap();
+    assert_eq!(system.pci.get(CONFIG), 0x8000_0011);
+    assert_eq!(system.pci.get(SMN), 0x0000_0100);
+    assert_eq!(tweak.initial_state().unwrap(), TweakOption::Enabled(true));
+}
+
+#[test]
+fn test_ignored_write_puts_the_other_register_back() {
+    let system = system();
+    let _backends = system.install();
+    let tweak = tweak();
+
+    system.pci.set_locked(SMN, true);
+    let error = tweak.apply(TweakOption::Enabled(true)).unwrap_err();
+    assert!(
+        format!("{:#}", error).contains("every register was restored"),
+        "{:#}",
+        error
+    );
+    assert_eq!(system.pci.get(CONFIG), 0x8000_0001);
+}
+
+#[test]
+fn test_restoring_the_snapshot_puts_back_the_original_values() {
+    let system = system();
+    let _backends = system.install();
+    let tweak = tweak();
+
+    let snapshot = tweak.snapshot().unwrap().unwrap();
+    tweak.apply(TweakOption::Enabled(true)).unwrap();
+    tweak.restore(&snapshot).unwrap();
+    assert_eq!(system.pci.get(CONFIG), 0x8000_0001);
+    assert_eq!(system.pci.get(SMN), 0x0000_0300);
+}
@@ -0,0 +1,146 @@
+// tests/powershell_runner.rs
+
+//! Judges PowerShell scripts by the exit codes and standard error their actions allow,
+//! and stops scripts that time out or are cancelled.
+
+use std::time::Duration;
+
+use oc_tool::{
+    backend::{fake::FakeSystem, CancelToken, ScriptOutput},
+    tweaks::{
+        option_map,
+        powershell::method::{PowershellAction, PowershellTweak},
+        CustomTweakId, TweakId, TweakMethod, TweakOption,
+    },
+    utils::powershell::{execute_powershell_script, execute_powershell_script_with, ScriptPolicy},
+};
+
+/// Also allows 3010, which asks for a reboot.
+const FAST_POLICY: ScriptPolicy = ScriptPolicy {
+    exit_codes: &[0, 3010],
+    timeout: Duration::from_secs(5),
+    stderr_fails: false,
+};
+
+/// Returns a tweak whose enabling script runs under `FAST_POLICY`.
+fn tweak() -> PowershellTweak<'static> {
+    PowershellTweak {
+        id: TweakId::Custom(CustomTweakId::new("TestPowershellRunner").register()),
+        read_script: None,
+        options: option_map(vec![
+            (
+                TweakOption::Enabled(false),
+                &PowershellAction {
+                    script: "Set-Foo -Default",
+                    state: None,
+                    policy: ScriptPolicy::DEFAULT,
+                },
+            ),
+            (
+                TweakOption::Enabled(true),
+                &PowershellAction {
+                    script: "Set-Foo -Fast",
+                    state: None,
+                    policy: FAST_POLICY,
+                },
+            ),
+        ]),
+    }
+}
+
+#[test]
+fn test_warning_on_standard_error_does_not_fail() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+
+    system.shell.set_result(
+        "Set-Foo -Default",
+        ScriptOutput {
+            stderr: "The value is already set.".to_string(),
+            ..Default::default()
+        },
+    );
+    tweak().revert().unwrap();
+}
+
+#[test]
+fn test_only_allowed_exit_codes_succeed() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweak = tweak();
+
+    system.shell.set_result(
+        "Set-Foo -Fast",
+        ScriptOutput {
+            exit_code: 3010,
+            ..Default::default()
+        },
+    );
+    tweak.apply(TweakOption::Enabled(true)).unwrap();
+    system.shell.set_result(
+        "Set-Foo -Default",
+        ScriptOutput {
+            exit_code: 1,
+            stderr: "Access is denied.".to_string(),
+            ..Default::default()
+        },
+    );
+    let error = format!("{:#}", tweak.revert().unwrap_err());
+    assert!(
+        error.contains("exited with code 1: Access is denied."),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_script_outliving_its_timeout_is_stopped() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+
+    system.shell.set_result(
+        "Set-Foo -Fast",
+        ScriptOutput {
+            duration: Duration::from_secs(6),
+            ..Default::default()
+        },
+    );
+    let error = format!(
+        "{:#}",
+        tweak().apply(TweakOption::Enabled(true)).unwrap_err()
+    );
+    assert!(error.contains("timed out after 5s"), "{}", error);
+}
+
+#[test]
+fn test_stricter_policy_fails_on_standard_error() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+
+    system.shell.set_result(
+        "Get-Foo",
+        ScriptOutput {
+            stdout: "1".to_string(),
+            stderr: "Deprecated parameter.".to_string(),
+            ..Default::default()
+        },
+    );
+    assert_eq!(execute_powershell_script("Get-Foo").unwrap(), "1");
+    let strict = ScriptPolicy {
+        stderr_fails: true,
+        ..ScriptPolicy::DEFAULT
+    };
+    assert!(execute_powershell_script_with("Get-Foo", &strict, None).is_err());
+}
+
+#[test]
+fn test_cancelled_script_fails() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let error = execute_powershell_script_with("Get-Foo", &ScriptPolicy::DEFAULT, Some(&cancel))
+        .unwrap_err();
+    assert!(error.to_string().contains("cancelled"), "{}", error);
+}
@@ -0,0 +1,134 @@
+// tests/powershell_state.rs
+
+//! Reads the state of PowerShell tweaks from the JSON their read scripts output, and
+//! checks that output matching no option is reported as such.
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    backup::TweakSnapshot,
+    tweaks::{
+        all_tweaks, option_map,
+        powershell::method::{
+            json_read_script, PowershellAction, PowershellState, PowershellTweak,
+        },
+        CustomTweakId, TweakId, TweakMethod, TweakOption,
+    },
+    utils::powershell::ScriptPolicy,
+};
+
+const READ_SCRIPT: &str = "(Get-Service -Name Foo).Status.ToString()";
+
+/// Returns a tweak that stops a service, reading its status to tell the state.
+fn service_tweak() -> PowershellTweak<'static> {
+    let tweak = PowershellTweak {
+        id: TweakId::Custom(CustomTweakId::new("TestPowershellState").register()),
+        read_script: Some(READ_SCRIPT),
+        options: option_map(vec![
+            (
+                TweakOption::Enabled(false),
+                &PowershellAction {
+                    script: "Start-Service -Name Foo",
+                    state: Some(PowershellState::String("Running")),
+                    policy: ScriptPolicy::DEFAULT,
+                },
+            ),
+            (
+                TweakOption::Enabled(true),
+                &PowershellAction {
+                    script: "Stop-Service -Name Foo",
+                    state: Some(PowershellState::String("Stopped")),
+                    policy: ScriptPolicy::DEFAULT,
+                },
+            ),
+        ]),
+    };
+    assert!(tweak.validate().is_empty(), "{:?}", tweak.validate());
+    tweak
+}
+
+/// Makes the read script of `service_tweak` output `json`.
+fn read_outputs(system: &FakeSystem, json: &str) {
+    system
+        .shell
+        .set_output(&json_read_script(READ_SCRIPT), json);
+}
+
+#[test]
+fn test_state_is_the_option_the_output_matches() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweak = service_tweak();
+
+    read_outputs(&system, "\"Stopped\"");
+    assert_eq!(tweak.initial_state().unwrap(), TweakOption::Enabled(true));
+}
+
+#[test]
+fn test_unexpected_output_matches_no_option() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweak = service_tweak();
+
+    // A value no option expects is unknown, rather than assumed to be the default
+    read_outputs(&system, "\"StopPending\"");
+    let TweakOption::Mixed(states) = tweak.initial_state().unwrap() else {
+        panic!("Unexpected output was not reported as matching no option");
+    };
+    assert_eq!(states[0].value.as_deref(), Some("\"StopPending\""));
+    assert!(states[0].matches.is_empty());
+}
+
+#[test]
+fn test_output_that_is_not_json_is_an_error() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweak = service_tweak();
+
+    read_outputs(&system, "Stopped");
+    assert!(tweak.initial_state().is_err());
+}
+
+#[test]
+fn test_restoring_runs_the_script_of_the_captured_option() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+    let tweak = service_tweak();
+
+    read_outputs(&system, "\"Running\"");
+    let snapshot = tweak.snapshot().unwrap().unwrap();
+    assert_eq!(
+        snapshot,
+        TweakSnapshot::Powershell("\"Running\"".to_string())
+    );
+    tweak.restore(&snapshot).unwrap();
+    assert_eq!(
+        system.shell.history().last().map(String::as_str),
+        Some("Start-Service -Name Foo")
+    );
+}
+
+#[test]
+fn test_options_keep_their_order() {
+    let options: Vec<TweakOption> = service_tweak().options.into_keys().collect();
+    assert_eq!(
+        options,
+        [TweakOption::Enabled(false), TweakOption::Enabled(true)]
+    );
+}
+
+#[test]
+fn test_boolean_output_matches_ram_compression() {
+    let system = FakeSystem::new(4);
+    let _backends = system.install();
+
+    // RAM compression reads a boolean, which once could never match its options
+    let ram_compression = &all_tweaks()[&TweakId::DisableRamCompression].method;
+    system.shell.set_output(
+        &json_read_script("(Get-MMAgent).MemoryCompression"),
+        "false",
+    );
+    assert_eq!(
+        ram_compression.initial_state().unwrap(),
+        TweakOption::Enabled(true)
+    );
+}
@@ -0,0 +1,138 @@
+// tests/round_trip.rs
+
+//! Applies and reverts every built-in tweak against the in-memory fake backends.
+
+use std::sync::Arc;
+
+use oc_tool::{
+    backend::{
+        fake::{FakeCpuid, FakeSystem},
+        ScopedBackends,
+    },
+    backup::TweakSnapshot,
+    tweaks::{all_tweaks, plan::PlanStep, Tweak, TweakId, TweakOption},
+    ui::TweakWidget,
+};
+
+/// Returns whether reading the state back after a change is expected to reflect it.
+/// Buttons have no lasting state, and the fake shell does not run PowerShell scripts.
+fn state_is_observable(tweak: &Tweak, option: &TweakOption) -> bool {
+    *tweak.widget != TweakWidget::Button
+        && !tweak.method.plan(option).is_ok_and(|plan| {
+            plan.steps
+                .iter()
+                .any(|step| matches!(step, PlanStep::Powershell { .. }))
+        })
+}
+
+/// Builds a tweak against fakes it can run on, which stay installed on this thread
+/// until the returned guard is dropped.
+fn tweak_on_fakes(id: TweakId) -> (Tweak<'static>, FakeSystem, ScopedBackends) {
+    let mut system = FakeSystem::new(16);
+    let mut backends = system.install();
+    let tweak = all_tweaks().remove(&id).unwrap();
+    // The fake processor is AMD; Intel-only tweaks run on an Intel one instead
+    if tweak.method.unsupported().is_some() {
+        drop(backends);
+        system.cpuid = Arc::new(FakeCpuid::core_i9_9900k());
+        backends = system.install();
+        assert_eq!(tweak.method.unsupported(), None, "{:?}", id);
+    }
+    (tweak, system, backends)
+}
+
+/// Puts a tweak into `option`, reverting it for `Enabled(false)` like the UI does.
+fn set(id: TweakId, tweak: &Tweak, option: &TweakOption) {
+    if *option == TweakOption::Enabled(false) {
+        tweak.method.revert()
+    } else {
+        tweak.method.apply(option.clone())
+    }
+    .unwrap_or_else(|e| panic!("{:?} -> Failed to set {:?}: {:?}", id, option, e));
+}
+
+#[test]
+fn test_every_option_reads_back() {
+    for id in all_tweaks().into_keys() {
+        let (tweak, _system, _backends) = tweak_on_fakes(id);
+
+        for option in &tweak.options {
+            set(id, &tweak, option);
+            if state_is_observable(&tweak, option) {
+                assert_eq!(
+                    tweak.method.initial_state().unwrap(),
+                    *option,
+                    "{:?} -> State does not match the option just set",
+                    id
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_revert_disables_every_toggle() {
+    let disabled = TweakOption::Enabled(false);
+    // Tweaks offering only named options, such as combo boxes, have nothing to revert to
+    for id in all_tweaks().into_keys() {
+        let (tweak, _system, _backends) = tweak_on_fakes(id);
+        if !tweak.options.contains(&disabled) {
+            continue;
+        }
+
+        for option in &tweak.options {
+            set(id, &tweak, option);
+        }
+        tweak
+            .method
+            .revert()
+            .unwrap_or_else(|e| panic!("{:?} -> Failed to revert: {:?}", id, e));
+        if state_is_observable(&tweak, &disabled) {
+            assert_eq!(
+                tweak.method.initial_state().unwrap(),
+                disabled,
+                "{:?} -> State after revert is not disabled",
+                id
+            );
+        }
+    }
+}
+
+#[test]
+fn test_restoring_a_snapshot_puts_the_system_back() {
+    for id in all_tweaks().into_keys() {
+        let (tweak, system, _backends) = tweak_on_fakes(id);
+
+        let before = system.state().unwrap();
+        let initial = tweak.method.initial_state();
+        // A PowerShell snapshot holds script output, which the fake shell does not produce
+        let Some(snapshot) = tweak
+            .method
+            .snapshot()
+            .unwrap()
+            .filter(|s| !matches!(s, TweakSnapshot::Powershell(_)))
+        else {
+            continue;
+        };
+
+        for option in &tweak.options {
+            set(id, &tweak, option);
+        }
+        tweak
+            .method
+            .restore(&snapshot)
+            .unwrap_or_else(|e| panic!("{:?} -> Failed to restore: {:?}", id, e));
+        assert_eq!(
+            system.state().unwrap(),
+            before,
+            "{:?} -> Restoring the snapshot did not put the system back",
+            id
+        );
+        assert_eq!(
+            tweak.method.initial_state().ok(),
+            initial.ok(),
+            "{:?} -> State after restoring differs from the initial state",
+            id
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// tests/topology.rs
+
+//! Checks that MSR tweaks reach every logical processor on systems with more than 64,
+//! which Windows splits across processor groups.
+
+use oc_tool::{
+    backend::fake::FakeSystem,
+    backup::TweakSnapshot,
+    tweaks::{msr::all_msr_tweaks, Tweak, TweakId, TweakOption},
+    utils::cpu::{CpuTopology, ProcessorNumber},
+};
+
+const EFER: u32 = 0xC000_0080;
+const FFXSR: u64 = 1 << 14;
+
+/// Returns a Threadripper 3990X: 8 dies of 2 core complexes, 4 cores each, with SMT.
+fn threadripper() -> FakeSystem {
+    FakeSystem::with_topology(CpuTopology::uniform(8, 2, 4, 2))
+}
+
+fn fast_fxsave_frstor() -> Tweak<'static> {
+    all_msr_tweaks()
+        .into_iter()
+        .find(|(id, _)| *id == TweakId::EnableFastFxsaveFrstor)
+        .unwrap()
+        .1
+}
+
+#[test]
+fn test_apply_reaches_every_processor_group() {
+    let system = threadripper();
+    let _backends = system.install();
+    let tweak = fast_fxsave_frstor();
+
+    tweak.method.apply(TweakOption::Enabled(true)).unwrap();
+    for cpu in &system.topology.0.cpus {
+        assert_eq!(
+            system.msr.get(cpu.processor, EFER),
+            FFXSR,
+            "{} was not written",
+            cpu
+        );
+    }
+    assert_eq!(
+        system.msr.get(
+            ProcessorNumber {
+                group: 1,
+                index: 63
+            },
+            EFER
+        ),
+        FFXSR
+    );
+    assert_eq!(
+        tweak.method.initial_state().unwrap(),
+        TweakOption::Enabled(true)
+    );
+}
+
+#[test]
+fn test_snapshots_record_every_processor_by_number() {
+    let system = threadripper();
+    let _backends = system.install();
+    let tweak = fast_fxsave_frstor();
+
+    let Some(TweakSnapshot::Msr(values)) = tweak.method.snapshot().unwrap() else {
+        panic!("MSR tweaks take MSR snapshots");
+    };
+    let mut numbers: Vec<usize> = values.iter().map(|value| value.core).collect();
+    numbers.sort();
+    assert_eq!(numbers, (0..128).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_revert_reaches_every_processor_group() {
+    let system = threadripper();
+    let _backends = system.install();
+    let tweak = fast_fxsave_frstor();
+
+    tweak.method.apply(TweakOption::Enabled(true)).unwrap();
+    tweak.method.revert().unwrap();
+    assert!(system.state().unwrap().msrs.is_empty());
+}